@@ -7,8 +7,12 @@ use std::io::prelude::*;
 use std::env;
 use toml::Value;
 
+//Choosing the configuration directories at build time is deprecated. upm reads them at runtime
+//from --config-dir, UPM_CONFIG_DIRS, or /etc/upm/upm.toml, and only adds the directories of a
+//config.toml here after those for builds that still rely on it.
 fn main() {
-    let mut global_conf_dir = String::from("./");
+    println!("cargo:rerun-if-changed=config.toml");
+    let mut global_conf_dir = String::from("");
     let mut secondary_conf_dir = String::from("");
 
     //Open a config file if one exists and read configuration values
@@ -17,6 +21,7 @@ fn main() {
             let mut conffile = String::new();
             file.read_to_string(&mut conffile).expect("Failure to read config.toml. Check priviledges.");
             let config = conffile.as_str().parse::<Value>().expect("Failure to read config.toml. Is this valid toml?");
+            println!("cargo:warning=config.toml is deprecated. List the directories in the config_dirs of /etc/upm/upm.toml instead.");
 
             if config.get("global_conf_dir").is_some() {
                 global_conf_dir = config.get("global_conf_dir").unwrap().as_str().unwrap().to_owned();
@@ -1,83 +1,2075 @@
 #[macro_use] extern crate clap;
-extern crate cursive;
+#[cfg(feature = "tui")]
+extern crate ratatui;
+extern crate env_logger;
+extern crate failure;
+extern crate libc;
+extern crate serde;
+extern crate serde_json;
 extern crate upm_lib;
 
-use clap::{Arg, App, SubCommand, AppSettings};
+mod output;
+mod signals;
+#[cfg(feature = "tui")]
+mod tui;
 
-use cursive::Cursive;
-use cursive::traits::*;
-use cursive::views::{TextView, SelectView, LinearLayout};
-use cursive::theme::{Theme};
+use clap::{Arg, App, SubCommand, AppSettings, ArgMatches};
+use upm_lib::{Interaction, Package, PackageManager, ManagerSet, ManagerSpecifier, PermissionDenied, VersionReq, read_trusted_config_dirs, clean_managers};
+use upm_lib::alias::AliasResolver;
+use upm_lib::audit::AuditLog;
+use upm_lib::cache::QueryCache;
+use upm_lib::changelog;
+use upm_lib::concurrency::ConcurrencyPolicy;
+use upm_lib::confirm::{self, AlwaysNo, AlwaysYes, Confirmation, Request, TerminalPrompt};
+use upm_lib::conflicts::ConflictReport;
+use upm_lib::daemon;
+use upm_lib::detect;
+use upm_lib::dirs::ConfigDirs;
+use upm_lib::doctor;
+use upm_lib::events::EventStream;
+use upm_lib::exit::{ExitCode, FailureKind};
+use upm_lib::fallback::FallbackChain;
+#[cfg(feature = "fuzzy")]
+use upm_lib::fuzzy::FuzzyMatcher;
+use upm_lib::history::{self, HistoryFilter, HistoryLog, Operation};
+use upm_lib::install::InstallOptions;
+use upm_lib::keys;
+use upm_lib::kind::PackageKind;
+use upm_lib::lock::{ProcessLock, WaitPolicy};
+use upm_lib::manifest::{ChangeKind, Manifest, SyncOptions};
+use upm_lib::network::NetworkConfig;
+use upm_lib::pin::{Pin, PinList};
+use upm_lib::policy::{self, OrgPolicy};
+#[cfg(feature = "remote")]
+use upm_lib::remote::SshTarget;
+use upm_lib::repos;
+use upm_lib::report;
+use upm_lib::resolve::{Resolution, ResolutionPolicy, Resolver};
+use upm_lib::restart;
+use upm_lib::rules::RuleSet;
+use upm_lib::scaffold;
+use upm_lib::state::StateDb;
+use upm_lib::status;
+use upm_lib::shadow::{self, Shadowing};
+use upm_lib::schema::{Action, AvailabilityInfo, CapabilityInfo, ChangeInfo, FindingInfo, HistoryInfo, KeyInfo, ManagerInfo, Outcome, PackageInfo, PinInfo, RepoInfo, SecurityInfo, StalenessInfo, StatusInfo, UsageInfo};
+use upm_lib::security::SecurityReport;
+use upm_lib::settings::Settings;
+use upm_lib::suggest::SuggestionEngine;
+use upm_lib::transaction::Transaction;
+use upm_lib::trust::TrustPolicy;
+use upm_lib::usage;
+use upm_lib::which;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::process::ExitStatus;
+use failure::Error;
+use output::{Format, Output};
+use upm_lib::StdioPolicy;
 
+//Deprecated: the directories chosen by a config.toml when upm was built
 include!(concat!(env!("OUT_DIR"), "/config.rs"));
 
+/// The settings of upm.toml, read once. Exits if they can't be read.
+fn settings() -> &'static Settings {
+    static SETTINGS: OnceLock<Settings> = OnceLock::new();
+    SETTINGS.get_or_init(|| match Settings::load_default() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Couldn't read the settings: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    })
+}
+
+/// The organization's policy, read once from the policy file
+fn policy() -> &'static Arc<OrgPolicy> {
+    static POLICY: OnceLock<Arc<OrgPolicy>> = OnceLock::new();
+    POLICY.get_or_init(|| match OrgPolicy::load_default() {
+        Ok(policy) => Arc::new(policy),
+        Err(e) => {
+            eprintln!("Couldn't read the policy: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    })
+}
+
+/// Should every question be answered yes, because of --yes or assume_yes in the settings?
+fn assume_yes(matches: &ArgMatches) -> bool {
+    matches.is_present("yes") || settings().assume_yes
+}
+
+/// Ask before an operation unless the confirmation thresholds of the settings let it go ahead.
+/// --yes answers yes, and only human output asks the user, so other formats answer no.
+fn confirm_request(matches: &ArgMatches, format: Format, request: &Request) -> bool {
+    let confirmation: &dyn Confirmation = if assume_yes(matches) {
+        &AlwaysYes
+    } else if format == Format::Human {
+        &TerminalPrompt
+    } else {
+        &AlwaysNo
+    };
+    settings().confirm.confirm(request, confirmation)
+}
+
+/// How the configuration directories are chosen: --config-dir, then UPM_CONFIG_DIRS, then the user's
+/// directory and the directories of the global settings file
+fn config_locations(matches: &ArgMatches) -> ConfigDirs {
+    let overrides = matches.values_of("config dir").map_or_else(Vec::new, |dirs| dirs.map(PathBuf::from).collect());
+    ConfigDirs::new()
+        .with_overrides(overrides)
+        .with_legacy(vec![PathBuf::from(global_conf_dir()), PathBuf::from(secondary_conf_dir())])
+}
+
+/// The directories package managers are configured in, from highest precedence to lowest
+fn config_dirs(matches: &ArgMatches) -> Vec<PathBuf> {
+    match config_locations(matches).resolve() {
+        Ok(dirs) => dirs,
+        Err(e) => {
+            eprintln!("Couldn't read the configuration directories: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    }
+}
+
 /// Checks what package managers are on the system by calling
 /// the version command
-fn find_package_managers() {
-    //TODO
+fn find_package_managers(matches: &ArgMatches) -> Vec<PackageManager> {
+    let specifier = settings().specifier(ManagerSpecifier::Empty);
+    let managers = read_trusted_config_dirs(config_dirs(matches), &specifier, &load_trust_policy());
+    let mut managers = if managers.is_empty() { detect::detect_managers_except(&specifier) } else { managers };
+    settings().apply(&mut managers);
+    managers.sort_by(|a, b| a.cmp_priority(b));
+    managers.into_iter()
+        .filter(|manager| manager.exists())
+        .collect()
+}
+
+fn install(matches: &ArgMatches) {
+    let mut out: Output<Outcome> = Output::new(matches, "install");
+    let name = matches.value_of("package").unwrap();
+    let mut managers = load_managers(matches);
+    managers.retain(PackageManager::can_install);
+    let chain = load_fallback();
+    if !chain.is_empty() && !matches.is_present("manager") && !matches.is_present("excludes managers") {
+        //The manager that ends up installing isn't known yet, so a block for any of them applies
+        for manager in &chain.managers {
+            if let Err(e) = policy().check_install(name, manager) {
+                eprintln!("{}", e);
+                out.record(ExitCode::Failure);
+                return out.finish();
+            }
+        }
+        return install_with_fallback(out, &chain, &managers, name);
+    }
+    let package = match resolve_package(&managers, name, out.format) {
+        Some(package) => package,
+        None => return,
+    };
+    if let Err(e) = policy().check_install(&package.name, &package.owner.name) {
+        eprintln!("{}", e);
+        out.record(ExitCode::Failure);
+        return out.finish();
+    }
+    if out.is_human() {
+        print_estimate(&managers, &[(package.owner.name.clone(), package.name.clone())]);
+        println!("Installing {} with {}", package.name, package.owner.name);
+    }
+    let result = install_package(&package, out.format);
+    out.outcome(&package.owner, "install", Some(&package.name), &result);
+    if out.is_human() {
+        report(&package.owner.name, result);
+    }
+    out.finish();
+}
+
+/// Tell the user how much installing packages would download and use, if any of their package
+/// managers can estimate it
+fn print_estimate(managers: &ManagerSet, installs: &[(String, String)]) {
+    let estimate = usage::estimate_installs(managers, installs);
+    if !estimate.is_known() {
+        return;
+    }
+    if estimate.unknown.is_empty() {
+        println!("This will {}", estimate.total);
+    } else {
+        println!("This will {}, plus what {} can't estimate", estimate.total, estimate.unknown.join(", "));
+    }
+}
+
+/// Find the package to act on among the package managers, asking the user if several could be
+/// meant. Exits if no package manager provides it, and gives None if the user didn't choose one.
+fn resolve_package(managers: &ManagerSet, name: &str, format: Format) -> Option<Package> {
+    let resolver = Resolver::new(ResolutionPolicy::PreferPriority)
+        .with_aliases(load_aliases())
+        .with_rules(load_rules());
+    match resolver.resolve(name, managers) {
+        Resolution::Found(package) => Some(*package),
+        Resolution::Ambiguous(mut candidates) => {
+            choose_package(&candidates, format).map(|index| candidates.swap_remove(index))
+        },
+        Resolution::NotFound => {
+            eprintln!("No package manager provides '{}'", name);
+            signals::exit(ExitCode::NotFound);
+        },
+    }
+}
+
+/// Download a package without installing it
+fn fetch(matches: &ArgMatches) {
+    let mut out: Output<Outcome> = Output::new(matches, "fetch");
+    let name = matches.value_of("package").unwrap();
+    let dir = matches.value_of("dir").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let mut managers = load_managers(matches);
+    managers.retain(|m| m.has_command("download"));
+    let package = match resolve_package(&managers, name, out.format) {
+        Some(package) => package,
+        None => return,
+    };
+    if out.is_human() {
+        println!("Downloading {} with {} to {}", package.name, package.owner.name, dir.display());
+    }
+    let stdio = if out.is_human() { StdioPolicy::Inherit } else { StdioPolicy::Capture };
+    let result = package.owner.download(&package.name, &dir, stdio).map(|output| {
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        let _ = stderr.write_all(&output.stdout);
+        let _ = stderr.write_all(&output.stderr);
+        output.status
+    });
+    out.outcome(&package.owner, "download", Some(&package.name), &result);
+    if out.is_human() {
+        report(&package.owner.name, result);
+    }
+    out.finish();
+}
+
+/// Install a package with the package managers of the fallback chain in turn
+fn install_with_fallback(mut out: Output<Outcome>, chain: &FallbackChain, managers: &ManagerSet, name: &str) {
+    let stdio = if out.is_human() { StdioPolicy::Inherit } else { StdioPolicy::Capture };
+    let attempts = chain.install(managers, name, stdio);
+    if attempts.is_empty() {
+        eprintln!("None of {} can install packages", chain.managers.join(", "));
+        signals::exit(ExitCode::Unsupported);
+    }
+    //The chain stops at the first install that succeeds, so the last attempt decides the code
+    let mut code = ExitCode::Failure;
+    for attempt in attempts {
+        if let Ok(ref output) = attempt.result {
+            let stderr = io::stderr();
+            let mut stderr = stderr.lock();
+            let _ = stderr.write_all(&output.stdout);
+            let _ = stderr.write_all(&output.stderr);
+        }
+        let result = attempt.result.map(|output| output.status);
+        code = ExitCode::from_result(&result, attempt.failure.unwrap_or(FailureKind::Other));
+        out.push(Outcome::new(&attempt.manager, "install", Some(name), &result));
+        if !out.is_human() {
+            continue;
+        }
+        match attempt.failure {
+            Some(FailureKind::NotFound) => println!("{} doesn't have {}", attempt.manager, name),
+            _ if result.as_ref().is_ok_and(|status| status.success()) => println!("Installed {} with {}", name, attempt.manager),
+            _ => report(&attempt.manager, result),
+        }
+    }
+    out.record(code);
+    out.finish();
+}
+
+/// Run a package manager command. For machine-readable formats its output is sent to stderr so
+/// that stdout only holds upm's own output.
+fn execute(manager: &PackageManager, command: &str, args: &str, format: Format) -> Result<ExitStatus, Error> {
+    if format == Format::Human {
+        return manager.execute(command, args);
+    }
+    Ok(forward_output(manager.execute_with(command, args, StdioPolicy::Capture)?))
+}
+
+/// Install a package in the scope its package manager can install in, for the system if it can
+/// and for the user otherwise. Its output is handled like execute handles it.
+fn install_package(package: &Package, format: Format) -> Result<ExitStatus, Error> {
+    let stdio = if format == Format::Human { StdioPolicy::Inherit } else { StdioPolicy::Capture };
+    Ok(forward_output(package.install_with(&InstallOptions::new(), stdio)?))
+}
+
+/// Remove a package in the scope its package manager can remove it in, like install_package
+fn uninstall_package(package: &Package, format: Format) -> Result<ExitStatus, Error> {
+    let stdio = if format == Format::Human { StdioPolicy::Inherit } else { StdioPolicy::Capture };
+    Ok(forward_output(package.uninstall_with(stdio)?))
+}
+
+/// Write the captured output of a command to stderr, so machine-readable output keeps stdout
+fn forward_output(output: std::process::Output) -> ExitStatus {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let _ = stderr.write_all(&output.stdout);
+    let _ = stderr.write_all(&output.stderr);
+    output.status
+}
+
+/// Ask the user which of several packages to use, giving None if they don't choose one. Nothing
+/// is asked for machine-readable formats.
+fn choose_package(candidates: &[Package], format: Format) -> Option<usize> {
+    if format != Format::Human {
+        let owners: Vec<&str> = candidates.iter().map(|c| c.owner.name.as_str()).collect();
+        eprintln!("Several package managers provide {}, choose one of {} with --manager",
+                  candidates[0].name, owners.join(", "));
+        signals::exit(ExitCode::Usage);
+    }
+    println!("Several package managers provide {}:", candidates[0].name);
+    for (number, package) in candidates.iter().enumerate() {
+        println!("  {}) {} {}", number + 1, package.owner.name,
+                 package.version.get_representation());
+    }
+    print!("Choose a package manager [1-{}]: ", candidates.len());
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    match answer.trim().parse::<usize>() {
+        Ok(number) if number >= 1 && number <= candidates.len() => Some(number - 1),
+        _ => None,
+    }
+}
+
+fn query(matches: &ArgMatches) {
+    let mut out: Output<PackageInfo> = Output::new(matches, "query");
+    let name = matches.value_of("package").unwrap();
+    let mut managers = load_managers(matches);
+    managers.retain(|m| m.has_command("search"));
+    refresh_stale(matches, &managers);
+    let results = load_aliases().search_all(managers.as_slice(), name, &settings().concurrency());
+    let code = results.exit_code();
+    let kind = matches.value_of("kind").map(|kind| kind.parse::<PackageKind>().unwrap());
+    let results = results.into_vec().into_iter()
+        .map(|(manager, result)| (manager, result.map(|packages| match kind {
+            Some(kind) => packages.into_iter().filter(|package| package.kind() == Some(kind)).collect(),
+            None => packages,
+        })))
+        .collect();
+    let found = print_search_results(&mut out, name, results);
+    out.record(if found || !code.is_success() { code } else { ExitCode::NotFound });
+    if !found && out.is_human() {
+        let names: Vec<&str> = managers.iter().map(|m| m.name.as_str()).collect();
+        println!("No '{}' in {}", name, names.join(", "));
+        for suggestion in SuggestionEngine::with_builtins().suggest(name, managers.as_slice()) {
+            println!("  {}", suggestion);
+        }
+    }
+    out.finish();
+}
+
+/// Print the results of a search grouped by package manager, giving whether anything was found
+#[cfg(not(feature = "fuzzy"))]
+fn print_search_results(out: &mut Output<PackageInfo>, _query: &str, results: Vec<(&PackageManager, Result<Vec<Package>, Error>)>) -> bool {
+    let mut found = false;
+    for (manager, result) in results {
+        match result {
+            Ok(ref packages) if packages.is_empty() => {},
+            Ok(packages) => {
+                found = true;
+                if !out.is_human() {
+                    packages.iter().for_each(|package| out.push(PackageInfo::from(package)));
+                    continue;
+                }
+                println!("{}:", manager.name);
+                for package in packages {
+                    println!("  {} {}", package.name, package.version.get_representation());
+                    if !package.description.is_empty() {
+                        println!("      {}", package.description);
+                    }
+                }
+            },
+            Err(e) => out.error(&manager.name, format!("Couldn't search {}: {}", manager.name, e)),
+        }
+    }
+    found
+}
+
+/// Print the results of a search from every package manager together, best match of the query
+/// first, giving whether anything was found
+#[cfg(feature = "fuzzy")]
+fn print_search_results(out: &mut Output<PackageInfo>, query: &str, results: Vec<(&PackageManager, Result<Vec<Package>, Error>)>) -> bool {
+    let mut packages = Vec::new();
+    for (manager, result) in results {
+        match result {
+            Ok(found) => packages.extend(found),
+            Err(e) => out.error(&manager.name, format!("Couldn't search {}: {}", manager.name, e)),
+        }
+    }
+    FuzzyMatcher::new().rank(query, &mut packages);
+    for package in &packages {
+        if out.is_human() {
+            println!("{} {} ({})", package.name, package.version.get_representation(), package.owner.name);
+            if !package.description.is_empty() {
+                println!("    {}", package.description);
+            }
+        } else {
+            out.push(PackageInfo::from(package));
+        }
+    }
+    !packages.is_empty()
+}
+
+fn owns(matches: &ArgMatches) {
+    let path = matches.value_of("file").unwrap();
+    let managers = load_managers(matches);
+    let owners = managers.who_owns(path);
+    print_packages(matches, "owns", &owners, &format!("No package manager owns {}", path));
+}
+
+fn provides(matches: &ArgMatches) {
+    let capability = matches.value_of("capability").unwrap();
+    let managers = load_managers(matches);
+    let providers = managers.provides(capability);
+    print_packages(matches, "provides", &providers, &format!("No package provides {}", capability));
+}
+
+/// Print packages along with their package managers, or the message if there are none
+fn print_packages(matches: &ArgMatches, command: &'static str, packages: &[Package], none: &str) {
+    let mut out: Output<PackageInfo> = Output::new(matches, command);
+    if out.is_human() {
+        if packages.is_empty() {
+            println!("{}", none);
+        }
+        for package in packages {
+            println!("{} {} ({})", package.name, package.version, package.owner.name);
+        }
+    }
+    packages.iter().for_each(|package| out.push(PackageInfo::from(package)));
+    if packages.is_empty() {
+        out.record(ExitCode::NotFound);
+    }
+    out.finish();
+}
+
+fn which(matches: &ArgMatches) {
+    let name = matches.value_of("package").unwrap();
+    let managers = load_managers(matches);
+    let found = which::locate(&managers, name, &load_aliases());
+    let mut out: Output<AvailabilityInfo> = Output::new(matches, "which");
+    if out.is_human() {
+        if found.is_empty() {
+            println!("No package manager offers '{}'", name);
+        }
+        for availability in &found {
+            println!("{}", availability);
+        }
+        for shadowing in shadow::analyze(&managers, &[name]) {
+            if let Some(winner) = shadowing.winner().filter(|_| shadowing.is_shadowed()) {
+                println!("{} runs {}", name, winner);
+            }
+            if let Some(warning) = shadowing.warning() {
+                eprintln!("warning: {}", warning);
+            }
+        }
+    } else {
+        found.iter().for_each(|availability| out.push(AvailabilityInfo::from(availability)));
+        out.finish();
+    }
+    if found.is_empty() {
+        signals::exit(ExitCode::NotFound);
+    }
+}
+
+fn status(matches: &ArgMatches) {
+    let name = matches.value_of("package").unwrap();
+    let managers = load_managers(matches);
+    let found = status::status(&managers, name, &load_aliases());
+    let mut out: Output<StatusInfo> = Output::new(matches, "status");
+    if out.is_human() {
+        if found.is_empty() {
+            println!("No package manager offers '{}'", name);
+        }
+        for status in &found {
+            println!("{}", status);
+        }
+    } else {
+        found.iter().for_each(|status| out.push(StatusInfo::from(status)));
+        out.finish();
+    }
+    if found.is_empty() {
+        signals::exit(ExitCode::NotFound);
+    }
+}
+
+fn uninstall(matches: &ArgMatches) {
+    let mut out: Output<Outcome> = Output::new(matches, "uninstall");
+    let name = matches.value_of("package").unwrap();
+    let mut managers = load_managers(matches);
+    managers.retain(|m| m.can_uninstall() && m.has_command("list_installed"));
+    let mut candidates = Vec::new();
+    for manager in &managers {
+        match manager.list_installed() {
+            Ok(installed) => candidates.extend(installed.into_iter().filter(|p| p.is_called(name))),
+            Err(e) => out.error(&manager.name, format!("Couldn't list the packages installed by {}: {}", manager.name, e)),
+        }
+    }
+    let package = match candidates.len() {
+        0 => {
+            eprintln!("No package manager has '{}' installed", name);
+            signals::exit(ExitCode::NotFound);
+        },
+        1 => candidates.remove(0),
+        _ => match choose_package(&candidates, out.format) {
+            Some(index) => candidates.swap_remove(index),
+            None => return,
+        },
+    };
+    if let Err(e) = policy().check_remove(&package.name, &package.owner.name) {
+        eprintln!("{}", e);
+        out.record(ExitCode::Failure);
+        return out.finish();
+    }
+    let mut request = Request::new(confirm::Action::Remove, format!("Remove {} with {}?", package.name, package.owner.name))
+        .with_packages(vec![package.name.clone()]);
+    if package.owner.has_command("reverse_deps") && !assume_yes(matches) {
+        match package.owner.reverse_deps(&package.name) {
+            Ok(ref dependents) if !dependents.is_empty() => {
+                let mut details = vec![format!("These packages depend on {}:", package.name)];
+                details.extend(dependents.iter().map(|dependent| format!("  {} {}", dependent.name, dependent.version)));
+                request = request.with_details(details);
+                request.question = String::from("Remove it anyway?");
+            },
+            Ok(_) => {},
+            Err(e) => out.error(&package.owner.name, format!("Couldn't check what depends on {}: {}", package.name, e)),
+        }
+    }
+    if !confirm_request(matches, out.format, &request) {
+        if !out.is_human() {
+            eprintln!("Not removing {} without confirmation, remove it anyway with --yes", package.name);
+            signals::exit(ExitCode::Failure);
+        }
+        return;
+    }
+    if out.is_human() {
+        println!("Removing {} with {}", package.name, package.owner.name);
+    }
+    let result = uninstall_package(&package, out.format);
+    out.outcome(&package.owner, "remove", Some(&package.name), &result);
+    if out.is_human() {
+        report(&package.owner.name, result);
+    }
+    out.finish();
+}
+
+fn reverse_deps(matches: &ArgMatches) {
+    let name = matches.value_of("package").unwrap();
+    let managers = load_managers(matches);
+    let dependents = managers.reverse_deps(name);
+    print_packages(matches, "rdeps", &dependents, &format!("Nothing depends on {}", name));
+}
+
+/// Build a ManagerSpecifier from the --manager and --exclude-managers arguments and the exclusions
+/// in the settings
+fn manager_specifier(matches: &ArgMatches) -> ManagerSpecifier {
+    let specifier = if let Some(names) = matches.values_of("manager") {
+        ManagerSpecifier::Includes(names.map(String::from).collect())
+    } else if let Some(names) = matches.values_of("excludes managers") {
+        ManagerSpecifier::Excludes(names.map(String::from).collect())
+    } else {
+        ManagerSpecifier::Empty
+    };
+    settings().specifier(specifier)
+}
+
+/// Read the package managers from the configuration directories, honoring the manager arguments,
+/// --non-interactive, and the settings
+fn load_managers(matches: &ArgMatches) -> ManagerSet {
+    let specifier = manager_specifier(matches);
+    let mut managers = read_trusted_config_dirs(config_dirs(matches), &specifier, &load_trust_policy());
+    if managers.is_empty() {
+        //Work on a fresh machine by falling back to the package managers found in PATH
+        managers = detect::detect_managers_except(&specifier);
+    }
+    settings().apply(&mut managers);
+    if policy().user_managers.is_some() {
+        for name in policy().restrict(&mut managers, &policy::current_groups()) {
+            if matches.is_present("manager") && specifier.allows(&name) {
+                eprintln!("The policy only lets administrators use {}", name);
+            }
+        }
+    }
+    if matches.is_present("non interactive") {
+        for manager in &mut managers {
+            manager.set_interaction(Interaction::NonInteractive);
+        }
+    }
+    if let Some(log) = HistoryLog::default_log() {
+        let log = Arc::new(log);
+        for manager in &mut managers {
+            manager.set_history(log.clone());
+        }
+    }
+    if let Some(ref path) = settings().audit_log {
+        let log = Arc::new(AuditLog::at(path));
+        for manager in &mut managers {
+            manager.set_audit(log.clone());
+        }
+    }
+    if let Some(state) = StateDb::default_db() {
+        let state = Arc::new(state);
+        for manager in &mut managers {
+            manager.set_state(state.clone());
+        }
+    }
+    let network = load_network();
+    if !network.is_empty() {
+        let network = Arc::new(network);
+        for manager in &mut managers {
+            manager.set_network(network.clone());
+        }
+    }
+    if let Some(events) = event_stream(matches) {
+        let events = Arc::new(events);
+        for manager in &mut managers {
+            manager.set_events(events.clone());
+        }
+    }
+    let mut managers = ManagerSet::new(managers);
+    managers.set_policy(policy().clone());
+    if let Some(license) = settings().license.as_ref().filter(|license| license.is_restrictive()) {
+        managers.set_license(Arc::new(license.clone()));
+    }
+    managers.set_cancellation(signals::token());
+    #[cfg(feature = "remote")]
+    {
+        if let Some(target) = remote_target(matches) {
+            managers.set_remote(target);
+        }
+    }
+    for (_, e) in managers.remove_unusable() {
+        eprintln!("warning: {}, skipping it", e);
+    }
+    if let Some(ttl) = settings().cache_ttl {
+        managers.set_cache(Arc::new(QueryCache::on_disk(ttl)));
+    }
+    managers
+}
+
+/// The machine to run the package managers on, from --remote
+#[cfg(feature = "remote")]
+fn remote_target(matches: &ArgMatches) -> Option<SshTarget> {
+    matches.value_of("remote").map(|target| match target.parse() {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("{}", e);
+            signals::exit(ExitCode::Usage);
+        },
+    })
+}
+
+/// Open the event stream asked for with --events, on stderr unless --events-fd chose another
+/// file descriptor
+fn event_stream(matches: &ArgMatches) -> Option<EventStream> {
+    if !matches.is_present("events") {
+        return None;
+    }
+    match matches.value_of("events fd").map(|fd| fd.parse::<i32>()) {
+        Some(Ok(fd)) => Some(EventStream::from_fd(fd)),
+        Some(Err(_)) => {
+            eprintln!("--events-fd must be a file descriptor number");
+            signals::exit(ExitCode::Usage);
+        },
+        None => Some(EventStream::stderr()),
+    }
+}
+
+/// Report if a package manager command didn't succeed
+fn report<E: Display>(manager: &str, result: Result<ExitStatus, E>) {
+    match result {
+        Ok(status) => if !status.success() {
+            eprintln!("{} exited with {}", manager, status);
+        },
+        Err(e) => eprintln!("Couldn't run {}: {}", manager, e),
+    }
+}
+
+/// Whether to wait for the upm process lock, which is waited for unless --no-wait was given before
+/// or after the subcommand
+fn wait_policy(matches: &ArgMatches) -> WaitPolicy {
+    if matches.is_present("no wait") {
+        WaitPolicy::NoWait
+    } else {
+        matches.subcommand().1.map_or(WaitPolicy::Wait, wait_policy)
+    }
+}
+
+/// Take the upm process lock, waiting for it unless --no-wait was given. Exits if the lock can't
+/// be taken.
+fn lock(matches: &ArgMatches) -> ProcessLock {
+    match ProcessLock::acquire(wait_policy(matches)) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("{}", e);
+            signals::exit(ExitCode::from_error(&e));
+        },
+    }
+}
+
+/// Write a configuration file, offering to write it with sudo if the user can't write to the
+/// configuration directory
+fn write_config(matches: &ArgMatches, path: &Path, contents: &str) {
+    let error = match upm_lib::write_config(path, contents) {
+        Ok(()) => {
+            println!("Wrote {}", path.display());
+            return;
+        },
+        Err(e) => e,
+    };
+    if error.downcast_ref::<PermissionDenied>().is_none() {
+        eprintln!("Couldn't write {}: {}", path.display(), error);
+        return;
+    }
+    println!("You don't have permission to write {}. It would contain:\n", path.display());
+    println!("{}", contents);
+    if confirm_request(matches, Format::Human, &Request::new(confirm::Action::Escalate, "Write this file using sudo?")) {
+        match upm_lib::write_config_escalated(path, contents, settings().sudo()) {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(e) => eprintln!("Couldn't write {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn config(matches: &ArgMatches) {
+    let config_dir = match config_locations(matches).global_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Couldn't read the configuration directories: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    if let Some(matches) = matches.subcommand_matches("new") {
+        let name = matches.value_of("manager").unwrap();
+        let user_dir = match upm_lib::user_config_dir() {
+            Some(dir) if !matches.is_present("global") => dir,
+            _ => {
+                let path = config_dir.join(format!("{}.toml", name));
+                if path.exists() {
+                    eprintln!("{} already exists", path.display());
+                    return;
+                }
+                return write_config(matches, &path, &scaffold::scaffold(name));
+            },
+        };
+        match scaffold::write_scaffold(name, &user_dir) {
+            Ok(path) => println!("Wrote {}", path.display()),
+            Err(e) => eprintln!("Couldn't write the definition of {}: {}", name, e),
+        }
+    } else if let Some(matches) = matches.subcommand_matches("lint") {
+        lint_configs(matches);
+    } else if let Some(matches) = matches.subcommand_matches("enable") {
+        let source = Path::new(matches.value_of("file").unwrap());
+        if let Err(e) = PackageManager::from_file(source) {
+            eprintln!("{} isn't a valid package manager definition: {}", source.display(), e);
+            return;
+        }
+        let mut contents = String::new();
+        if let Err(e) = File::open(source).and_then(|mut file| file.read_to_string(&mut contents)) {
+            eprintln!("Couldn't read {}: {}", source.display(), e);
+            return;
+        }
+        match source.file_name() {
+            Some(file_name) => write_config(matches, &config_dir.join(file_name), &contents),
+            None => eprintln!("{} isn't a file", source.display()),
+        }
+    }
 }
 
-fn install() {
-    //TODO
-    
+/// Check every package manager definition without running anything, exiting with 1 if any of
+/// them has errors
+fn lint_configs(matches: &ArgMatches) {
+    let reports = doctor::lint_config_dirs(config_dirs(matches));
+    let mut out: Output<FindingInfo> = Output::new(matches, "config-lint");
+    for report in &reports {
+        if out.is_human() && !report.findings.is_empty() {
+            println!("{}:", report.path.display());
+            for finding in &report.findings {
+                println!("  {}", finding);
+            }
+        }
+        FindingInfo::from_report(report).into_iter().for_each(|finding| out.push(finding));
+    }
+    if out.is_human() && reports.iter().all(|report| report.findings.is_empty()) {
+        println!("No problems found in {} package manager definitions", reports.len());
+    }
+    out.finish();
+    if !reports.iter().all(|report| report.is_healthy()) {
+        signals::exit(ExitCode::Failure);
+    }
 }
 
-fn query() {
-    //TODO
+fn export(matches: &ArgMatches) {
+    let managers = load_managers(matches);
+    let manifest = Manifest::export(&managers);
+    let mut out: Output<PackageInfo> = Output::new(matches, "export");
+    match matches.value_of("file") {
+        Some(path) => match File::create(path).and_then(|mut file| write!(file, "{}", manifest)) {
+            Ok(()) if out.is_human() => println!("Wrote {}", path),
+            Ok(()) => {},
+            Err(e) => eprintln!("Couldn't write {}: {}", path, e),
+        },
+        None if out.is_human() => print!("{}", manifest),
+        None => {},
+    }
+    //The manifest is listed as packages for machine-readable formats
+    for (manager, packages) in &manifest.managers {
+        for (name, version) in packages {
+            out.push(PackageInfo {
+                name: name.clone(),
+                version: version.clone(),
+                description: String::new(),
+                manager: manager.clone(),
+                kind: None,
+            });
+        }
+    }
+    out.finish();
 }
 
-fn uninstall() {
-//TODO
+fn apply(matches: &ArgMatches) {
+    let path = matches.value_of("file").unwrap();
+    let manifest = match Manifest::from_file(path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", path, e);
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    let managers = load_managers(matches);
+    let mut options = SyncOptions {
+        remove_extraneous: matches.is_present("remove extraneous"),
+        dry_run: true,
+    };
+    let preview = manifest.apply(&managers, &options);
+    let format = Format::of(matches);
+    if format == Format::Human {
+        if preview.changes.is_empty() {
+            println!("Already up to date with {}", path);
+            return;
+        }
+        for change in &preview.changes {
+            println!("{}", change);
+        }
+        let installs: Vec<(String, String)> = preview.changes.iter()
+            .filter(|change| change.kind == ChangeKind::Install)
+            .map(|change| (change.manager.clone(), change.package.clone()))
+            .collect();
+        print_estimate(&managers, &installs);
+    }
+    let action = if preview.changes.iter().any(|change| change.kind == ChangeKind::Remove) {
+        confirm::Action::Remove
+    } else {
+        confirm::Action::Install
+    };
+    let request = Request::new(action, "Make these changes?")
+        .with_packages(preview.changes.iter().map(|change| change.package.clone()).collect());
+    //A dry run doesn't ask, since nothing would be changed either way
+    if preview.changes.is_empty() || matches.is_present("dry run") || !confirm_request(matches, format, &request) {
+        //Machine-readable formats list the changes when they aren't made
+        let mut out: Output<ChangeInfo> = Output::new(matches, "apply");
+        preview.changes.iter().for_each(|change| out.push(ChangeInfo::from(change)));
+        return out.finish();
+    }
+    options.dry_run = false;
+    let report = manifest.apply(&managers, &options);
+    let mut out: Output<Outcome> = Output::new(matches, "apply");
+    for (change, result) in report.changes.iter().zip(&report.results) {
+        let command = ChangeInfo::from(change).action.name();
+        let kind = match (managers.get(&change.manager), result) {
+            (Some(manager), &Ok(status)) => manager.failure_kind(command, status),
+            _ => FailureKind::Other,
+        };
+        out.record(ExitCode::from_result(result, kind));
+        out.push(Outcome::new(&change.manager, command, Some(&change.package), result));
+        match *result {
+            Ok(status) if status.success() => {},
+            Ok(status) if out.is_human() => eprintln!("{}: exited with {}", change, status),
+            Err(ref e) if out.is_human() => eprintln!("{}: {}", change, e),
+            _ => {},
+        }
+    }
+    out.finish();
 }
 
-//TODO look into a TUI interface that can be used for viewing install and query commands which
-//often will exceed scrollback buffers.
+fn doctor(matches: &ArgMatches) {
+    let reports = doctor::check_config_dirs(config_dirs(matches));
+    let healthy = reports.iter().all(|report| report.is_healthy());
+    let managers = load_managers(matches);
+    let conflicts = ConflictReport::check(&managers, true);
+    let conflicting: Vec<&str> = conflicts.conflicts.iter().map(|conflict| conflict.package.as_str()).collect();
+    let stale: Vec<Shadowing> = shadow::analyze(&managers, &conflicting).into_iter()
+        .filter(|shadowing| shadowing.warning().is_some())
+        .collect();
+    let mut out: Output<FindingInfo> = Output::new(matches, "doctor");
+    if !out.is_human() {
+        for report in &reports {
+            FindingInfo::from_report(report).into_iter().for_each(|finding| out.push(finding));
+        }
+        for conflict in &conflicts.conflicts {
+            FindingInfo::from_conflict(conflict).into_iter().for_each(|finding| out.push(finding));
+        }
+        stale.iter().filter_map(FindingInfo::from_shadowing).for_each(|finding| out.push(finding));
+        out.finish();
+    } else {
+        for report in &reports {
+            if report.findings.is_empty() {
+                println!("{}: ok", report.name);
+                continue;
+            }
+            println!("{} ({}):", report.name, report.path.display());
+            for finding in &report.findings {
+                println!("  {}", finding);
+            }
+        }
+        if !conflicts.is_empty() {
+            println!("Packages installed through several package managers:");
+            for conflict in &conflicts.conflicts {
+                println!("  warning: {}", conflict);
+            }
+        }
+        for shadowing in &stale {
+            println!("  warning: {}", shadowing.warning().unwrap());
+        }
+    }
+    if !healthy {
+        signals::exit(ExitCode::Failure);
+    }
+}
+
+/// Verify the installed packages and look for known vulnerabilities, listing the most serious
+/// vulnerabilities first and exiting with 1 if anything was found
+fn audit(matches: &ArgMatches) {
+    let managers = load_managers(matches);
+    let report = SecurityReport::check(&managers);
+    let mut out: Output<SecurityInfo> = Output::new(matches, "audit");
+    for (manager, error) in &report.failed {
+        out.record(ExitCode::Failure);
+        out.error(manager, format!("{}: {}", manager, error));
+    }
+    if out.is_human() {
+        if report.is_clean() {
+            println!("No problems or vulnerabilities found");
+        }
+        for problem in &report.problems {
+            println!("damaged: {}", problem);
+        }
+        for advisory in report.advisories() {
+            println!("vulnerable: {}", advisory);
+        }
+    }
+    report.problems.iter().for_each(|problem| out.push(SecurityInfo::from(problem)));
+    report.vulnerabilities.iter().for_each(|vulnerability| out.push(SecurityInfo::from(vulnerability)));
+    out.finish();
+    if !report.is_clean() {
+        signals::exit(ExitCode::Failure);
+    }
+}
+
+/// Read the global package name aliases, using none if they can't be read
+fn load_aliases() -> AliasResolver {
+    match AliasResolver::load_default() {
+        Ok(aliases) => aliases,
+        Err(e) => {
+            eprintln!("Couldn't read the package name aliases: {}", e);
+            AliasResolver::new()
+        },
+    }
+}
+
+fn load_rules() -> RuleSet {
+    match RuleSet::load_default() {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Couldn't read the package manager rules: {}", e);
+            RuleSet::new()
+        },
+    }
+}
+
+fn load_network() -> NetworkConfig {
+    match NetworkConfig::load_default() {
+        Ok(network) => network,
+        Err(e) => {
+            //Without the proxies the package managers would fail in confusing ways
+            eprintln!("Couldn't read the network settings: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    }
+}
+
+fn load_fallback() -> FallbackChain {
+    match FallbackChain::load_default() {
+        Ok(chain) => chain,
+        Err(e) => {
+            eprintln!("Couldn't read the install fallback chain: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    }
+}
+
+fn load_trust_policy() -> TrustPolicy {
+    match TrustPolicy::load_default() {
+        Ok(policy) => policy,
+        Err(e) => {
+            //Loading configurations without the policy could run commands it would refuse
+            eprintln!("Couldn't read the trust policy: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    }
+}
+
+/// Load the default pin list, exiting if it can't be read
+fn load_pins() -> PinList {
+    match PinList::load_default() {
+        Ok(pins) => pins,
+        Err(e) => {
+            eprintln!("Couldn't read the pinned packages: {}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    }
+}
+
+fn upgrade(matches: &ArgMatches) {
+    let managers = load_managers(matches);
+    refresh_stale(matches, &managers);
+    let mut pins = load_pins();
+    policy().apply_pins(&mut pins);
+    let mut out: Output<Outcome> = Output::new(matches, "upgrade");
+    match matches.values_of("package") {
+        None => {
+            let mut request = Request::new(confirm::Action::UpgradeAll, "Upgrade every package?");
+            if matches.is_present("changelog") && out.is_human() {
+                let pending = changelog::pending_changes(managers.as_slice(), &settings().concurrency());
+                request = request.with_details(pending.iter().flat_map(|changes| changes.snippet(changelog::DEFAULT_SNIPPET_LINES)).collect());
+            }
+            if !confirm_request(matches, out.format, &request) {
+                if !out.is_human() {
+                    eprintln!("Upgrading every package needs confirmation, upgrade anyway with --yes");
+                    signals::exit(ExitCode::Failure);
+                }
+                return;
+            }
+            //Upgrades can prompt the user, so only one manager is run at a time
+            let run = |managers: &ManagerSet| report::upgrade(managers.as_slice(), &ConcurrencyPolicy::sequential(), &pins, StdioPolicy::Inherit);
+            let upgraded = match settings().snapshot {
+                Some(ref policy) => match policy.around(Operation::UpgradeAll, &managers, run) {
+                    Ok((upgraded, snapshot)) => {
+                        if let Some(pre) = snapshot.and_then(|snapshot| snapshot.pre).filter(|_| out.is_human()) {
+                            println!("Took snapshot {} before upgrading", pre);
+                        }
+                        upgraded
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        out.record(ExitCode::from_error(&e));
+                        return out.finish();
+                    },
+                },
+                None => run(&managers),
+            };
+            out.record(upgraded.exit_code());
+            for manager in &upgraded.managers {
+                out.push(Outcome::new(&manager.manager, "upgrade_all", None, &manager.result));
+                match manager.result {
+                    Ok(ref status) if status.success() && out.is_human() => println!("Upgraded {}", manager.manager),
+                    Ok(ref status) if status.success() => {},
+                    Ok(status) => out.error(&manager.manager, format!("{} exited with {}", manager.manager, status)),
+                    Err(ref e) => out.error(&manager.manager, format!("Couldn't upgrade {}: {}", manager.manager, e)),
+                }
+            }
+            if out.is_human() && !upgraded.managers.is_empty() {
+                println!("{}", upgraded);
+                let restarts = restart::check(managers.as_slice(), &settings().concurrency());
+                if !restarts.managers.is_empty() || !restarts.unknown.is_empty() {
+                    print!("{}", restarts);
+                }
+            }
+        },
+        Some(names) => {
+            let mut remaining: HashSet<&str> = names.collect();
+            for manager in managers.iter().filter(|m| m.has_command("upgrade") && m.has_command("list_installed")) {
+                let installed = match manager.list_installed() {
+                    Ok(installed) => installed,
+                    Err(e) => {
+                        out.record(ExitCode::from_error(&e));
+                        out.error(&manager.name, format!("Couldn't list packages installed with {}: {}", manager.name, e));
+                        continue;
+                    }
+                };
+                for package in installed {
+                    if !remaining.remove(package.name.as_str()) {
+                        continue;
+                    }
+                    let held = match pins.pin_for(&package) {
+                        Some(pin) if pin.req.is_some() => match manager.candidate(&package.name) {
+                            Ok(Some(ref candidate)) if pin.allows(candidate) => None,
+                            Ok(Some(candidate)) => Some(format!("{} {} is outside the range {} it's pinned to for {}",
+                                                                package.name, candidate, pin.req.as_ref().unwrap(), manager.name)),
+                            Ok(None) => Some(format!("{} isn't available from {}", package.name, manager.name)),
+                            Err(e) => Some(format!("Couldn't find the newest version of {}: {}", package.name, e)),
+                        },
+                        Some(_) => Some(format!("{} is pinned for {}, unpin it to upgrade it", package.name, manager.name)),
+                        None => None,
+                    };
+                    if let Some(held) = held {
+                        eprintln!("{}", held);
+                    } else {
+                        if out.is_human() {
+                            println!("Upgrading {} with {}", package.name, manager.name);
+                        }
+                        let result = execute(manager, "upgrade", &package.name, out.format);
+                        out.outcome(manager, "upgrade", Some(&package.name), &result);
+                        if out.is_human() {
+                            report(&manager.name, result);
+                        }
+                    }
+                }
+            }
+            for name in remaining {
+                out.record(ExitCode::NotFound);
+                eprintln!("{} isn't installed through any of the selected package managers", name);
+            }
+        }
+    }
+    out.finish();
+}
+
+fn refresh(matches: &ArgMatches) {
+    let managers = load_managers(matches);
+    if matches.is_present("status") {
+        let mut out: Output<StalenessInfo> = Output::new(matches, "refresh-status");
+        for staleness in managers.staleness() {
+            if out.is_human() {
+                println!("{}", staleness);
+            }
+            out.push(StalenessInfo::from(&staleness));
+        }
+        return out.finish();
+    }
+    let mut out: Output<Outcome> = Output::new(matches, "refresh");
+    let stdio = if out.is_human() { StdioPolicy::Inherit } else { StdioPolicy::Null };
+    let reports = managers.refresh_all(stdio);
+    if reports.is_empty() {
+        if out.is_human() {
+            println!("None of the selected package managers can refresh their metadata");
+        }
+        out.record(ExitCode::Unsupported);
+    }
+    out.record(reports.exit_code());
+    for (manager, report) in reports {
+        out.push(Outcome::new(&manager.name, "refresh", None, &report.result));
+        match report.result {
+            Ok(ref status) if status.success() && out.is_human() => {
+                println!("Refreshed {} in {:.1}s", manager.name, report.elapsed.as_secs_f64());
+            },
+            Ok(ref status) if status.success() => {},
+            Ok(status) => out.error(&manager.name, format!("{} exited with {}", manager.name, status)),
+            Err(e) => out.error(&manager.name, format!("Couldn't refresh {}: {}", manager.name, e)),
+        }
+    }
+    out.finish();
+}
+
+/// Refresh the metadata of the package managers whose metadata is older than their
+/// refresh_after, unless --no-refresh was given. Only failures are reported.
+fn refresh_stale(matches: &ArgMatches, managers: &ManagerSet) {
+    if matches.is_present("no refresh") {
+        return;
+    }
+    let human = Format::of(matches) == Format::Human;
+    let stdio = if human { StdioPolicy::Inherit } else { StdioPolicy::Null };
+    let reports = managers.refresh_stale(stdio);
+    for (manager, report) in reports.failed() {
+        match report.result {
+            Ok(status) => eprintln!("Refreshing {} exited with {}", manager.name, status),
+            Err(ref e) => eprintln!("Couldn't refresh {}: {}", manager.name, e),
+        }
+    }
+}
+
+fn clean(matches: &ArgMatches) {
+    let managers = load_managers(matches);
+    let autoremove = matches.is_present("autoremove");
+    //Autoremove can prompt the user, so only one manager is run at a time
+    let reports = clean_managers(managers.as_slice(), &ConcurrencyPolicy::sequential(), autoremove);
+    let mut out: Output<Outcome> = Output::new(matches, "clean");
+    out.record(if reports.is_empty() { ExitCode::Unsupported } else { reports.exit_code() });
+    if !out.is_human() {
+        for &(manager, ref cleaned) in &reports {
+            if let Some(ref result) = cleaned.clean_cache {
+                out.push(Outcome::new(&manager.name, "clean_cache", None, result));
+            }
+            if let Some(ref result) = cleaned.autoremove {
+                out.push(Outcome::new(&manager.name, "autoremove", None, result));
+            }
+        }
+        return out.finish();
+    }
+    if reports.is_empty() {
+        println!("None of the selected package managers can be cleaned");
+    }
+    for (manager, cleaned) in reports {
+        if let Some(result) = cleaned.clean_cache {
+            match result {
+                Ok(ref status) if status.success() => println!("Cleaned the cache of {}", manager.name),
+                result => report(&manager.name, result),
+            }
+        }
+        if let Some(result) = cleaned.autoremove {
+            match result {
+                Ok(ref status) if status.success() => println!("Removed unneeded packages from {}", manager.name),
+                result => report(&manager.name, result),
+            }
+        }
+    }
+    out.finish();
+}
+
+fn disk_usage(matches: &ArgMatches) {
+    let managers = load_managers(matches);
+    let report = usage::measure(managers.as_slice(), &settings().concurrency());
+    let mut out: Output<UsageInfo> = Output::new(matches, "du");
+    if !out.is_human() {
+        for (manager, usage) in &report.managers {
+            out.push(UsageInfo { manager: manager.clone(), cache: usage.cache, installed: usage.installed });
+        }
+        return out.finish();
+    }
+    if report.managers.is_empty() {
+        println!("None of the selected package managers can report their disk usage");
+        return;
+    }
+    for (manager, usage) in &report.managers {
+        println!("{}: {}", manager, usage);
+    }
+    let total = report.total();
+    println!("total: {} ({})", total, usage::human_size(total.total()));
+}
+
+fn verify_audit() {
+    let log = match settings().audit_log {
+        Some(ref path) => AuditLog::at(path),
+        None => {
+            eprintln!("There's no audit log, set audit_log in the settings to keep one");
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    let result = log.verify().and_then(|count| Ok((count, log.head()?)));
+    match result {
+        Ok((count, head)) => {
+            println!("{} entr{} in {} hold together", count, if count == 1 { "y" } else { "ies" }, log.path().display());
+            if let Some(head) = head {
+                println!("head {}", head);
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            signals::exit(ExitCode::Failure);
+        },
+    }
+}
+
+fn show_history(matches: &ArgMatches) {
+    let log = match HistoryLog::default_log() {
+        Some(log) => log,
+        None => {
+            eprintln!("There's no data directory to keep the history in");
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    let date = |arg: &str| matches.value_of(arg).map(|date| match history::parse_date(date) {
+        Ok(time) => time,
+        Err(e) => {
+            eprintln!("{}", e);
+            signals::exit(ExitCode::Usage);
+        },
+    });
+    let filter = HistoryFilter {
+        manager: matches.value_of("manager").map(String::from),
+        package: matches.value_of("package").map(String::from),
+        operation: matches.value_of("operation").map(|operation| operation.parse().unwrap()),
+        since: date("since"),
+        until: date("until"),
+    };
+    let entries = match log.query(&filter) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", log.path().display(), e);
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    let mut out: Output<HistoryInfo> = Output::new(matches, "history");
+    for entry in &entries {
+        if out.is_human() {
+            println!("{}", entry);
+        }
+        out.push(HistoryInfo::from(entry));
+    }
+    out.finish();
+}
+
+fn undo(matches: &ArgMatches) {
+    let log = match HistoryLog::default_log() {
+        Some(log) => log,
+        None => {
+            eprintln!("There's no data directory to keep the history in");
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    let managers = load_managers(matches);
+    let transaction = match Transaction::last(&log) {
+        Ok(Some(transaction)) => transaction,
+        Ok(None) => {
+            eprintln!("There's nothing to undo");
+            signals::exit(ExitCode::Failure);
+        },
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", log.path().display(), e);
+            signals::exit(ExitCode::Failure);
+        },
+    };
+    let plan = match transaction.plan(&managers) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("Can't undo {}: {}", transaction.entry, e);
+            signals::exit(ExitCode::from_error(&e));
+        },
+    };
+    let format = Format::of(matches);
+    if format == Format::Human {
+        println!("Undoing: {}", transaction.entry);
+        println!("Will run: {}", plan.command_line);
+    }
+    //A dry run doesn't ask, since nothing would be undone either way
+    if matches.is_present("dry run")
+        || !confirm_request(matches, format, &Request::new(confirm::Action::Rollback, "Undo it?").with_packages(plan.packages.clone())) {
+        //Machine-readable formats list the changes when they aren't made
+        let mut out: Output<ChangeInfo> = Output::new(matches, "undo");
+        let action = if plan.operation == Operation::Install { Action::Install } else { Action::Remove };
+        for package in &plan.packages {
+            out.push(ChangeInfo { action, manager: plan.manager.clone(), package: package.clone() });
+        }
+        return out.finish();
+    }
+    let result = transaction.undo(&log, &managers);
+    let mut out: Output<Outcome> = Output::new(matches, "undo");
+    out.record(ExitCode::from_result(&result, FailureKind::Other));
+    out.push(Outcome::new(&plan.manager, plan.operation.name(), Some(&plan.packages.join(" ")), &result));
+    if out.is_human() {
+        report(&plan.manager, result);
+    }
+    out.finish();
+}
+
+/// Serve the daemon's socket API until it fails
+fn daemon(matches: &ArgMatches) {
+    let path = match matches.value_of("socket") {
+        Some(path) => PathBuf::from(path),
+        None => daemon::socket_path(),
+    };
+    let resolver = Resolver::new(ResolutionPolicy::PreferPriority)
+        .with_aliases(load_aliases())
+        .with_rules(load_rules());
+    let daemon = daemon::Daemon::new(load_managers(matches), resolver);
+    println!("Listening on {}", path.display());
+    if let Err(e) = daemon.serve(&path) {
+        eprintln!("The daemon stopped: {}", e);
+        signals::exit(ExitCode::Failure);
+    }
+}
+
+fn trust(matches: &ArgMatches) {
+    if let Some(matches) = matches.subcommand_matches("add") {
+        run_on_manager(matches, "trust-add", "add_key", matches.value_of("key").unwrap());
+    } else if let Some(matches) = matches.subcommand_matches("list") {
+        let managers = load_managers(matches);
+        let mut out: Output<KeyInfo> = Output::new(matches, "trust-list");
+        let listed = keys::list_all(managers.as_slice());
+        out.record(listed.exit_code());
+        for (manager, result) in listed {
+            match result {
+                Ok(keys) => for key in keys {
+                    if out.is_human() {
+                        println!("{}", key);
+                    }
+                    out.push(KeyInfo::from(&key));
+                },
+                Err(e) => out.error(&manager.name, format!("{}: couldn't list the keys: {}", manager.name, e)),
+            }
+        }
+        out.finish();
+    }
+}
+
+fn repo(matches: &ArgMatches) {
+    if let Some(matches) = matches.subcommand_matches("add") {
+        run_on_manager(matches, "repo-add", "add_repo", matches.value_of("repo").unwrap());
+    } else if let Some(matches) = matches.subcommand_matches("remove") {
+        run_on_manager(matches, "repo-remove", "remove_repo", matches.value_of("repo").unwrap());
+    } else if let Some(matches) = matches.subcommand_matches("list") {
+        let managers = load_managers(matches);
+        let mut out: Output<RepoInfo> = Output::new(matches, "repo-list");
+        let listed = repos::list_all(managers.as_slice());
+        out.record(listed.exit_code());
+        for (manager, result) in listed {
+            match result {
+                Ok(repos) => for repo in repos {
+                    if out.is_human() {
+                        println!("{}", repo);
+                    }
+                    out.push(RepoInfo::from(&repo));
+                },
+                Err(e) => out.error(&manager.name, format!("{}: couldn't list the repositories: {}", manager.name, e)),
+            }
+        }
+        out.finish();
+    }
+}
+
+/// Run a command of the package manager given with --manager, exiting if it isn't configured or
+/// doesn't have the command
+fn run_on_manager(matches: &ArgMatches, name: &'static str, command: &str, args: &str) {
+    let manager_name = matches.value_of("manager").unwrap();
+    let managers = load_managers(matches);
+    let manager = match managers.get(manager_name) {
+        Some(manager) if manager.has_command(command) => manager,
+        Some(_) => {
+            eprintln!("{} has no {} command", manager_name, command);
+            signals::exit(ExitCode::Unsupported);
+        },
+        None => {
+            eprintln!("{} isn't configured", manager_name);
+            signals::exit(ExitCode::Usage);
+        },
+    };
+    let mut out: Output<Outcome> = Output::new(matches, name);
+    let result = execute(manager, command, args, out.format);
+    out.outcome(manager, command, Some(args), &result);
+    if out.is_human() {
+        report(manager_name, result);
+    }
+    out.finish();
+}
+
+/// Run an extra command of the package manager given with --manager, exiting if it isn't
+/// configured or doesn't have the extra command
+fn extra(matches: &ArgMatches) {
+    let manager_name = matches.value_of("manager").unwrap();
+    let command = matches.value_of("command").unwrap();
+    let args = matches.values_of("args").map_or_else(Vec::new, |args| args.collect()).join(" ");
+    let managers = load_managers(matches);
+    let manager = match managers.get(manager_name) {
+        Some(manager) if manager.extras.contains_key(command) => manager,
+        Some(_) => {
+            eprintln!("{} has no extra command {}", manager_name, command);
+            signals::exit(ExitCode::Unsupported);
+        },
+        None => {
+            eprintln!("{} isn't configured", manager_name);
+            signals::exit(ExitCode::Usage);
+        },
+    };
+    let mut out: Output<Outcome> = Output::new(matches, "extra");
+    let result = manager.run_extra(command, &args);
+    out.outcome(manager, command, Some(&args), &result);
+    if out.is_human() {
+        report(manager_name, result);
+    }
+    out.finish();
+}
+
+/// List the commands and extra commands of each package manager
+fn capabilities(matches: &ArgMatches) {
+    let mut out: Output<CapabilityInfo> = Output::new(matches, "capabilities");
+    for manager in load_managers(matches) {
+        let capabilities = CapabilityInfo::list(&manager);
+        if out.is_human() {
+            let (extras, commands): (Vec<&CapabilityInfo>, Vec<&CapabilityInfo>) = capabilities.iter().partition(|c| c.extra);
+            let names = |list: &[&CapabilityInfo]| list.iter().map(|c| c.command.as_str()).collect::<Vec<&str>>().join(", ");
+            println!("{}: {}", manager.name, names(&commands));
+            if !extras.is_empty() {
+                println!("{} extras: {}", manager.name, names(&extras));
+            }
+        }
+        capabilities.into_iter().for_each(|capability| out.push(capability));
+    }
+    out.finish();
+}
+
+fn pin(matches: &ArgMatches) {
+    let req = match matches.value_of("req").map(|req| req.parse::<VersionReq>()) {
+        Some(Ok(req)) => Some(req),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            signals::exit(ExitCode::Usage);
+        },
+        None => None,
+    };
+    let mut pins = load_pins();
+    pins.pin(Pin {
+        package: matches.value_of("package").unwrap().to_owned(),
+        manager: matches.value_of("manager").unwrap().to_owned(),
+        version: matches.value_of("version").map(String::from),
+        req,
+    });
+    save_pins(&pins);
+}
+
+fn unpin(matches: &ArgMatches) {
+    let mut pins = load_pins();
+    let package = matches.value_of("package").unwrap();
+    if pins.unpin(package, matches.value_of("manager")) {
+        save_pins(&pins);
+    } else {
+        eprintln!("{} isn't pinned", package);
+    }
+}
+
+/// Save the default pin list, exiting if it can't be written
+fn save_pins(pins: &PinList) {
+    if let Err(e) = pins.save() {
+        eprintln!("Couldn't save the pinned packages: {}", e);
+        signals::exit(ExitCode::Failure);
+    }
+}
 
 fn main() {
+    //Library logging is shown by setting RUST_LOG, such as RUST_LOG=upm_lib=debug
+    env_logger::init();
+    signals::install();
 
     let managers_arg = Arg::with_name("manager")
          .short("m")
          .long("manager")
          .help("Specifies the package managers to search for the package in")
          .value_name("MANAGER")
-         .takes_value(true);
+         .takes_value(true)
+         .multiple(true)
+         .number_of_values(1);
     let exclude_managers = Arg::with_name("excludes managers")
         .long("exclude-managers")
         .help("Specifies package managers to not use")
         .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
         .value_name("MANAGER");
 
     //Clap is awesome! 
-    let matches = App::new("universal package manager")
+    let app = App::new("universal package manager")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Universal package manager provides a single interface for basic \npackage management across multiple package managers.")
-        .global_setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::ArgRequiredElseHelp)
         .arg(Arg::with_name("list managers")
              .long("list-managers")
              .help("list the package managers available on this system"))
+        .arg(Arg::with_name("non interactive")
+             .long("non-interactive")
+             .global(true)
+             .help("run package managers without prompts, for scripts and CI"))
+        .arg(Arg::with_name("output")
+             .long("output")
+             .global(true)
+             .takes_value(true)
+             .value_name("FORMAT")
+             .possible_values(&["human", "json"])
+             .help("print results for people, or as JSON following upm's versioned schema"))
+        .arg(Arg::with_name("porcelain")
+             .long("porcelain")
+             .global(true)
+             .conflicts_with("output")
+             .help("print results as stable tab separated lines for scripts"))
+        .arg(Arg::with_name("events")
+             .long("events")
+             .global(true)
+             .takes_value(true)
+             .value_name("FORMAT")
+             .possible_values(&["ndjson"])
+             .help("stream every command started, line of output, result, and error as JSON lines to stderr"))
+        .arg(Arg::with_name("events fd")
+             .long("events-fd")
+             .global(true)
+             .takes_value(true)
+             .value_name("FD")
+             .requires("events")
+             .help("write the event stream to this open file descriptor instead of stderr"))
+        .arg(Arg::with_name("config dir")
+             .long("config-dir")
+             .global(true)
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("DIR")
+             .help("read package manager definitions from this directory instead of the configured ones"))
+        .arg(Arg::with_name("no refresh")
+             .long("no-refresh")
+             .global(true)
+             .help("don't refresh stale package metadata before searching or upgrading"))
+        .arg(Arg::with_name("wait")
+             .long("wait")
+             .global(true)
+             .help("wait for other upm processes to finish changing packages (the default)"))
+        .arg(Arg::with_name("no wait")
+             .long("no-wait")
+             .global(true)
+             .conflicts_with("wait")
+             .help("fail instead of waiting for other upm processes to finish changing packages"))
         .subcommand(SubCommand::with_name("query")
                     .about("Search for a package")
+                    .setting(AppSettings::ArgRequiredElseHelp)
                     .arg(&managers_arg)
-                    .arg(&exclude_managers))
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("kind")
+                         .long("kind")
+                         .value_name("KIND")
+                         .possible_values(&["application", "library", "tool"])
+                         .help("Only show packages of this kind"))
+                    .arg(Arg::with_name("package")
+                         .help("The package to search for")
+                         .value_name("PACKAGE")
+                         .required(true)))
         .subcommand(SubCommand::with_name("install")
                     .about("Search for a package and then install via a chosen package manager")
+                    .setting(AppSettings::ArgRequiredElseHelp)
                     .arg(&managers_arg)
-                    .arg(&exclude_managers))
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("package")
+                         .help("The package to install")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("fetch")
+                    .about("Download a package without installing it, such as for an offline install")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("dir")
+                         .help("The directory to download to, the current directory by default")
+                         .short("d")
+                         .long("dir")
+                         .value_name("DIR"))
+                    .arg(Arg::with_name("package")
+                         .help("The package to download")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("which")
+                    .about("Show which package managers offer a package and which one installed it")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("package")
+                         .help("The package to look for")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("status")
+                    .about("Compare the installed version of a package with the version each package manager would install")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("package")
+                         .help("The package to check")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("owns")
+                    .about("Find the package that installed a file")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("file")
+                         .help("The file to find the owner of")
+                         .value_name("FILE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("provides")
+                    .about("Find the packages that provide a file, library, or virtual package")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("capability")
+                         .help("What the package provides, such as libssl.so.3")
+                         .value_name("CAPABILITY")
+                         .required(true)))
         .subcommand(SubCommand::with_name("uninstall")
                     .about("Search for an installed package and then uninstall it")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("yes")
+                         .short("y")
+                         .long("yes")
+                         .help("Remove the package without asking, even if other packages depend on it"))
+                    .arg(Arg::with_name("package")
+                         .help("The package to uninstall")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("rdeps")
+                    .about("List the installed packages that depend on a package")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("package")
+                         .help("The package to find the dependents of")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("upgrade")
+                    .about("Upgrade the given packages, or every package when none are given")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("yes")
+                         .short("y")
+                         .long("yes")
+                         .help("Upgrade every package without asking"))
+                    .arg(Arg::with_name("changelog")
+                         .long("changelog")
+                         .help("Show what changed in the pending upgrades before asking to upgrade every package"))
+                    .arg(Arg::with_name("package")
+                         .help("The packages to upgrade")
+                         .value_name("PACKAGE")
+                         .multiple(true)))
+        .subcommand(SubCommand::with_name("refresh")
+                    .about("Download the latest package metadata of every package manager")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("status")
+                         .long("status")
+                         .help("Show how old the metadata is instead of refreshing it")))
+        .subcommand(SubCommand::with_name("clean")
+                    .about("Reclaim disk space by cleaning the caches of every package manager")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("autoremove")
+                         .long("autoremove")
+                         .help("Also remove packages that were installed as dependencies and aren't needed anymore")))
+        .subcommand(SubCommand::with_name("du")
+                    .about("Show the disk space used by each package manager")
                     .arg(&managers_arg)
                     .arg(&exclude_managers))
-        .get_matches();
-
-    if let Some(_matches) = matches.subcommand_matches("query") {
-        query()
-    } else if let Some(_matches) = matches.subcommand_matches("install") {
-        install()
-    } else if let Some(_matches) = matches.subcommand_matches("uninstall") {
-        uninstall()
+        .subcommand(SubCommand::with_name("history")
+                    .about("Show the packages upm installed, removed, and upgraded, oldest first")
+                    .arg(Arg::with_name("manager")
+                         .help("Only show commands run with this package manager")
+                         .short("m")
+                         .long("manager")
+                         .value_name("MANAGER"))
+                    .arg(Arg::with_name("operation")
+                         .help("Only show this kind of command")
+                         .long("operation")
+                         .value_name("OPERATION")
+                         .possible_values(&["install", "remove", "upgrade", "upgrade_all", "clean_cache", "autoremove"]))
+                    .arg(Arg::with_name("since")
+                         .help("Only show commands run on or after this date")
+                         .long("since")
+                         .value_name("YYYY-MM-DD"))
+                    .arg(Arg::with_name("until")
+                         .help("Only show commands run before this date")
+                         .long("until")
+                         .value_name("YYYY-MM-DD"))
+                    .arg(Arg::with_name("package")
+                         .help("Only show commands run for this package")
+                         .value_name("PACKAGE")))
+        .subcommand(SubCommand::with_name("verify-audit")
+                    .about("Check that the audit log hasn't been tampered with and show the hash vouching for it"))
+        .subcommand(SubCommand::with_name("undo")
+                    .about("Revert the most recent install or removal made through upm")
+                    .arg(Arg::with_name("dry run")
+                         .long("dry-run")
+                         .help("Show the command that would be run without running it"))
+                    .arg(Arg::with_name("yes")
+                         .short("y")
+                         .long("yes")
+                         .help("Undo without asking")))
+        .subcommand(SubCommand::with_name("daemon")
+                    .about("Keep package managers loaded and serve searches and installs over a socket")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("socket")
+                         .long("socket")
+                         .help("The socket to listen on instead of upm.sock in the runtime directory")
+                         .value_name("PATH")))
+        .subcommand(SubCommand::with_name("trust")
+                    .about("Manage the keys package managers trust their repositories with")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("add")
+                                .about("Trust a repository signing key")
+                                .setting(AppSettings::ArgRequiredElseHelp)
+                                .arg(Arg::with_name("manager")
+                                     .help("The package manager to trust the key with")
+                                     .short("m")
+                                     .long("manager")
+                                     .value_name("MANAGER")
+                                     .required(true))
+                                .arg(Arg::with_name("key")
+                                     .help("The key file or URL")
+                                     .value_name("KEY")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("list")
+                                .about("List the trusted repository signing keys")
+                                .arg(&managers_arg)
+                                .arg(&exclude_managers)))
+        .subcommand(SubCommand::with_name("repo")
+                    .about("Manage the repositories package managers install from")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("add")
+                                .about("Enable a repository, such as a PPA or a brew tap")
+                                .setting(AppSettings::ArgRequiredElseHelp)
+                                .arg(Arg::with_name("manager")
+                                     .help("The package manager to enable the repository in")
+                                     .short("m")
+                                     .long("manager")
+                                     .value_name("MANAGER")
+                                     .required(true))
+                                .arg(Arg::with_name("repo")
+                                     .help("The repository as the package manager names it")
+                                     .value_name("REPO")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("remove")
+                                .about("Disable a repository")
+                                .setting(AppSettings::ArgRequiredElseHelp)
+                                .arg(Arg::with_name("manager")
+                                     .help("The package manager to disable the repository in")
+                                     .short("m")
+                                     .long("manager")
+                                     .value_name("MANAGER")
+                                     .required(true))
+                                .arg(Arg::with_name("repo")
+                                     .help("The repository as the package manager names it")
+                                     .value_name("REPO")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("list")
+                                .about("List the enabled repositories")
+                                .arg(&managers_arg)
+                                .arg(&exclude_managers)))
+        .subcommand(SubCommand::with_name("extra")
+                    .about("Run one of a package manager's extra commands, such as npm outdated")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .setting(AppSettings::TrailingVarArg)
+                    .arg(Arg::with_name("manager")
+                         .help("The package manager to run the command of")
+                         .short("m")
+                         .long("manager")
+                         .value_name("MANAGER")
+                         .required(true))
+                    .arg(Arg::with_name("command")
+                         .help("The name of the extra command")
+                         .value_name("COMMAND")
+                         .required(true))
+                    .arg(Arg::with_name("args")
+                         .help("Arguments passed on to the command")
+                         .value_name("ARGS")
+                         .multiple(true)))
+        .subcommand(SubCommand::with_name("audit")
+                    .about("Check installed packages for damaged files and known vulnerabilities")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers))
+        .subcommand(SubCommand::with_name("capabilities")
+                    .about("List the commands and extra commands each package manager has")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers))
+        .subcommand(SubCommand::with_name("pin")
+                    .about("Hold a package back from upgrades")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(Arg::with_name("manager")
+                         .help("The package manager the package is installed with")
+                         .short("m")
+                         .long("manager")
+                         .value_name("MANAGER")
+                         .required(true))
+                    .arg(Arg::with_name("version")
+                         .help("The version the package is held at")
+                         .long("version")
+                         .value_name("VERSION"))
+                    .arg(Arg::with_name("req")
+                         .help("Let the package be upgraded within a range of versions, such as ^20")
+                         .long("req")
+                         .value_name("RANGE"))
+                    .arg(Arg::with_name("package")
+                         .help("The package to pin")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("unpin")
+                    .about("Let a pinned package be upgraded again")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(Arg::with_name("manager")
+                         .help("Only unpin the package for this package manager")
+                         .short("m")
+                         .long("manager")
+                         .value_name("MANAGER"))
+                    .arg(Arg::with_name("package")
+                         .help("The package to unpin")
+                         .value_name("PACKAGE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("pins")
+                    .about("List the pinned packages"))
+        .subcommand(SubCommand::with_name("export")
+                    .about("Write a manifest of the installed packages")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("file")
+                         .help("The file to write the manifest to instead of printing it")
+                         .value_name("FILE")))
+        .subcommand(SubCommand::with_name("apply")
+                    .about("Install the packages in a manifest that are missing")
+                    .setting(AppSettings::ArgRequiredElseHelp)
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers)
+                    .arg(Arg::with_name("remove extraneous")
+                         .long("remove-extraneous")
+                         .help("Also remove packages that aren't in the manifest"))
+                    .arg(Arg::with_name("dry run")
+                         .long("dry-run")
+                         .help("Show the changes without making them"))
+                    .arg(Arg::with_name("yes")
+                         .short("y")
+                         .long("yes")
+                         .help("Make the changes without asking"))
+                    .arg(Arg::with_name("file")
+                         .help("The manifest to apply")
+                         .value_name("FILE")
+                         .required(true)))
+        .subcommand(SubCommand::with_name("doctor")
+                    .about("Check the configured package managers for problems and packages installed through several of them")
+                    .arg(&managers_arg)
+                    .arg(&exclude_managers))
+        .subcommand(SubCommand::with_name("config")
+                    .about("Manage package manager definitions in the configuration directory")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("new")
+                                .about("Create a commented definition for a new package manager in your configuration directory")
+                                .arg(Arg::with_name("manager")
+                                     .help("The name of the package manager, filled in from upm's built-in definition if it has one")
+                                     .value_name("MANAGER")
+                                     .required(true))
+                                .arg(Arg::with_name("global")
+                                     .long("global")
+                                     .help("write the definition to the global configuration directory for every user")))
+                    .subcommand(SubCommand::with_name("lint")
+                                .about("Check every package manager definition for mistakes without running anything"))
+                    .subcommand(SubCommand::with_name("enable")
+                                .about("Copy a package manager definition into the configuration directory")
+                                .arg(Arg::with_name("file")
+                                     .help("The package manager definition to enable")
+                                     .value_name("FILE")
+                                     .required(true))));
+    #[cfg(feature = "remote")]
+    let app = app.arg(Arg::with_name("remote")
+                      .long("remote")
+                      .global(true)
+                      .takes_value(true)
+                      .value_name("HOST")
+                      .help("run the package managers on another machine over SSH, such as user@host:port"));
+    #[cfg(feature = "tui")]
+    let app = app.subcommand(SubCommand::with_name("tui")
+                             .about("Browse search results in a terminal interface")
+                             .arg(&managers_arg)
+                             .arg(&exclude_managers));
+    let matches = match app.get_matches_safe() {
+        Ok(matches) => matches,
+        //clap exits with 1 for invalid arguments, which is upm's code for other failures
+        Err(ref e) if e.use_stderr() => {
+            eprintln!("{}", e);
+            signals::exit(ExitCode::Usage);
+        },
+        Err(e) => e.exit(),
+    };
+
+    #[cfg(feature = "tui")]
+    {
+        if let Some(matches) = matches.subcommand_matches("tui") {
+            return tui::run(matches);
+        }
+    }
+
+    let _lock = match matches.subcommand_name() {
+        Some("install") | Some("uninstall") | Some("upgrade") | Some("apply") | Some("clean") | Some("undo") | Some("extra") => Some(lock(&matches)),
+        Some("refresh") if !matches.subcommand_matches("refresh").is_some_and(|m| m.is_present("status")) => Some(lock(&matches)),
+        Some("trust") if matches.subcommand_matches("trust").and_then(|m| m.subcommand_matches("add")).is_some() => Some(lock(&matches)),
+        Some("repo") if matches.subcommand_matches("repo").and_then(|m| m.subcommand_name()) != Some("list") => Some(lock(&matches)),
+        Some("config") if matches.subcommand_matches("config").and_then(|m| m.subcommand_name()) != Some("lint") => Some(lock(&matches)),
+        Some("pin") | Some("unpin") => Some(lock(&matches)),
+        _ => None,
+    };
+
+    if let Some(matches) = matches.subcommand_matches("query") {
+        query(matches)
+    } else if let Some(matches) = matches.subcommand_matches("install") {
+        install(matches)
+    } else if let Some(matches) = matches.subcommand_matches("fetch") {
+        fetch(matches)
+    } else if let Some(matches) = matches.subcommand_matches("which") {
+        which(matches)
+    } else if let Some(matches) = matches.subcommand_matches("status") {
+        status(matches)
+    } else if let Some(matches) = matches.subcommand_matches("owns") {
+        owns(matches)
+    } else if let Some(matches) = matches.subcommand_matches("provides") {
+        provides(matches)
+    } else if let Some(matches) = matches.subcommand_matches("uninstall") {
+        uninstall(matches)
+    } else if let Some(matches) = matches.subcommand_matches("rdeps") {
+        reverse_deps(matches)
+    } else if let Some(matches) = matches.subcommand_matches("upgrade") {
+        upgrade(matches)
+    } else if let Some(matches) = matches.subcommand_matches("config") {
+        config(matches)
+    } else if let Some(matches) = matches.subcommand_matches("refresh") {
+        refresh(matches)
+    } else if let Some(matches) = matches.subcommand_matches("clean") {
+        clean(matches)
+    } else if let Some(matches) = matches.subcommand_matches("du") {
+        disk_usage(matches)
+    } else if let Some(matches) = matches.subcommand_matches("history") {
+        show_history(matches)
+    } else if matches.subcommand_matches("verify-audit").is_some() {
+        verify_audit()
+    } else if let Some(matches) = matches.subcommand_matches("undo") {
+        undo(matches)
+    } else if let Some(matches) = matches.subcommand_matches("daemon") {
+        daemon(matches)
+    } else if let Some(matches) = matches.subcommand_matches("trust") {
+        trust(matches)
+    } else if let Some(matches) = matches.subcommand_matches("repo") {
+        repo(matches)
+    } else if let Some(matches) = matches.subcommand_matches("extra") {
+        extra(matches)
+    } else if let Some(matches) = matches.subcommand_matches("capabilities") {
+        capabilities(matches)
+    } else if let Some(matches) = matches.subcommand_matches("pin") {
+        pin(matches)
+    } else if let Some(matches) = matches.subcommand_matches("unpin") {
+        unpin(matches)
+    } else if let Some(matches) = matches.subcommand_matches("pins") {
+        let mut out: Output<PinInfo> = Output::new(matches, "pins");
+        for pin in load_pins().pins() {
+            if out.is_human() {
+                println!("{}", pin);
+            }
+            out.push(PinInfo::from(pin));
+        }
+        out.finish();
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        export(matches)
+    } else if let Some(matches) = matches.subcommand_matches("apply") {
+        apply(matches)
+    } else if let Some(matches) = matches.subcommand_matches("doctor") {
+        doctor(matches)
+    } else if let Some(matches) = matches.subcommand_matches("audit") {
+        audit(matches)
     } else if matches.is_present("list managers") {
-        //TODO
+        let mut out: Output<ManagerInfo> = Output::new(&matches, "list-managers");
+        for manager in find_package_managers(&matches) {
+            if out.is_human() {
+                println!("{}", manager.name);
+            }
+            out.push(ManagerInfo { name: manager.name, priority: manager.priority });
+        }
+        out.finish();
+    }
+
+    //A command that was interrupted but finished on its own still exits with the signal's code
+    if let Some(signal) = signals::received() {
+        std::process::exit(signals::exit_code(signal));
     }
 }
 
@@ -0,0 +1,132 @@
+//! Printing the results of a command for people, or for other programs with `--output json` or
+//! `--porcelain`. The machine-readable formats follow the schema in `upm_lib::schema`. Once the
+//! results are printed upm exits with the code for how the command went.
+
+use std::io::{self, Write};
+use std::process::ExitStatus;
+
+use clap::ArgMatches;
+use failure::Error;
+use serde::Serialize;
+use serde_json;
+use upm_lib::PackageManager;
+use upm_lib::exit::ExitCode;
+use upm_lib::multi::Completion;
+use upm_lib::schema::{Document, ManagerError, Outcome, Porcelain};
+use upm_lib::settings::OutputFormat;
+
+/// How the results of a command are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Free form text for people
+    Human,
+    /// A single JSON document
+    Json,
+    /// One line of tab separated fields per record
+    Porcelain,
+}
+
+impl Format {
+    /// Get the format chosen by the global --output and --porcelain arguments, or else by the
+    /// settings
+    pub fn of(matches: &ArgMatches) -> Format {
+        if matches.is_present("porcelain") {
+            Format::Porcelain
+        } else if let Some(format) = matches.value_of("output") {
+            if format == "json" { Format::Json } else { Format::Human }
+        } else {
+            match super::settings().output {
+                Some(OutputFormat::Json) => Format::Json,
+                Some(OutputFormat::Porcelain) => Format::Porcelain,
+                _ => Format::Human,
+            }
+        }
+    }
+}
+
+/// Collects the records and errors of a command to print them once it's done. In the human format
+/// nothing is collected: the command prints as it goes and errors are printed immediately. The
+/// exit codes of what the command did are collected in every format.
+pub struct Output<T> {
+    pub format: Format,
+    command: &'static str,
+    records: Vec<T>,
+    errors: Vec<ManagerError>,
+    codes: Vec<ExitCode>,
+}
+
+impl<T: Serialize + Porcelain> Output<T> {
+    pub fn new(matches: &ArgMatches, command: &'static str) -> Output<T> {
+        Output {
+            format: Format::of(matches),
+            command,
+            records: Vec::new(),
+            errors: Vec::new(),
+            codes: Vec::new(),
+        }
+    }
+
+    /// Is the output for people?
+    pub fn is_human(&self) -> bool {
+        self.format == Format::Human
+    }
+
+    pub fn push(&mut self, record: T) {
+        self.records.push(record);
+    }
+
+    /// Record how part of the command went, such as the command of one package manager
+    pub fn record(&mut self, code: ExitCode) {
+        self.codes.push(code);
+    }
+
+    /// The code for everything recorded so far
+    pub fn code(&self) -> ExitCode {
+        ExitCode::combine(self.codes.iter().cloned())
+    }
+
+    /// Report a package manager that failed. The message is printed as is for people.
+    pub fn error(&mut self, manager: &str, message: String) {
+        match self.format {
+            Format::Human => eprintln!("{}", message),
+            _ => self.errors.push(ManagerError { manager: manager.to_owned(), message }),
+        }
+    }
+
+    /// Print what was collected, then exit if anything failed
+    pub fn finish(self) {
+        let code = self.code();
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        match self.format {
+            Format::Human => {},
+            Format::Json => {
+                let document = Document::new(self.command, self.records, self.errors);
+                if let Err(e) = serde_json::to_writer(&mut stdout, &document) {
+                    eprintln!("Couldn't write the output: {}", e);
+                }
+                let _ = writeln!(stdout);
+            },
+            Format::Porcelain => {
+                for record in &self.records {
+                    let _ = writeln!(stdout, "{}", record.porcelain());
+                }
+                for error in &self.errors {
+                    eprintln!("{}", error.porcelain());
+                }
+            },
+        }
+        if !code.is_success() {
+            super::signals::exit(code);
+        }
+    }
+}
+
+impl Output<Outcome> {
+    /// Record the result of a package manager command, understanding its exit status with the
+    /// package manager's exit codes
+    pub fn outcome(&mut self, manager: &PackageManager, command: &str, package: Option<&str>, result: &Result<ExitStatus, Error>) {
+        self.record(result.exit_code(manager, command));
+        self.push(Outcome::new(&manager.name, command, package, result));
+    }
+}
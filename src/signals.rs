@@ -0,0 +1,77 @@
+//! Stopping cleanly on Ctrl+C. The first SIGINT, SIGTERM, or SIGHUP cancels the token every
+//! package manager is given, so the commands they're running are sent SIGTERM and commands that
+//! change packages are recorded in the history as interrupted. upm then exits with 128 plus the
+//! signal number, as shells do. If the commands haven't stopped within their grace period and a
+//! little more, upm exits without waiting for them, and a second signal kills upm right away.
+
+use std::process;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use std::time::Duration;
+use libc;
+use upm_lib::cancel::CancellationToken;
+use upm_lib::exit::ExitCode;
+
+/// The signals that stop upm
+const SIGNALS: &[libc::c_int] = &[libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+/// How long after the grace period upm waits for its commands before exiting anyway
+const EXTRA_WAIT: Duration = Duration::from_secs(2);
+
+//How often the watcher checks for a signal
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+//The signal received, or 0 if none has been
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handle(signal: libc::c_int) {
+    RECEIVED.store(signal, Ordering::SeqCst);
+}
+
+/// The token cancelled when upm receives a signal
+pub fn token() -> &'static CancellationToken {
+    static TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+    TOKEN.get_or_init(CancellationToken::new)
+}
+
+/// Catch the signals that stop upm, watching for them on another thread
+pub fn install() {
+    for &signal in SIGNALS {
+        unsafe { libc::signal(signal, handle as *const () as libc::sighandler_t); }
+    }
+    thread::spawn(|| {
+        let signal = loop {
+            match received() {
+                Some(signal) => break signal,
+                None => thread::sleep(POLL_INTERVAL),
+            }
+        };
+        for &signal in SIGNALS {
+            unsafe { libc::signal(signal, libc::SIG_DFL); }
+        }
+        eprintln!("Interrupted, stopping the package managers");
+        token().cancel();
+        thread::sleep(token().grace_period() + EXTRA_WAIT);
+        eprintln!("The package managers didn't stop, exiting without them");
+        process::exit(exit_code(signal));
+    });
+}
+
+/// The signal upm received, if it has received one
+pub fn received() -> Option<i32> {
+    match RECEIVED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// The code upm exits with after a signal
+pub fn exit_code(signal: i32) -> i32 {
+    128 + signal
+}
+
+/// Exit with the code, or with the code for the signal if upm received one
+pub fn exit(code: ExitCode) -> ! {
+    process::exit(received().map_or(code.code(), exit_code))
+}
@@ -0,0 +1,215 @@
+//! A terminal interface for browsing search results, which will often exceed the scrollback
+//! buffer when printed. The highlighted package can be installed or uninstalled, in which case the
+//! interface closes first so that the package manager has the terminal to itself.
+
+use std::io;
+
+use clap::ArgMatches;
+use ratatui::{DefaultTerminal, Frame};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap};
+
+use upm_lib::{Package, PackageManager, StdioPolicy, search_all};
+use upm_lib::confirm::{self, Request};
+use upm_lib::install::InstallOptions;
+use upm_lib::lock::ProcessLock;
+
+use super::{confirm_request, load_managers, report, settings, wait_policy};
+use output::Format;
+
+const HELP: &str = "Enter: search  Tab: switch  i: install  u: uninstall  Esc: quit";
+
+/// What to do with the highlighted package once the interface closes
+#[derive(Clone, Copy)]
+enum Action {
+    Install,
+    Uninstall,
+}
+
+/// Where typed keys go
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Search,
+    Results,
+}
+
+struct State {
+    managers: Vec<PackageManager>,
+    query: String,
+    focus: Focus,
+    results: Vec<Package>,
+    list: ListState,
+    errors: Option<String>,
+    action: Option<Action>,
+}
+
+/// Run the interface until the user quits or chooses a package to install or uninstall, taking the
+/// process lock before changing packages
+pub fn run(matches: &ArgMatches) {
+    let mut state = State {
+        managers: load_managers(matches).into_iter().collect(),
+        query: String::new(),
+        focus: Focus::Search,
+        results: Vec::new(),
+        list: ListState::default(),
+        errors: None,
+        action: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut state);
+    //Give the terminal back before running any package manager commands
+    ratatui::restore();
+    if let Err(e) = result {
+        return eprintln!("The terminal interface failed: {}", e);
+    }
+
+    if let (Some(action), Some(index)) = (state.action, state.list.selected()) {
+        let package = &state.results[index];
+        let manager = &package.owner;
+        let _lock = match ProcessLock::acquire(wait_policy(matches)) {
+            Ok(lock) => lock,
+            Err(e) => return eprintln!("{}", e),
+        };
+        match action {
+            Action::Install => {
+                println!("Installing {} with {}", package.name, manager.name);
+                report(&manager.name, package.install_with(&InstallOptions::new(), StdioPolicy::Inherit)
+                       .map(|output| output.status));
+            },
+            Action::Uninstall => {
+                let request = Request::new(confirm::Action::Remove, format!("Uninstall {} with {}?", package.name, manager.name))
+                    .with_packages(vec![package.name.clone()]);
+                if !confirm_request(matches, Format::Human, &request) {
+                    return;
+                }
+                println!("Uninstalling {} with {}", package.name, manager.name);
+                report(&manager.name, package.uninstall_with(StdioPolicy::Inherit).map(|output| output.status));
+            },
+        }
+    }
+}
+
+//Draw the interface and handle keys until the user quits or chooses a package
+fn event_loop(terminal: &mut DefaultTerminal, state: &mut State) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, state))?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        //Raw mode keeps ^C from interrupting, so it quits like Esc
+        if key.code == KeyCode::Esc || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)) {
+            if state.errors.take().is_none() {
+                return Ok(());
+            }
+            continue;
+        }
+        if state.errors.take().is_some() {
+            continue;
+        }
+        match (state.focus, key.code) {
+            (Focus::Search, KeyCode::Enter) => search(state),
+            (Focus::Search, KeyCode::Backspace) => { state.query.pop(); },
+            (Focus::Search, KeyCode::Char(c)) => state.query.push(c),
+            (Focus::Search, KeyCode::Tab) => state.focus = Focus::Results,
+            (Focus::Results, KeyCode::Tab) => state.focus = Focus::Search,
+            (Focus::Results, KeyCode::Down) => select(state, 1),
+            (Focus::Results, KeyCode::Up) => select(state, -1),
+            (Focus::Results, KeyCode::Char('i')) => choose(state, Action::Install),
+            (Focus::Results, KeyCode::Char('u')) => choose(state, Action::Uninstall),
+            _ => {},
+        }
+        if state.action.is_some() {
+            return Ok(());
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, state: &mut State) {
+    let [search_area, body, help] = Layout::vertical([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)])
+        .areas(frame.area());
+    let [results_area, details_area] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(body);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let border = |focus: Focus| if state.focus == focus { bold } else { Style::default() };
+    let search_block = Block::bordered().title("Search").border_style(border(Focus::Search));
+    let results_block = Block::bordered().title("Results").border_style(border(Focus::Results));
+
+    frame.render_widget(Paragraph::new(state.query.as_str()).block(search_block), search_area);
+    if state.focus == Focus::Search {
+        let column = search_area.x + 1 + state.query.chars().count() as u16;
+        frame.set_cursor_position((column.min(search_area.right().saturating_sub(2)), search_area.y + 1));
+    }
+    let items: Vec<ListItem> = state.results.iter()
+        .map(|package| ListItem::new(format!("{:<10} {} {}", package.owner.name, package.name,
+                                             package.version.get_representation())))
+        .collect();
+    let list = List::new(items)
+        .block(results_block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, results_area, &mut state.list);
+    let details = state.list.selected().and_then(|index| state.results.get(index)).map_or_else(String::new, describe);
+    frame.render_widget(Paragraph::new(details).wrap(Wrap { trim: false }).block(Block::bordered().title("Details")),
+                        details_area);
+    frame.render_widget(Paragraph::new(HELP), help);
+
+    if let Some(ref errors) = state.errors {
+        let area = centered(frame.area());
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(errors.as_str()).wrap(Wrap { trim: false })
+                                .block(Block::bordered().title("Errors")), area);
+    }
+}
+
+//Search every manager and list the results, which come back grouped by manager
+fn search(state: &mut State) {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for (manager, result) in search_all(&state.managers, &state.query, &settings().concurrency()) {
+        match result {
+            Ok(packages) => results.extend(packages),
+            Err(e) => errors.push(format!("{}: {}", manager.name, e)),
+        }
+    }
+    state.results = results;
+    state.list.select(if state.results.is_empty() { None } else { Some(0) });
+    if !state.results.is_empty() {
+        state.focus = Focus::Results;
+    }
+    if !errors.is_empty() {
+        state.errors = Some(errors.join("\n"));
+    }
+}
+
+fn describe(package: &Package) -> String {
+    format!("{}\n\nVersion: {}\nManager: {}\n\n{}",
+            package.name,
+            package.version.get_representation(),
+            package.owner.name,
+            package.description)
+}
+
+//Move the highlight up or down the results, stopping at either end
+fn select(state: &mut State, offset: isize) {
+    if let Some(index) = state.list.selected() {
+        let last = state.results.len().saturating_sub(1) as isize;
+        state.list.select(Some((index as isize + offset).clamp(0, last) as usize));
+    }
+}
+
+//Close the interface to act on the highlighted package, if there is one
+fn choose(state: &mut State, action: Action) {
+    if state.list.selected().is_some() {
+        state.action = Some(action);
+    }
+}
+
+//The middle of the screen, for showing errors over the results
+fn centered(area: Rect) -> Rect {
+    let [_, middle, _] = Layout::vertical([Constraint::Percentage(25), Constraint::Percentage(50), Constraint::Percentage(25)])
+        .areas(area);
+    let [_, middle, _] = Layout::horizontal([Constraint::Percentage(15), Constraint::Percentage(70), Constraint::Percentage(15)])
+        .areas(middle);
+    middle
+}
@@ -0,0 +1,146 @@
+//! Translating package names between package managers. The same software is often packaged under
+//! different names, such as `node` on npm's registry being `nodejs` on Debian, so users can use
+//! one canonical name and an [`AliasResolver`](struct.AliasResolver.html) gives the name to use
+//! with each package manager.
+//!
+//! Aliases can be given for every package manager in `aliases.toml` in the
+//! [data directory](../fn.data_dir.html), with a table for each canonical name:
+//!
+//! ```toml
+//! [node]
+//! apt = "nodejs"
+//! dnf = "nodejs"
+//!
+//! [ripgrep]
+//! brew = "rg"
+//! ```
+//!
+//! A package manager can also give its own aliases in an `aliases` table of its configuration,
+//! such as `[aliases]` with `node = "nodejs"`. These take precedence over the global table.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use toml::Value;
+use super::{Package, PackageManager, data_dir};
+use concurrency::{ConcurrencyPolicy, run_all};
+use multi::MultiResult;
+
+/// Translates canonical package names into the names used by each package manager
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AliasResolver {
+    //Manager specific names by canonical name and then manager name
+    aliases: HashMap<String, HashMap<String, String>>,
+}
+
+/// Get the path of the global alias table, `aliases.toml` in the data directory
+pub fn aliases_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("aliases.toml"))
+}
+
+impl AliasResolver {
+    /// Create a resolver without any global aliases. Aliases in package manager configurations
+    /// are still used.
+    pub fn new() -> AliasResolver {
+        AliasResolver::default()
+    }
+
+    /// Read the global aliases from a TOML value
+    pub fn from_toml(resource: &Value) -> Result<AliasResolver, Error> {
+        let mut resolver = AliasResolver::new();
+        let table = match resource.as_table() {
+            Some(table) => table,
+            None => bail!("The alias table must be a table"),
+        };
+        for (canonical, names) in table {
+            let names = match names.as_table() {
+                Some(names) => names,
+                None => bail!("The aliases of {} must be a table of package manager names", canonical),
+            };
+            for (manager, name) in names {
+                match name.as_str() {
+                    Some(name) => resolver.add(canonical, manager, name),
+                    None => bail!("The alias of {} for {} must be a string", canonical, manager),
+                }
+            }
+        }
+        Ok(resolver)
+    }
+
+    /// Read the global aliases from a file. A file that doesn't exist gives no aliases.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AliasResolver, Error> {
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(AliasResolver::new()),
+            Err(e) => return Err(e.into()),
+        }
+        AliasResolver::from_toml(&content.parse::<Value>()?)
+    }
+
+    /// Read the default global alias table
+    pub fn load_default() -> Result<AliasResolver, Error> {
+        match aliases_path() {
+            Some(path) => AliasResolver::load(path),
+            None => Ok(AliasResolver::new()),
+        }
+    }
+
+    /// Record the name a package manager uses for a canonical name
+    pub fn add(&mut self, canonical: &str, manager: &str, name: &str) {
+        self.aliases.entry(canonical.to_owned())
+            .or_default()
+            .insert(manager.to_owned(), name.to_owned());
+    }
+
+    /// Get the name a package manager uses for a canonical name. Names without an alias are used
+    /// as they are.
+    pub fn translate<'a>(&'a self, name: &'a str, manager: &'a PackageManager) -> &'a str {
+        manager.aliases.get(name)
+            .or_else(|| self.aliases.get(name).and_then(|names| names.get(&manager.name)))
+            .map_or(name, |alias| alias.as_str())
+    }
+
+    /// Search every package manager with a search command for a canonical name, translating it
+    /// for each package manager. The results are given alongside their package manager in the
+    /// same order as the managers.
+    pub fn search_all<'a>(&self, managers: &'a [PackageManager], name: &str, policy: &ConcurrencyPolicy) -> MultiResult<'a, Result<Vec<Package>, Error>> {
+        MultiResult::new("search", run_all(managers, policy, |manager| {
+            if manager.has_command("search") {
+                Some(manager.search_packages(self.translate(name, manager)))
+            } else {
+                None
+            }
+        }).into_iter()
+            .filter_map(|(manager, result)| result.map(|result| (manager, result)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(name: &str) -> PackageManager {
+        PackageManager {
+            name: String::from(name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn translation() {
+        let resource = "[node]\napt = 'nodejs'\n[ripgrep]\nbrew = 'rg'\n".parse::<Value>().unwrap();
+        let resolver = AliasResolver::from_toml(&resource).unwrap();
+        let mut brew = manager("brew");
+        assert_eq!(resolver.translate("node", &manager("apt")), "nodejs");
+        assert_eq!(resolver.translate("node", &brew), "node");
+        assert_eq!(resolver.translate("ripgrep", &brew), "rg");
+        brew.aliases.insert(String::from("ripgrep"), String::from("ripgrep"));
+        assert_eq!(resolver.translate("ripgrep", &brew), "ripgrep");
+        assert!(AliasResolver::from_toml(&"node = 'nodejs'".parse::<Value>().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! A tamper-evident record of every command that changed packages, for compliance. Unlike the
+//! [history](../history/index.html), which is there to answer questions and undo operations, the
+//! audit log is only ever appended to. Each line is a JSON [`AuditEntry`](struct.AuditEntry.html)
+//! holding who ran what, when, and how it ended, along with the SHA-256 hash of the entry before
+//! it and its own hash:
+//!
+//! ```text
+//! {"seq":1,"time":1528311542,"user":"alice","sudo_user":null,"argv":["upm","install","ripgrep"],"manager":"cargo","command":"install","args":"ripgrep","status":0,"prev":"000…","hash":"3f2…"}
+//! ```
+//!
+//! Changing or removing an entry breaks the chain from there on, which
+//! [`verify`](struct.AuditLog.html#method.verify) finds. The [`head`](struct.AuditLog.html#method.head)
+//! hash can be copied somewhere safe now and then so that rewriting the whole log is caught too.
+//! It's enabled with `audit_log` in the [settings](../settings/index.html).
+//!
+//! Entries from several threads are chained in turn. Processes changing packages hold the
+//! [process lock](../lock/index.html), so their entries don't race either.
+
+use std::env;
+use std::fs::{File, OpenOptions, create_dir_all};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use failure::Error;
+use serde_json;
+use sha2::{Digest, Sha256};
+use history::epoch_seconds;
+
+/// The hash the first entry of a log is chained to
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One command recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// The position of the entry in the log, starting from 1
+    pub seq: u64,
+    /// When the command finished, in seconds since the Unix epoch
+    pub time: u64,
+    /// The user running upm
+    pub user: String,
+    /// The user who ran upm through sudo, if it was
+    pub sudo_user: Option<String>,
+    /// The arguments upm was run with
+    pub argv: Vec<String>,
+    /// The package manager that ran the command
+    pub manager: String,
+    /// The command, such as `install` or `remove_local`
+    pub command: String,
+    /// The arguments the command was given, joined by spaces
+    pub args: String,
+    /// The exit code, or None if the command was killed by a signal
+    pub status: Option<i32>,
+    /// The hash of the entry before this one
+    pub prev: String,
+    /// The hash of this entry
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Describe a command run by the current process, not yet chained into a log
+    pub fn new(manager: &str, command: &str, args: &str, status: ExitStatus) -> AuditEntry {
+        let user = env::var("USER").or_else(|_| env::var("LOGNAME")).unwrap_or_else(|_| String::from("unknown"));
+        AuditEntry {
+            seq: 0,
+            time: epoch_seconds(SystemTime::now()),
+            user,
+            sudo_user: env::var("SUDO_USER").ok(),
+            argv: env::args().collect(),
+            manager: manager.to_owned(),
+            command: command.to_owned(),
+            args: args.to_owned(),
+            status: status.code(),
+            prev: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// Compute the hash of the entry: the SHA-256 of its JSON without the hash, as lowercase hex
+    pub fn digest(&self) -> String {
+        let unhashed = AuditEntry { hash: String::new(), ..self.clone() };
+        let json = serde_json::to_string(&unhashed).expect("audit entries always serialize");
+        format!("{:x}", Sha256::digest(json.as_bytes()))
+    }
+}
+
+/// The error returned when an audit log doesn't hold together
+#[derive(Debug, Fail)]
+#[fail(display = "The audit log {:?} was tampered with at line {}: {}", path, line, problem)]
+pub struct Tampered {
+    pub path: PathBuf,
+    pub line: usize,
+    pub problem: String,
+}
+
+/// An append-only, hash-chained log of commands
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    appending: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Use the log at the given path, which is created when the first entry is recorded
+    pub fn at<P: AsRef<Path>>(path: P) -> AuditLog {
+        AuditLog { path: path.as_ref().to_path_buf(), appending: Mutex::new(()) }
+    }
+
+    /// The path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Chain an entry to the end of the log and append it, giving the entry as it was written
+    pub fn record(&self, entry: AuditEntry) -> Result<AuditEntry, Error> {
+        let _appending = self.appending.lock().unwrap_or_else(|e| e.into_inner());
+        let last = self.last()?;
+        let mut entry = AuditEntry {
+            seq: last.as_ref().map_or(1, |last| last.seq + 1),
+            prev: last.map_or_else(|| String::from(GENESIS), |last| last.hash),
+            ..entry
+        };
+        entry.hash = entry.digest();
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())?;
+        Ok(entry)
+    }
+
+    /// Read every entry in the log without checking the chain
+    pub fn entries(&self) -> Result<Vec<AuditEntry>, Error> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The hash of the last entry, which vouches for the whole log, or None if it's empty
+    pub fn head(&self) -> Result<Option<String>, Error> {
+        Ok(self.last()?.map(|entry| entry.hash))
+    }
+
+    /// Check that every entry is chained to the one before it and matches its hash, giving how
+    /// many entries there are. A broken chain gives a [`Tampered`](struct.Tampered.html) error.
+    pub fn verify(&self) -> Result<usize, Error> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let tampered = |line: usize, problem: String| Tampered { path: self.path.clone(), line, problem };
+        let mut prev = String::from(GENESIS);
+        let mut count = 0;
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            //Blank lines aren't entries, as in entries
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: AuditEntry = serde_json::from_str(&line)
+                .map_err(|e| tampered(number + 1, format!("the entry can't be read: {}", e)))?;
+            if entry.seq != count as u64 + 1 {
+                return Err(tampered(number + 1, format!("entry {} follows entry {}", entry.seq, count)).into());
+            }
+            if entry.prev != prev {
+                return Err(tampered(number + 1, String::from("the entry isn't chained to the one before it")).into());
+            }
+            if entry.digest() != entry.hash {
+                return Err(tampered(number + 1, String::from("the entry doesn't match its hash")).into());
+            }
+            prev = entry.hash;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    //The last entry in the log
+    fn last(&self) -> Result<Option<AuditEntry>, Error> {
+        Ok(self.entries()?.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn chained_entries() {
+        let dir = env::temp_dir().join(format!("upm-audit-{}", ::std::process::id()));
+        let log = AuditLog::at(dir.join("audit.log"));
+        assert_eq!(log.verify().unwrap(), 0);
+        let status = Command::new("true").status().unwrap();
+        let first = log.record(AuditEntry::new("apt", "install", "ripgrep", status)).unwrap();
+        let second = log.record(AuditEntry::new("apt", "remove", "ripgrep", status)).unwrap();
+        assert_eq!(first.prev, GENESIS);
+        assert_eq!(second.prev, first.hash);
+        assert_eq!(second.seq, 2);
+        assert_eq!(log.head().unwrap(), Some(second.hash.clone()));
+        assert_eq!(log.verify().unwrap(), 2);
+
+        let content = fs::read_to_string(log.path()).unwrap();
+        fs::write(log.path(), content.replace("\"remove\"", "\"install\"")).unwrap();
+        let error = log.verify().unwrap_err();
+        assert_eq!(error.downcast_ref::<Tampered>().unwrap().line, 2);
+        let lines: Vec<&str> = content.lines().collect();
+        fs::write(log.path(), format!("{}\n\n{}\n", lines[0], lines[1])).unwrap();
+        assert_eq!(log.verify().unwrap(), log.entries().unwrap().len());
+        fs::write(log.path(), format!("{}\n", lines[1])).unwrap();
+        assert!(log.verify().is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
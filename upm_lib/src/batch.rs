@@ -0,0 +1,193 @@
+//! Installing or removing several packages with a single run of a package manager, which is much
+//! faster than running it once per package for most package managers. See
+//! [`PackageManager::install_many`](../struct.PackageManager.html#method.install_many).
+//!
+//! The command still runs interactively, so scripts can't report on each package through their
+//! output. Instead the path of a status file is given in the `UPM_STATUS_FILE` environment
+//! variable. A script that knows what happened to each package writes one line per package to the
+//! file: the name and then `ok` or `failed`, separated by whitespace.
+//!
+//! ```text
+//! ripgrep ok
+//! fd-find failed
+//! ```
+//!
+//! Packages that aren't in the file are [unreported](enum.PackageStatus.html), and only the exit
+//! status of the whole command says what happened to them.
+
+use std::fs::{self, DirBuilder, File};
+use std::io::prelude::*;
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::env;
+use failure::Error;
+
+/// The environment variable that holds the path scripts write package statuses to
+pub const STATUS_FILE_VAR: &str = "UPM_STATUS_FILE";
+
+static NEXT_STATUS_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// What happened to a single package in a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageStatus {
+    /// The package manager reported the package succeeded
+    Succeeded,
+    /// The package manager reported the package failed
+    Failed,
+    /// The package manager didn't report on the package
+    Unreported,
+}
+
+/// The outcome of running a command for several packages at once
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    /// The exit status of the command
+    pub status: ExitStatus,
+    /// The status of each package in the order they were given
+    pub packages: Vec<(String, PackageStatus)>,
+}
+
+impl BatchResult {
+    /// Did the command succeed with no package reported as failed?
+    pub fn is_success(&self) -> bool {
+        self.status.success() && self.failed().next().is_none()
+    }
+
+    /// Get the packages that were reported as failed
+    pub fn failed<'a>(&'a self) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+        Box::new(self.packages.iter()
+                 .filter(|&&(_, status)| status == PackageStatus::Failed)
+                 .map(|(package, _)| package.as_str()))
+    }
+
+    /// Get the status of a package in the batch
+    pub fn status_of(&self, package: &str) -> Option<PackageStatus> {
+        self.packages.iter().find(|(name, _)| name == package).map(|&(_, status)| status)
+    }
+}
+
+//Get a fresh path for a package manager to write statuses to, in a new directory only the user
+//can use. Like mkdtemp, a directory that already exists is never reused, since anyone can create
+//one in the temporary directory.
+pub(crate) fn status_path(manager: &str) -> Result<PathBuf, Error> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    builder.mode(0o700);
+    loop {
+        let number = NEXT_STATUS_DIR.fetch_add(1, Ordering::SeqCst);
+        let dir = env::temp_dir().join(format!("upm-status-{}-{}-{}", manager, process::id(), number));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir.join("status")),
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+//Remove a status file along with the directory it was made in
+pub(crate) fn remove_status_file(path: &Path) {
+    let _ = fs::remove_file(path);
+    if let Some(dir) = path.parent() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+//Read the statuses a package manager wrote for the packages, removing the status file and its
+//directory
+pub(crate) fn read_statuses(path: &Path, packages: &[&str], status: ExitStatus) -> BatchResult {
+    let mut reported = HashMap::new();
+    let mut bytes = Vec::new();
+    if let Ok(mut file) = File::open(path) {
+        if let Err(e) = file.read_to_end(&mut bytes) {
+            warn!("Couldn't read the package statuses in {:?}: {}", path, e);
+        }
+    }
+    remove_status_file(path);
+    //A script that was killed while writing can leave part of a line or invalid UTF-8
+    let content = String::from_utf8_lossy(&bytes);
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some(package), Some("ok")) => { reported.insert(package, PackageStatus::Succeeded); },
+            (Some(package), Some("failed")) => { reported.insert(package, PackageStatus::Failed); },
+            (None, _) => {},
+            _ => warn!("Ignoring malformed package status {:?}", line),
+        }
+    }
+    BatchResult {
+        status,
+        packages: packages.iter()
+            .map(|&package| {
+                let status = reported.get(package).cloned().unwrap_or(PackageStatus::Unreported);
+                (package.to_owned(), status)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn statuses(content: Option<&[u8]>, packages: &[&str], command: &str) -> (BatchResult, PathBuf) {
+        let path = status_path("batch-test").unwrap();
+        if let Some(content) = content {
+            fs::write(&path, content).unwrap();
+        }
+        let status = Command::new(command).status().unwrap();
+        (read_statuses(&path, packages, status), path)
+    }
+
+    #[test]
+    fn status_paths() {
+        let first = status_path("apt").unwrap();
+        let second = status_path("apt").unwrap();
+        assert!(first != second);
+        let dir = first.parent().unwrap();
+        assert!(dir.starts_with(env::temp_dir()));
+        assert!(dir.file_name().unwrap().to_str().unwrap().starts_with("upm-status-apt-"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        for path in &[first, second] {
+            fs::remove_dir(path.parent().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn partial_statuses() {
+        let (result, path) = statuses(Some(b"ripgrep ok\n"), &["ripgrep", "fd-find"], "true");
+        assert_eq!(result.packages, vec![(String::from("ripgrep"), PackageStatus::Succeeded),
+                                         (String::from("fd-find"), PackageStatus::Unreported)]);
+        assert!(result.is_success());
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn missing_statuses() {
+        let (result, path) = statuses(None, &["ripgrep"], "false");
+        assert!(!path.parent().unwrap().exists());
+        assert_eq!(result.status_of("ripgrep"), Some(PackageStatus::Unreported));
+        assert_eq!(result.status_of("exa"), None);
+        assert!(!result.is_success());
+    }
+
+    #[test]
+    fn corrupt_statuses() {
+        let content: &[u8] = b"ripgrep maybe\n\nfd-find failed\n\xff\xfe ok\nexa";
+        let (result, path) = statuses(Some(content), &["ripgrep", "fd-find", "exa"], "true");
+        assert_eq!(result.status_of("ripgrep"), Some(PackageStatus::Unreported));
+        assert_eq!(result.status_of("exa"), Some(PackageStatus::Unreported));
+        assert_eq!(result.failed().collect::<Vec<_>>(), vec!["fd-find"]);
+        assert!(!result.is_success());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,174 @@
+//! Caching of query results. Probing versions and repeating searches means running package
+//! managers that are often slow to start, so a [`QueryCache`](struct.QueryCache.html) can be
+//! attached to a [`PackageManager`](../struct.PackageManager.html) to remember their output. The
+//! cache is kept in memory and optionally on disk under the XDG cache directory, with entries
+//! keyed by the package manager name, the command, and its arguments.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Get the directory upm caches to, `$XDG_CACHE_HOME/upm` or `~/.cache/upm`
+pub fn cache_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_CACHE_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("upm")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("upm")),
+    }
+}
+
+struct Entry {
+    stored: SystemTime,
+    output: String,
+}
+
+/// A cache of command output that expires after a time to live
+pub struct QueryCache {
+    ttl: Duration,
+    directory: Option<PathBuf>,
+    memory: Mutex<HashMap<(String, String, String), Entry>>,
+}
+
+impl QueryCache {
+    /// Create a cache that only lives as long as the process
+    pub fn in_memory(ttl: Duration) -> QueryCache {
+        QueryCache {
+            ttl,
+            directory: None,
+            memory: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a cache that is also stored in the XDG cache directory. If there is no cache
+    /// directory then the cache is only kept in memory.
+    pub fn on_disk(ttl: Duration) -> QueryCache {
+        let mut cache = QueryCache::in_memory(ttl);
+        cache.directory = cache_dir();
+        cache
+    }
+
+    /// Create a cache that is also stored in the given directory
+    pub fn in_directory(ttl: Duration, directory: PathBuf) -> QueryCache {
+        let mut cache = QueryCache::in_memory(ttl);
+        cache.directory = Some(directory);
+        cache
+    }
+
+    /// How long entries stay valid
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Get the cached output of a command if there is an entry that hasn't expired
+    pub fn get(&self, manager: &str, command: &str, args: &str) -> Option<String> {
+        let key = (manager.to_owned(), command.to_owned(), args.to_owned());
+        if let Some(entry) = self.memory.lock().unwrap().get(&key) {
+            if self.is_fresh(entry.stored) {
+                return Some(entry.output.clone());
+            }
+        }
+        let path = self.entry_path(manager, command, args)?;
+        let stored = path.metadata().and_then(|metadata| metadata.modified()).ok()?;
+        if !self.is_fresh(stored) {
+            return None;
+        }
+        let mut output = String::new();
+        File::open(&path).and_then(|mut file| file.read_to_string(&mut output)).ok()?;
+        self.memory.lock().unwrap().insert(key, Entry { stored, output: output.clone() });
+        Some(output)
+    }
+
+    /// Store the output of a command. Failing to write the on-disk cache isn't an error, the
+    /// entry is still kept in memory.
+    pub fn put(&self, manager: &str, command: &str, args: &str, output: &str) {
+        let key = (manager.to_owned(), command.to_owned(), args.to_owned());
+        self.memory.lock().unwrap().insert(key, Entry {
+            stored: SystemTime::now(),
+            output: output.to_owned(),
+        });
+        if let Some(path) = self.entry_path(manager, command, args) {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let _ = File::create(&path).and_then(|mut file| file.write_all(output.as_bytes()));
+        }
+    }
+
+    /// Remove the entry for a command
+    pub fn invalidate(&self, manager: &str, command: &str, args: &str) {
+        let key = (manager.to_owned(), command.to_owned(), args.to_owned());
+        self.memory.lock().unwrap().remove(&key);
+        if let Some(path) = self.entry_path(manager, command, args) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Remove every entry for a package manager
+    pub fn invalidate_manager(&self, manager: &str) {
+        self.memory.lock().unwrap().retain(|key, _| key.0 != manager);
+        if let Some(ref directory) = self.directory {
+            let _ = fs::remove_dir_all(directory.join(manager));
+        }
+    }
+
+    /// Remove every entry
+    pub fn clear(&self) {
+        self.memory.lock().unwrap().clear();
+        if let Some(ref directory) = self.directory {
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+
+    fn is_fresh(&self, stored: SystemTime) -> bool {
+        match stored.elapsed() {
+            Ok(age) => age < self.ttl,
+            //The entry was stored in the future, so the clock has changed and it can't be trusted
+            Err(_) => false,
+        }
+    }
+
+    //Entries are stored as <directory>/<manager>/<command>-<hash of args>
+    fn entry_path(&self, manager: &str, command: &str, args: &str) -> Option<PathBuf> {
+        let directory = self.directory.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        args.hash(&mut hasher);
+        Some(directory.join(manager).join(format!("{}-{:016x}", command, hasher.finish())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_cache() {
+        let cache = QueryCache::in_memory(Duration::from_secs(60));
+        assert_eq!(cache.get("pacman", "search", "ripgrep"), None);
+        cache.put("pacman", "search", "ripgrep", "ripgrep 0.7.1");
+        assert_eq!(cache.get("pacman", "search", "ripgrep"), Some(String::from("ripgrep 0.7.1")));
+        assert_eq!(cache.get("pacman", "search", "fd"), None);
+        cache.invalidate_manager("pacman");
+        assert_eq!(cache.get("pacman", "search", "ripgrep"), None);
+
+        let expired = QueryCache::in_memory(Duration::from_secs(0));
+        expired.put("pacman", "version", "", "5.0.2");
+        assert_eq!(expired.get("pacman", "version", ""), None);
+    }
+
+    #[test]
+    fn disk_cache() {
+        let directory = env::temp_dir().join("upm-disk-cache-test");
+        let cache = QueryCache::in_directory(Duration::from_secs(60), directory.clone());
+        cache.clear();
+        cache.put("cargo", "version", "", "0.24.0");
+        let reloaded = QueryCache::in_directory(Duration::from_secs(60), directory);
+        assert_eq!(reloaded.get("cargo", "version", ""), Some(String::from("0.24.0")));
+        reloaded.invalidate("cargo", "version", "");
+        assert_eq!(reloaded.get("cargo", "version", ""), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Stopping commands while they run, such as when the user of a TUI presses Esc during a long
+//! search. A frontend gives its package managers a
+//! [`CancellationToken`](struct.CancellationToken.html) with
+//! [`set_cancellation`](../struct.PackageManager.html#method.set_cancellation) and cancels it from
+//! another thread. A command that's running is sent SIGTERM, and SIGKILL if it hasn't exited after
+//! the token's grace period, and the operation gives an [`Aborted`](struct.Aborted.html) error.
+//! Commands that change packages are recorded in the [history](../history/index.html) as they
+//! would be if they had finished, with the status they were stopped with. Commands aren't started
+//! once the token is cancelled.
+//!
+//! A cancelled token stays cancelled, so each operation that can be cancelled on its own should
+//! get a new token.
+
+use std::io::{self, Read};
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use failure::Error;
+
+/// How long a command has to exit after SIGTERM before it's killed, unless the token says
+/// otherwise
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+//How often a running command is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A flag shared between the thread running commands and the one that cancels them. Clones share
+/// the flag.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    grace_period: Duration,
+}
+
+impl CancellationToken {
+    /// A token that hasn't been cancelled, with the default grace period
+    pub fn new() -> CancellationToken {
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            grace_period: DEFAULT_GRACE_PERIOD,
+        }
+    }
+
+    /// Give commands a different time to exit after SIGTERM
+    pub fn with_grace_period(mut self, grace_period: Duration) -> CancellationToken {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// How long commands have to exit after SIGTERM
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    /// Stop the commands using this token and every clone of it
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Has the token been cancelled?
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait for a child to exit, terminating it if the token is cancelled first. Gives the status
+    /// it exited with, or an error holding the status it was terminated with.
+    pub fn wait(&self, child: &mut Child) -> io::Result<Result<ExitStatus, ExitStatus>> {
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Ok(status));
+            }
+            if self.is_cancelled() {
+                return Ok(Err(terminate(child, self.grace_period)?));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Wait for a child to exit and collect its output like `Child::wait_with_output`,
+    /// terminating it if the token is cancelled first. Gives an error holding the status it was
+    /// terminated with if it was, without waiting for anything the child started to close its
+    /// pipes.
+    pub fn wait_with_output(&self, mut child: Child) -> io::Result<Result<Output, ExitStatus>> {
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let stdout = thread::spawn(move || read_all(stdout));
+        let stderr = thread::spawn(move || read_all(stderr));
+        Ok(self.wait(&mut child)?.map(|status| Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        }))
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> CancellationToken {
+        CancellationToken::new()
+    }
+}
+
+//Reads a pipe of a child until it's closed, if the child has the pipe
+fn read_all<R: Read>(stream: Option<R>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Some(mut stream) = stream {
+        let _ = stream.read_to_end(&mut buffer);
+    }
+    buffer
+}
+
+/// Send a child SIGTERM, and SIGKILL if it hasn't exited after the grace period
+pub fn terminate(child: &mut Child, grace_period: Duration) -> io::Result<ExitStatus> {
+    let signalled = Command::new("kill").arg("-TERM").arg(child.id().to_string()).status();
+    if signalled.map(|status| status.success()).unwrap_or(false) {
+        let deadline = Instant::now() + grace_period;
+        while Instant::now() < deadline {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    let _ = child.kill();
+    child.wait()
+}
+
+/// The error given by an operation whose command was cancelled
+#[derive(Debug, Fail)]
+#[fail(display = "{} command for {} was aborted", command, manager)]
+pub struct Aborted {
+    pub manager: String,
+    pub command: String,
+}
+
+/// Was the operation that gave the error cancelled?
+pub fn is_aborted(error: &Error) -> bool {
+    error.downcast_ref::<Aborted>().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    #[test]
+    fn cancelling() {
+        let token = CancellationToken::new().with_grace_period(Duration::from_millis(200));
+        let child = Command::new("echo").arg("done").stdout(Stdio::piped()).spawn().unwrap();
+        assert_eq!(token.wait_with_output(child).unwrap().unwrap().stdout, b"done\n");
+
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let canceller = token.clone();
+        let started = Instant::now();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        assert!(!token.wait(&mut child).unwrap().unwrap_err().success());
+        assert!(token.is_cancelled());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        //Ignores SIGTERM so it has to be killed
+        let mut child = Command::new("sh").arg("-c").arg("trap '' TERM; sleep 10").spawn().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!terminate(&mut child, Duration::from_millis(100)).unwrap().success());
+    }
+}
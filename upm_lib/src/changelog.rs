@@ -0,0 +1,238 @@
+//! What changed in the packages about to be upgraded. The optional `changelog` command is given a
+//! package and prints its changelog, such as `apt changelog` or `dnf changelog`:
+//!
+//! ```toml
+//! changelog = "apt changelog"
+//! ```
+//!
+//! Changelogs in the Debian and RPM formats are split into [entries](struct.ChangelogEntry.html),
+//! and anything else becomes a single entry without a version. Only the entries newer than the
+//! installed version are kept, so [`pending_changes`](fn.pending_changes.html) gives what every
+//! upgrade would bring across package managers, for showing before asking to upgrade.
+
+use std::cmp::Ordering;
+use std::fmt;
+use regex::Regex;
+use super::{PackageManager, Version};
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// The most lines of a package's changes shown before asking to upgrade by default
+pub const DEFAULT_SNIPPET_LINES: usize = 8;
+
+/// One release in a changelog
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangelogEntry {
+    /// The version released, if the changelog says
+    pub version: Option<String>,
+    /// When it was released, as the changelog writes it
+    pub date: Option<String>,
+    /// Who made the release
+    pub author: Option<String>,
+    /// The changes, one per line without their bullets
+    pub changes: Vec<String>,
+}
+
+impl fmt::Display for ChangelogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.version.as_ref().map_or("unknown version", String::as_str))?;
+        if let Some(ref date) = self.date {
+            write!(f, " ({})", date)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split a changelog into entries, newest first as changelogs are written
+pub fn parse_changelog(text: &str) -> Vec<ChangelogEntry> {
+    let debian = Regex::new(r"^\S+ \(([^)]+)\)\s+[^;]*;").unwrap();
+    let trailer = Regex::new(r"^ -- (.*?)\s{2,}(.+)$").unwrap();
+    let rpm = Regex::new(r"^\* (\w{3} \w{3} +\d{1,2} \d{4}) (.*?)(?: - (\S+))?\s*$").unwrap();
+    let mut entries: Vec<ChangelogEntry> = Vec::new();
+    for line in text.lines() {
+        if let Some(captures) = debian.captures(line) {
+            entries.push(ChangelogEntry { version: Some(captures[1].to_owned()), ..Default::default() });
+        } else if let Some(captures) = rpm.captures(line) {
+            entries.push(ChangelogEntry {
+                version: captures.get(3).map(|version| version.as_str().to_owned()),
+                date: Some(captures[1].to_owned()),
+                author: Some(captures[2].trim().to_owned()).filter(|author| !author.is_empty()),
+                changes: Vec::new(),
+            });
+        } else if let Some(entry) = entries.last_mut() {
+            if let Some(captures) = trailer.captures(line) {
+                entry.author = Some(captures[1].to_owned());
+                entry.date = Some(captures[2].to_owned());
+                continue;
+            }
+            let change = line.trim().trim_start_matches(['*', '-', '+']).trim();
+            if !change.is_empty() && !change.starts_with('[') {
+                entry.changes.push(change.to_owned());
+            }
+        }
+    }
+    if entries.is_empty() {
+        let changes: Vec<String> = text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_owned).collect();
+        if !changes.is_empty() {
+            entries.push(ChangelogEntry { changes, ..Default::default() });
+        }
+    }
+    entries
+}
+
+/// Get the entries of a changelog for versions newer than the installed one, compared with the
+/// package manager's version scheme. Entries without a version are kept since they can't be told
+/// apart.
+pub fn newer_than(entries: Vec<ChangelogEntry>, installed: &Version, manager: &PackageManager) -> Vec<ChangelogEntry> {
+    entries.into_iter()
+        .filter(|entry| match entry.version {
+            Some(ref version) => manager.compare_versions(&Version::from(version.as_str()), installed) == Some(Ordering::Greater),
+            None => true,
+        })
+        .collect()
+}
+
+/// What upgrading a package would change
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageChanges {
+    /// The name of the package manager
+    pub manager: String,
+    pub package: String,
+    pub installed: Version,
+    /// The version the package would be upgraded to
+    pub candidate: Version,
+    /// The changelog entries newer than the installed version, newest first
+    pub entries: Vec<ChangelogEntry>,
+}
+
+impl PackageChanges {
+    /// Describe the changes in at most some number of lines after the heading, saying how many
+    /// more there are
+    pub fn snippet(&self, max_lines: usize) -> Vec<String> {
+        let mut lines = vec![format!("{} {} -> {} ({})", self.package, self.installed, self.candidate, self.manager)];
+        let mut body = Vec::new();
+        for entry in &self.entries {
+            body.push(format!("  {}", entry));
+            body.extend(entry.changes.iter().map(|change| format!("    - {}", change)));
+        }
+        if body.len() > max_lines {
+            let more = body.len() - max_lines;
+            body.truncate(max_lines);
+            body.push(format!("  ... {} more line{}", more, if more == 1 { "" } else { "s" }));
+        }
+        lines.extend(body);
+        lines
+    }
+}
+
+/// Find the packages that upgrading would change with every package manager that has
+/// changelog, list_installed, and search commands, along with what their changelogs say changed.
+/// Packages whose changelog can't be read are given without entries.
+pub fn pending_changes(managers: &[PackageManager], policy: &ConcurrencyPolicy) -> Vec<PackageChanges> {
+    let managers: Vec<PackageManager> = managers.iter()
+        .filter(|manager| ["changelog", "list_installed", "search"].iter().all(|command| manager.has_command(command)))
+        .cloned()
+        .collect();
+    run_all(&managers, policy, |manager| {
+        let installed = match manager.list_installed() {
+            Ok(installed) => installed,
+            Err(e) => {
+                warn!("{}: couldn't list the installed packages: {}", manager.name, e);
+                return Vec::new();
+            },
+        };
+        let mut result = Vec::new();
+        for package in installed {
+            let candidate = match manager.candidate(&package.name) {
+                Ok(Some(candidate)) => candidate,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("{}: couldn't find the newest version of {}: {}", manager.name, package.name, e);
+                    continue;
+                },
+            };
+            if manager.compare_versions(&candidate, &package.version) != Some(Ordering::Greater) {
+                continue;
+            }
+            let entries = match manager.changelog(&package.name) {
+                Ok(entries) => newer_than(entries, &package.version, manager),
+                Err(e) => {
+                    warn!("{}: couldn't read the changelog of {}: {}", manager.name, package.name, e);
+                    Vec::new()
+                },
+            };
+            result.push(PackageChanges { manager: manager.name.clone(), package: package.name, installed: package.version, candidate, entries });
+        }
+        result
+    }).into_iter().flat_map(|(_, changes)| changes).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::VersionScheme;
+
+    const DEBIAN: &str = "\
+ripgrep (14.1.0-1) unstable; urgency=medium
+
+  * New upstream release.
+  * [ Jane Doe ]
+  * Drop the old patch.
+
+ -- Jane Doe <jane@example.com>  Mon, 08 Jan 2024 10:00:00 +0100
+
+ripgrep (13.0.0-4) unstable; urgency=medium
+
+  * Rebuild.
+
+ -- Jane Doe <jane@example.com>  Tue, 01 Aug 2023 10:00:00 +0100
+";
+
+    const RPM: &str = "\
+Changelogs for ripgrep-14.1.0-1.fc39.x86_64
+* Mon Jan 08 2024 Jane Doe <jane@example.com> - 14.1.0-1
+- Update to 14.1.0
+
+* Tue Aug 01 2023 Jane Doe <jane@example.com> - 13.0.0-4
+- Rebuilt
+";
+
+    #[test]
+    fn changelogs() {
+        let entries = parse_changelog(DEBIAN);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].version.as_ref().unwrap(), "14.1.0-1");
+        assert_eq!(entries[0].changes, vec!["New upstream release.", "Drop the old patch."]);
+        assert_eq!(entries[0].author.as_ref().unwrap(), "Jane Doe <jane@example.com>");
+        assert_eq!(entries[1].to_string(), "13.0.0-4 (Tue, 01 Aug 2023 10:00:00 +0100)");
+
+        let entries = parse_changelog(RPM);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].version.as_ref().unwrap(), "14.1.0-1");
+        assert_eq!(entries[0].date.as_ref().unwrap(), "Mon Jan 08 2024");
+        assert_eq!(entries[1].changes, vec!["Rebuilt"]);
+
+        let entries = parse_changelog("Fixed a crash\n\nFaster search\n");
+        assert_eq!(entries, vec![ChangelogEntry { changes: vec![String::from("Fixed a crash"), String::from("Faster search")], ..Default::default() }]);
+        assert!(parse_changelog("").is_empty());
+    }
+
+    #[test]
+    fn pending_entries() {
+        let manager = PackageManager { version_scheme: VersionScheme::Debian, ..Default::default() };
+        let entries = newer_than(parse_changelog(DEBIAN), &Version::from("13.0.0-4"), &manager);
+        assert_eq!(entries.len(), 1);
+        let changes = PackageChanges {
+            manager: String::from("apt"),
+            package: String::from("ripgrep"),
+            installed: Version::from("13.0.0-4"),
+            candidate: Version::from("14.1.0-1"),
+            entries,
+        };
+        assert_eq!(changes.snippet(2), vec![
+            "ripgrep 13.0.0-4 -> 14.1.0-1 (apt)",
+            "  14.1.0-1 (Mon, 08 Jan 2024 10:00:00 +0100)",
+            "    - New upstream release.",
+            "  ... 1 more line",
+        ]);
+    }
+}
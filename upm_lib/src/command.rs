@@ -0,0 +1,302 @@
+//! The commands a package manager configuration can give. Every [kind](enum.CommandKind.html) of
+//! command can be given once for each [scope](enum.Scope.html): the plain key, such as `install`,
+//! is the system command and the key with `_local` appended, such as `install_local`, is the
+//! command that works in the user's home directory.
+//!
+//! ```toml
+//! version = "npm --version"
+//! install = "sudo npm install --global"
+//! install_local = "npm install --prefix ~/.local"
+//! list_installed_local = "./list.sh --prefix ~/.local"
+//! ```
+//!
+//! Commands outside the fixed set, such as `npm outdated`, can be given as extras. The library
+//! only knows them by name, so frontends can offer them without upm knowing what they do.
+//!
+//! ```toml
+//! [extras]
+//! outdated = "npm outdated --global"
+//! doctor = "npm doctor"
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+
+/// Whether a package is installed for every user or only in a user's home directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Scope {
+    System,
+    User,
+}
+
+impl Scope {
+    /// The name of the scope as upm shows it
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::System => "system",
+            Scope::User => "user",
+        }
+    }
+}
+
+/// Something a package manager can be asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CommandKind {
+    Version,
+    Install,
+    Remove,
+    Search,
+    Info,
+    Upgrade,
+    UpgradeAll,
+    SelfUpdate,
+    ListInstalled,
+    OwnsFile,
+    ReverseDeps,
+    Provides,
+    Download,
+    PredictSize,
+    Verify,
+    Audit,
+    NeedsRestart,
+    Changelog,
+    CleanCache,
+    Autoremove,
+    CacheSize,
+    DiskUsage,
+    AddKey,
+    ListKeys,
+    AddRepo,
+    RemoveRepo,
+    ListRepos,
+    Refresh,
+}
+
+impl CommandKind {
+    /// Every kind of command
+    pub const ALL: &'static [CommandKind] = &[
+        CommandKind::Version,
+        CommandKind::Install,
+        CommandKind::Remove,
+        CommandKind::Search,
+        CommandKind::Info,
+        CommandKind::Upgrade,
+        CommandKind::UpgradeAll,
+        CommandKind::SelfUpdate,
+        CommandKind::ListInstalled,
+        CommandKind::OwnsFile,
+        CommandKind::ReverseDeps,
+        CommandKind::Provides,
+        CommandKind::Download,
+        CommandKind::PredictSize,
+        CommandKind::Verify,
+        CommandKind::Audit,
+        CommandKind::NeedsRestart,
+        CommandKind::Changelog,
+        CommandKind::CleanCache,
+        CommandKind::Autoremove,
+        CommandKind::CacheSize,
+        CommandKind::DiskUsage,
+        CommandKind::AddKey,
+        CommandKind::ListKeys,
+        CommandKind::AddRepo,
+        CommandKind::RemoveRepo,
+        CommandKind::ListRepos,
+        CommandKind::Refresh,
+    ];
+
+    /// The name of the command, which is its key in a configuration
+    pub fn name(self) -> &'static str {
+        match self {
+            CommandKind::Version => "version",
+            CommandKind::Install => "install",
+            CommandKind::Remove => "remove",
+            CommandKind::Search => "search",
+            CommandKind::Info => "info",
+            CommandKind::Upgrade => "upgrade",
+            CommandKind::UpgradeAll => "upgrade_all",
+            CommandKind::SelfUpdate => "self_update",
+            CommandKind::ListInstalled => "list_installed",
+            CommandKind::OwnsFile => "owns_file",
+            CommandKind::ReverseDeps => "reverse_deps",
+            CommandKind::Provides => "provides",
+            CommandKind::Download => "download",
+            CommandKind::PredictSize => "predict_size",
+            CommandKind::Verify => "verify",
+            CommandKind::Audit => "audit",
+            CommandKind::NeedsRestart => "needs_restart",
+            CommandKind::Changelog => "changelog",
+            CommandKind::CleanCache => "clean_cache",
+            CommandKind::Autoremove => "autoremove",
+            CommandKind::CacheSize => "cache_size",
+            CommandKind::DiskUsage => "disk_usage",
+            CommandKind::AddKey => "add_key",
+            CommandKind::ListKeys => "list_keys",
+            CommandKind::AddRepo => "add_repo",
+            CommandKind::RemoveRepo => "remove_repo",
+            CommandKind::ListRepos => "list_repos",
+            CommandKind::Refresh => "refresh",
+        }
+    }
+
+    /// What the command does, in a few words
+    pub fn description(self) -> &'static str {
+        match self {
+            CommandKind::Version => "Print the version, which shows the package manager is installed",
+            CommandKind::Install => "Install packages",
+            CommandKind::Remove => "Remove packages",
+            CommandKind::Search => "Search for packages",
+            CommandKind::Info => "Print the details of a package, such as its homepage and license",
+            CommandKind::Upgrade => "Upgrade packages",
+            CommandKind::UpgradeAll => "Upgrade every installed package",
+            CommandKind::SelfUpdate => "Update the package manager itself",
+            CommandKind::ListInstalled => "List the installed packages",
+            CommandKind::OwnsFile => "Find the package that installed a file",
+            CommandKind::ReverseDeps => "List the installed packages that depend on a package",
+            CommandKind::Provides => "Find the packages that provide a file, library, or virtual package",
+            CommandKind::Download => "Download packages into the working directory without installing them",
+            CommandKind::PredictSize => "Print how many bytes installing packages would download and use",
+            CommandKind::Verify => "Check the files of installed packages for damage",
+            CommandKind::Audit => "List known vulnerabilities in the installed packages",
+            CommandKind::NeedsRestart => "List the services, or the whole system, that need restarting after an upgrade",
+            CommandKind::Changelog => "Print the changelog of a package",
+            CommandKind::CleanCache => "Remove downloaded package files",
+            CommandKind::Autoremove => "Remove packages that nothing depends on anymore",
+            CommandKind::CacheSize => "Print the size of the download cache in bytes",
+            CommandKind::DiskUsage => "Print the size of the installed packages in bytes",
+            CommandKind::AddKey => "Trust a repository signing key",
+            CommandKind::ListKeys => "List the trusted repository signing keys",
+            CommandKind::AddRepo => "Enable a repository",
+            CommandKind::RemoveRepo => "Disable a repository",
+            CommandKind::ListRepos => "List the enabled repositories",
+            CommandKind::Refresh => "Download the latest package metadata",
+        }
+    }
+
+    /// The configuration key of the command in a scope, such as `install_local`
+    pub fn key(self, scope: Scope) -> String {
+        match scope {
+            Scope::System => self.name().to_owned(),
+            Scope::User => format!("{}_local", self.name()),
+        }
+    }
+}
+
+impl FromStr for CommandKind {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<CommandKind, Error> {
+        match CommandKind::ALL.iter().find(|kind| kind.name() == name) {
+            Some(&kind) => Ok(kind),
+            None => bail!("Unknown command {}", name),
+        }
+    }
+}
+
+impl fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Get the kind and scope of the command with a configuration key, such as `install_local`
+pub fn parse_key(key: &str) -> Option<(CommandKind, Scope)> {
+    if let Ok(kind) = key.parse() {
+        return Some((kind, Scope::System));
+    }
+    key.strip_suffix("_local")
+        .and_then(|name| name.parse().ok())
+        .map(|kind| (kind, Scope::User))
+}
+
+/// A package manager was asked to run a command it doesn't have. Frontends can catch it to fall
+/// back to the command's other scope or to another package manager.
+#[derive(Debug, Fail)]
+#[fail(display = "{} has no {} command", manager, command)]
+pub struct MissingCommand {
+    pub manager: String,
+    /// The configuration key of the command, such as `install_local`, or the name of an extra
+    /// command
+    pub command: String,
+}
+
+/// Get the kind and scope of the command with a configuration key, giving an error for keys that
+/// aren't commands
+pub fn resolve(key: &str) -> Result<(CommandKind, Scope), Error> {
+    match parse_key(key) {
+        Some(key) => Ok(key),
+        None => bail!("Unknown command {}", key),
+    }
+}
+
+/// The string describing a command, such as `apt install` or `./search.sh {args}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTemplate(String);
+
+impl CommandTemplate {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> From<&'a str> for CommandTemplate {
+    fn from(template: &'a str) -> CommandTemplate {
+        CommandTemplate(template.to_owned())
+    }
+}
+
+impl From<String> for CommandTemplate {
+    fn from(template: String) -> CommandTemplate {
+        CommandTemplate(template)
+    }
+}
+
+impl fmt::Display for CommandTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The commands of a package manager
+pub type CommandMap = HashMap<(CommandKind, Scope), CommandTemplate>;
+
+/// What a package manager can be asked to do
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The commands it has, sorted by kind and then scope
+    pub commands: Vec<(CommandKind, Scope)>,
+    /// The names of its extra commands, sorted
+    pub extras: Vec<String>,
+}
+
+impl Capabilities {
+    /// Does the package manager have the command of a kind in a scope?
+    pub fn has(&self, kind: CommandKind, scope: Scope) -> bool {
+        self.commands.contains(&(kind, scope))
+    }
+
+    /// Does the package manager have the named extra command?
+    pub fn has_extra(&self, name: &str) -> bool {
+        self.extras.iter().any(|extra| extra == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        assert_eq!(parse_key("install"), Some((CommandKind::Install, Scope::System)));
+        assert_eq!(parse_key("install_local"), Some((CommandKind::Install, Scope::User)));
+        assert_eq!(parse_key("list_installed_local"), Some((CommandKind::ListInstalled, Scope::User)));
+        assert_eq!(parse_key("uninstall"), None);
+        assert_eq!(parse_key("_local"), None);
+        assert!(resolve("uninstall").is_err());
+        for &kind in CommandKind::ALL {
+            assert_eq!(parse_key(&kind.key(Scope::User)), Some((kind, Scope::User)));
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Limits on running commands for several package managers at once. Running everything at once can
+//! thrash the system or fail outright when two package managers share a lock (apt and aptitude
+//! both take the dpkg lock), so a [`ConcurrencyPolicy`](struct.ConcurrencyPolicy.html) caps the
+//! number of managers run in parallel and serializes managers in the same lock class.
+//!
+//! A policy is read from the `concurrency` table of the [settings](../settings/index.html):
+//!
+//! ```toml
+//! [concurrency]
+//! max_parallel = 2
+//!
+//! [concurrency.lock_classes]
+//! dpkg = ["apt", "apt-get", "aptitude"]
+//! rpm = ["dnf", "yum"]
+//! ```
+//!
+//! A package manager can also declare its own lock class with `lock_class = "dpkg"` in its
+//! configuration. Lock classes given in the policy take precedence.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::thread;
+use failure::Error;
+use toml::Value;
+use super::PackageManager;
+
+/// How many package managers may run at once and which must not run at the same time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyPolicy {
+    /// The most package managers that can be running at once. At least one is always allowed.
+    pub max_parallel: usize,
+    /// The lock class of each package manager, by manager name. Managers in the same lock class
+    /// are run one after another.
+    pub lock_classes: HashMap<String, String>,
+}
+
+impl Default for ConcurrencyPolicy {
+    fn default() -> ConcurrencyPolicy {
+        ConcurrencyPolicy {
+            max_parallel: 4,
+            lock_classes: HashMap::new(),
+        }
+    }
+}
+
+impl ConcurrencyPolicy {
+    /// A policy that runs one package manager at a time
+    pub fn sequential() -> ConcurrencyPolicy {
+        ConcurrencyPolicy {
+            max_parallel: 1,
+            lock_classes: HashMap::new(),
+        }
+    }
+
+    /// Read a policy from the `concurrency` table of a TOML value. Anything not given keeps its
+    /// default.
+    pub fn from_toml(table: &Value) -> Result<ConcurrencyPolicy, Error> {
+        let mut policy = ConcurrencyPolicy::default();
+        if let Some(max) = table.get("max_parallel") {
+            match max.as_integer() {
+                Some(max) if max > 0 => policy.max_parallel = max as usize,
+                _ => bail!("max_parallel must be a positive integer"),
+            }
+        }
+        if let Some(classes) = table.get("lock_classes") {
+            let classes = match classes.as_table() {
+                Some(classes) => classes,
+                None => bail!("lock_classes must be a table"),
+            };
+            for (class, managers) in classes {
+                let managers = match managers.as_array() {
+                    Some(managers) => managers,
+                    None => bail!("lock class {} must be an array of manager names", class),
+                };
+                for manager in managers {
+                    match manager.as_str() {
+                        Some(manager) => policy.set_lock_class(manager, class),
+                        None => bail!("lock class {} must be an array of manager names", class),
+                    }
+                }
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Put a package manager in a lock class
+    pub fn set_lock_class(&mut self, manager: &str, class: &str) {
+        self.lock_classes.insert(manager.to_owned(), class.to_owned());
+    }
+
+    /// Get the lock class of a package manager if it has one, either from the policy or from the
+    /// package manager's configuration
+    pub fn lock_class<'a>(&'a self, manager: &'a PackageManager) -> Option<&'a str> {
+        self.lock_classes.get(&manager.name)
+            .or(manager.lock_class.as_ref())
+            .map(|class| class.as_str())
+    }
+
+    //Group the indexes of managers that have to run one after another
+    fn lanes(&self, managers: &[PackageManager]) -> VecDeque<Vec<usize>> {
+        let mut lanes: VecDeque<Vec<usize>> = VecDeque::new();
+        let mut class_lanes: HashMap<&str, usize> = HashMap::new();
+        for (index, manager) in managers.iter().enumerate() {
+            match self.lock_class(manager) {
+                Some(class) => match class_lanes.get(class) {
+                    Some(&lane) => lanes[lane].push(index),
+                    None => {
+                        class_lanes.insert(class, lanes.len());
+                        lanes.push_back(vec![index]);
+                    },
+                },
+                None => lanes.push_back(vec![index]),
+            }
+        }
+        lanes
+    }
+}
+
+/// Run an operation for every package manager following the policy. The results are given
+/// alongside their package manager in the same order as the managers.
+pub fn run_all<'a, T, F>(managers: &'a [PackageManager], policy: &ConcurrencyPolicy, operation: F) -> Vec<(&'a PackageManager, T)>
+    where T: Send, F: Fn(&PackageManager) -> T + Sync
+{
+    let lanes = Mutex::new(policy.lanes(managers));
+    let results: Mutex<Vec<Option<T>>> = Mutex::new(managers.iter().map(|_| None).collect());
+    let workers = policy.max_parallel.max(1).min(managers.len());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let lane = match lanes.lock().unwrap().pop_front() {
+                    Some(lane) => lane,
+                    None => break,
+                };
+                for index in lane {
+                    let result = operation(&managers[index]);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    managers.iter()
+        .zip(results.into_inner().unwrap())
+        .map(|(manager, result)| (manager, result.expect("every manager is run")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn managers(names: &[&str]) -> Vec<PackageManager> {
+        names.iter().map(|name| PackageManager {
+            name: name.to_string(),
+            ..Default::default()
+        }).collect()
+    }
+
+    #[test]
+    fn results_keep_order() {
+        let managers = managers(&["apt", "brew", "cargo", "dnf", "npm", "pip"]);
+        let results = run_all(&managers, &ConcurrencyPolicy::default(), |m| m.name.len());
+        let names: Vec<&str> = results.iter().map(|&(m, _)| m.name.as_str()).collect();
+        assert_eq!(names, vec!["apt", "brew", "cargo", "dnf", "npm", "pip"]);
+        assert_eq!(results[1].1, 4);
+    }
+
+    #[test]
+    fn lock_classes_serialize() {
+        let managers = managers(&["apt", "aptitude", "apt-get", "cargo"]);
+        let mut policy = ConcurrencyPolicy::default();
+        for name in &["apt", "aptitude", "apt-get"] {
+            policy.set_lock_class(name, "dpkg");
+        }
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+        run_all(&managers, &policy, |m| {
+            if m.name != "cargo" {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(most_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn configured_lock_classes() {
+        let mut managers = managers(&["dnf", "yum", "zypper", "flatpak"]);
+        for manager in &mut managers[..3] {
+            manager.lock_class = Some(String::from("rpm"));
+        }
+        let mut policy = ConcurrencyPolicy::default();
+        policy.set_lock_class("zypper", "zypp");
+        assert_eq!(policy.lock_class(&managers[0]), Some("rpm"));
+        assert_eq!(policy.lock_class(&managers[2]), Some("zypp"));
+        assert_eq!(policy.lock_class(&managers[3]), None);
+        assert_eq!(policy.lanes(&managers), vec![vec![0, 1], vec![2], vec![3]]);
+    }
+}
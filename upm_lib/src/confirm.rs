@@ -0,0 +1,237 @@
+//! Asking before destructive operations. Frontends describe what they're about to do in a
+//! [`Request`](struct.Request.html) and ask a [`Confirmation`](trait.Confirmation.html), which
+//! can prompt in a terminal, always answer, or hand the question to a GUI through a callback. A
+//! [`ConfirmationPolicy`](struct.ConfirmationPolicy.html) confirms small operations without
+//! asking, read from the `confirm` table of the [settings](../settings/index.html):
+//!
+//! ```toml
+//! [confirm]
+//! install = 5
+//! remove = 2
+//! ```
+//!
+//! Each action is confirmed without asking when it involves fewer packages than its threshold, so
+//! here installing up to four packages and removing one go ahead on their own. Actions without a
+//! threshold always ask.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use failure::Error;
+use toml::Value;
+
+/// What a frontend wants to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    Install,
+    Remove,
+    UpgradeAll,
+    Rollback,
+    /// Running something with sudo, such as writing a configuration file the user can't write
+    Escalate,
+}
+
+impl Action {
+    /// Every action
+    pub const ALL: &'static [Action] = &[Action::Install, Action::Remove, Action::UpgradeAll, Action::Rollback, Action::Escalate];
+
+    /// The name of the action in the settings
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Install => "install",
+            Action::Remove => "remove",
+            Action::UpgradeAll => "upgrade_all",
+            Action::Rollback => "rollback",
+            Action::Escalate => "escalate",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Action, Error> {
+        match Action::ALL.iter().find(|action| action.name() == name) {
+            Some(&action) => Ok(action),
+            None => bail!("Unknown action {}", name),
+        }
+    }
+}
+
+/// A question to confirm before doing something
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub action: Action,
+    /// The question shown to the user, such as `Remove ripgrep?`
+    pub question: String,
+    /// The packages involved, which are unknown for actions like upgrading everything
+    pub packages: Vec<String>,
+    /// Lines shown before the question, such as the changelogs of pending upgrades
+    pub details: Vec<String>,
+}
+
+impl Request {
+    /// Ask a question about an action
+    pub fn new<S: Into<String>>(action: Action, question: S) -> Request {
+        Request {
+            action,
+            question: question.into(),
+            packages: Vec::new(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Say which packages are involved
+    pub fn with_packages(mut self, packages: Vec<String>) -> Request {
+        self.packages = packages;
+        self
+    }
+
+    /// Show some lines before the question
+    pub fn with_details(mut self, details: Vec<String>) -> Request {
+        self.details = details;
+        self
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.question)
+    }
+}
+
+/// Something that decides whether a request goes ahead
+pub trait Confirmation {
+    /// Should the request go ahead?
+    fn confirm(&self, request: &Request) -> bool;
+}
+
+/// Asks on the terminal, going ahead only if the user answers `y`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TerminalPrompt;
+
+impl Confirmation for TerminalPrompt {
+    fn confirm(&self, request: &Request) -> bool {
+        for line in &request.details {
+            println!("{}", line);
+        }
+        print!("{} [y/N] ", request);
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        match io::stdin().read_line(&mut answer) {
+            Ok(_) => answer.trim().eq_ignore_ascii_case("y"),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Lets every request go ahead, such as for `--yes`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysYes;
+
+impl Confirmation for AlwaysYes {
+    fn confirm(&self, _: &Request) -> bool {
+        true
+    }
+}
+
+/// Refuses every request, such as when there's nobody to ask
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysNo;
+
+impl Confirmation for AlwaysNo {
+    fn confirm(&self, _: &Request) -> bool {
+        false
+    }
+}
+
+/// Hands requests to a function, such as one showing a dialog in a GUI
+pub struct Callback<F: Fn(&Request) -> bool>(pub F);
+
+impl<F: Fn(&Request) -> bool> Confirmation for Callback<F> {
+    fn confirm(&self, request: &Request) -> bool {
+        (self.0)(request)
+    }
+}
+
+/// Which requests are confirmed without asking
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfirmationPolicy {
+    /// Requests for each action with fewer packages than the threshold are confirmed without
+    /// asking
+    pub thresholds: BTreeMap<Action, usize>,
+}
+
+impl ConfirmationPolicy {
+    /// A policy that always asks
+    pub fn new() -> ConfirmationPolicy {
+        ConfirmationPolicy::default()
+    }
+
+    /// Read the thresholds from the `confirm` table of the settings
+    pub fn from_toml(table: &Value) -> Result<ConfirmationPolicy, Error> {
+        let table = match table.as_table() {
+            Some(table) => table,
+            None => bail!("confirm must be a table of actions"),
+        };
+        let mut policy = ConfirmationPolicy::new();
+        for (action, threshold) in table {
+            match threshold.as_integer() {
+                Some(threshold) if threshold >= 0 => policy.set_threshold(action.parse()?, threshold as usize),
+                _ => bail!("The confirm threshold of {} must be a number of packages", action),
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Confirm requests for an action with fewer packages than the threshold without asking
+    pub fn set_threshold(&mut self, action: Action, threshold: usize) {
+        self.thresholds.insert(action, threshold);
+    }
+
+    /// Add the thresholds of another policy, replacing those for the same actions
+    pub fn merge(&mut self, other: ConfirmationPolicy) {
+        self.thresholds.extend(other.thresholds);
+    }
+
+    /// Should the request go ahead? It does without asking if the policy allows it, and
+    /// otherwise the confirmation decides. Requests without packages are always asked.
+    pub fn confirm(&self, request: &Request, confirmation: &dyn Confirmation) -> bool {
+        let allowed = match self.thresholds.get(&request.action) {
+            Some(&threshold) => !request.packages.is_empty() && request.packages.len() < threshold,
+            None => false,
+        };
+        allowed || confirmation.confirm(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn policies() {
+        let policy = ConfirmationPolicy::from_toml(&"install = 3\nremove = 0".parse::<Value>().unwrap()).unwrap();
+        let install = |count: usize| Request::new(Action::Install, "Install?")
+            .with_packages((0..count).map(|i| i.to_string()).collect());
+        assert!(policy.confirm(&install(2), &AlwaysNo));
+        assert!(!policy.confirm(&install(3), &AlwaysNo));
+        assert!(!policy.confirm(&install(0), &AlwaysNo));
+        assert!(policy.confirm(&install(3), &AlwaysYes));
+        assert!(!policy.confirm(&Request::new(Action::Remove, "Remove?").with_packages(vec![String::from("vim")]), &AlwaysNo));
+
+        let asked = Cell::new(0);
+        let callback = Callback(|request: &Request| {
+            asked.set(asked.get() + 1);
+            request.action == Action::Rollback
+        });
+        assert!(policy.confirm(&Request::new(Action::Rollback, "Undo?"), &callback));
+        assert!(!policy.confirm(&Request::new(Action::UpgradeAll, "Upgrade?"), &callback));
+        assert_eq!(asked.get(), 2);
+
+        assert!(ConfirmationPolicy::from_toml(&"purge = 3".parse::<Value>().unwrap()).is_err());
+        assert!(ConfirmationPolicy::from_toml(&"install = 'yes'".parse::<Value>().unwrap()).is_err());
+    }
+}
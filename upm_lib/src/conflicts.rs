@@ -0,0 +1,136 @@
+//! Finding packages installed through more than one package manager, such as `ansible` installed
+//! with both apt and pip. Only one of the installs is run from a shell, so upgrading or removing
+//! the other one quietly does nothing useful. A [`ConflictReport`](struct.ConflictReport.html)
+//! cross-references the installed packages of every package manager and can also list the
+//! binaries named after each conflicting package in `PATH`, in the order they're searched.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::PathBuf;
+use super::{ManagerSet, Version, find_all_in_path};
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// A package installed through several package managers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub package: String,
+    /// The package managers that installed the package and the installed versions, from highest
+    /// priority to lowest
+    pub installs: Vec<(String, Version)>,
+    /// The binaries named after the package in `PATH`, in the order they're searched, so the first
+    /// one is the one that runs. Empty unless `PATH` was searched.
+    pub binaries: Vec<PathBuf>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let installs: Vec<String> = self.installs.iter()
+            .map(|(manager, version)| format!("{} ({})", manager, version))
+            .collect();
+        write!(f, "{} is installed through {}", self.package, installs.join(" and "))?;
+        match self.binaries.split_first() {
+            Some((first, rest)) if rest.len() == 1 => write!(f, ", {} shadows {} in PATH", first.display(), rest[0].display()),
+            Some((first, rest)) if !rest.is_empty() => write!(f, ", {} shadows {} others in PATH", first.display(), rest.len()),
+            Some((first, _)) => write!(f, ", only {} is in PATH", first.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The packages installed through more than one package manager
+#[derive(Debug, Clone, Default)]
+pub struct ConflictReport {
+    /// The conflicts sorted by package name
+    pub conflicts: Vec<Conflict>,
+    /// The package managers whose installed packages couldn't be listed
+    pub unlisted: Vec<String>,
+}
+
+impl ConflictReport {
+    /// Compare the installed packages of every package manager with a list_installed command,
+    /// searching `PATH` for the binaries of conflicting packages if asked to
+    pub fn check(managers: &ManagerSet, search_path: bool) -> ConflictReport {
+        let path = if search_path { ::std::env::var_os("PATH") } else { None };
+        ConflictReport::check_with_path(managers, path.as_deref())
+    }
+
+    //Checks for conflicts, searching the given PATH value for binaries
+    fn check_with_path(managers: &ManagerSet, path: Option<&OsStr>) -> ConflictReport {
+        let mut report = ConflictReport::default();
+        let mut installs: BTreeMap<String, Vec<(String, Version)>> = BTreeMap::new();
+        let listed = run_all(managers.as_slice(), &ConcurrencyPolicy::default(), |manager| {
+            if manager.has_command("list_installed") {
+                Some(manager.list_installed())
+            } else {
+                None
+            }
+        });
+        for (manager, result) in listed {
+            match result {
+                Some(Ok(packages)) => for package in packages {
+                    installs.entry(package.name).or_default().push((manager.name.clone(), package.version));
+                },
+                Some(Err(e)) => {
+                    warn!("{}: couldn't list installed packages: {}", manager.name, e);
+                    report.unlisted.push(manager.name.clone());
+                },
+                None => {},
+            }
+        }
+        report.conflicts = installs.into_iter()
+            .filter(|(_, installs)| installs.len() > 1)
+            .map(|(package, installs)| Conflict {
+                binaries: path.map_or_else(Vec::new, |path| find_all_in_path(&package, path)),
+                package,
+                installs,
+            })
+            .collect();
+        report
+    }
+
+    /// Were no conflicts found?
+    pub fn is_empty(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use super::super::{CommandKind, PackageManager, Scope};
+
+    #[test]
+    fn finding_conflicts() {
+        let manager = |name: &str, list: &str| PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/conflicts"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::ListInstalled, Scope::System, list);
+        let set = ManagerSet::new(vec![
+            manager("apt", "./apt.sh"),
+            manager("pip", "./pip.sh"),
+            manager("npm", "false"),
+        ]);
+        let bin = env::temp_dir().join(format!("upm-conflicts-{}", ::std::process::id()));
+        let (local, system) = (bin.join("local"), bin.join("system"));
+        fs::create_dir_all(&local).unwrap();
+        fs::create_dir_all(&system).unwrap();
+        fs::write(local.join("ansible"), "").unwrap();
+        fs::write(system.join("ansible"), "").unwrap();
+        let path = env::join_paths(vec![&local, &system]).unwrap();
+        let report = ConflictReport::check_with_path(&set, Some(&path));
+        assert_eq!(report.unlisted, vec![String::from("npm")]);
+        assert_eq!(report.conflicts.len(), 1);
+        let conflict = &report.conflicts[0];
+        assert_eq!(conflict.package, "ansible");
+        assert_eq!(conflict.binaries, vec![local.join("ansible"), system.join("ansible")]);
+        assert_eq!(conflict.to_string(), format!("ansible is installed through apt (2.9.6) and pip (2.10.0), {} shadows {} in PATH",
+                                                 local.join("ansible").display(), system.join("ansible").display()));
+        assert!(ConflictReport::check_with_path(&set, None).conflicts[0].binaries.is_empty());
+        fs::remove_dir_all(&bin).unwrap();
+    }
+}
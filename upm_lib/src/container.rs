@@ -0,0 +1,178 @@
+//! Running package managers inside containers. On image-based distributions such as Silverblue
+//! and MicroOS the host's packages can't be changed directly, so package managers live in
+//! toolbox or distrobox containers. A package manager's configuration can give a wrapper that
+//! every one of its commands is run through:
+//!
+//! ```toml
+//! exec_wrapper = "distrobox enter dev --"
+//! ```
+//!
+//! The wrapper is outermost, so resource limits and sandboxing apply inside the container. Hooks
+//! still run on the host. [`detect_containers`](fn.detect_containers.html) finds the containers
+//! on the machine, and [`detect_in_container`](../detect/fn.detect_in_container.html) the
+//! package managers inside one.
+
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use super::find_in_path;
+
+/// A tool that manages development containers sharing the user's home directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContainerTool {
+    Toolbox,
+    Distrobox,
+}
+
+/// The container tools upm knows about
+pub const CONTAINER_TOOLS: &[ContainerTool] = &[ContainerTool::Toolbox, ContainerTool::Distrobox];
+
+impl ContainerTool {
+    /// The binary of the tool
+    pub fn binary(self) -> &'static str {
+        match self {
+            ContainerTool::Toolbox => "toolbox",
+            ContainerTool::Distrobox => "distrobox",
+        }
+    }
+
+    /// The wrapper that runs a command in one of the tool's containers
+    pub fn exec_wrapper(self, container: &str) -> String {
+        match self {
+            ContainerTool::Toolbox => format!("toolbox run --container {}", container),
+            ContainerTool::Distrobox => format!("distrobox enter {} --", container),
+        }
+    }
+
+    /// Get the names of the containers from the output of the tool's list command
+    pub fn parse_list(self, output: &str) -> Vec<String> {
+        output.lines()
+            .filter_map(|line| match self {
+                ContainerTool::Toolbox => line.split_whitespace().nth(1).filter(|_| !line.starts_with("CONTAINER ID")),
+                ContainerTool::Distrobox => line.split('|').nth(1).map(str::trim).filter(|&name| name != "NAME"),
+            })
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    //The command that lists the tool's containers
+    fn list_command(self) -> Command {
+        let mut command = Command::new(self.binary());
+        match self {
+            ContainerTool::Toolbox => command.args(["list", "--containers"]),
+            ContainerTool::Distrobox => command.args(["list", "--no-color"]),
+        };
+        command
+    }
+}
+
+impl fmt::Display for ContainerTool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.binary())
+    }
+}
+
+/// A container package managers can be run in
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Container {
+    pub tool: ContainerTool,
+    pub name: String,
+}
+
+impl Container {
+    /// The wrapper that runs a command in the container, for a package manager's `exec_wrapper`
+    pub fn exec_wrapper(&self) -> String {
+        self.tool.exec_wrapper(&self.name)
+    }
+
+    /// Is a program in the container's PATH? This starts the container if it isn't running.
+    pub fn has_binary(&self, binary: &str) -> bool {
+        let wrapper = self.exec_wrapper();
+        let mut words = wrapper.split_whitespace();
+        let mut command = Command::new(words.next().unwrap());
+        command.args(words)
+            .args(["sh", "-c", &format!("command -v {}", binary)])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        command.status().map(|status| status.success()).unwrap_or(false)
+    }
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.tool)
+    }
+}
+
+/// Find the containers of every container tool in PATH
+pub fn detect_containers() -> Vec<Container> {
+    let mut result = Vec::new();
+    for &tool in CONTAINER_TOOLS {
+        if find_in_path(tool.binary()).is_none() {
+            continue;
+        }
+        let output = match tool.list_command().stdin(Stdio::null()).stderr(Stdio::null()).output() {
+            Ok(ref output) if output.status.success() => String::from_utf8_lossy(&output.stdout).into_owned(),
+            Ok(output) => {
+                warn!("Couldn't list the {} containers, {} exited with {}", tool, tool, output.status);
+                continue;
+            },
+            Err(e) => {
+                warn!("Couldn't list the {} containers: {}", tool, e);
+                continue;
+            },
+        };
+        result.extend(tool.parse_list(&output).into_iter().map(|name| Container { tool, name }));
+    }
+    result
+}
+
+/// Is upm itself running inside a container?
+pub fn in_container() -> bool {
+    env::var_os("container").is_some() || Path::new("/run/.containerenv").exists() || Path::new("/.dockerenv").exists()
+}
+
+/// Is the host an image-based distribution whose packages are changed through containers or
+/// transactional updates, such as Silverblue or MicroOS?
+pub fn is_immutable_host() -> bool {
+    Path::new("/run/ostree-booted").exists() || find_in_path("transactional-update").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, PackageManager, Scope};
+    use toml::Value;
+
+    #[test]
+    fn listing_containers() {
+        let toolbox = "CONTAINER ID  CONTAINER NAME     CREATED      STATUS   IMAGE NAME\n\
+                       c1d2e3f4a5b6  fedora-toolbox-39  2 weeks ago  exited   registry.fedoraproject.org/fedora-toolbox:39\n";
+        assert_eq!(ContainerTool::Toolbox.parse_list(toolbox), vec!["fedora-toolbox-39"]);
+        let distrobox = "ID           | NAME   | STATUS     | IMAGE\n\
+                         a1b2c3d4e5f6 | dev    | Up 2 hours | docker.io/library/ubuntu:22.04\n\
+                         b2c3d4e5f6a1 | arch   | Exited     | docker.io/library/archlinux:latest\n";
+        assert_eq!(ContainerTool::Distrobox.parse_list(distrobox), vec!["dev", "arch"]);
+        let container = Container { tool: ContainerTool::Distrobox, name: String::from("dev") };
+        assert_eq!(container.exec_wrapper(), "distrobox enter dev --");
+        assert_eq!(container.to_string(), "dev (distrobox)");
+    }
+
+    #[test]
+    fn wrapped_commands() {
+        let config: Value = "version = 'apt --version'\ninstall = 'apt install'\nexec_wrapper = 'distrobox enter dev --'\n".parse().unwrap();
+        let manager = PackageManager::from_toml("apt", &config, PathBuf::from("./test-files/")).unwrap();
+        assert_eq!(manager.command_line("install", "ripgrep").unwrap(), "distrobox enter dev -- apt install ripgrep");
+        assert_eq!(manager.to_toml().get("exec_wrapper").and_then(Value::as_str), Some("distrobox enter dev --"));
+        let config: Value = "version = 'apt --version'\nexec_wrapper = ' '\n".parse().unwrap();
+        assert!(PackageManager::from_toml("apt", &config, PathBuf::new()).is_err());
+
+        let mut manager = PackageManager::default().with_command(CommandKind::Version, Scope::System, "--version");
+        manager.exec_wrapper = Some(String::from("echo"));
+        assert!(manager.execute("version", "").unwrap().success());
+    }
+}
@@ -0,0 +1,188 @@
+//! The contract that scripts referenced with `./` in a package manager configuration must follow.
+//! Scripts exist to call package manager commands and format their output for upm, so the output
+//! format and exit codes are fixed.
+//!
+//! # Exit codes
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | Success |
+//! | 1 | Generic failure |
+//! | 2 | The package wasn't found |
+//! | 3 | Permission denied |
+//! | 4 | The package manager is locked by another process |
+//!
+//! # Output
+//! | Command | Output |
+//! |---------|--------|
+//! | `version` | The version of the package manager on the first line |
+//! | `search` | One package per line: the name, version, and optionally a description, separated by whitespace |
+//! | `info` | One detail of the package per line: a key and a value separated by a colon, with the keys described in the [info module](../info/index.html) |
+//! | `list_installed` | One package per line: the name and then the version, separated by whitespace |
+//! | `reverse_deps` | One package per line that depends on the package: the name and then the version, separated by whitespace |
+//! | `verify` | One problem per line: the package and then a description of the problem, separated by whitespace, exiting with 0 even if there are problems |
+//! | `audit` | One vulnerability per line: the package, the installed version, the advisory ID, the severity, and optionally the version that fixes it (or `-` if none does) and a description, separated by whitespace, exiting with 0 even if there are vulnerabilities |
+//! | `provides` | One package per line that provides the file, library, or virtual package: the name, version, and optionally a description, separated by whitespace, or nothing with exit code 2 if no package provides it |
+//! | `needs_restart` | One line per service that needs restarting, or `reboot` if the whole system needs rebooting, optionally followed by a reason, exiting with 0 even if something needs restarting |
+//! | `changelog` | The changelog of the package, ideally in the Debian or RPM format so it can be [split into entries](../changelog/fn.parse_changelog.html) |
+//! | `cache_size`, `disk_usage` | A size in bytes as the first field of the first line |
+//! | `predict_size` | The bytes installing the packages would download and then the bytes they would use once installed, as the first two fields of the first line |
+//! | `list_keys` | One key per line: the key ID and then optionally a description, separated by whitespace |
+//! | `list_repos` | One repository per line: the name and then optionally its URL, separated by whitespace |
+//! | `owns_file` | The name and version of the package that owns the file, separated by whitespace, or nothing with exit code 2 if no package owns it |
+//! | everything else | Anything, the output is shown to the user as is |
+//!
+//! When installing or removing several packages at once a script may also report what happened to
+//! each package through the [status file](../batch/index.html).
+//!
+//! # Probe mode
+//! When the `UPM_PROBE` environment variable is set to `1` a script must not make any changes. It
+//! should exit with 0 and print example output in the format for its command, which can be empty
+//! for listing commands.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use info::parse_fields;
+use usage::{parse_estimate, parse_size};
+
+/// The environment variable set when a script is run in probe mode
+pub const PROBE_VAR: &str = "UPM_PROBE";
+
+/// The script succeeded
+pub const EXIT_SUCCESS: i32 = 0;
+/// The script failed for a reason not covered by another exit code
+pub const EXIT_FAILURE: i32 = 1;
+/// The package the script was asked about wasn't found
+pub const EXIT_NOT_FOUND: i32 = 2;
+/// The script doesn't have permission to do what it was asked
+pub const EXIT_PERMISSION_DENIED: i32 = 3;
+/// The package manager is locked by another process
+pub const EXIT_LOCKED: i32 = 4;
+
+/// The format a script must print its output in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A version on the first line
+    Version,
+    /// One package per line with at least the given number of whitespace separated fields
+    Packages { fields: usize },
+    /// A size in bytes as the first field of the first line
+    Size,
+    /// Download and installed sizes in bytes as the first two fields of the first line
+    Estimate,
+    /// Lines of a key and a value separated by a colon
+    Fields,
+    /// Output that isn't parsed by upm
+    Unparsed,
+}
+
+/// What is expected of the script for a particular command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptContract {
+    pub command: &'static str,
+    pub output: OutputFormat,
+}
+
+impl ScriptContract {
+    /// Get the contract for a command
+    pub fn for_command(command: &'static str) -> ScriptContract {
+        let output = match command {
+            "version" => OutputFormat::Version,
+            "search" => OutputFormat::Packages { fields: 2 },
+            "info" => OutputFormat::Fields,
+            "list_installed" => OutputFormat::Packages { fields: 2 },
+            "owns_file" => OutputFormat::Packages { fields: 2 },
+            "reverse_deps" => OutputFormat::Packages { fields: 2 },
+            "provides" => OutputFormat::Packages { fields: 2 },
+            "verify" => OutputFormat::Packages { fields: 1 },
+            "audit" => OutputFormat::Packages { fields: 4 },
+            "needs_restart" => OutputFormat::Packages { fields: 1 },
+            "cache_size" | "disk_usage" => OutputFormat::Size,
+            "predict_size" => OutputFormat::Estimate,
+            "list_keys" | "list_repos" => OutputFormat::Packages { fields: 1 },
+            _ => OutputFormat::Unparsed,
+        };
+        ScriptContract { command, output }
+    }
+
+    /// Check that the output of a script follows the contract, giving a description of the
+    /// problem if it doesn't.
+    pub fn check_output(&self, output: &str) -> Result<(), String> {
+        match self.output {
+            OutputFormat::Version => match output.lines().next() {
+                Some(line) if !line.trim().is_empty() => Ok(()),
+                _ => Err(String::from("no version on the first line")),
+            },
+            OutputFormat::Packages { fields } => {
+                for (number, line) in output.lines().enumerate() {
+                    if !line.trim().is_empty() && line.split_whitespace().count() < fields {
+                        return Err(format!("line {} has fewer than {} fields: {}", number + 1, fields, line));
+                    }
+                }
+                Ok(())
+            },
+            OutputFormat::Size => parse_size(output).map(|_| ()).map_err(|e| e.to_string()),
+            OutputFormat::Estimate => parse_estimate(output).map(|_| ()).map_err(|e| e.to_string()),
+            OutputFormat::Fields => parse_fields(output).map(|_| ()).map_err(|e| e.to_string()),
+            OutputFormat::Unparsed => Ok(()),
+        }
+    }
+
+    /// Run a script in probe mode and check that it follows the contract
+    pub fn probe(&self, script: &Path) -> Result<(), ContractViolation> {
+        let violation = |problem: String| ContractViolation {
+            command: self.command,
+            script: script.to_path_buf(),
+            problem,
+        };
+        let output = Command::new(script)
+            .env(PROBE_VAR, "1")
+            .output()
+            .map_err(|e| violation(format!("couldn't be run: {}", e)))?;
+        if output.status.code() != Some(EXIT_SUCCESS) {
+            return Err(violation(format!("exited with {} in probe mode", output.status)));
+        }
+        let stdout = String::from_utf8(output.stdout)
+            .map_err(|_| violation(String::from("printed invalid UTF-8")))?;
+        self.check_output(&stdout).map_err(violation)
+    }
+}
+
+/// A script that doesn't follow its contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractViolation {
+    pub command: &'static str,
+    pub script: PathBuf,
+    pub problem: String,
+}
+
+impl fmt::Display for ContractViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} script {:?} {}", self.command, self.script, self.problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_formats() {
+        let search = ScriptContract::for_command("search");
+        assert!(search.check_output("ripgrep 0.7.1 Recursively search\n\n").is_ok());
+        assert!(search.check_output("ripgrep 0.7.1\n").is_ok());
+        assert!(search.check_output("ripgrep\n").is_err());
+        let version = ScriptContract::for_command("version");
+        assert!(version.check_output("1.2.3\n").is_ok());
+        assert!(version.check_output("").is_err());
+        assert!(ScriptContract::for_command("install").check_output("").is_ok());
+    }
+
+    #[test]
+    fn probing() {
+        let search = ScriptContract::for_command("search");
+        assert!(search.probe(Path::new("./test-files/contract/search.sh")).is_ok());
+        let broken = search.probe(Path::new("./test-files/contract/broken-search.sh"));
+        assert!(broken.is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! A long running daemon that keeps package managers loaded and their query caches warm, driven
+//! over a Unix socket so that editors, launchers, and graphical frontends don't have to start upm
+//! for every query.
+//!
+//! # Protocol
+//! Clients connect to the [socket](fn.socket_path.html) and send one JSON [`Request`] per line.
+//! The daemon answers each with one JSON [`Response`] on a line of its own:
+//!
+//! ```text
+//! > {"method":"search","query":"ripgrep"}
+//! < {"type":"packages","packages":[{"name":"ripgrep","version":"0.8.1","description":"","manager":"cargo"}],"errors":[]}
+//! > {"method":"install","package":"ripgrep","manager":"cargo"}
+//! < {"type":"finished","job":1,"success":true,"code":0}
+//! ```
+//!
+//! After a `subscribe` request the connection only carries `progress` responses, one for every
+//! [`ProgressEvent`] of every install the daemon runs, until the client disconnects.
+//!
+//! Installs can't prompt, so the package managers are made
+//! [non-interactive](../enum.Interaction.html) and the process lock is taken around each one.
+//!
+//! [`Request`]: enum.Request.html
+//! [`Response`]: enum.Response.html
+//! [`ProgressEvent`]: enum.ProgressEvent.html
+
+use std::fs::remove_file;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+use failure::Error;
+use serde_json;
+use super::{Interaction, ManagerSet, StdioPolicy, create_private_dir, runtime_dir, search_all};
+use progress::ProgressReporter;
+use cache::QueryCache;
+use install::InstallOptions;
+use lock::{ProcessLock, WaitPolicy};
+use resolve::{Resolution, Resolver};
+
+pub use schema::{ManagerError, ManagerInfo, PackageInfo};
+
+/// The version of the protocol, given in the status response. It changes whenever a request or
+/// response changes in a way older clients wouldn't understand.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long the daemon remembers query results
+pub const CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Get the path of the daemon's socket, `upm.sock` in the [runtime directory](../fn.runtime_dir.html)
+pub fn socket_path() -> PathBuf {
+    runtime_dir().join("upm.sock")
+}
+
+/// A request from a client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Request {
+    /// Search every package manager
+    Search { query: String },
+    /// Install a package with the given package manager, or with the one the daemon's resolver
+    /// chooses
+    Install {
+        package: String,
+        #[serde(default)]
+        manager: Option<String>,
+    },
+    /// Describe the daemon, its package managers, and its running installs
+    Status,
+    /// Receive the progress of every install from now on
+    Subscribe,
+}
+
+/// An install that hasn't finished
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: usize,
+    pub manager: String,
+    pub package: String,
+}
+
+/// Something that happened during an install
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// The package manager was started
+    Started { job: usize, manager: String, package: String },
+    /// A line the package manager printed
+    Output { job: usize, line: String },
+    /// The package manager exited, with its exit code unless it was killed by a signal
+    Finished { job: usize, success: bool, code: Option<i32> },
+}
+
+/// The daemon's answer to a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The packages a search found, along with the package managers whose search failed
+    Packages { packages: Vec<PackageInfo>, errors: Vec<ManagerError> },
+    /// An install finished
+    Finished { job: usize, success: bool, code: Option<i32> },
+    /// The state of the daemon
+    Status { protocol: u32, managers: Vec<ManagerInfo>, jobs: Vec<Job> },
+    /// Progress will be sent on this connection from now on
+    Subscribed,
+    /// Progress of an install, sent to subscribers
+    Progress { event: ProgressEvent },
+    /// The request couldn't be carried out
+    Error { message: String },
+}
+
+impl Response {
+    fn error<S: Into<String>>(message: S) -> Response {
+        Response::Error { message: message.into() }
+    }
+}
+
+/// The state shared between the daemon's connections
+pub struct Daemon {
+    managers: ManagerSet,
+    resolver: Resolver,
+    subscribers: Arc<Mutex<Vec<Sender<ProgressEvent>>>>,
+    jobs: Mutex<Vec<Job>>,
+    next_job: AtomicUsize,
+}
+
+impl Daemon {
+    /// Create a daemon for the package managers, making them non-interactive and sharing an
+    /// in-memory query cache between them. Installs without a package manager are resolved with
+    /// the resolver.
+    pub fn new(mut managers: ManagerSet, resolver: Resolver) -> Daemon {
+        managers.set_interaction(Interaction::NonInteractive);
+        let cache = Arc::new(QueryCache::in_memory(CACHE_TTL));
+        managers.set_cache(cache);
+        Daemon {
+            managers,
+            resolver,
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            jobs: Mutex::new(Vec::new()),
+            next_job: AtomicUsize::new(1),
+        }
+    }
+
+    /// Answer a request. Subscriptions are handled by the connection rather than here.
+    pub fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::Search { query } => self.search(&query),
+            Request::Install { package, manager } => self.install(&package, manager.as_deref()),
+            Request::Status => Response::Status {
+                protocol: PROTOCOL_VERSION,
+                managers: self.managers.iter()
+                    .map(|manager| ManagerInfo { name: manager.name.clone(), priority: manager.priority })
+                    .collect(),
+                jobs: self.jobs.lock().unwrap().clone(),
+            },
+            Request::Subscribe => Response::error("subscriptions are only possible over a connection"),
+        }
+    }
+
+    /// Listen on the socket at the path until an error stops the daemon, serving each connection on
+    /// its own thread. A stale socket left at the path is replaced.
+    pub fn serve<P: AsRef<Path>>(self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                bail!("A daemon is already listening on {:?}", path);
+            }
+            remove_file(path)?;
+        }
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        info!("Listening on {:?}", path);
+        let daemon = Arc::new(self);
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = daemon.clone();
+            thread::spawn(move || {
+                if let Err(e) = daemon.connection(stream) {
+                    debug!("Connection closed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &str) -> Response {
+        let mut packages = Vec::new();
+        let mut errors = Vec::new();
+        for (manager, result) in search_all(self.managers.as_slice(), query, &self.resolver.concurrency) {
+            match result {
+                Ok(found) => packages.extend(found.iter().map(PackageInfo::from)),
+                Err(e) => errors.push(ManagerError { manager: manager.name.clone(), message: e.to_string() }),
+            }
+        }
+        Response::Packages { packages, errors }
+    }
+
+    fn install(&self, package: &str, manager: Option<&str>) -> Response {
+        let (manager, name) = match manager {
+            Some(name) => match self.managers.get(name) {
+                Some(manager) => (manager, package.to_owned()),
+                None => return Response::error(format!("no package manager is called {}", name)),
+            },
+            None => match self.resolver.resolve(package, &self.managers) {
+                Resolution::Found(found) => match self.managers.get(&found.owner.name) {
+                    Some(manager) => (manager, found.name),
+                    None => return Response::error(format!("no package manager is called {}", found.owner.name)),
+                },
+                Resolution::Ambiguous(candidates) => {
+                    let owners: Vec<&str> = candidates.iter().map(|c| c.owner.name.as_str()).collect();
+                    return Response::error(format!("{} is provided by {}, choose a package manager",
+                                                   package, owners.join(", ")));
+                },
+                Resolution::NotFound => return Response::error(format!("no package manager provides {}", package)),
+            },
+        };
+        let job = Job {
+            id: self.next_job.fetch_add(1, Ordering::SeqCst),
+            manager: manager.name.clone(),
+            package: name,
+        };
+        self.jobs.lock().unwrap().push(job.clone());
+        self.broadcast(ProgressEvent::Started { job: job.id, manager: job.manager.clone(), package: job.package.clone() });
+        //The reporter is given the output instead of the terminal, a line at a time as it's printed
+        let mut manager = manager.clone();
+        manager.set_reporter(Arc::new(JobReporter { job: job.id, subscribers: self.subscribers.clone() }));
+        let result = ProcessLock::acquire(WaitPolicy::Wait)
+            .and_then(|_lock| manager.install_with(&job.package, &InstallOptions::new(), StdioPolicy::Inherit));
+        manager.invalidate_cache();
+        self.jobs.lock().unwrap().retain(|running| running.id != job.id);
+        match result {
+            Ok(output) => {
+                let (success, code) = (output.status.success(), output.status.code());
+                self.broadcast(ProgressEvent::Finished { job: job.id, success, code });
+                Response::Finished { job: job.id, success, code }
+            },
+            Err(e) => {
+                self.broadcast(ProgressEvent::Finished { job: job.id, success: false, code: None });
+                Response::error(e.to_string())
+            },
+        }
+    }
+
+    fn broadcast(&self, event: ProgressEvent) {
+        broadcast(&self.subscribers, event);
+    }
+
+    fn connection(&self, stream: UnixStream) -> Result<(), Error> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    send(&mut writer, &Response::error(format!("invalid request: {}", e)))?;
+                    continue;
+                },
+            };
+            if request == Request::Subscribe {
+                return self.subscribe(&mut writer);
+            }
+            send(&mut writer, &self.handle(request))?;
+        }
+        Ok(())
+    }
+
+    //Forward progress until the subscriber goes away
+    fn subscribe(&self, writer: &mut UnixStream) -> Result<(), Error> {
+        let (sender, events) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        send(writer, &Response::Subscribed)?;
+        for event in events {
+            send(writer, &Response::Progress { event })?;
+        }
+        Ok(())
+    }
+}
+
+fn send<W: Write>(writer: &mut W, response: &Response) -> Result<(), Error> {
+    let mut line = serde_json::to_string(response)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Send a request to the daemon listening on the socket and read its response
+pub fn request<P: AsRef<Path>>(path: P, request: &Request) -> Result<Response, Error> {
+    let mut stream = UnixStream::connect(path)?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+//Sends each line an install prints to the subscribers while the install runs
+struct JobReporter {
+    job: usize,
+    subscribers: Arc<Mutex<Vec<Sender<ProgressEvent>>>>,
+}
+
+impl ProgressReporter for JobReporter {
+    fn output_line(&self, _manager: &str, line: &str) {
+        broadcast(&self.subscribers, ProgressEvent::Output { job: self.job, line: line.to_owned() });
+    }
+}
+
+//Send an event to every subscriber, forgetting those that have disconnected
+fn broadcast(subscribers: &Mutex<Vec<Sender<ProgressEvent>>>, event: ProgressEvent) {
+    subscribers.lock().unwrap().retain(|subscriber| subscriber.send(event.clone()).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, PackageManager, Scope};
+    use resolve::ResolutionPolicy;
+
+    fn daemon() -> Daemon {
+        let managers = ManagerSet::from_config_dirs(vec!["./test-files/priorities"], &super::super::ManagerSpecifier::Empty);
+        Daemon::new(managers, Resolver::new(ResolutionPolicy::PreferPriority))
+    }
+
+    #[test]
+    fn status() {
+        match daemon().handle(Request::Status) {
+            Response::Status { protocol, managers, jobs } => {
+                assert_eq!(protocol, PROTOCOL_VERSION);
+                assert!(!managers.is_empty());
+                assert!(jobs.is_empty());
+            },
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_manager() {
+        let request = Request::Install { package: String::from("ripgrep"), manager: Some(String::from("missing")) };
+        match daemon().handle(request) {
+            Response::Error { message } => assert!(message.contains("missing")),
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn streaming_installs() {
+        let manager = PackageManager { name: String::from("echo"), ..Default::default() }
+            .with_command(CommandKind::Install, Scope::System, "echo installing");
+        let daemon = Daemon::new(ManagerSet::new(vec![manager]), Resolver::new(ResolutionPolicy::PreferPriority));
+        let (sender, receiver) = channel();
+        daemon.subscribers.lock().unwrap().push(sender);
+        let request = Request::Install { package: String::from("ripgrep"), manager: Some(String::from("echo")) };
+        match daemon.handle(request) {
+            Response::Finished { success, .. } => assert!(success),
+            other => panic!("unexpected response {:?}", other),
+        }
+        let events: Vec<ProgressEvent> = receiver.try_iter().collect();
+        assert_eq!(events[1], ProgressEvent::Output { job: 1, line: String::from("installing ripgrep") });
+        assert_eq!(events[2], ProgressEvent::Finished { job: 1, success: true, code: Some(0) });
+    }
+
+    #[test]
+    fn json_protocol() {
+        let request: Request = serde_json::from_str(r#"{"method":"install","package":"ripgrep"}"#).unwrap();
+        assert_eq!(request, Request::Install { package: String::from("ripgrep"), manager: None });
+        let response = serde_json::to_string(&Response::Progress {
+            event: ProgressEvent::Output { job: 1, line: String::from("done") },
+        }).unwrap();
+        assert_eq!(response, r#"{"type":"progress","event":{"kind":"output","job":1,"line":"done"}}"#);
+    }
+}
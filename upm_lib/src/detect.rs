@@ -0,0 +1,282 @@
+//! Finding package managers on a machine without any configuration. Each well-known package
+//! manager has a built-in template, and [`detect_managers`](fn.detect_managers.html) creates
+//! package managers from the templates of those whose binaries are in PATH.
+//!
+//! The templates only use commands whose output upm doesn't need to parse, or whose native
+//! output already follows the [script contract](../contract/index.html), so searching usually
+//! needs a configuration with a script.
+
+use std::path::PathBuf;
+use failure::Error;
+use toml::Value;
+use super::{PackageManager, ManagerSpecifier, find_in_path};
+use container::Container;
+
+/// The built-in templates as the name of the package manager, the binary that shows it's
+/// installed, and its configuration
+const TEMPLATES: &[(&str, &str, &str)] = &[
+    ("apt", "apt", r#"
+version = "apt --version"
+install = "apt install"
+remove = "apt remove"
+upgrade = "apt install --only-upgrade"
+upgrade_all = "apt upgrade"
+list_installed = "dpkg-query -W"
+download = "apt-get download"
+changelog = "apt-get changelog"
+clean_cache = "apt clean"
+autoremove = "apt autoremove"
+refresh = "apt update"
+metadata_path = "/var/lib/apt/lists"
+add_repo = "add-apt-repository"
+remove_repo = "add-apt-repository --remove"
+lock_class = "dpkg"
+version_scheme = "debian"
+version_format = "{name}={version}"
+
+[noninteractive]
+install = "-y"
+remove = "-y"
+upgrade = "-y"
+upgrade_all = "-y"
+autoremove = "-y"
+"#),
+    ("dnf", "dnf", r#"
+version = "dnf --version"
+install = "dnf install"
+remove = "dnf remove"
+upgrade = "dnf upgrade"
+upgrade_all = "dnf upgrade"
+download = "dnf download"
+changelog = "dnf changelog"
+clean_cache = "dnf clean packages"
+autoremove = "dnf autoremove"
+needs_restart = "dnf needs-restarting --services"
+refresh = "dnf makecache"
+add_key = "rpm --import"
+list_keys = "rpm -q gpg-pubkey"
+add_repo = "dnf config-manager --add-repo"
+lock_class = "rpm"
+version_scheme = "rpm"
+version_format = "{name}-{version}"
+
+[noninteractive]
+install = "-y"
+remove = "-y"
+upgrade = "-y"
+upgrade_all = "-y"
+autoremove = "-y"
+"#),
+    ("zypper", "zypper", r#"
+version = "zypper --version"
+install = "zypper install"
+remove = "zypper remove"
+upgrade = "zypper update"
+upgrade_all = "zypper update"
+clean_cache = "zypper clean"
+refresh = "zypper refresh"
+add_repo = "zypper addrepo"
+remove_repo = "zypper removerepo"
+add_key = "rpm --import"
+list_keys = "rpm -q gpg-pubkey"
+lock_class = "rpm"
+version_scheme = "rpm"
+version_format = "{name}={version}"
+"#),
+    ("pacman", "pacman", r#"
+version = "pacman --version"
+install = "pacman -S"
+remove = "pacman -Rs"
+upgrade = "pacman -S"
+upgrade_all = "pacman -Syu"
+list_installed = "pacman -Q"
+clean_cache = "pacman -Sc"
+refresh = "pacman -Sy"
+metadata_path = "/var/lib/pacman/sync"
+add_key = "pacman-key --add"
+lock_class = "pacman"
+version_scheme = "rpm"
+
+[noninteractive]
+install = "--noconfirm"
+remove = "--noconfirm"
+upgrade = "--noconfirm"
+upgrade_all = "--noconfirm"
+clean_cache = "--noconfirm"
+"#),
+    ("apk", "apk", r#"
+version = "apk --version"
+install = "apk add"
+remove = "apk del"
+upgrade = "apk upgrade"
+upgrade_all = "apk upgrade"
+clean_cache = "apk cache clean"
+refresh = "apk update"
+lock_class = "apk"
+version_format = "{name}={version}"
+"#),
+    ("brew", "brew", r#"
+version = "brew --version"
+version_regex = '^Homebrew >?=?(?P<version>[\d.]+)'
+install = "brew install"
+remove = "brew uninstall"
+upgrade = "brew upgrade"
+upgrade_all = "brew upgrade"
+list_installed = "brew list --versions"
+clean_cache = "brew cleanup"
+autoremove = "brew autoremove"
+refresh = "brew update"
+add_repo = "brew tap"
+remove_repo = "brew untap"
+list_repos = "brew tap"
+lock_class = "brew"
+"#),
+    ("cargo", "cargo", r#"
+version = "cargo --version"
+install = "cargo install"
+remove = "cargo uninstall"
+upgrade = "cargo install --force"
+"#),
+    ("pip", "pip", r#"
+version = "pip --version"
+install = "pip install --user"
+self_update = "pip install --user --upgrade pip"
+remove = "pip uninstall"
+upgrade = "pip install --user --upgrade"
+download = "pip download"
+version_format = "{name}=={version}"
+
+[noninteractive]
+remove = "-y"
+"#),
+    ("flatpak", "flatpak", r#"
+kind = "application"
+version = "flatpak --version"
+install = "flatpak install"
+install_local = "flatpak install --user"
+remove = "flatpak uninstall"
+remove_local = "flatpak uninstall --user"
+upgrade = "flatpak update"
+upgrade_all = "flatpak update"
+list_installed = "flatpak list --app --system --columns=application,version"
+list_installed_local = "flatpak list --app --user --columns=application,version"
+autoremove = "flatpak uninstall --unused"
+add_repo = "flatpak remote-add --if-not-exists"
+add_repo_local = "flatpak remote-add --user --if-not-exists"
+remove_repo = "flatpak remote-delete"
+remove_repo_local = "flatpak remote-delete --user"
+
+[noninteractive]
+install = "-y"
+remove = "-y"
+upgrade = "-y"
+upgrade_all = "-y"
+autoremove = "-y"
+"#),
+    ("snap", "snap", r#"
+kind = "application"
+version = "snap --version"
+install = "snap install"
+remove = "snap remove"
+upgrade = "snap refresh"
+upgrade_all = "snap refresh"
+"#),
+    ("npm", "npm", r#"
+version = "npm --version"
+install = "npm install -g"
+remove = "npm uninstall -g"
+upgrade = "npm update -g"
+upgrade_all = "npm update -g"
+self_update = "npm install -g npm"
+clean_cache = "npm cache clean --force"
+version_format = "{name}@{version}"
+"#),
+];
+
+/// Get the names of the package managers with built-in templates
+pub fn template_names() -> Vec<&'static str> {
+    TEMPLATES.iter().map(|&(name, _, _)| name).collect()
+}
+
+/// Get the built-in configuration of a package manager
+pub fn template(name: &str) -> Option<&'static str> {
+    TEMPLATES.iter()
+        .find(|&&(template, _, _)| template == name)
+        .map(|&(_, _, config)| config.trim_start())
+}
+
+/// Create package managers from the built-in templates for every well-known package manager with
+/// its binary in PATH, sorted by priority and then name
+pub fn detect_managers() -> Vec<PackageManager> {
+    detect_managers_except(&ManagerSpecifier::Empty)
+}
+
+/// Detect package managers like [`detect_managers`](fn.detect_managers.html), leaving out those
+/// the specifier excludes
+pub fn detect_managers_except(names: &ManagerSpecifier) -> Vec<PackageManager> {
+    from_templates(names, |binary| find_in_path(binary).is_some())
+}
+
+/// Create package managers from the built-in templates for every well-known package manager
+/// inside a [container](../container/index.html), run through the container's wrapper. They're
+/// named after the package manager and the container, such as `apt-dev`.
+pub fn detect_in_container(container: &Container) -> Vec<PackageManager> {
+    let mut result = from_templates(&ManagerSpecifier::Empty, |binary| container.has_binary(binary));
+    for manager in &mut result {
+        manager.name = format!("{}-{}", manager.name, container.name);
+        manager.exec_wrapper = Some(container.exec_wrapper());
+    }
+    result
+}
+
+//Create package managers from the templates the specifier allows whose binaries exist, sorted by
+//priority and then name
+fn from_templates<F: Fn(&str) -> bool>(names: &ManagerSpecifier, exists: F) -> Vec<PackageManager> {
+    let mut result: Vec<PackageManager> = TEMPLATES.iter()
+        .filter(|&&(name, _, _)| names.allows(name))
+        .filter(|&&(_, binary, _)| exists(binary))
+        .filter_map(|&(name, _, config)| match from_template(name, config) {
+            Ok(manager) => Some(manager),
+            Err(e) => {
+                warn!("The built-in template for {} is broken: {}", name, e);
+                None
+            },
+        })
+        .collect();
+    result.sort_by(|a, b| a.cmp_priority(b));
+    result
+}
+
+//Create a package manager from a built-in template
+fn from_template(name: &str, config: &str) -> Result<PackageManager, Error> {
+    debug!("Detected {} and using its built-in template", name);
+    PackageManager::from_toml(name, &config.parse::<Value>()?, PathBuf::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn templates_parse() {
+        for &(name, _, config) in TEMPLATES {
+            let manager = from_template(name, config).unwrap();
+            assert_eq!(manager.name, name);
+        }
+        assert!(template("apt").unwrap().starts_with("version"));
+        assert!(template("nix").is_none());
+        let flatpak = from_template("flatpak", template("flatpak").unwrap()).unwrap();
+        assert_eq!(flatpak.kind, Some(::kind::PackageKind::Application));
+        assert!(flatpak.has_command("install_local"));
+    }
+
+    #[test]
+    fn detection_follows_path() {
+        let mut names = HashSet::new();
+        names.insert(String::from("cargo"));
+        let detected = detect_managers_except(&ManagerSpecifier::Includes(names));
+        assert!(detected.iter().all(|manager| manager.name == "cargo"));
+        assert_eq!(detected.len(), find_in_path("cargo").map_or(0, |_| 1));
+    }
+}
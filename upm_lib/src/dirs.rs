@@ -0,0 +1,169 @@
+//! Where package manager configurations are read from, decided when upm runs instead of when it's
+//! built, so distributions and users can move them without rebuilding. The directories are, from
+//! the strongest choice to the weakest:
+//!
+//! 1. The directories a frontend was given, such as upm's `--config-dir`
+//! 2. The `UPM_CONFIG_DIRS` environment variable, a list separated like `PATH`
+//! 3. The user's [configuration directory](../fn.user_config_dir.html) followed by the
+//!    `config_dirs` of the global [settings file](../settings/index.html), `/etc/upm/upm.toml`
+//! 4. The user's configuration directory followed by `/etc/upm/managers` and
+//!    `/usr/share/upm/managers`
+//!
+//! The first choice that gives any directories is used alone. Relative directories in the settings
+//! file are relative to the file.
+//!
+//! ```toml
+//! config_dirs = ["/etc/upm/managers", "/usr/local/share/upm/managers"]
+//! ```
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use super::user_config_dir;
+use super::settings::{self, Settings};
+
+/// The environment variable listing the configuration directories
+pub const ENV_VAR: &str = "UPM_CONFIG_DIRS";
+
+/// The system directories read when neither the environment nor the settings file lists any
+pub const DEFAULT_DIRS: &[&str] = &["/etc/upm/managers", "/usr/share/upm/managers"];
+
+/// The choices that decide the configuration directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDirs {
+    overrides: Vec<PathBuf>,
+    env: Option<OsString>,
+    settings: PathBuf,
+    user: Option<PathBuf>,
+    legacy: Vec<PathBuf>,
+}
+
+impl ConfigDirs {
+    /// Decide the directories from the environment and the global settings file
+    pub fn new() -> ConfigDirs {
+        ConfigDirs {
+            overrides: Vec::new(),
+            env: env::var_os(ENV_VAR),
+            settings: PathBuf::from(settings::GLOBAL_PATH),
+            user: user_config_dir(),
+            legacy: Vec::new(),
+        }
+    }
+
+    /// Use these directories instead of any others
+    pub fn with_overrides(mut self, dirs: Vec<PathBuf>) -> ConfigDirs {
+        self.overrides = dirs;
+        self
+    }
+
+    /// Read the `config_dirs` of another settings file instead of the global one
+    pub fn with_settings<P: AsRef<Path>>(mut self, path: P) -> ConfigDirs {
+        self.settings = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Use this list of directories, separated like `PATH`, in place of `UPM_CONFIG_DIRS`
+    pub fn with_env(mut self, dirs: Option<OsString>) -> ConfigDirs {
+        self.env = dirs;
+        self
+    }
+
+    /// Use another directory for the user's own configurations, or none
+    pub fn with_user(mut self, dir: Option<PathBuf>) -> ConfigDirs {
+        self.user = dir;
+        self
+    }
+
+    /// Read these directories after the system directories unless the directories were
+    /// overridden. This is only for the directories chosen when upm was built, which is
+    /// deprecated.
+    pub fn with_legacy(mut self, dirs: Vec<PathBuf>) -> ConfigDirs {
+        self.legacy = dirs.into_iter().filter(|dir| !dir.as_os_str().is_empty()).collect();
+        self
+    }
+
+    /// The directories to read configurations from, from highest precedence to lowest. Gives an
+    /// error if the settings file can't be read.
+    pub fn resolve(&self) -> Result<Vec<PathBuf>, Error> {
+        if !self.overrides.is_empty() {
+            return Ok(self.overrides.clone());
+        }
+        if let Some(ref list) = self.env {
+            let dirs: Vec<PathBuf> = env::split_paths(list).filter(|dir| !dir.as_os_str().is_empty()).collect();
+            if !dirs.is_empty() {
+                return Ok(dirs);
+            }
+        }
+        let mut dirs: Vec<PathBuf> = self.user.iter().cloned().collect();
+        dirs.extend(self.system_dirs()?);
+        for dir in &self.legacy {
+            warn!("{:?} was chosen when upm was built, which is deprecated. List it in the config_dirs of {:?} instead.",
+                  dir, self.settings);
+        }
+        dirs.extend(self.legacy.iter().cloned());
+        Ok(dirs)
+    }
+
+    /// The directory system wide configurations are written to: the first overriding directory,
+    /// or otherwise the first system directory
+    pub fn global_dir(&self) -> Result<PathBuf, Error> {
+        if let Some(dir) = self.overrides.first() {
+            return Ok(dir.clone());
+        }
+        Ok(self.system_dirs()?.into_iter().next().unwrap_or_else(|| PathBuf::from(DEFAULT_DIRS[0])))
+    }
+
+    //The directories of the settings file, or the defaults if it doesn't list any
+    fn system_dirs(&self) -> Result<Vec<PathBuf>, Error> {
+        let dirs = Settings::from_file(&self.settings)?.config_dirs;
+        if dirs.is_empty() {
+            Ok(DEFAULT_DIRS.iter().map(PathBuf::from).collect())
+        } else {
+            Ok(dirs)
+        }
+    }
+}
+
+impl Default for ConfigDirs {
+    fn default() -> ConfigDirs {
+        ConfigDirs::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+
+    #[test]
+    fn precedence() {
+        let dir = env::temp_dir().join(format!("upm-dirs-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let settings = dir.join("upm.toml");
+        File::create(&settings).unwrap().write_all(b"config_dirs = ['managers', '/opt/upm']\n").unwrap();
+        let dirs = ConfigDirs::new()
+            .with_settings(&settings)
+            .with_user(Some(PathBuf::from("/home/me/.config/upm")))
+            .with_env(None)
+            .with_legacy(vec![PathBuf::from("/etc/"), PathBuf::new()]);
+        assert_eq!(dirs.resolve().unwrap(), vec![PathBuf::from("/home/me/.config/upm"), dir.join("managers"),
+                                                 PathBuf::from("/opt/upm"), PathBuf::from("/etc/")]);
+        assert_eq!(dirs.global_dir().unwrap(), dir.join("managers"));
+
+        let dirs = dirs.with_env(Some(OsString::from("/a:/b")));
+        assert_eq!(dirs.resolve().unwrap(), vec![PathBuf::from("/a"), PathBuf::from("/b")]);
+        let dirs = dirs.with_overrides(vec![PathBuf::from("/c")]);
+        assert_eq!(dirs.resolve().unwrap(), vec![PathBuf::from("/c")]);
+        assert_eq!(dirs.global_dir().unwrap(), PathBuf::from("/c"));
+
+        let dirs = ConfigDirs::new().with_settings(dir.join("missing.toml")).with_user(None).with_env(Some(OsString::new()));
+        assert_eq!(dirs.resolve().unwrap(), vec![PathBuf::from("/etc/upm/managers"), PathBuf::from("/usr/share/upm/managers")]);
+
+        File::create(&settings).unwrap().write_all(b"config_dirs = '/opt/upm'\n").unwrap();
+        assert!(dirs.with_settings(&settings).resolve().is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+//! Health checks for package manager configurations. A configuration can be perfectly valid TOML
+//! and still be unusable because a binary isn't installed or a script isn't executable, so
+//! [`check_config_dirs`](fn.check_config_dirs.html) merges the configuration files like upm does and
+//! reports the problems found for each package manager.
+//!
+//! [`lint_config_dirs`](fn.lint_config_dirs.html) checks the configuration files themselves
+//! without running anything: unknown keys, a missing version command, scripts that don't exist or
+//! aren't executable, and placeholders that aren't known.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{File, read_dir};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use toml::Value;
+use toml::value::Table;
+use super::{PackageManager, ManagerSpecifier, CommandKind, Scope, ARGS_PLACEHOLDER, CONFIG_KEYS, find_in_path};
+use command;
+use overlay::{INHERIT_KEY, merge_layered};
+use settings;
+use trust::TrustPolicy;
+use version;
+
+/// How serious a problem found by the doctor is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The package manager can be used, but something looks off
+    Warning,
+    /// Some or all of the package manager can't be used
+    Error,
+}
+
+/// A single problem with a package manager's configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+/// The problems found with a single package manager configuration
+#[derive(Debug, Clone)]
+pub struct ManagerReport {
+    pub name: String,
+    pub path: PathBuf,
+    pub findings: Vec<Finding>,
+}
+
+impl ManagerReport {
+    fn new(name: &str, path: &Path) -> ManagerReport {
+        ManagerReport {
+            name: name.to_owned(),
+            path: path.to_path_buf(),
+            findings: Vec::new(),
+        }
+    }
+
+    fn warn(&mut self, message: String) {
+        self.findings.push(Finding { severity: Severity::Warning, message });
+    }
+
+    fn error(&mut self, message: String) {
+        self.findings.push(Finding { severity: Severity::Error, message });
+    }
+
+    /// Is the package manager free of errors? Warnings are allowed.
+    pub fn is_healthy(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// The findings that make the package manager unusable
+    pub fn errors<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Finding> + 'a> {
+        Box::new(self.findings.iter().filter(|f| f.severity == Severity::Error))
+    }
+
+    /// The findings that don't stop the package manager from being used
+    pub fn warnings<'a>(&'a self) -> Box<dyn Iterator<Item = &'a Finding> + 'a> {
+        Box::new(self.findings.iter().filter(|f| f.severity == Severity::Warning))
+    }
+}
+
+/// Check every package manager configured in the given directories, as it is once its
+/// [layers](../overlay/index.html) are merged. Files that can't be read are reported on their own.
+/// The reports are sorted by name and then by path.
+pub fn check_config_dirs<P: AsRef<Path>>(directories: Vec<P>) -> Vec<ManagerReport> {
+    let directories: Vec<PathBuf> = directories.iter().map(|dir| dir.as_ref().to_path_buf()).collect();
+    let mut result = Vec::new();
+    for (name, manager) in merge_layered(directories.clone(), &ManagerSpecifier::Empty, &TrustPolicy::new()) {
+        match manager {
+            Ok(manager) => result.push(check_manager(&manager)),
+            Err(e) => {
+                let mut report = ManagerReport::new(&name, &directories[0]);
+                report.error(format!("couldn't be merged: {}", e));
+                result.push(report);
+            },
+        }
+    }
+    //The layers leave out files that can't be read, which would otherwise go unreported
+    for dir in directories {
+        if let Ok(entries) = read_dir(dir) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                if !settings::is_definition(&path) {
+                    continue;
+                }
+                if let Err(e) = read_table(&path) {
+                    let mut report = ManagerReport::new(&stem(&path), &path);
+                    report.error(e);
+                    result.push(report);
+                }
+            }
+        }
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+    result
+}
+
+/// Check a single package manager configuration file
+pub fn check_file<P: AsRef<Path>>(path: P) -> ManagerReport {
+    let path = path.as_ref();
+    let name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    match PackageManager::from_file(path) {
+        Ok(manager) => {
+            let mut report = check_manager(&manager);
+            report.path = path.to_path_buf();
+            report
+        },
+        Err(e) => {
+            let mut report = ManagerReport::new(&name, path);
+            report.error(format!("couldn't be read: {}", e));
+            report
+        },
+    }
+}
+
+/// Check that a package manager's commands can be run. This runs the version command.
+pub fn check_manager(manager: &PackageManager) -> ManagerReport {
+    let mut report = ManagerReport::new(&manager.name, &manager.config_dir);
+    let mut commands: Vec<(&(CommandKind, Scope), &str)> = manager.commands.iter()
+        .map(|(key, template)| (key, template.as_str()))
+        .collect();
+    commands.sort_by_key(|&(key, _)| *key);
+    for (&(kind, scope), template) in commands {
+        let name = kind.key(scope);
+        check_placeholders(&mut report, &name, template);
+        let program = match template.split_whitespace().next() {
+            Some(program) => program,
+            None => {
+                report.error(format!("{} command is empty", name));
+                continue;
+            },
+        };
+        if program.starts_with("./") {
+            check_script(&mut report, &name, &manager.config_dir.join(program));
+        } else if program.contains('/') {
+            if !Path::new(program).exists() {
+                report.error(format!("{} command uses {} which doesn't exist", name, program));
+            }
+        } else if find_in_path(program).is_none() {
+            report.error(format!("{} command uses {} which isn't in PATH", name, program));
+        }
+    }
+    check_version(&mut report, manager);
+    report
+}
+
+fn check_placeholders(report: &mut ManagerReport, name: &str, template: &str) {
+    let placeholder = Regex::new(r"\{[^}]*\}").unwrap();
+    for found in placeholder.find_iter(template) {
+        if found.as_str() != ARGS_PLACEHOLDER {
+            report.error(format!("{} command has unknown placeholder {}", name, found.as_str()));
+        }
+    }
+}
+
+fn check_script(report: &mut ManagerReport, name: &str, script: &Path) {
+    if !script.is_file() {
+        report.error(format!("{} command uses script {:?} which doesn't exist", name, script));
+    } else if !is_executable(script) {
+        report.error(format!("{} command uses script {:?} which isn't executable", name, script));
+    }
+}
+
+fn check_version(report: &mut ManagerReport, manager: &PackageManager) {
+    let mut command = match manager.make_command("version", "") {
+        Ok(command) => command,
+        Err(e) => return report.error(e.to_string()),
+    };
+    match command.output() {
+        Ok(ref output) if !output.status.success() => {
+            report.error(format!("version command exited with {}", output.status));
+        },
+        Ok(ref output) if String::from_utf8_lossy(&output.stdout).trim().is_empty() => {
+            report.warn(String::from("version command didn't print a version"));
+        },
+        Ok(ref output) => {
+            let output = String::from_utf8_lossy(&output.stdout);
+            match manager.parse_version_output(&output) {
+                Ok(_) => check_manager_version(report, manager, &output),
+                Err(_) => report.warn(String::from("version command's output doesn't match its version_regex")),
+            }
+        },
+        Err(e) => report.error(format!("version command couldn't be run: {}", e)),
+    }
+}
+
+fn check_manager_version(report: &mut ManagerReport, manager: &PackageManager, output: &str) {
+    let wanted = match manager.min_version.as_ref().or(manager.recommended_version.as_ref()) {
+        Some(wanted) => wanted,
+        None => return,
+    };
+    let version = match manager.parse_version_output(output).ok().filter(version::is_numeric) {
+        Some(version) => version,
+        None => return report.warn(format!("version command didn't print a version to compare with {}", wanted)),
+    };
+    let older = |other: &&version::Version| version::compare_manager_versions(&version, other) == Ordering::Less;
+    if let Some(required) = manager.min_version.as_ref().filter(older) {
+        report.error(format!("version {} is older than {}, the oldest version its configuration supports", version, required));
+    } else if let Some(recommended) = manager.recommended_version.as_ref().filter(older) {
+        let advice = if manager.has_command("self_update") { ", update it with its self_update command" } else { "" };
+        report.warn(format!("version {} is older than the recommended {}{}", version, recommended, advice));
+    }
+}
+
+/// Check every package manager configuration file in the given directories without running
+/// anything. A file without a version command is only an error if no other file of the same name
+/// in the directories has one and it doesn't inherit one, since
+/// [layers](../overlay/index.html) are merged. The reports are sorted by name and then by path.
+pub fn lint_config_dirs<P: AsRef<Path>>(directories: Vec<P>) -> Vec<ManagerReport> {
+    let mut files: Vec<(PathBuf, Result<Table, String>)> = Vec::new();
+    for dir in directories {
+        if let Ok(entries) = read_dir(dir) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                if settings::is_definition(&path) {
+                    let table = read_table(&path);
+                    files.push((path, table));
+                }
+            }
+        }
+    }
+    let versioned: HashSet<String> = files.iter()
+        .filter(|(_, table)| table.as_ref().is_ok_and(|table| table.contains_key("version")))
+        .map(|(path, _)| stem(path))
+        .collect();
+    let mut result: Vec<ManagerReport> = files.into_iter()
+        .map(|(path, table)| lint_table(&path, table, versioned.contains(&stem(&path))))
+        .collect();
+    result.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+    result
+}
+
+/// Check a single package manager configuration file without running anything
+pub fn lint_file<P: AsRef<Path>>(path: P) -> ManagerReport {
+    let path = path.as_ref();
+    lint_table(path, read_table(path), false)
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+}
+
+fn read_table(path: &Path) -> Result<Table, String> {
+    let mut content = String::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+        return Err(format!("couldn't be read: {}", e));
+    }
+    match content.parse::<Value>() {
+        Ok(Value::Table(table)) => Ok(table),
+        Ok(_) => Err(String::from("isn't a table")),
+        Err(e) => Err(format!("isn't valid TOML: {}", e)),
+    }
+}
+
+//Lints a parsed configuration file. The version command may come from another layer.
+fn lint_table(path: &Path, table: Result<Table, String>, versioned_elsewhere: bool) -> ManagerReport {
+    let name = stem(path);
+    let mut report = ManagerReport::new(&name, path);
+    let mut table = match table {
+        Ok(table) => table,
+        Err(e) => {
+            report.error(e);
+            return report;
+        },
+    };
+    let config_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut templates: Vec<(String, String)> = Vec::new();
+    for (key, value) in &table {
+        if command::parse_key(key).is_some() {
+            if let Some(template) = value.as_str() {
+                templates.push((key.clone(), template.to_owned()));
+            }
+        } else if !CONFIG_KEYS.contains(&key.as_str()) {
+            report.warn(format!("unknown key {}", key));
+        }
+    }
+    for section in &["extras", "hooks"] {
+        if let Some(section) = table.get(*section).and_then(Value::as_table) {
+            for (key, value) in section {
+                if let Some(template) = value.as_str() {
+                    templates.push((key.clone(), template.to_owned()));
+                }
+            }
+        }
+    }
+    for (name, template) in &templates {
+        check_placeholders(&mut report, name, template);
+        match template.split_whitespace().next() {
+            Some(program) if program.starts_with("./") => check_script(&mut report, name, &config_dir.join(program)),
+            Some(program) if program.starts_with('/') && !Path::new(program).exists() => {
+                report.error(format!("{} command uses {} which doesn't exist", name, program));
+            },
+            _ => {},
+        }
+    }
+    if !table.contains_key("version") {
+        if !versioned_elsewhere && !table.contains_key(INHERIT_KEY) {
+            report.error(String::from("no version command"));
+        }
+        //Check the rest of the configuration as if it had one, like it will once it's merged
+        table.insert(String::from("version"), Value::String(String::from("true")));
+    }
+    if let Err(e) = PackageManager::from_toml(&name, &Value::Table(table), config_dir.to_path_buf()) {
+        report.error(e.to_string());
+    }
+    report
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match path.metadata() {
+        Ok(metadata) => metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{ManagerSet, TooOld, Version};
+
+    #[test]
+    fn unreadable_config() {
+        let report = check_file("./test-files/nonexistent.toml");
+        assert_eq!(report.name, "nonexistent");
+        assert!(!report.is_healthy());
+    }
+
+    #[test]
+    fn report_ordering() {
+        let reports = check_config_dirs(vec!["./test-files/priorities", "./test-files"]);
+        let names: Vec<&str> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["apt", "brew", "cargo", "pacman", "zypper"]);
+    }
+
+    #[test]
+    fn merged_layers() {
+        let reports = check_config_dirs(vec!["./test-files/overlay/user", "./test-files/overlay/system"]);
+        let names: Vec<&str> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["loop", "pacman", "yay"]);
+        assert!(reports[0].errors().any(|f| f.message.contains("inherits from itself")));
+        //The user layer has no version command of its own, but the merged configuration does
+        let version = |report: &ManagerReport| report.findings.iter().any(|f| f.message.contains("version"));
+        assert!(!version(&reports[1]));
+        assert!(!version(&reports[2]));
+    }
+
+    #[test]
+    fn broken_manager() {
+        let fake_manager = PackageManager {
+            name: String::from("fake"),
+            config_dir: PathBuf::from("./test-files/"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "./fake/version.sh") //this file is not executable
+            .with_command(CommandKind::Install, Scope::System, "upm-missing-binary {package}");
+        let report = check_manager(&fake_manager);
+        assert!(!report.is_healthy());
+        let messages: Vec<&str> = report.errors().map(|f| f.message.as_str()).collect();
+        assert!(messages.iter().any(|m| m.contains("isn't executable")));
+        assert!(messages.iter().any(|m| m.contains("isn't in PATH")));
+        assert!(messages.iter().any(|m| m.contains("unknown placeholder {package}")));
+    }
+
+    #[test]
+    fn outdated_manager() {
+        let mut manager = PackageManager { name: String::from("pip"), ..Default::default() }
+            .with_command(CommandKind::Version, Scope::System, "echo pip 9.0.1 from /usr/lib/python3");
+        manager.recommended_version = Some(Version::from("23.0.0"));
+        assert!(check_manager(&manager).warnings().any(|f| f.message.contains("older than the recommended 23.0.0")));
+        assert_eq!(manager.is_outdated(), Some(true));
+        manager.recommended_version = Some(Version::from("9.0.1"));
+        assert_eq!(check_manager(&manager).warnings().count(), 0);
+
+        manager.min_version = Some(Version::from("10.0"));
+        let report = check_manager(&manager);
+        assert!(!report.is_healthy());
+        assert!(report.findings.iter().any(|f| f.message.contains("older than 10.0, the oldest version")));
+        assert!(manager.check_min_version().unwrap_err().downcast::<TooOld>().is_ok());
+        let mut set = ManagerSet::new(vec![manager.clone()]);
+        assert_eq!(set.remove_unusable().len(), 1);
+        assert!(set.is_empty());
+        manager.min_version = Some(Version::from("9.0"));
+        assert!(manager.check_min_version().is_ok());
+    }
+
+    #[test]
+    fn linting() {
+        let reports = lint_config_dirs(vec!["./test-files/lint", "./test-files/priorities"]);
+        let names: Vec<&str> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["apt", "brew", "broken", "cargo", "cargo", "zypper"]);
+        let broken = &reports[2];
+        let messages: Vec<String> = broken.findings.iter().map(Finding::to_string).collect();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0], "warning: unknown key instal");
+        assert!(messages[1].contains("missing.sh\" which doesn't exist"));
+        assert_eq!(messages[2..], ["error: search command has unknown placeholder {query}",
+                                   "error: no version command",
+                                   "error: Package manager priority must be an integer"]);
+        assert!(reports[3].is_healthy());
+        assert!(!lint_file("./test-files/lint/layered.toml").is_healthy());
+        assert!(!lint_file("./test-files/nonexistent.toml").is_healthy());
+    }
+}
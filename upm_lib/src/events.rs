@@ -0,0 +1,131 @@
+//! A machine-readable stream of what package managers are doing, for tools that integrate with
+//! upm. An [`EventStream`](struct.EventStream.html) attached to a package manager with
+//! [`set_events`](../struct.PackageManager.html#method.set_events) is given an
+//! [`Event`](enum.Event.html) when each command starts, for every line of output upm reads, when
+//! the command finishes, when its output is parsed, and when it fails. Each event is written as a
+//! line of JSON, so the stream is newline-delimited JSON:
+//!
+//! ```text
+//! {"event":"started","manager":"apt","command":"search","args":"ripgrep"}
+//! {"event":"output","manager":"apt","command":"search","line":"ripgrep 13.0.0 Recursively search"}
+//! {"event":"finished","manager":"apt","command":"search","status":0,"duration_ms":412}
+//! {"event":"parsed","manager":"apt","command":"search","records":1}
+//! ```
+//!
+//! Output lines are only streamed for commands whose output upm captures, or that are given to a
+//! [progress reporter](../progress/index.html). Output that goes straight to the terminal isn't
+//! seen by upm.
+
+use std::fs::File;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::Path;
+use std::sync::Mutex;
+use serde_json;
+
+/// Something that happened while running a package manager command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A command or hook was started with the given arguments
+    Started { manager: String, command: String, args: String },
+    /// A command printed a line
+    Output { manager: String, command: String, line: String },
+    /// A command finished, with its exit code unless it couldn't be run or was killed by a signal
+    Finished { manager: String, command: String, status: Option<i32>, duration_ms: u64 },
+    /// The output of a command was parsed into the given number of records
+    Parsed { manager: String, command: String, records: usize },
+    /// A command failed
+    Error { manager: String, command: String, message: String },
+}
+
+/// Writes events as newline-delimited JSON. The stream can be shared between package managers
+/// running at once, and each event is written as a whole line.
+pub struct EventStream {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl EventStream {
+    /// Write events to a writer, such as a file or a socket
+    pub fn new<W: Write + Send + 'static>(writer: W) -> EventStream {
+        EventStream { writer: Mutex::new(Box::new(writer)) }
+    }
+
+    /// Write events to stderr
+    pub fn stderr() -> EventStream {
+        EventStream::new(io::stderr())
+    }
+
+    /// Write events to a file descriptor that was opened for upm, such as one a parent process
+    /// passed down. Descriptors 1 and 2 are stdout and stderr, and any other descriptor is closed
+    /// once the stream is dropped.
+    #[cfg(unix)]
+    pub fn from_fd(fd: RawFd) -> EventStream {
+        match fd {
+            1 => EventStream::new(io::stdout()),
+            2 => EventStream::stderr(),
+            //Writing to a descriptor that isn't open fails, and is warned about, instead of
+            //writing anywhere else
+            _ => EventStream::new(unsafe { File::from_raw_fd(fd) }),
+        }
+    }
+
+    /// Write events to a file, replacing it if it exists
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<EventStream> {
+        Ok(EventStream::new(File::create(path)?))
+    }
+
+    /// Write an event as a line of JSON. Events that can't be written are logged and dropped so
+    /// a broken stream never stops a command.
+    pub fn emit(&self, event: &Event) {
+        let mut line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Couldn't serialize the event {:?}: {}", event, e);
+                return;
+            },
+        };
+        line.push('\n');
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Err(e) = writer.write_all(line.as_bytes()).and_then(|_| writer.flush()) {
+            warn!("Couldn't write the event {:?}: {}", event, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    //A writer that keeps what was written so the test can read it back
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writing_ndjson() {
+        let written = Shared::default();
+        let stream = EventStream::new(written.clone());
+        stream.emit(&Event::Started { manager: String::from("apt"), command: String::from("search"), args: String::from("rg") });
+        stream.emit(&Event::Finished { manager: String::from("apt"), command: String::from("search"), status: Some(0), duration_ms: 5 });
+        let written = String::from_utf8(written.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines[0], r#"{"event":"started","manager":"apt","command":"search","args":"rg"}"#);
+        let finished: Event = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(finished, Event::Finished { manager: String::from("apt"), command: String::from("search"), status: Some(0), duration_ms: 5 });
+    }
+}
@@ -0,0 +1,252 @@
+//! The meaning of the exit codes of package manager commands. Package managers use their own exit
+//! codes, such as 100 for apt failing to find a package, so a package manager's configuration can
+//! map codes to the kinds of failure upm understands:
+//!
+//! ```toml
+//! [exit_codes]
+//! 100 = "not_found"
+//! 101 = "network_error"
+//! ```
+//!
+//! Codes that aren't mapped are understood following the [script contract](../contract/index.html)
+//! when the command is a script, and are otherwise generic failures.
+//!
+//! Frontends such as the upm binary exit with an [`ExitCode`](enum.ExitCode.html) chosen from the
+//! errors of the library, so scripts can tell failures apart:
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0    | Success |
+//! | 1    | Any other failure |
+//! | 2    | Invalid arguments |
+//! | 3    | The package wasn't found |
+//! | 4    | Some package managers succeeded and others failed |
+//! | 5    | Permission denied |
+//! | 6    | A package manager or upm itself is locked by another process |
+//! | 7    | A package manager couldn't reach the network |
+//! | 8    | No package manager can do what was asked, such as one without the command or too old |
+//! | 130  | Interrupted, such as by Ctrl+C |
+
+use std::fmt;
+use std::io;
+use std::process::ExitStatus;
+use std::str::FromStr;
+use failure::Error;
+use super::{MissingCommand, PermissionDenied, TooOld};
+use cancel::Aborted;
+use contract::{EXIT_NOT_FOUND, EXIT_PERMISSION_DENIED, EXIT_LOCKED};
+use lock::Locked;
+
+/// Why a command failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// The package wasn't found
+    NotFound,
+    /// The package manager is locked by another process
+    Locked,
+    /// The package manager couldn't reach the network
+    NetworkError,
+    /// The command didn't have permission to do what it was asked
+    PermissionDenied,
+    /// Any other failure
+    Other,
+}
+
+impl FailureKind {
+    /// Understand an exit code following the script contract
+    pub fn from_contract(code: i32) -> FailureKind {
+        match code {
+            EXIT_NOT_FOUND => FailureKind::NotFound,
+            EXIT_PERMISSION_DENIED => FailureKind::PermissionDenied,
+            EXIT_LOCKED => FailureKind::Locked,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+impl FailureKind {
+    /// The name of the meaning in a configuration's exit_codes, such as `network_error`. Other
+    /// failures can't be configured so they have no name.
+    pub fn config_name(self) -> Option<&'static str> {
+        match self {
+            FailureKind::NotFound => Some("not_found"),
+            FailureKind::Locked => Some("locked"),
+            FailureKind::NetworkError => Some("network_error"),
+            FailureKind::PermissionDenied => Some("permission_denied"),
+            FailureKind::Other => None,
+        }
+    }
+}
+
+impl FromStr for FailureKind {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> Result<FailureKind, Error> {
+        match kind {
+            "not_found" => Ok(FailureKind::NotFound),
+            "locked" => Ok(FailureKind::Locked),
+            "network_error" => Ok(FailureKind::NetworkError),
+            "permission_denied" => Ok(FailureKind::PermissionDenied),
+            _ => bail!("Unknown exit code meaning {}, expected not_found, locked, network_error, or permission_denied", kind),
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FailureKind::NotFound => write!(f, "package not found"),
+            FailureKind::Locked => write!(f, "package manager locked"),
+            FailureKind::NetworkError => write!(f, "network error"),
+            FailureKind::PermissionDenied => write!(f, "permission denied"),
+            FailureKind::Other => write!(f, "failed"),
+        }
+    }
+}
+
+/// A package manager command exited unsuccessfully
+#[derive(Debug, Fail)]
+#[fail(display = "{} command for {} exited with {}: {}", command, manager, status, kind)]
+pub struct CommandFailed {
+    pub manager: String,
+    pub command: String,
+    pub status: ExitStatus,
+    pub kind: FailureKind,
+}
+
+/// Get why a command failed from an error, if the error is a failed command
+pub fn failure_kind(error: &Error) -> Option<FailureKind> {
+    error.downcast_ref::<CommandFailed>().map(|failed| failed.kind)
+}
+
+/// The code a frontend exits with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum ExitCode {
+    #[default]
+    Success,
+    /// Any failure without a code of its own
+    Failure,
+    /// The arguments were invalid
+    Usage,
+    NotFound,
+    /// Some package managers succeeded and others failed
+    Partial,
+    PermissionDenied,
+    Locked,
+    NetworkError,
+    /// No package manager can do what was asked
+    Unsupported,
+    Interrupted,
+}
+
+impl ExitCode {
+    /// The number the process exits with
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Failure => 1,
+            ExitCode::Usage => 2,
+            ExitCode::NotFound => 3,
+            ExitCode::Partial => 4,
+            ExitCode::PermissionDenied => 5,
+            ExitCode::Locked => 6,
+            ExitCode::NetworkError => 7,
+            ExitCode::Unsupported => 8,
+            ExitCode::Interrupted => 130,
+        }
+    }
+
+    /// The code for a kind of failure
+    pub fn from_kind(kind: FailureKind) -> ExitCode {
+        match kind {
+            FailureKind::NotFound => ExitCode::NotFound,
+            FailureKind::Locked => ExitCode::Locked,
+            FailureKind::NetworkError => ExitCode::NetworkError,
+            FailureKind::PermissionDenied => ExitCode::PermissionDenied,
+            FailureKind::Other => ExitCode::Failure,
+        }
+    }
+
+    /// The code for an error from the library
+    pub fn from_error(error: &Error) -> ExitCode {
+        if let Some(kind) = failure_kind(error) {
+            ExitCode::from_kind(kind)
+        } else if error.downcast_ref::<Aborted>().is_some() {
+            ExitCode::Interrupted
+        } else if error.downcast_ref::<Locked>().is_some() {
+            ExitCode::Locked
+        } else if error.downcast_ref::<PermissionDenied>().is_some()
+            || error.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied) {
+            ExitCode::PermissionDenied
+        } else if error.downcast_ref::<MissingCommand>().is_some() || error.downcast_ref::<TooOld>().is_some() {
+            ExitCode::Unsupported
+        } else {
+            ExitCode::Failure
+        }
+    }
+
+    /// The code for a command that ran, or couldn't be run, where `kind` says why it exited
+    /// unsuccessfully
+    pub fn from_result(result: &Result<ExitStatus, Error>, kind: FailureKind) -> ExitCode {
+        match *result {
+            Ok(status) if status.success() => ExitCode::Success,
+            Ok(_) => ExitCode::from_kind(kind),
+            Err(ref e) => ExitCode::from_error(e),
+        }
+    }
+
+    /// The code for several operations together: success if every one succeeded, the code they
+    /// share if every one failed the same way, a generic failure if they failed differently, and
+    /// a partial failure if only some failed. Being interrupted overrides the rest.
+    pub fn combine<I: IntoIterator<Item = ExitCode>>(codes: I) -> ExitCode {
+        let (mut succeeded, mut failed) = (false, Vec::new());
+        for code in codes {
+            match code {
+                ExitCode::Success => succeeded = true,
+                ExitCode::Interrupted => return ExitCode::Interrupted,
+                code => failed.push(code),
+            }
+        }
+        match failed.first() {
+            None => ExitCode::Success,
+            Some(_) if succeeded => ExitCode::Partial,
+            Some(&first) if failed.iter().all(|&code| code == first) => first,
+            Some(_) => ExitCode::Failure,
+        }
+    }
+
+    /// Is it the code for success?
+    pub fn is_success(self) -> bool {
+        self == ExitCode::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        assert_eq!("network_error".parse::<FailureKind>().unwrap(), FailureKind::NetworkError);
+        assert!("NotFound".parse::<FailureKind>().is_err());
+        assert_eq!(FailureKind::from_contract(4), FailureKind::Locked);
+        assert_eq!(FailureKind::from_contract(100), FailureKind::Other);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let missing: Error = MissingCommand { manager: String::from("apt"), command: String::from("audit") }.into();
+        assert_eq!(ExitCode::from_error(&missing), ExitCode::Unsupported);
+        let denied: Error = io::Error::new(io::ErrorKind::PermissionDenied, "denied").into();
+        assert_eq!(ExitCode::from_error(&denied).code(), 5);
+        assert_eq!(ExitCode::from_error(&format_err!("broken")), ExitCode::Failure);
+
+        use self::ExitCode::*;
+        assert_eq!(ExitCode::combine(vec![Success, Success]), Success);
+        assert_eq!(ExitCode::combine(vec![Success, NotFound]), Partial);
+        assert_eq!(ExitCode::combine(vec![NotFound, NotFound]), NotFound);
+        assert_eq!(ExitCode::combine(vec![NotFound, Locked]), Failure);
+        assert_eq!(ExitCode::combine(vec![Success, Interrupted, Locked]), Interrupted);
+        assert_eq!(ExitCode::combine(Vec::new()), Success);
+    }
+}
@@ -0,0 +1,168 @@
+//! Installing through a chain of package managers, such as pacman, then yay for the AUR, then
+//! flatpak. The chain is read from the `fallback` table of upm's
+//! [configuration file](../fn.config_path.html):
+//!
+//! ```toml
+//! [fallback]
+//! install = ["pacman", "yay", "flatpak"]
+//! ```
+//!
+//! Each package manager is tried in turn until one installs the package. Only an install that
+//! fails because the package wasn't found, according to the package manager's
+//! [exit codes](../exit/index.html), moves on to the next one, since any other failure would
+//! likely happen again. Package managers in the chain that aren't configured or can't install
+//! are skipped. Every attempt is recorded in the [history log](../history/index.html) like any
+//! other install.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Output;
+use failure::Error;
+use toml::Value;
+use super::{ManagerSet, StdioPolicy, config_path};
+use exit::FailureKind;
+use install::InstallOptions;
+
+/// The package managers to try installing with, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FallbackChain {
+    pub managers: Vec<String>,
+}
+
+/// An install tried while walking a fallback chain
+#[derive(Debug)]
+pub struct Attempt {
+    pub manager: String,
+    /// The result of the install command. The output is empty unless it was captured.
+    pub result: Result<Output, Error>,
+    /// Why the install failed, if it ran and failed
+    pub failure: Option<FailureKind>,
+}
+
+impl Attempt {
+    /// Did the install succeed?
+    pub fn is_success(&self) -> bool {
+        self.result.as_ref().is_ok_and(|output| output.status.success())
+    }
+}
+
+impl FallbackChain {
+    /// A chain that tries nothing
+    pub fn new() -> FallbackChain {
+        FallbackChain::default()
+    }
+
+    /// Read the chain from the `fallback` table of a TOML value
+    pub fn from_toml(table: &Value) -> Result<FallbackChain, Error> {
+        let mut managers = Vec::new();
+        if let Some(install) = table.get("install") {
+            let install = match install.as_array() {
+                Some(install) => install,
+                None => bail!("fallback install must be an array of package manager names"),
+            };
+            for manager in install {
+                match manager.as_str() {
+                    Some(manager) => managers.push(manager.to_owned()),
+                    None => bail!("{} in fallback install must be a package manager name", manager),
+                }
+            }
+        }
+        Ok(FallbackChain { managers })
+    }
+
+    /// Read the chain from the `fallback` table of a TOML file. A file that doesn't exist or
+    /// doesn't have the table gives a chain that tries nothing.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<FallbackChain, Error> {
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(FallbackChain::new()),
+            Err(e) => return Err(e.into()),
+        }
+        match content.parse::<Value>()?.get("fallback") {
+            Some(table) => FallbackChain::from_toml(table),
+            None => Ok(FallbackChain::new()),
+        }
+    }
+
+    /// Read the chain from upm's configuration file
+    pub fn load_default() -> Result<FallbackChain, Error> {
+        match config_path() {
+            Some(path) => FallbackChain::from_file(path),
+            None => Ok(FallbackChain::new()),
+        }
+    }
+
+    /// Does the chain try nothing?
+    pub fn is_empty(&self) -> bool {
+        self.managers.is_empty()
+    }
+
+    /// Install a package with each package manager in the chain until one installs it or fails
+    /// for a reason other than not finding it. The attempts are given in the order they were made,
+    /// so the last one says how the chain ended.
+    pub fn install(&self, managers: &ManagerSet, package: &str, stdio: StdioPolicy) -> Vec<Attempt> {
+        let mut attempts = Vec::new();
+        for name in &self.managers {
+            let manager = match managers.get(name) {
+                Some(manager) if manager.can_install() => manager,
+                _ => {
+                    debug!("Skipping {} in the fallback chain since it can't install", name);
+                    continue;
+                },
+            };
+            let result = manager.install_with(package, &InstallOptions::new(), stdio);
+            let failure = match result {
+                Ok(ref output) if !output.status.success() => Some(manager.failure_kind("install", output.status)),
+                _ => None,
+            };
+            let not_found = failure == Some(FailureKind::NotFound);
+            attempts.push(Attempt { manager: name.clone(), result, failure });
+            if !not_found {
+                break;
+            }
+            info!("{} doesn't have {}, falling back to the next package manager", name, package);
+        }
+        attempts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, PackageManager, Scope};
+
+    #[test]
+    fn falling_back() {
+        let manager = |name: &str, install: &str, not_found: bool| {
+            let mut manager = PackageManager { name: String::from(name), ..Default::default() }
+                .with_command(CommandKind::Version, Scope::System, "true")
+                .with_command(CommandKind::Install, Scope::System, install);
+            if not_found {
+                manager.exit_codes.insert(1, FailureKind::NotFound);
+            }
+            manager
+        };
+        let set = ManagerSet::new(vec![
+            manager("pacman", "false", true),
+            manager("yay", "true", false),
+            manager("flatpak", "false", false),
+            manager("snap", "false", true),
+        ]);
+        let chain = FallbackChain::from_toml(&"install = ['pacman', 'missing', 'yay', 'flatpak']\n".parse::<Value>().unwrap()).unwrap();
+        let attempts = chain.install(&set, "ripgrep", StdioPolicy::Null);
+        let tried: Vec<&str> = attempts.iter().map(|attempt| attempt.manager.as_str()).collect();
+        assert_eq!(tried, vec!["pacman", "yay"]);
+        assert_eq!(attempts[0].failure, Some(FailureKind::NotFound));
+        assert!(attempts[1].is_success());
+        let chain = FallbackChain { managers: vec![String::from("flatpak"), String::from("yay")] };
+        let attempts = chain.install(&set, "ripgrep", StdioPolicy::Null);
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].failure, Some(FailureKind::Other));
+        let chain = FallbackChain { managers: vec![String::from("snap")] };
+        assert!(!chain.install(&set, "ripgrep", StdioPolicy::Null).iter().any(Attempt::is_success));
+        assert!(FallbackChain::from_toml(&"install = 'pacman'\n".parse::<Value>().unwrap()).is_err());
+    }
+}
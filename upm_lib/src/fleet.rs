@@ -0,0 +1,261 @@
+//! Running the same operation on many machines at once, with the `remote` feature. A
+//! [`Fleet`](struct.Fleet.html) has a copy of the package managers for each
+//! [host](../remote/struct.SshTarget.html), runs an install or upgrade on several hosts at a
+//! time, and gives a [`FleetReport`](struct.FleetReport.html) with the
+//! [`OperationReport`](../report/struct.OperationReport.html) of every host.
+//!
+//! With fail-fast, hosts that haven't started when one fails are skipped. Hosts already running
+//! finish, since stopping a package manager partway through can leave a machine broken.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use super::{ManagerSet, StdioPolicy};
+use concurrency::ConcurrencyPolicy;
+use exit::ExitCode;
+use history::Operation;
+use pin::PinList;
+use remote::SshTarget;
+use report::{self, ManagerReport, OperationReport, format_duration};
+
+/// The most hosts a fleet runs an operation on at once by default
+pub const DEFAULT_MAX_HOSTS: usize = 8;
+
+/// A machine in a fleet and the package managers run on it
+#[derive(Clone)]
+pub struct Host {
+    pub target: SshTarget,
+    pub managers: ManagerSet,
+}
+
+/// Machines that operations are run on together
+#[derive(Clone)]
+pub struct Fleet {
+    hosts: Vec<Host>,
+    max_hosts: usize,
+    fail_fast: bool,
+}
+
+impl Fleet {
+    /// Create a fleet running the package managers on each of the hosts
+    pub fn new(managers: &ManagerSet, targets: Vec<SshTarget>) -> Fleet {
+        let mut fleet = Fleet { hosts: Vec::new(), max_hosts: DEFAULT_MAX_HOSTS, fail_fast: false };
+        for target in targets {
+            fleet.add_host(target, managers.clone());
+        }
+        fleet
+    }
+
+    /// Add a host with its own package managers, which are bound to it
+    pub fn add_host(&mut self, target: SshTarget, mut managers: ManagerSet) {
+        managers.set_remote(target.clone());
+        self.hosts.push(Host { target, managers });
+    }
+
+    /// Run an operation on at most this many hosts at once. At least one is always allowed.
+    pub fn with_max_hosts(mut self, max_hosts: usize) -> Fleet {
+        self.max_hosts = max_hosts;
+        self
+    }
+
+    /// Skip the hosts that haven't started once the operation fails on one
+    pub fn fail_fast(mut self, fail_fast: bool) -> Fleet {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The hosts in the fleet
+    pub fn hosts(&self) -> &[Host] {
+        &self.hosts
+    }
+
+    /// Run an operation on every host, following the host limit and fail-fast. The reports are in
+    /// the same order as the hosts.
+    pub fn run<F>(&self, operation: Operation, run: F) -> FleetReport
+        where F: Fn(&ManagerSet) -> OperationReport + Sync
+    {
+        let start = Instant::now();
+        let queue: Mutex<VecDeque<usize>> = Mutex::new((0..self.hosts.len()).collect());
+        let results: Mutex<Vec<Option<OperationReport>>> = Mutex::new(self.hosts.iter().map(|_| None).collect());
+        let failed = AtomicBool::new(false);
+        let workers = self.max_hosts.max(1).min(self.hosts.len());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    if self.fail_fast && failed.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let index = match queue.lock().unwrap().pop_front() {
+                        Some(index) => index,
+                        None => break,
+                    };
+                    let report = run(&self.hosts[index].managers);
+                    if !report.is_success() {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    results.lock().unwrap()[index] = Some(report);
+                });
+            }
+        });
+        let hosts = self.hosts.iter()
+            .zip(results.into_inner().unwrap())
+            .map(|(host, report)| HostReport { host: host.target.clone(), report })
+            .collect();
+        FleetReport { operation, hosts, elapsed: start.elapsed() }
+    }
+
+    /// Install packages on every host as [`report::install`](../report/fn.install.html) does
+    pub fn install(&self, installs: &[(String, String)], policy: &ConcurrencyPolicy, stdio: StdioPolicy) -> FleetReport {
+        self.run(Operation::Install, |managers| report::install(managers, installs, policy, stdio))
+    }
+
+    /// Upgrade every package on every host as [`report::upgrade`](../report/fn.upgrade.html) does
+    pub fn upgrade(&self, policy: &ConcurrencyPolicy, pins: &PinList, stdio: StdioPolicy) -> FleetReport {
+        self.run(Operation::UpgradeAll, |managers| {
+            let managers: Vec<_> = managers.iter().cloned().collect();
+            report::upgrade(&managers, policy, pins, stdio)
+        })
+    }
+}
+
+/// What an operation did on one host
+#[derive(Debug)]
+pub struct HostReport {
+    pub host: SshTarget,
+    /// The report for the host, or None if it was skipped after another host failed
+    pub report: Option<OperationReport>,
+}
+
+impl HostReport {
+    /// Was the host skipped?
+    pub fn is_skipped(&self) -> bool {
+        self.report.is_none()
+    }
+
+    /// Did the operation run and succeed on the host?
+    pub fn is_success(&self) -> bool {
+        self.report.as_ref().is_some_and(OperationReport::is_success)
+    }
+
+    /// The exit code for the host. A skipped host counts as a failure.
+    pub fn exit_code(&self) -> ExitCode {
+        self.report.as_ref().map_or(ExitCode::Failure, OperationReport::exit_code)
+    }
+}
+
+/// What an operation did across a fleet
+#[derive(Debug)]
+pub struct FleetReport {
+    pub operation: Operation,
+    /// A report for each host, in the order of the hosts
+    pub hosts: Vec<HostReport>,
+    /// How long the operation took on the whole fleet
+    pub elapsed: Duration,
+}
+
+impl FleetReport {
+    /// Get the hosts the operation succeeded on
+    pub fn succeeded(&self) -> Vec<&HostReport> {
+        self.hosts.iter().filter(|host| host.is_success()).collect()
+    }
+
+    /// Get the hosts the operation ran and failed on
+    pub fn failed(&self) -> Vec<&HostReport> {
+        self.hosts.iter().filter(|host| !host.is_skipped() && !host.is_success()).collect()
+    }
+
+    /// Get the hosts that were skipped after another host failed
+    pub fn skipped(&self) -> Vec<&HostReport> {
+        self.hosts.iter().filter(|host| host.is_skipped()).collect()
+    }
+
+    /// Get what each package manager did on each host it ran on, by package manager name
+    pub fn by_manager(&self) -> BTreeMap<&str, Vec<(&SshTarget, &ManagerReport)>> {
+        let mut managers: BTreeMap<&str, Vec<(&SshTarget, &ManagerReport)>> = BTreeMap::new();
+        for host in &self.hosts {
+            for report in host.report.iter().flat_map(|report| report.managers.iter()) {
+                managers.entry(report.manager.as_str()).or_default().push((&host.host, report));
+            }
+        }
+        managers
+    }
+
+    /// How many packages were changed across the fleet, counting only package managers that know
+    pub fn packages_changed(&self) -> usize {
+        self.hosts.iter().filter_map(|host| host.report.as_ref()).map(OperationReport::packages_changed).sum()
+    }
+
+    /// Did the operation succeed on every host?
+    pub fn is_success(&self) -> bool {
+        self.hosts.iter().all(HostReport::is_success)
+    }
+
+    /// The exit code for the whole fleet: a partial failure if the operation failed on only some
+    /// of the hosts
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::combine(self.hosts.iter().map(HostReport::exit_code))
+    }
+}
+
+impl fmt::Display for FleetReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.hosts.len();
+        write!(f, "{} host{}, {} succeeded, {} failed", count, if count == 1 { "" } else { "s" },
+               self.succeeded().len(), self.failed().len())?;
+        let skipped = self.skipped().len();
+        if skipped > 0 {
+            write!(f, ", {} skipped", skipped)?;
+        }
+        write!(f, ", {}", format_duration(self.elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::PackageManager;
+
+    fn fleet() -> Fleet {
+        let managers = ManagerSet::new(vec![
+            PackageManager { name: String::from("apt"), ..Default::default() },
+            PackageManager { name: String::from("pip"), ..Default::default() },
+        ]);
+        let targets = vec![SshTarget::new("web1"), SshTarget::new("web2"), SshTarget::new("db1")];
+        Fleet::new(&managers, targets)
+    }
+
+    //Pretends to upgrade, failing pip on web2
+    fn upgrade(managers: &ManagerSet) -> OperationReport {
+        let host = managers.iter().next().and_then(PackageManager::remote).unwrap().host.clone();
+        let reports = managers.iter().map(|manager| {
+            let mut report = ManagerReport::failed(&manager.name, "upgrade_all", format_err!("offline"));
+            if host != "web2" || manager.name != "pip" {
+                report.result = Ok(Default::default());
+                report.packages = Some(vec![format!("{}-package", host)]);
+            }
+            report
+        }).collect();
+        OperationReport { operation: Operation::UpgradeAll, managers: reports, elapsed: Duration::default() }
+    }
+
+    #[test]
+    fn running_on_hosts() {
+        let report = fleet().run(Operation::UpgradeAll, upgrade);
+        assert_eq!(report.hosts.len(), 3);
+        assert_eq!(report.succeeded().len(), 2);
+        assert_eq!(report.failed()[0].host.host, "web2");
+        assert_eq!(report.packages_changed(), 5);
+        assert_eq!(report.exit_code(), ExitCode::Partial);
+        let by_manager = report.by_manager();
+        assert_eq!(by_manager["pip"].len(), 3);
+        assert!(!by_manager["pip"][1].1.is_success());
+
+        let report = fleet().with_max_hosts(1).fail_fast(true).run(Operation::UpgradeAll, upgrade);
+        let skipped: Vec<&str> = report.skipped().iter().map(|host| host.host.host.as_str()).collect();
+        assert_eq!(skipped, vec!["db1"]);
+        assert!(report.to_string().starts_with("3 hosts, 1 succeeded, 1 failed, 1 skipped"));
+    }
+}
@@ -0,0 +1,136 @@
+//! Fuzzy ranking of search results, so the package that best matches a query comes first no
+//! matter how each package manager's own search orders its results or which package manager found
+//! it. Names that match exactly rank above names starting with the query, which rank above names
+//! containing it, which rank above names containing its letters in order. With typo tolerance,
+//! names a few typos away from the query come last, so `riggrep` still finds `ripgrep`.
+//!
+//! Results whose names don't match at all, such as those a package manager found through their
+//! descriptions, keep their order after the matching results.
+//!
+//! This module is only built with the `fuzzy` feature.
+
+use std::cmp::Reverse;
+use super::Package;
+use suggest::{edit_distance, typo_allowance};
+
+const EXACT: u32 = 1000;
+const PREFIX: u32 = 900;
+const SUBSTRING: u32 = 700;
+const SUBSEQUENCE: u32 = 500;
+const TYPO: u32 = 300;
+
+/// Scores how well package names match a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzyMatcher {
+    typo_tolerance: bool,
+}
+
+impl FuzzyMatcher {
+    /// A matcher that tolerates typos
+    pub fn new() -> FuzzyMatcher {
+        FuzzyMatcher { typo_tolerance: true }
+    }
+
+    /// Choose whether names a few typos away from the query match
+    pub fn with_typo_tolerance(mut self, typo_tolerance: bool) -> FuzzyMatcher {
+        self.typo_tolerance = typo_tolerance;
+        self
+    }
+
+    /// How well a name matches the query, higher being better, or None if it doesn't match.
+    /// Case is ignored.
+    pub fn score(&self, query: &str, name: &str) -> Option<u32> {
+        let query = query.to_lowercase();
+        let name = name.to_lowercase();
+        if query.is_empty() {
+            return None;
+        }
+        let extra = name.chars().count().saturating_sub(query.chars().count()) as u32;
+        if name == query {
+            return Some(EXACT);
+        }
+        if name.starts_with(&query) {
+            return Some(PREFIX.saturating_sub(extra).max(SUBSTRING + 1));
+        }
+        if let Some(position) = name.find(&query) {
+            return Some(SUBSTRING.saturating_sub(position as u32 + extra).max(SUBSEQUENCE + 1));
+        }
+        if let Some(gaps) = subsequence_gaps(&query, &name) {
+            return Some(SUBSEQUENCE.saturating_sub(gaps * 10 + extra).max(TYPO + 1));
+        }
+        if self.typo_tolerance {
+            let distance = edit_distance(&query, &name);
+            if distance <= typo_allowance(&query) {
+                return Some(TYPO.saturating_sub(distance as u32 * 50 + extra).max(1));
+            }
+        }
+        None
+    }
+
+    /// Order packages from the best match of the query to the worst. Packages that don't match
+    /// keep their order after those that do.
+    pub fn rank(&self, query: &str, packages: &mut [Package]) {
+        packages.sort_by_key(|package| Reverse(self.score(query, &package.name).unwrap_or(0)));
+    }
+}
+
+impl Default for FuzzyMatcher {
+    fn default() -> FuzzyMatcher {
+        FuzzyMatcher::new()
+    }
+}
+
+//The number of letters skipped between the letters of the query if they're all in the name in
+//order
+fn subsequence_gaps(query: &str, name: &str) -> Option<u32> {
+    let mut name = name.chars();
+    let mut gaps = 0;
+    let mut started = false;
+    for letter in query.chars() {
+        loop {
+            match name.next() {
+                Some(candidate) if candidate == letter => break,
+                Some(_) => if started { gaps += 1 },
+                None => return None,
+            }
+        }
+        started = true;
+    }
+    Some(gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{PackageManager, Version};
+
+    fn package(name: &str) -> Package {
+        Package {
+            name: String::from(name),
+            owner: PackageManager::default(),
+            version: Version::from("1.0"),
+            description: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scoring() {
+        let matcher = FuzzyMatcher::new();
+        assert_eq!(matcher.score("ripgrep", "RipGrep"), Some(EXACT));
+        assert!(matcher.score("rip", "ripgrep") > matcher.score("grep", "ripgrep"));
+        assert!(matcher.score("grep", "ripgrep") > matcher.score("rgp", "ripgrep"));
+        assert!(matcher.score("rgp", "ripgrep") > matcher.score("riggrep", "ripgrep"));
+        assert!(matcher.score("riggrep", "ripgrep").is_some());
+        assert_eq!(matcher.with_typo_tolerance(false).score("riggrep", "ripgrep"), None);
+        assert_eq!(matcher.score("zzz", "ripgrep"), None);
+    }
+
+    #[test]
+    fn ranking() {
+        let mut packages = vec![package("ripgrep-all"), package("grep"), package("ripgrep"), package("vim")];
+        FuzzyMatcher::new().rank("riggrep", &mut packages);
+        let names: Vec<&str> = packages.iter().map(|package| package.name.as_str()).collect();
+        assert_eq!(names, vec!["ripgrep", "ripgrep-all", "grep", "vim"]);
+    }
+}
@@ -0,0 +1,395 @@
+//! A log of the commands that changed packages, answering when a package was installed and with
+//! which package manager. A [`HistoryLog`](struct.HistoryLog.html) attached to a package manager
+//! records every install, removal, upgrade, and cleanup it executes, and can be read back as
+//! [`HistoryEntry`](struct.HistoryEntry.html) records filtered with a
+//! [`HistoryFilter`](struct.HistoryFilter.html).
+//!
+//! The log is a text file with one entry per line, so it can be appended to by several processes
+//! and read without upm. Each line holds tab separated fields, such as
+//! `1528311542 install cargo ripgrep fd-find 0` with the tabs shown as spaces.
+//!
+//! The fields are the time in seconds since the Unix epoch, the operation, the package manager,
+//! the packages separated by spaces, and the exit code, which is `-` if the command was killed by
+//! a signal. Commands run to [undo](../transaction/index.html) another have an extra `undo`
+//! field, commands run in the user's [scope](../command/enum.Scope.html) have a `user` field, and
+//! commands run after a [snapshot](../snapshot/index.html) was taken have a `snapshot=ID` field.
+
+use std::fmt;
+use std::fs::{OpenOptions, create_dir_all};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use failure::Error;
+use super::data_dir;
+use command::Scope;
+
+/// Get the path of the default history log, `history.log` in the data directory
+pub fn history_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("history.log"))
+}
+
+/// What a recorded command did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Install,
+    Remove,
+    Upgrade,
+    UpgradeAll,
+    CleanCache,
+    Autoremove,
+}
+
+impl Operation {
+    /// Get the operation a package manager command performs, if it changes anything
+    pub fn for_command(command: &str) -> Option<Operation> {
+        match command {
+            "install" | "install_local" => Some(Operation::Install),
+            "remove" | "remove_local" => Some(Operation::Remove),
+            "upgrade" => Some(Operation::Upgrade),
+            "upgrade_all" => Some(Operation::UpgradeAll),
+            "clean_cache" => Some(Operation::CleanCache),
+            "autoremove" => Some(Operation::Autoremove),
+            _ => None,
+        }
+    }
+
+    /// Get the operation that reverts this one, if there is one
+    pub fn inverse(self) -> Option<Operation> {
+        match self {
+            Operation::Install => Some(Operation::Remove),
+            Operation::Remove => Some(Operation::Install),
+            _ => None,
+        }
+    }
+
+    /// The name of the operation as written in the log, which is also the package manager command
+    /// that performs it
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Install => "install",
+            Operation::Remove => "remove",
+            Operation::Upgrade => "upgrade",
+            Operation::UpgradeAll => "upgrade_all",
+            Operation::CleanCache => "clean_cache",
+            Operation::Autoremove => "autoremove",
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Operation, Error> {
+        match s {
+            "install" => Ok(Operation::Install),
+            "remove" => Ok(Operation::Remove),
+            "upgrade" => Ok(Operation::Upgrade),
+            "upgrade_all" => Ok(Operation::UpgradeAll),
+            "clean_cache" => Ok(Operation::CleanCache),
+            "autoremove" => Ok(Operation::Autoremove),
+            _ => bail!("Unknown operation {}", s),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A command that changed packages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub time: SystemTime,
+    pub operation: Operation,
+    pub manager: String,
+    /// The packages the command was given, empty for commands such as `upgrade_all`
+    pub packages: Vec<String>,
+    /// The exit code, or None if the command was killed by a signal
+    pub code: Option<i32>,
+    /// Whether the command reverted an earlier one
+    pub undo: bool,
+    /// The scope the command ran in, which the command reverting it runs in too
+    pub scope: Scope,
+    /// The [snapshot](../snapshot/index.html) taken before the command, to roll back to
+    pub snapshot: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Describe a command that just finished
+    pub fn new(operation: Operation, manager: &str, args: &str, status: ExitStatus) -> HistoryEntry {
+        HistoryEntry {
+            time: SystemTime::now(),
+            operation,
+            manager: manager.to_owned(),
+            packages: args.split_whitespace().map(String::from).collect(),
+            code: status.code(),
+            undo: false,
+            scope: Scope::System,
+            snapshot: None,
+        }
+    }
+
+    /// Did the command succeed?
+    pub fn is_success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Parse a line of the log
+    pub fn parse(line: &str) -> Result<HistoryEntry, Error> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            bail!("History entries have at least 5 fields but found {}", fields.len());
+        }
+        //The fields after the exit code are flags, each given at most once
+        let mut undo = false;
+        let mut scope = Scope::System;
+        let mut snapshot = None;
+        for &flag in &fields[5..] {
+            match flag {
+                "undo" if !undo => undo = true,
+                "user" if scope == Scope::System => scope = Scope::User,
+                _ if flag.starts_with("snapshot=") && snapshot.is_none() => snapshot = Some(flag["snapshot=".len()..].to_owned()),
+                _ => bail!("Unknown history entry field {}", flag),
+            }
+        }
+        let code = match fields[4] {
+            "-" => None,
+            code => Some(code.parse()?),
+        };
+        Ok(HistoryEntry {
+            time: UNIX_EPOCH + Duration::from_secs(fields[0].parse()?),
+            operation: fields[1].parse()?,
+            manager: fields[2].to_owned(),
+            packages: fields[3].split_whitespace().map(String::from).collect(),
+            code,
+            undo,
+            scope,
+            snapshot,
+        })
+    }
+
+    /// Format the entry as a line of the log, without a newline
+    pub fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}{}{}{}", epoch_seconds(self.time), self.operation, self.manager,
+                self.packages.join(" "), self.code.map_or(String::from("-"), |code| code.to_string()),
+                if self.undo { "\tundo" } else { "" },
+                if self.scope == Scope::User { "\tuser" } else { "" },
+                self.snapshot.as_ref().map_or(String::new(), |snapshot| format!("\tsnapshot={}", snapshot)))
+    }
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}{}{} {}", format_time(self.time), if self.undo { "undo " } else { "" },
+               self.operation, if self.scope == Scope::User { " (user)" } else { "" }, self.manager)?;
+        if !self.packages.is_empty() {
+            write!(f, " {}", self.packages.join(" "))?;
+        }
+        match self.code {
+            Some(0) => {},
+            Some(code) => write!(f, " (failed with {})", code)?,
+            None => write!(f, " (killed)")?,
+        }
+        match self.snapshot {
+            Some(ref snapshot) => write!(f, " [snapshot {}]", snapshot),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Which history entries to give back. Every condition that's set must hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryFilter {
+    pub manager: Option<String>,
+    /// Only entries for commands given this package
+    pub package: Option<String>,
+    pub operation: Option<Operation>,
+    /// Only entries at or after this time
+    pub since: Option<SystemTime>,
+    /// Only entries before this time
+    pub until: Option<SystemTime>,
+}
+
+impl HistoryFilter {
+    /// Does the entry pass the filter?
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.manager.as_ref().is_none_or(|manager| *manager == entry.manager)
+            && self.package.as_ref().is_none_or(|package| entry.packages.contains(package))
+            && self.operation.is_none_or(|operation| operation == entry.operation)
+            && self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time < until)
+    }
+}
+
+/// A history log file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryLog {
+    path: PathBuf,
+}
+
+impl HistoryLog {
+    /// Use the log at the given path, which is created when the first entry is recorded
+    pub fn at<P: AsRef<Path>>(path: P) -> HistoryLog {
+        HistoryLog { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Use the default log, if there's a data directory to keep it in
+    pub fn default_log() -> Option<HistoryLog> {
+        history_path().map(HistoryLog::at)
+    }
+
+    /// The path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry to the log
+    pub fn record(&self, entry: &HistoryEntry) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        //A single write keeps lines from several processes from interleaving
+        file.write_all(format!("{}\n", entry.to_line()).as_bytes())?;
+        Ok(())
+    }
+
+    /// Read every entry, oldest first. Lines that can't be parsed are skipped with a warning.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, Error> {
+        let file = match OpenOptions::new().read(true).open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match HistoryEntry::parse(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Skipping line {} of {:?}: {}", number + 1, self.path, e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Read the entries that pass the filter, oldest first
+    pub fn query(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>, Error> {
+        Ok(self.entries()?.into_iter().filter(|entry| filter.matches(entry)).collect())
+    }
+}
+
+/// Parse a date given as `YYYY-MM-DD` into the start of that day in UTC
+pub fn parse_date(date: &str) -> Result<SystemTime, Error> {
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3 {
+        bail!("Dates must be given as YYYY-MM-DD, not {}", date);
+    }
+    let (year, month, day): (i64, i64, i64) = (parts[0].parse()?, parts[1].parse()?, parts[2].parse()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        bail!("{} isn't a valid date", date);
+    }
+    let days = days_from_civil(year, month, day);
+    Ok(UNIX_EPOCH + Duration::from_secs(days as u64 * 86_400))
+}
+
+/// Format a time as `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = epoch_seconds(time);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds = seconds % 86_400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day,
+            seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Get a time as seconds since the Unix epoch
+pub fn epoch_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+//Days since the epoch for a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+//The inverse of days_from_civil
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), UNIX_EPOCH);
+        let date = parse_date("2018-06-06").unwrap();
+        assert_eq!(epoch_seconds(date), 1_528_243_200);
+        assert_eq!(format_time(date + Duration::from_secs(3723)), "2018-06-06 01:02:03");
+        assert!(parse_date("2018-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn recording_and_filtering() {
+        let log = HistoryLog::at(temp_dir().join(format!("upm-history-{}.log", ::std::process::id())));
+        let _ = remove_file(log.path());
+        assert!(log.entries().unwrap().is_empty());
+        let entries = vec![
+            HistoryEntry::parse("1528243200\tinstall\tcargo\tripgrep fd-find\t0").unwrap(),
+            HistoryEntry::parse("1528329600\tremove\tapt\tripgrep\t100").unwrap(),
+            HistoryEntry::parse("1528416000\tupgrade_all\tbrew\t\t-").unwrap(),
+            HistoryEntry::parse("1528416001\tinstall\tapt\tripgrep\t0\tundo").unwrap(),
+        ];
+        for entry in &entries {
+            log.record(entry).unwrap();
+        }
+        assert_eq!(log.entries().unwrap(), entries);
+
+        let by_package = HistoryFilter { package: Some(String::from("ripgrep")), ..Default::default() };
+        assert_eq!(log.query(&by_package).unwrap().len(), 3);
+        let by_operation = HistoryFilter { operation: Some(Operation::Install), ..Default::default() };
+        assert_eq!(log.query(&by_operation).unwrap(), vec![entries[0].clone(), entries[3].clone()]);
+        let by_date = HistoryFilter {
+            since: Some(parse_date("2018-06-07").unwrap()),
+            until: Some(parse_date("2018-06-08").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&by_date).unwrap(), vec![entries[1].clone()]);
+        assert_eq!(entries[2].to_string(), "2018-06-08 00:00:00 upgrade_all brew (killed)");
+        assert_eq!(entries[3].to_string(), "2018-06-08 00:00:01 undo install apt ripgrep");
+        assert!(HistoryEntry::parse("1528416001\tinstall\tapt\tripgrep\t0\tredo").is_err());
+        let snapshotted = HistoryEntry::parse("1528416002\tupgrade_all\tdnf\t\t0\tsnapshot=42").unwrap();
+        assert_eq!(snapshotted.snapshot.as_deref(), Some("42"));
+        assert_eq!(snapshotted.to_line(), "1528416002\tupgrade_all\tdnf\t\t0\tsnapshot=42");
+        assert_eq!(snapshotted.to_string(), "2018-06-08 00:00:02 upgrade_all dnf [snapshot 42]");
+        let user = HistoryEntry::parse("1528416003\tremove\tnpm\ttypescript\t0\tundo\tuser").unwrap();
+        assert_eq!(user.scope, Scope::User);
+        assert_eq!(user.to_line(), "1528416003\tremove\tnpm\ttypescript\t0\tundo\tuser");
+        assert_eq!(user.to_string(), "2018-06-08 00:00:03 undo remove (user) npm typescript");
+        remove_file(log.path()).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! Details on a package beyond its name, version, and description: whether and how it's
+//! installed, where it comes from, its homepage, its license, and its kind. Packages from
+//! [`list_installed`](../struct.PackageManager.html#method.list_installed) know they're installed,
+//! and [`PackageManager::info`](../struct.PackageManager.html#method.info) fills in the rest from
+//! the `info` command, which prints one detail per line as a key and a value separated by a
+//! colon:
+//!
+//! ```text
+//! version: 14.1.0
+//! description: Recursively search directories for a regex pattern
+//! homepage: https://github.com/BurntSushi/ripgrep
+//! license: MIT
+//! source: crates.io
+//! installed: 13.0.0
+//! scope: user
+//! install_date: 2024-03-01
+//! kind: tool
+//! ```
+//!
+//! `version` is the version that would be installed, and `installed` the version that is. Every
+//! key is optional and unknown keys are ignored.
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use failure::Error;
+use super::{Package, Scope, Version};
+use history;
+
+/// The keys the info command can print
+pub const KEYS: &[&str] = &["version", "description", "homepage", "license", "source", "installed", "scope",
+                            "install_date", "kind"];
+
+/// How a package is installed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledInfo {
+    /// The installed version
+    pub version: Version,
+    /// Whether it's installed for the system or the user
+    pub scope: Scope,
+    /// When it was installed, if the package manager knows
+    pub install_date: Option<SystemTime>,
+}
+
+impl InstalledInfo {
+    /// A package installed at a version in a scope at an unknown time
+    pub fn new(version: Version, scope: Scope) -> InstalledInfo {
+        InstalledInfo { version, scope, install_date: None }
+    }
+}
+
+/// Split the output of an info command into its keys and values
+pub fn parse_fields(output: &str) -> Result<BTreeMap<String, String>, Error> {
+    let mut fields = BTreeMap::new();
+    for (number, line) in output.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.find(':') {
+            Some(colon) => fields.insert(line[..colon].trim().to_owned(), line[colon + 1..].trim().to_owned()),
+            None => bail!("line {} isn't a key and a value separated by a colon: {}", number + 1, line),
+        };
+    }
+    Ok(fields)
+}
+
+/// Fill in the details of a package from the output of an info command
+pub fn apply_info(package: &mut Package, output: &str) -> Result<(), Error> {
+    let mut fields = parse_fields(output)?;
+    let mut take = |key: &str| fields.remove(key).filter(|value| !value.is_empty());
+    if let Some(version) = take("version") {
+        package.version = Version::from(version.as_str());
+    }
+    if let Some(description) = take("description") {
+        package.description = description;
+    }
+    package.homepage = take("homepage").or_else(|| package.homepage.take());
+    package.license = take("license").or_else(|| package.license.take());
+    package.source = take("source").or_else(|| package.source.take());
+    if let Some(kind) = take("kind") {
+        package.kind = Some(kind.parse()?);
+    }
+    let scope = match take("scope").as_deref() {
+        Some("system") | None => Scope::System,
+        Some("user") => Scope::User,
+        Some(scope) => bail!("Unknown scope {}, expected system or user", scope),
+    };
+    let install_date = match take("install_date") {
+        Some(date) => Some(history::parse_date(&date)?),
+        None => None,
+    };
+    if let Some(version) = take("installed") {
+        package.installed = Some(InstalledInfo {
+            version: Version::from(version.as_str()),
+            scope,
+            install_date,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_info() {
+        let mut package = Package { name: String::from("ripgrep"), ..Default::default() };
+        apply_info(&mut package, "version: 14.1.0\nhomepage: https://github.com/BurntSushi/ripgrep\n\n\
+                                  license: MIT\ninstalled: 13.0.0\nscope: user\ninstall_date: 2024-03-01\nkind: tool\nstars: 40000\n").unwrap();
+        assert_eq!(package.version, Version::from("14.1.0"));
+        assert_eq!(package.homepage.as_ref().unwrap(), "https://github.com/BurntSushi/ripgrep");
+        assert_eq!(package.license.as_ref().unwrap(), "MIT");
+        assert_eq!(package.source, None);
+        assert_eq!(package.kind(), Some(::kind::PackageKind::Tool));
+        let installed = package.installed.as_ref().unwrap();
+        assert_eq!(installed.scope, Scope::User);
+        assert_eq!(installed.install_date, Some(history::parse_date("2024-03-01").unwrap()));
+        assert!(package.has_update());
+
+        assert!(apply_info(&mut package, "version 14.1.0").is_err());
+        assert!(apply_info(&mut package, "installed: 1.0\nscope: global").is_err());
+        assert!(apply_info(&mut package, "kind: gui").is_err());
+    }
+}
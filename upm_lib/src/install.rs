@@ -0,0 +1,195 @@
+//! Installing a package with more than its name. [`InstallOptions`](struct.InstallOptions.html)
+//! adds flags for a single install, such as `--no-install-recommends`, chooses whether the
+//! package is installed for the system or only the user, and can ask for a particular version.
+//!
+//! Each extra argument is passed to the install command as one argument, even if it contains
+//! spaces, and is never interpreted by a shell. Versions are written the way the package manager
+//! expects with its `version_format`, where `{name}` and `{version}` are replaced:
+//!
+//! ```toml
+//! install = "apt install"
+//! version_format = "{name}={version}"
+//! ```
+//!
+//! Package managers without a `version_format` can't install a particular version.
+//!
+//! Some package managers, such as cargo and pip, only install for the user. Unless a scope is
+//! asked for, packages are installed for the system when the package manager can and for the user
+//! otherwise, as chosen by the [`ScopeFallback`](enum.ScopeFallback.html).
+
+use failure::Error;
+use super::{CommandKind, MissingCommand, PackageManager, Scope, Version};
+
+/// The placeholder in a version_format replaced by the name of the package
+pub const NAME_PLACEHOLDER: &str = "{name}";
+
+/// The placeholder in a version_format replaced by the version
+pub const VERSION_PLACEHOLDER: &str = "{version}";
+
+/// Which scope a package is installed in when the package manager might not have an install
+/// command for every scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeFallback {
+    /// Install for the system if the package manager can, and for the user otherwise
+    PreferSystem,
+    /// Install for the user if the package manager can, and for the system otherwise
+    PreferLocal,
+    /// Install in the scope of the options, failing if the package manager can't
+    Exact,
+}
+
+impl ScopeFallback {
+    /// The scope a package manager runs a kind of command in, with `scope` being the one asked for
+    /// by an exact fallback. Gives an error if the package manager has the command in neither.
+    pub fn choose(self, manager: &PackageManager, kind: CommandKind, scope: Scope) -> Result<Scope, Error> {
+        let order: &[Scope] = match self {
+            ScopeFallback::PreferSystem => &[Scope::System, Scope::User],
+            ScopeFallback::PreferLocal => &[Scope::User, Scope::System],
+            ScopeFallback::Exact if scope == Scope::System => &[Scope::System],
+            ScopeFallback::Exact => &[Scope::User],
+        };
+        match order.iter().find(|&&scope| manager.template(kind, scope).is_some()) {
+            Some(&scope) => Ok(scope),
+            None => Err(MissingCommand { manager: manager.name.clone(), command: kind.key(order[0]) }.into()),
+        }
+    }
+}
+
+/// How a package is installed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallOptions {
+    /// Arguments added after the package, each passed as a single argument
+    pub extra_args: Vec<String>,
+    /// Whether the package is installed for the system or the user, if the fallback is exact
+    pub scope: Scope,
+    /// How the scope is chosen
+    pub fallback: ScopeFallback,
+    /// The version to install, or None for the one the package manager chooses
+    pub version: Option<Version>,
+}
+
+impl InstallOptions {
+    /// Install the version the package manager chooses, for the system if it can, with no extra
+    /// arguments
+    pub fn new() -> InstallOptions {
+        InstallOptions {
+            extra_args: Vec::new(),
+            scope: Scope::System,
+            fallback: ScopeFallback::PreferSystem,
+            version: None,
+        }
+    }
+
+    /// Pass an extra argument to the install command
+    pub fn with_arg<S: Into<String>>(mut self, arg: S) -> InstallOptions {
+        self.extra_args.push(arg.into());
+        self
+    }
+
+    /// Pass several extra arguments to the install command
+    pub fn with_args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> InstallOptions {
+        self.extra_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Install for the system or the user and nowhere else
+    pub fn in_scope(mut self, scope: Scope) -> InstallOptions {
+        self.scope = scope;
+        self.fallback = ScopeFallback::Exact;
+        self
+    }
+
+    /// Choose the scope with a fallback
+    pub fn with_fallback(mut self, fallback: ScopeFallback) -> InstallOptions {
+        self.fallback = fallback;
+        self
+    }
+
+    /// The scope a package manager installs in with these options
+    pub fn scope_for(&self, manager: &PackageManager) -> Result<Scope, Error> {
+        self.fallback.choose(manager, CommandKind::Install, self.scope)
+    }
+
+    /// Install a particular version
+    pub fn with_version(mut self, version: Version) -> InstallOptions {
+        self.version = Some(version);
+        self
+    }
+
+    /// The arguments given to the install command of a package manager for a package: the
+    /// package, with its version if one was asked for, and then the extra arguments
+    pub fn args(&self, manager: &PackageManager, package: &str) -> Result<Vec<String>, Error> {
+        if package.is_empty() || package.contains(char::is_whitespace) {
+            bail!("{:?} isn't a valid package name", package);
+        }
+        let package = match self.version {
+            Some(ref version) => match manager.version_format {
+                Some(ref format) => render_version(format, package, &version.to_string())?,
+                None => bail!("{} can't install a particular version of a package", manager.name),
+            },
+            None => package.to_owned(),
+        };
+        let mut args = vec![package];
+        args.extend(self.extra_args.iter().cloned());
+        Ok(args)
+    }
+}
+
+impl Default for InstallOptions {
+    fn default() -> InstallOptions {
+        InstallOptions::new()
+    }
+}
+
+/// Write a package at a version with a version_format, such as `ripgrep=14.1.0` for
+/// `{name}={version}`
+pub fn render_version(format: &str, name: &str, version: &str) -> Result<String, Error> {
+    if version.is_empty() || version.contains(char::is_whitespace) {
+        bail!("{:?} isn't a valid version", version);
+    }
+    if !format.contains(NAME_PLACEHOLDER) || !format.contains(VERSION_PLACEHOLDER) {
+        bail!("The version_format {:?} must contain both {} and {}", format, NAME_PLACEHOLDER, VERSION_PLACEHOLDER);
+    }
+    Ok(format.replace(NAME_PLACEHOLDER, name).replace(VERSION_PLACEHOLDER, version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::StdioPolicy;
+
+    #[test]
+    fn arguments() {
+        let mut manager = PackageManager { name: String::from("apt"), ..Default::default() };
+        let options = InstallOptions::new().with_arg("--no-install-recommends").with_arg("-o Debug::pkgProblemResolver=1");
+        assert_eq!(options.args(&manager, "ripgrep").unwrap(),
+                   vec!["ripgrep", "--no-install-recommends", "-o Debug::pkgProblemResolver=1"]);
+        assert!(options.args(&manager, "rip grep").is_err());
+
+        let options = options.with_version(Version::from("14.1.0"));
+        assert!(options.args(&manager, "ripgrep").is_err());
+        manager.version_format = Some(String::from("{name}={version}"));
+        assert_eq!(options.args(&manager, "ripgrep").unwrap()[0], "ripgrep=14.1.0");
+        assert!(render_version("{name}", "ripgrep", "14.1.0").is_err());
+        assert!(render_version("{name}@{version}", "ripgrep", "").is_err());
+    }
+
+    #[test]
+    fn scopes() {
+        let cargo = PackageManager::default().with_command(CommandKind::Install, Scope::User, "cargo install");
+        assert_eq!(InstallOptions::new().scope_for(&cargo).unwrap(), Scope::User);
+        assert!(cargo.can_install());
+        assert!(!PackageManager::default().can_install());
+        let local = PackageManager::default().with_command(CommandKind::Install, Scope::User, "true");
+        assert!(local.install_with("ripgrep", &InstallOptions::new(), StdioPolicy::Null).unwrap().status.success());
+        assert!(InstallOptions::new().in_scope(Scope::System).scope_for(&cargo).is_err());
+        let apt = cargo.with_command(CommandKind::Install, Scope::System, "apt install");
+        assert_eq!(InstallOptions::new().scope_for(&apt).unwrap(), Scope::System);
+        assert_eq!(InstallOptions::new().with_fallback(ScopeFallback::PreferLocal).scope_for(&apt).unwrap(), Scope::User);
+        assert!(ScopeFallback::PreferSystem.choose(&apt, CommandKind::Remove, Scope::System).is_err());
+        assert!(!apt.can_uninstall());
+        let local = local.with_command(CommandKind::Remove, Scope::User, "true");
+        assert!(local.can_uninstall());
+        assert!(local.uninstall_with("ripgrep", StdioPolicy::Null).unwrap().status.success());
+    }
+}
@@ -0,0 +1,370 @@
+//! Reading the hosts of a [fleet](../fleet/index.html) from an inventory, with the `remote`
+//! feature. An inventory is a TOML file listing each host, the groups it's in, which package
+//! managers to run on it, and changes to their configurations for that host:
+//!
+//! ```toml
+//! [hosts.web1]
+//! address = "web1.example.com"
+//! user = "deploy"
+//! port = 2222
+//! groups = ["web"]
+//! managers = ["apt", "npm"]
+//! ssh_options = ["ConnectTimeout=10"]
+//!
+//! [hosts.web1.overrides.apt]
+//! exec_wrapper = "sudo"
+//!
+//! [hosts.db1]
+//! groups = ["db"]
+//! ```
+//!
+//! Every key is optional. The address defaults to the host's name, and a host without
+//! `managers` runs every package manager.
+//!
+//! Existing Ansible INI inventories can be read as well. `ansible_host`, `ansible_user`, and
+//! `ansible_port` give the address, and `upm_managers=apt,npm` which package managers to run.
+//! Variables can be set for groups with `[group:vars]`, and groups can contain other groups with
+//! `[group:children]`. Host ranges such as `web[01:10]` aren't supported.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use super::ManagerSet;
+use fleet::Fleet;
+use remote::SshTarget;
+
+/// The group every host is in
+pub const ALL_GROUP: &str = "all";
+
+/// A host from an inventory
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryHost {
+    /// The name the inventory gives the host
+    pub name: String,
+    pub target: SshTarget,
+    pub groups: Vec<String>,
+    /// The package managers to run on the host, or None to run every one
+    pub managers: Option<Vec<String>>,
+    /// Configuration to change for the host, by package manager name
+    pub overrides: BTreeMap<String, Table>,
+}
+
+impl InventoryHost {
+    /// Create a host reached at its name, in no groups
+    pub fn new(name: &str) -> InventoryHost {
+        InventoryHost {
+            name: name.to_owned(),
+            target: SshTarget::new(name),
+            groups: Vec::new(),
+            managers: None,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Is the host in the group?
+    pub fn in_group(&self, group: &str) -> bool {
+        group == ALL_GROUP || self.groups.iter().any(|name| name == group)
+    }
+
+    /// Get the package managers to run on the host, with its overrides applied
+    pub fn managers(&self, managers: &ManagerSet) -> Result<ManagerSet, Error> {
+        for name in self.overrides.keys() {
+            if managers.get(name).is_none() {
+                bail!("{} has overrides for {}, which isn't configured", self.name, name);
+            }
+        }
+        let mut result = Vec::new();
+        for manager in managers.iter() {
+            if self.managers.as_ref().is_some_and(|names| !names.contains(&manager.name)) {
+                continue;
+            }
+            match self.overrides.get(&manager.name) {
+                Some(overrides) => result.push(manager.with_overrides(overrides)
+                    .map_err(|e| format_err!("The overrides of {} on {} are invalid: {}", manager.name, self.name, e))?),
+                None => result.push(manager.clone()),
+            }
+        }
+        Ok(ManagerSet::new(result))
+    }
+}
+
+/// The hosts a fleet is made of
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    pub hosts: Vec<InventoryHost>,
+}
+
+impl Inventory {
+    /// Read an inventory from a file, as an Ansible INI inventory unless it ends with `.toml`
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Inventory, Error> {
+        let mut content = String::new();
+        File::open(&path)?.read_to_string(&mut content)?;
+        if path.as_ref().extension().is_some_and(|extension| extension == "toml") {
+            Inventory::from_toml(&content.parse::<Value>()?)
+        } else {
+            Inventory::from_ansible_ini(&content)
+        }
+    }
+
+    /// Read an inventory from its TOML
+    pub fn from_toml(resource: &Value) -> Result<Inventory, Error> {
+        let hosts = match resource.get("hosts") {
+            Some(Value::Table(hosts)) => hosts,
+            Some(_) => bail!("The inventory's hosts must be a table"),
+            None => return Ok(Inventory::default()),
+        };
+        let mut inventory = Inventory::default();
+        for (name, host) in hosts {
+            inventory.hosts.push(host_from_toml(name, host)?);
+        }
+        Ok(inventory)
+    }
+
+    /// Read an Ansible INI inventory
+    pub fn from_ansible_ini(content: &str) -> Result<Inventory, Error> {
+        let mut hosts: Vec<(String, HashMap<String, String>, Vec<String>)> = Vec::new();
+        let mut group_vars: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        //The group and kind of section being read, such as ("web", "vars")
+        let mut section = (String::from("ungrouped"), String::new());
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                let header = &line[1..line.len() - 1];
+                section = match header.find(':') {
+                    Some(colon) => (header[..colon].to_owned(), header[colon + 1..].to_owned()),
+                    None => (header.to_owned(), String::new()),
+                };
+                if !["", "vars", "children"].contains(&section.1.as_str()) {
+                    bail!("line {}: unknown section kind {}", number + 1, section.1);
+                }
+                continue;
+            }
+            match section.1.as_str() {
+                "vars" => {
+                    let (key, value) = parse_var(line)
+                        .ok_or_else(|| format_err!("line {}: expected a variable such as key=value", number + 1))?;
+                    group_vars.entry(section.0.clone()).or_default().insert(key, value);
+                },
+                "children" => children.entry(section.0.clone()).or_default().push(line.to_owned()),
+                _ => {
+                    let mut words = line.split_whitespace();
+                    let name = words.next().unwrap();
+                    if name.contains('[') {
+                        bail!("line {}: host ranges such as {} aren't supported", number + 1, name);
+                    }
+                    let mut vars = HashMap::new();
+                    for word in words {
+                        let (key, value) = parse_var(word)
+                            .ok_or_else(|| format_err!("line {}: expected a variable such as key=value, not {}", number + 1, word))?;
+                        vars.insert(key, value);
+                    }
+                    match hosts.iter_mut().find(|host| host.0 == name) {
+                        Some(host) => {
+                            host.1.extend(vars);
+                            host.2.push(section.0.clone());
+                        },
+                        None => hosts.push((name.to_owned(), vars, vec![section.0.clone()])),
+                    }
+                },
+            }
+        }
+
+        let mut inventory = Inventory::default();
+        for (name, host_vars, direct) in hosts {
+            let groups = with_parents(&direct, &children);
+            //Variables of outer groups are applied first, so inner groups and the host win
+            let mut vars: HashMap<String, String> = group_vars.get(ALL_GROUP).cloned().unwrap_or_default();
+            for group in groups.iter().rev() {
+                vars.extend(group_vars.get(group).cloned().unwrap_or_default());
+            }
+            vars.extend(host_vars);
+            let mut host = InventoryHost::new(&name);
+            let address = vars.get("ansible_host").unwrap_or(&name);
+            let address = match vars.get("ansible_user") {
+                Some(user) => format!("{}@{}", user, address),
+                None => address.clone(),
+            };
+            host.target = address.parse().map_err(|e| format_err!("The address of {} is invalid: {}", name, e))?;
+            if let Some(port) = vars.get("ansible_port") {
+                host.target.port = Some(port.parse().map_err(|_| format_err!("The ansible_port of {} must be a number", name))?);
+            }
+            host.managers = vars.get("upm_managers").map(|managers| managers.split(',').map(|manager| manager.trim().to_owned()).collect());
+            host.groups = groups.into_iter().filter(|group| group != "ungrouped").collect();
+            inventory.hosts.push(host);
+        }
+        Ok(inventory)
+    }
+
+    /// Get the hosts in a group as an inventory of their own
+    pub fn group(&self, group: &str) -> Inventory {
+        Inventory { hosts: self.hosts.iter().filter(|host| host.in_group(group)).cloned().collect() }
+    }
+
+    /// Get a host by name
+    pub fn get(&self, name: &str) -> Option<&InventoryHost> {
+        self.hosts.iter().find(|host| host.name == name)
+    }
+
+    /// Create a fleet of the hosts, each running the package managers the inventory chooses for it
+    pub fn fleet(&self, managers: &ManagerSet) -> Result<Fleet, Error> {
+        let mut fleet = Fleet::new(managers, Vec::new());
+        for host in &self.hosts {
+            fleet.add_host(host.target.clone(), host.managers(managers)?);
+        }
+        Ok(fleet)
+    }
+}
+
+//Reads a host from its table in a TOML inventory
+fn host_from_toml(name: &str, host: &Value) -> Result<InventoryHost, Error> {
+    let strings = |key: &str| -> Result<Option<Vec<String>>, Error> {
+        match host.get(key) {
+            Some(Value::Array(values)) => values.iter()
+                .map(|value| value.as_str().map(str::to_owned).ok_or_else(|| format_err!("The {} of {} must be strings", key, name)))
+                .collect::<Result<Vec<String>, Error>>()
+                .map(Some),
+            Some(_) => bail!("The {} of {} must be an array of strings", key, name),
+            None => Ok(None),
+        }
+    };
+    let mut result = InventoryHost::new(name);
+    let address = match host.get("address") {
+        Some(address) => address.as_str().ok_or_else(|| format_err!("The address of {} must be a string", name))?,
+        None => name,
+    };
+    result.target = address.parse().map_err(|e| format_err!("The address of {} is invalid: {}", name, e))?;
+    if let Some(user) = host.get("user") {
+        match user.as_str() {
+            //Parsed with the host so a user ssh would take for an option is refused
+            Some(user) => result.target.user = format!("{}@{}", user, result.target.host).parse::<SshTarget>()
+                .map_err(|_| format_err!("The user of {} is invalid: {}", name, user))?
+                .user,
+            None => bail!("The user of {} must be a string", name),
+        }
+    }
+    if let Some(port) = host.get("port") {
+        match port.as_integer() {
+            Some(port) if port > 0 && port <= i64::from(u16::MAX) => result.target.port = Some(port as u16),
+            _ => bail!("The port of {} must be a port number", name),
+        }
+    }
+    result.target.options.extend(strings("ssh_options")?.unwrap_or_default());
+    result.groups = strings("groups")?.unwrap_or_default();
+    result.managers = strings("managers")?;
+    if let Some(overrides) = host.get("overrides") {
+        let overrides = match overrides.as_table() {
+            Some(overrides) => overrides,
+            None => bail!("The overrides of {} must be a table of package managers", name),
+        };
+        for (manager, config) in overrides {
+            match config.as_table() {
+                Some(config) => result.overrides.insert(manager.clone(), config.clone()),
+                None => bail!("The overrides of {} for {} must be a table", name, manager),
+            };
+        }
+    }
+    Ok(result)
+}
+
+//Splits a variable such as key=value, removing quotes around the value
+fn parse_var(word: &str) -> Option<(String, String)> {
+    let equals = word.find('=')?;
+    let key = word[..equals].trim();
+    let value = word[equals + 1..].trim().trim_matches(|c| c == '"' || c == '\'');
+    if key.is_empty() {
+        None
+    } else {
+        Some((key.to_owned(), value.to_owned()))
+    }
+}
+
+//Gives the groups along with every group containing them, innermost first
+fn with_parents(groups: &[String], children: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let mut pending: Vec<String> = groups.to_vec();
+    while !pending.is_empty() {
+        let group = pending.remove(0);
+        if result.contains(&group) {
+            continue;
+        }
+        pending.extend(children.iter()
+            .filter(|&(_, members)| members.contains(&group))
+            .map(|(parent, _)| parent.clone()));
+        result.push(group);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, PackageManager, Scope};
+
+    fn managers() -> ManagerSet {
+        ManagerSet::new(["apt", "npm", "pip"].iter().map(|&name| PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::Install, Scope::System, format!("{} install", name))).collect())
+    }
+
+    #[test]
+    fn toml_inventories() {
+        let config = "[hosts.web1]\naddress = 'web1.example.com'\nuser = 'deploy'\nport = 2222\ngroups = ['web']\n\
+                      managers = ['apt', 'npm']\nssh_options = ['ConnectTimeout=10']\n\
+                      [hosts.web1.overrides.apt]\nexec_wrapper = 'sudo'\n[hosts.db1]\ngroups = ['db']\n";
+        let inventory = Inventory::from_toml(&config.parse::<Value>().unwrap()).unwrap();
+        let web1 = inventory.get("web1").unwrap();
+        assert_eq!(web1.target.to_string(), "deploy@web1.example.com:2222");
+        assert!(web1.target.options.contains(&String::from("ConnectTimeout=10")));
+        assert_eq!(inventory.group("db").hosts.len(), 1);
+        assert_eq!(inventory.group(ALL_GROUP).hosts.len(), 2);
+
+        let fleet = inventory.fleet(&managers()).unwrap();
+        let web1 = &fleet.hosts()[1].managers;
+        assert_eq!(web1.len(), 2);
+        assert_eq!(web1.get("apt").unwrap().command_line("install", "curl").unwrap(),
+                   "ssh -p 2222 -o BatchMode=yes -o ConnectTimeout=10 -- deploy@web1.example.com sudo apt install curl");
+        assert_eq!(fleet.hosts()[0].managers.len(), 3);
+
+        assert!(Inventory::from_toml(&"[hosts.web1]\nport = 'ssh'\n".parse::<Value>().unwrap()).is_err());
+        assert!(Inventory::from_toml(&"[hosts.web1]\naddress = '-oProxyCommand=x'\n".parse::<Value>().unwrap()).is_err());
+        assert!(Inventory::from_toml(&"[hosts.web1]\nuser = '-oProxyCommand=x'\n".parse::<Value>().unwrap()).is_err());
+        let unknown = Inventory::from_toml(&"[hosts.web1.overrides.gem]\nexec_wrapper = 'sudo'\n".parse::<Value>().unwrap()).unwrap();
+        assert!(unknown.fleet(&managers()).is_err());
+    }
+
+    #[test]
+    fn ansible_inventories() {
+        let content = "# Production\nbastion.example.com ansible_user=root\n\n\
+                       [web]\nweb1 ansible_host=10.0.0.1\nweb2 ansible_port=2222 upm_managers=apt,npm\n\n\
+                       [db]\ndb1\n\n[servers:children]\nweb\ndb\n\n\
+                       [servers:vars]\nansible_user=deploy\n[web:vars]\nansible_user=www\n";
+        let inventory = Inventory::from_ansible_ini(content).unwrap();
+        assert_eq!(inventory.hosts.len(), 4);
+        let bastion = inventory.get("bastion.example.com").unwrap();
+        assert!(bastion.groups.is_empty());
+        assert_eq!(bastion.target.to_string(), "root@bastion.example.com");
+        let web1 = inventory.get("web1").unwrap();
+        assert_eq!(web1.target.to_string(), "www@10.0.0.1");
+        assert_eq!(web1.groups, vec!["web", "servers"]);
+        assert_eq!(inventory.get("web2").unwrap().managers, Some(vec![String::from("apt"), String::from("npm")]));
+        assert_eq!(inventory.get("db1").unwrap().target.to_string(), "deploy@db1");
+        assert_eq!(inventory.group("servers").hosts.len(), 3);
+
+        assert!(Inventory::from_ansible_ini("[web]\nweb[01:10]\n").is_err());
+        assert!(Inventory::from_ansible_ini("[web]\nweb1 ansible_port=ssh\n").is_err());
+        assert!(Inventory::from_ansible_ini("[web]\nweb1 ansible_host=-oProxyCommand=x\n").is_err());
+        assert!(Inventory::from_ansible_ini("[web]\nweb1 ansible_user=-oProxyCommand=x\n").is_err());
+    }
+}
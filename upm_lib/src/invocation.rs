@@ -0,0 +1,118 @@
+//! Timing of package manager invocations. Every command upm runs to completion is wrapped in an
+//! [`Invocation`](struct.Invocation.html), which logs how long the command took and how it exited
+//! at debug level.
+//!
+//! With the `tracing` feature each invocation is also a `tracing` span named `invocation` so
+//! frontends can collect structured telemetry, such as a flamegraph of a multi-manager upgrade.
+//! The span has the fields:
+//!
+//! | Field | Value |
+//! |-------|-------|
+//! | `manager` | The name of the package manager |
+//! | `command` | The command being run, such as `install` or a hook such as `pre_install` |
+//! | `args` | The arguments given to the command |
+//! | `duration_ms` | How long the command took in milliseconds, recorded once it finishes |
+//! | `status` | The exit code of the command, recorded if it exited normally |
+//!
+//! An invocation given an [event stream](../events/index.html) also emits events when the command
+//! starts and finishes.
+
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use events::{Event, EventStream};
+
+/// A single run of a package manager command
+pub struct Invocation {
+    manager: String,
+    command: String,
+    args: String,
+    start: Instant,
+    events: Option<Arc<EventStream>>,
+    #[cfg(feature = "tracing")]
+    span: ::tracing::Span,
+}
+
+impl Invocation {
+    /// Start timing a command
+    pub fn new(manager: &str, command: &str, args: &str) -> Invocation {
+        Invocation::reported(manager, command, args, None)
+    }
+
+    /// Start timing a command, emitting events for it to the stream if there is one
+    pub fn reported(manager: &str, command: &str, args: &str, events: Option<Arc<EventStream>>) -> Invocation {
+        if let Some(ref events) = events {
+            events.emit(&Event::Started {
+                manager: manager.to_owned(),
+                command: command.to_owned(),
+                args: args.to_owned(),
+            });
+        }
+        Invocation {
+            manager: manager.to_owned(),
+            command: command.to_owned(),
+            args: args.to_owned(),
+            start: Instant::now(),
+            events,
+            #[cfg(feature = "tracing")]
+            span: info_span!("invocation",
+                             manager = manager,
+                             command = command,
+                             args = args,
+                             duration_ms = ::tracing::field::Empty,
+                             status = ::tracing::field::Empty),
+        }
+    }
+
+    /// Run a closure inside the invocation's span so that anything it logs or traces, including
+    /// nested invocations, is attributed to this command.
+    pub fn in_scope<T, F: FnOnce() -> T>(&self, operation: F) -> T {
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.enter();
+        operation()
+    }
+
+    /// Finish timing the command, recording its exit status if it ran. The time taken is given.
+    pub fn finish(self, status: Option<ExitStatus>) -> Duration {
+        let duration = self.start.elapsed();
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("duration_ms", duration.as_millis() as u64);
+            if let Some(code) = status.and_then(|status| status.code()) {
+                self.span.record("status", code);
+            }
+        }
+        if let Some(ref events) = self.events {
+            events.emit(&Event::Finished {
+                manager: self.manager.clone(),
+                command: self.command.clone(),
+                status: status.and_then(|status| status.code()),
+                duration_ms: duration.as_millis() as u64,
+            });
+        }
+        match status {
+            Some(status) => debug!("{}: {} command with args {:?} exited with {} after {:?}",
+                                   self.manager, self.command, self.args, status, duration),
+            None => debug!("{}: {} command with args {:?} couldn't be run after {:?}",
+                           self.manager, self.command, self.args, duration),
+        }
+        duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::thread;
+
+    #[test]
+    fn timing() {
+        let invocation = Invocation::new("test", "version", "");
+        let status = invocation.in_scope(|| {
+            thread::sleep(Duration::from_millis(10));
+            Command::new("true").status().ok()
+        });
+        assert!(invocation.finish(status) >= Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,92 @@
+//! The signing keys package managers trust their repositories with. A package manager's
+//! configuration can give an `add_key` command that trusts a key file or URL, such as
+//! `rpm --import`, and a `list_keys` command that prints the trusted keys following the
+//! [script contract](../contract/index.html).
+
+use std::fmt;
+use failure::Error;
+use super::PackageManager;
+use concurrency::{ConcurrencyPolicy, run_all};
+use multi::MultiResult;
+
+/// A repository signing key trusted by a package manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoKey {
+    /// The key's ID or fingerprint as the package manager shows it
+    pub id: String,
+    pub description: String,
+    /// The name of the package manager that trusts the key
+    pub manager: String,
+}
+
+impl fmt::Display for RepoKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.description.is_empty() {
+            write!(f, "{} ({})", self.id, self.manager)
+        } else {
+            write!(f, "{} ({}): {}", self.id, self.manager, self.description)
+        }
+    }
+}
+
+/// Parse the output of a list_keys command, one key per line as the ID and then the description
+pub fn parse_keys(listing: &str, manager: &str) -> Vec<RepoKey> {
+    listing.lines().filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next().map(|id| RepoKey {
+            id: id.to_owned(),
+            description: fields.collect::<Vec<&str>>().join(" "),
+            manager: manager.to_owned(),
+        })
+    }).collect()
+}
+
+/// List the keys of every package manager with a list_keys command
+pub fn list_all(managers: &[PackageManager]) -> MultiResult<'_, Result<Vec<RepoKey>, Error>> {
+    MultiResult::new("list_keys", run_all(managers, &ConcurrencyPolicy::default(), |manager| {
+        if manager.has_command("list_keys") {
+            Some(manager.list_keys())
+        } else {
+            None
+        }
+    }).into_iter().filter_map(|(manager, result)| result.map(|result| (manager, result))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, Scope};
+
+    #[test]
+    fn parsing_keys() {
+        let keys = parse_keys("gpg-pubkey-a3cc4e62-5c5e8d8b Fedora 30 key\n\n  8A4B2C1D\n", "dnf");
+        assert_eq!(keys, vec![
+            RepoKey {
+                id: String::from("gpg-pubkey-a3cc4e62-5c5e8d8b"),
+                description: String::from("Fedora 30 key"),
+                manager: String::from("dnf"),
+            },
+            RepoKey { id: String::from("8A4B2C1D"), description: String::new(), manager: String::from("dnf") },
+        ]);
+        assert_eq!(keys[0].to_string(), "gpg-pubkey-a3cc4e62-5c5e8d8b (dnf): Fedora 30 key");
+    }
+
+    #[test]
+    fn listing_keys() {
+        let managers = vec![
+            PackageManager {
+                name: String::from("keyed"),
+                ..Default::default()
+            }.with_command(CommandKind::Version, Scope::System, "true")
+                .with_command(CommandKind::ListKeys, Scope::System, "echo"),
+            PackageManager {
+                name: String::from("keyless"),
+                ..Default::default()
+            }.with_command(CommandKind::Version, Scope::System, "true"),
+        ];
+        let listed = list_all(&managers);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].0.name, "keyed");
+        assert!(listed[0].1.as_ref().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+//! What kind of software a package is. Desktop app stores such as Flatpak and Snap install
+//! graphical applications, while most package managers install libraries and command line tools,
+//! so frontends showing results from several package managers can tell them apart. A package
+//! manager declares the kind of everything it installs:
+//!
+//! ```toml
+//! kind = "application"
+//! ```
+//!
+//! Package managers that install several kinds leave it out, and their `info` command can give
+//! the kind of each package with a `kind` key.
+
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+use super::Package;
+
+/// What kind of software a package is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PackageKind {
+    /// A desktop application, such as one from Flatpak or Snap
+    Application,
+    /// A library other software is built with or loads
+    Library,
+    /// A command line tool
+    Tool,
+}
+
+impl PackageKind {
+    /// The name of the kind as upm shows it
+    pub fn name(self) -> &'static str {
+        match self {
+            PackageKind::Application => "application",
+            PackageKind::Library => "library",
+            PackageKind::Tool => "tool",
+        }
+    }
+}
+
+impl FromStr for PackageKind {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> Result<PackageKind, Error> {
+        match kind {
+            "application" | "app" => Ok(PackageKind::Application),
+            "library" | "lib" => Ok(PackageKind::Library),
+            "tool" => Ok(PackageKind::Tool),
+            _ => bail!("Unknown package kind {}, expected application, library, or tool", kind),
+        }
+    }
+}
+
+impl fmt::Display for PackageKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Get the packages of a kind. Packages whose kind isn't known are left out.
+pub fn of_kind<'a, I>(packages: I, kind: PackageKind) -> Box<dyn Iterator<Item = &'a Package> + 'a>
+    where I: IntoIterator<Item = &'a Package>, I::IntoIter: 'a {
+    Box::new(packages.into_iter().filter(move |package| package.kind() == Some(kind)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::PackageManager;
+
+    #[test]
+    fn package_kinds() {
+        assert_eq!("app".parse::<PackageKind>().unwrap(), PackageKind::Application);
+        assert_eq!("library".parse::<PackageKind>().unwrap().to_string(), "library");
+        assert!("gui".parse::<PackageKind>().is_err());
+
+        let flatpak = PackageManager { name: String::from("flatpak"), kind: Some(PackageKind::Application), ..Default::default() };
+        let pip = PackageManager { name: String::from("pip"), ..Default::default() };
+        let packages = vec![
+            Package { name: String::from("org.gimp.GIMP"), owner: flatpak, ..Default::default() },
+            Package { name: String::from("requests"), owner: pip.clone(), ..Default::default() },
+            Package { name: String::from("httpie"), owner: pip, kind: Some(PackageKind::Tool), ..Default::default() },
+        ];
+        assert!(packages[0].is_application());
+        assert_eq!(packages[1].kind(), None);
+        let apps: Vec<&str> = of_kind(&packages, PackageKind::Application).map(|package| package.name.as_str()).collect();
+        assert_eq!(apps, vec!["org.gimp.GIMP"]);
+        assert_eq!(of_kind(&packages, PackageKind::Tool).count(), 1);
+    }
+}
@@ -0,0 +1,271 @@
+//! Checking the licenses of packages before they're installed, for organizations that only allow
+//! some. The `license` table of the [settings](../settings/index.html) lists the licenses that
+//! are allowed and those that are denied, as SPDX identifiers where `*` matches anything:
+//!
+//! ```toml
+//! [license]
+//! allow = ["MIT", "Apache-2.0", "BSD-*", "ISC"]
+//! deny = ["AGPL-*"]
+//! action = "refuse"
+//! unknown = "warn"
+//! ```
+//!
+//! A denied license is never permitted, and when there's an allow list only the licenses on it
+//! are. Expressions such as `MIT OR Apache-2.0` are permitted if any alternative is, and
+//! `MIT AND Zlib` only if every part is. `action` says whether a package that isn't permitted is
+//! refused or only warned about, and `unknown` what happens to packages whose license the
+//! [info](../info/index.html) command doesn't give. Matching ignores case.
+//!
+//! Package managers given the policy with
+//! [`set_license`](../struct.PackageManager.html#method.set_license) check every package their
+//! install commands are run with, so a refused package gives a
+//! [`LicenseRefused`](struct.LicenseRefused.html) error.
+
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+use toml::Value;
+use super::Package;
+
+/// What happens to a package whose license isn't permitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseAction {
+    /// Install it anyway without saying anything
+    Allow,
+    /// Install it after a warning
+    Warn,
+    /// Don't install it
+    Refuse,
+}
+
+impl LicenseAction {
+    /// The name of the action in the settings
+    pub fn name(self) -> &'static str {
+        match self {
+            LicenseAction::Allow => "allow",
+            LicenseAction::Warn => "warn",
+            LicenseAction::Refuse => "refuse",
+        }
+    }
+}
+
+impl FromStr for LicenseAction {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<LicenseAction, Error> {
+        match name {
+            "allow" => Ok(LicenseAction::Allow),
+            "warn" => Ok(LicenseAction::Warn),
+            "refuse" => Ok(LicenseAction::Refuse),
+            _ => bail!("Unknown license action {}, expected allow, warn, or refuse", name),
+        }
+    }
+}
+
+/// What the policy says about a license
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LicenseVerdict {
+    Permitted,
+    /// The license isn't permitted
+    NotPermitted(String),
+    /// The package didn't say what its license is
+    Unknown,
+}
+
+/// The error returned when the policy refuses to install a package because of its license
+#[derive(Debug, Fail)]
+#[fail(display = "Not installing {} with {}: {}", package, manager, verdict)]
+pub struct LicenseRefused {
+    pub package: String,
+    pub manager: String,
+    pub verdict: LicenseVerdict,
+}
+
+/// Which licenses packages may have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LicensePolicy {
+    /// The licenses permitted, or every license that isn't denied if empty
+    pub allow: Vec<String>,
+    /// The licenses never permitted
+    pub deny: Vec<String>,
+    /// What happens to packages whose license isn't permitted
+    pub action: LicenseAction,
+    /// What happens to packages whose license is unknown
+    pub unknown: LicenseAction,
+}
+
+impl Default for LicensePolicy {
+    fn default() -> LicensePolicy {
+        LicensePolicy { allow: Vec::new(), deny: Vec::new(), action: LicenseAction::Refuse, unknown: LicenseAction::Allow }
+    }
+}
+
+impl LicensePolicy {
+    /// A policy that permits every license
+    pub fn new() -> LicensePolicy {
+        LicensePolicy::default()
+    }
+
+    /// Read the policy from the `license` table of the settings
+    pub fn from_toml(table: &Value) -> Result<LicensePolicy, Error> {
+        if !table.is_table() {
+            bail!("license must be a table with allow and deny lists");
+        }
+        let licenses = |key: &str| -> Result<Vec<String>, Error> {
+            match table.get(key) {
+                Some(value) => match value.as_array() {
+                    Some(values) => values.iter()
+                        .map(|value| match value.as_str() {
+                            Some(value) => Ok(value.to_owned()),
+                            None => bail!("license.{} must be an array of licenses", key),
+                        })
+                        .collect(),
+                    None => bail!("license.{} must be an array of licenses", key),
+                },
+                None => Ok(Vec::new()),
+            }
+        };
+        let action = |key: &str, default: LicenseAction| -> Result<LicenseAction, Error> {
+            match table.get(key) {
+                Some(value) => match value.as_str() {
+                    Some(value) => value.parse(),
+                    None => bail!("license.{} must be allow, warn, or refuse", key),
+                },
+                None => Ok(default),
+            }
+        };
+        let defaults = LicensePolicy::default();
+        Ok(LicensePolicy {
+            allow: licenses("allow")?,
+            deny: licenses("deny")?,
+            action: action("action", defaults.action)?,
+            unknown: action("unknown", defaults.unknown)?,
+        })
+    }
+
+    /// Does the policy restrict any license?
+    pub fn is_restrictive(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty() || self.unknown != LicenseAction::Allow
+    }
+
+    /// Check a license, which may be an expression such as `MIT OR Apache-2.0`
+    pub fn check(&self, license: Option<&str>) -> LicenseVerdict {
+        let license = match license.map(str::trim) {
+            Some(license) if !license.is_empty() => license,
+            _ => return LicenseVerdict::Unknown,
+        };
+        let permitted = alternatives(license).iter()
+            .any(|parts| parts.iter().all(|part| self.permits(part)));
+        if permitted {
+            LicenseVerdict::Permitted
+        } else {
+            LicenseVerdict::NotPermitted(license.to_owned())
+        }
+    }
+
+    /// Check the license of a package
+    pub fn check_package(&self, package: &Package) -> LicenseVerdict {
+        self.check(package.license.as_deref())
+    }
+
+    /// What happens to a package given the verdict on its license
+    pub fn action(&self, verdict: &LicenseVerdict) -> LicenseAction {
+        match *verdict {
+            LicenseVerdict::Permitted => LicenseAction::Allow,
+            LicenseVerdict::NotPermitted(_) => self.action,
+            LicenseVerdict::Unknown => self.unknown,
+        }
+    }
+
+    //Is a single license permitted?
+    fn permits(&self, license: &str) -> bool {
+        if self.deny.iter().any(|pattern| matches(pattern, license)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|pattern| matches(pattern, license))
+    }
+}
+
+impl fmt::Display for LicenseVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LicenseVerdict::Permitted => write!(f, "the license is permitted"),
+            LicenseVerdict::NotPermitted(ref license) => write!(f, "the license {} isn't permitted", license),
+            LicenseVerdict::Unknown => write!(f, "the license is unknown"),
+        }
+    }
+}
+
+/// Split a license expression into its alternatives, each the licenses that must all be
+/// permitted. `/` is read as `OR`, as older package metadata writes it. Parentheses are ignored,
+/// so `AND` always binds tighter than `OR`.
+pub fn alternatives(expression: &str) -> Vec<Vec<String>> {
+    let expression = expression.replace(['(', ')'], " ").replace('/', " OR ");
+    let mut result = vec![Vec::new()];
+    let mut words = expression.split_whitespace();
+    while let Some(word) = words.next() {
+        if word.eq_ignore_ascii_case("OR") {
+            result.push(Vec::new());
+        } else if word.eq_ignore_ascii_case("WITH") {
+            //An exception such as Classpath-exception-2.0 only loosens the license before it
+            words.next();
+        } else if !word.eq_ignore_ascii_case("AND") {
+            result.last_mut().unwrap().push(word.to_owned());
+        }
+    }
+    result.retain(|parts| !parts.is_empty());
+    result
+}
+
+//Does a license match a pattern where * matches anything, ignoring case?
+fn matches(pattern: &str, license: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let license = license.to_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !license.starts_with(first) {
+        return false;
+    }
+    let mut rest = &license[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(index) => rest = &rest[index + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.len() >= last.len() && rest.ends_with(last)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn license_policies() {
+        let config = "allow = ['MIT', 'Apache-2.0', 'BSD-*']\ndeny = ['BSD-4-Clause']\naction = 'warn'\nunknown = 'refuse'\n";
+        let policy = LicensePolicy::from_toml(&config.parse::<Value>().unwrap()).unwrap();
+        assert_eq!(policy.check(Some("mit")), LicenseVerdict::Permitted);
+        assert_eq!(policy.check(Some("BSD-3-Clause")), LicenseVerdict::Permitted);
+        assert_eq!(policy.check(Some("BSD-4-Clause")), LicenseVerdict::NotPermitted(String::from("BSD-4-Clause")));
+        assert_eq!(policy.check(Some("GPL-3.0-only OR MIT")), LicenseVerdict::Permitted);
+        assert_eq!(policy.check(Some("MIT/Apache-2.0")), LicenseVerdict::Permitted);
+        assert!(policy.check(Some("(MIT AND GPL-2.0-only)")) != LicenseVerdict::Permitted);
+        assert_eq!(policy.check(Some(" ")), LicenseVerdict::Unknown);
+        assert_eq!(policy.action(&policy.check(Some("GPL-3.0-only"))), LicenseAction::Warn);
+        assert_eq!(policy.action(&policy.check(None)), LicenseAction::Refuse);
+
+        let policy = LicensePolicy { deny: vec![String::from("AGPL-*")], ..LicensePolicy::new() };
+        assert_eq!(policy.check(Some("GPL-2.0-or-later WITH Classpath-exception-2.0")), LicenseVerdict::Permitted);
+        assert_eq!(policy.action(&policy.check(Some("AGPL-3.0-only"))), LicenseAction::Refuse);
+        assert!(!LicensePolicy::new().is_restrictive());
+
+        assert!(LicensePolicy::from_toml(&"action = 'ignore'".parse::<Value>().unwrap()).is_err());
+        assert!(LicensePolicy::from_toml(&"allow = 'MIT'".parse::<Value>().unwrap()).is_err());
+    }
+}
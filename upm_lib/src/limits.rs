@@ -0,0 +1,191 @@
+//! Limiting the resources package manager commands use, so a large upgrade doesn't make the rest
+//! of the system unusable. A package manager's configuration can lower the CPU and IO priority of
+//! its commands and restrict which CPUs they run on:
+//!
+//! ```toml
+//! nice = 10
+//! ionice_class = "idle"
+//! cpu_affinity = [0, 1]
+//! ```
+//!
+//! On Linux the commands are run through `nice`, `ionice`, and `taskset`. The limits are ignored
+//! on other platforms.
+
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+
+/// The IO scheduling class of a command, as understood by `ionice`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    /// Served before every other class. Usually needs root.
+    Realtime,
+    /// The default class, sharing IO fairly
+    BestEffort,
+    /// Only served when nothing else needs the disk
+    Idle,
+}
+
+impl IoClass {
+    /// The number ionice uses for the class
+    pub fn number(self) -> u8 {
+        match self {
+            IoClass::Realtime => 1,
+            IoClass::BestEffort => 2,
+            IoClass::Idle => 3,
+        }
+    }
+}
+
+impl FromStr for IoClass {
+    type Err = Error;
+
+    fn from_str(class: &str) -> Result<IoClass, Error> {
+        match class {
+            "realtime" => Ok(IoClass::Realtime),
+            "best_effort" => Ok(IoClass::BestEffort),
+            "idle" => Ok(IoClass::Idle),
+            _ => bail!("Unknown ionice_class {}, expected realtime, best_effort, or idle", class),
+        }
+    }
+}
+
+impl fmt::Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            IoClass::Realtime => "realtime",
+            IoClass::BestEffort => "best_effort",
+            IoClass::Idle => "idle",
+        })
+    }
+}
+
+/// The resource limits of a package manager's commands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The niceness of the commands, from -20 to 19
+    pub nice: Option<i32>,
+    pub ionice_class: Option<IoClass>,
+    /// The CPUs the commands may run on. Empty means any CPU.
+    pub cpu_affinity: Vec<usize>,
+}
+
+impl ResourceLimits {
+    /// Read the limits from the top level of a package manager's configuration
+    pub fn from_toml(resource: &Value) -> Result<ResourceLimits, Error> {
+        let nice = match resource.get("nice") {
+            Some(nice) => match nice.as_integer() {
+                Some(nice) if (-20..=19).contains(&nice) => Some(nice as i32),
+                _ => bail!("Package manager nice must be an integer from -20 to 19"),
+            },
+            None => None,
+        };
+        let ionice_class = match resource.get("ionice_class") {
+            Some(class) => match class.as_str() {
+                Some(class) => Some(class.parse()?),
+                None => bail!("Package manager ionice_class must be a string"),
+            },
+            None => None,
+        };
+        let mut cpu_affinity = Vec::new();
+        if let Some(cpus) = resource.get("cpu_affinity") {
+            let cpus = match cpus.as_array() {
+                Some(cpus) => cpus,
+                None => bail!("Package manager cpu_affinity must be an array of CPU numbers"),
+            };
+            for cpu in cpus {
+                match cpu.as_integer() {
+                    Some(cpu) if cpu >= 0 => cpu_affinity.push(cpu as usize),
+                    _ => bail!("CPU {} in cpu_affinity must be a non-negative integer", cpu),
+                }
+            }
+        }
+        Ok(ResourceLimits { nice, ionice_class, cpu_affinity })
+    }
+
+    /// Write the limits to the top level of a package manager's configuration
+    pub fn write_toml(&self, table: &mut Table) {
+        if let Some(nice) = self.nice {
+            table.insert(String::from("nice"), Value::Integer(i64::from(nice)));
+        }
+        if let Some(class) = self.ionice_class {
+            table.insert(String::from("ionice_class"), Value::String(class.to_string()));
+        }
+        if !self.cpu_affinity.is_empty() {
+            let cpus = self.cpu_affinity.iter().map(|&cpu| Value::Integer(cpu as i64)).collect();
+            table.insert(String::from("cpu_affinity"), Value::Array(cpus));
+        }
+    }
+
+    /// Are there no limits?
+    pub fn is_empty(&self) -> bool {
+        self.nice.is_none() && self.ionice_class.is_none() && self.cpu_affinity.is_empty()
+    }
+
+    /// The words a command is prefixed with to run it under the limits. This is empty on
+    /// platforms other than Linux.
+    pub fn wrapper(&self) -> Vec<String> {
+        if cfg!(target_os = "linux") {
+            self.linux_wrapper()
+        } else {
+            if !self.is_empty() {
+                debug!("Resource limits are only supported on Linux, ignoring them");
+            }
+            Vec::new()
+        }
+    }
+
+    //Builds the nice, ionice, and taskset prefix
+    fn linux_wrapper(&self) -> Vec<String> {
+        let mut wrapper = Vec::new();
+        if let Some(nice) = self.nice {
+            wrapper.extend(vec![String::from("nice"), String::from("-n"), nice.to_string()]);
+        }
+        if let Some(class) = self.ionice_class {
+            wrapper.extend(vec![String::from("ionice"), String::from("-c"), class.number().to_string()]);
+        }
+        if !self.cpu_affinity.is_empty() {
+            let cpus: Vec<String> = self.cpu_affinity.iter().map(|cpu| cpu.to_string()).collect();
+            wrapper.extend(vec![String::from("taskset"), String::from("-c"), cpus.join(",")]);
+        }
+        wrapper
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(config: &str) -> Result<ResourceLimits, Error> {
+        ResourceLimits::from_toml(&config.parse::<Value>().unwrap())
+    }
+
+    #[test]
+    fn parsing() {
+        let parsed = limits("nice = 10\nionice_class = 'idle'\ncpu_affinity = [0, 2]\n").unwrap();
+        assert_eq!(parsed, ResourceLimits {
+            nice: Some(10),
+            ionice_class: Some(IoClass::Idle),
+            cpu_affinity: vec![0, 2],
+        });
+        assert!(limits("version = 'true'\n").unwrap().is_empty());
+        assert!(limits("nice = 40\n").is_err());
+        assert!(limits("ionice_class = 'lazy'\n").is_err());
+        assert!(limits("cpu_affinity = [-1]\n").is_err());
+        assert!(limits("cpu_affinity = 1\n").is_err());
+    }
+
+    #[test]
+    fn wrapping() {
+        let parsed = limits("nice = 10\nionice_class = 'best_effort'\ncpu_affinity = [0, 2]\n").unwrap();
+        let expected = if cfg!(target_os = "linux") {
+            "nice -n 10 ionice -c 2 taskset -c 0,2"
+        } else {
+            ""
+        };
+        assert_eq!(parsed.wrapper().join(" "), expected);
+        assert!(ResourceLimits::default().wrapper().is_empty());
+    }
+}
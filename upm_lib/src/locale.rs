@@ -0,0 +1,104 @@
+//! Keeping the output upm parses in one language. Package managers translate their messages, so a
+//! search that works in English can fail to parse in German. Commands whose output upm parses,
+//! such as `version`, `search`, and `list_installed`, are run with `LC_ALL` and `LANG` set to the
+//! `C` locale, while commands whose output goes to the user, such as `install`, keep the user's
+//! locale. A package manager's configuration can choose another locale, or keep the user's for
+//! every command:
+//!
+//! ```toml
+//! locale = "C.UTF-8"
+//! ```
+//!
+//! ```toml
+//! locale = false
+//! ```
+
+use std::process::Command;
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use contract::{OutputFormat, ScriptContract};
+
+/// The locale parsed commands are run in unless the configuration chooses another
+pub const DEFAULT_LOCALE: &str = "C";
+
+/// The environment variables the locale is set in. `LC_ALL` overrides the rest, and `LANG` is
+/// set too for programs that only read it.
+pub const LOCALE_VARS: &[&str] = &["LC_ALL", "LANG"];
+
+/// Which locale the commands of a package manager whose output is parsed are run in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalePolicy {
+    /// Run them in this locale
+    Fixed(String),
+    /// Leave them in the user's locale
+    Inherit,
+}
+
+impl LocalePolicy {
+    /// Read the locale from the top level of a package manager's configuration
+    pub fn from_toml(resource: &Value) -> Result<LocalePolicy, Error> {
+        let locale = match resource.get("locale") {
+            Some(locale) => locale,
+            None => return Ok(LocalePolicy::default()),
+        };
+        match (locale.as_str().map(str::trim), locale.as_bool()) {
+            (Some(locale), _) if !locale.is_empty() => Ok(LocalePolicy::Fixed(locale.to_owned())),
+            (_, Some(false)) => Ok(LocalePolicy::Inherit),
+            (_, Some(true)) => Ok(LocalePolicy::default()),
+            _ => bail!("Package manager locale must be the name of a locale or false"),
+        }
+    }
+
+    /// Write the locale to a package manager's configuration, leaving out the default
+    pub fn write_toml(&self, table: &mut Table) {
+        match *self {
+            LocalePolicy::Fixed(ref locale) if locale == DEFAULT_LOCALE => {},
+            LocalePolicy::Fixed(ref locale) => { table.insert(String::from("locale"), Value::String(locale.clone())); },
+            LocalePolicy::Inherit => { table.insert(String::from("locale"), Value::Boolean(false)); },
+        }
+    }
+
+    /// Set the locale of a command whose output is parsed
+    pub fn apply(&self, command: &mut Command) {
+        if let LocalePolicy::Fixed(ref locale) = *self {
+            for var in LOCALE_VARS {
+                command.env(var, locale);
+            }
+        }
+    }
+}
+
+impl Default for LocalePolicy {
+    fn default() -> LocalePolicy {
+        LocalePolicy::Fixed(String::from(DEFAULT_LOCALE))
+    }
+}
+
+/// Does upm parse the output of the command?
+pub fn is_parsed(command: &'static str) -> bool {
+    ScriptContract::for_command(command).output != OutputFormat::Unparsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales() {
+        let read = |toml: &str| LocalePolicy::from_toml(&toml.parse::<Value>().unwrap());
+        assert_eq!(read("").unwrap(), LocalePolicy::Fixed(String::from("C")));
+        assert_eq!(read("locale = 'C.UTF-8'").unwrap(), LocalePolicy::Fixed(String::from("C.UTF-8")));
+        assert_eq!(read("locale = false").unwrap(), LocalePolicy::Inherit);
+        assert!(read("locale = ''").is_err());
+        assert!(read("locale = 1").is_err());
+
+        let mut command = Command::new("apt");
+        LocalePolicy::default().apply(&mut command);
+        assert_eq!(command.get_envs().count(), 2);
+        assert!(command.get_envs().all(|(_, value)| value.is_some_and(|value| value == "C")));
+
+        assert!(is_parsed("search"));
+        assert!(!is_parsed("install"));
+    }
+}
@@ -0,0 +1,126 @@
+//! An advisory lock held by upm while it changes packages, so that two frontends running at the
+//! same time can't interleave installs and removals. The lock is an flock on `upm.lock` in the
+//! [runtime directory](../fn.runtime_dir.html), and is released when the
+//! [`ProcessLock`](struct.ProcessLock.html) is dropped or the process exits.
+//!
+//! The lock isn't reentrant. A process that already holds it must not try to take it again.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::prelude::*;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(unix)]
+use libc;
+use std::path::{Path, PathBuf};
+use std::process;
+use failure::Error;
+use super::{create_private_dir, runtime_dir};
+
+/// What to do when another process holds the lock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WaitPolicy {
+    /// Wait until the other process releases the lock
+    #[default]
+    Wait,
+    /// Fail with a [`Locked`](struct.Locked.html) error straight away
+    NoWait,
+}
+
+/// Another process holds the lock
+#[derive(Debug, Fail)]
+#[fail(display = "Another upm process holds the lock {:?}", path)]
+pub struct Locked {
+    pub path: PathBuf,
+}
+
+/// The held lock, released when dropped
+#[derive(Debug)]
+pub struct ProcessLock {
+    file: File,
+    path: PathBuf,
+}
+
+/// Get the path of the lock file, `upm.lock` in the runtime directory
+pub fn lock_path() -> PathBuf {
+    runtime_dir().join("upm.lock")
+}
+
+impl ProcessLock {
+    /// Take the lock at the default path
+    pub fn acquire(policy: WaitPolicy) -> Result<ProcessLock, Error> {
+        ProcessLock::acquire_at(lock_path(), policy)
+    }
+
+    /// Take the lock at a path, creating the file and a directory only the user can use for it if
+    /// they don't exist. The file is given the id of the process holding the lock, and is never
+    /// followed if it's a symlink.
+    pub fn acquire_at<P: AsRef<Path>>(path: P, policy: WaitPolicy) -> Result<ProcessLock, Error> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).truncate(false).write(true);
+        #[cfg(unix)]
+        options.custom_flags(libc::O_NOFOLLOW);
+        let mut file = options.open(path)?;
+        match file.try_lock() {
+            Ok(()) => {},
+            Err(TryLockError::WouldBlock) if policy == WaitPolicy::Wait => {
+                info!("Waiting for another upm process to release {:?}", path);
+                file.lock()?;
+            },
+            Err(TryLockError::WouldBlock) => return Err(Locked { path: path.to_path_buf() }.into()),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        //Padded so a shorter id overwrites a longer one without truncating the file
+        writeln!(file, "{:10}", process::id())?;
+        debug!("Took the process lock {:?}", path);
+        Ok(ProcessLock { file, path: path.to_path_buf() })
+    }
+
+    /// Get the path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ProcessLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            warn!("Couldn't release the process lock {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn exclusive() {
+        let path = env::temp_dir().join("upm-lock-test").join("upm.lock");
+        let held = ProcessLock::acquire_at(&path, WaitPolicy::NoWait).unwrap();
+        let error = ProcessLock::acquire_at(&path, WaitPolicy::NoWait).unwrap_err();
+        assert!(error.downcast_ref::<Locked>().is_some());
+        drop(held);
+        assert!(ProcessLock::acquire_at(&path, WaitPolicy::NoWait).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private() {
+        use std::fs;
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let dir = env::temp_dir().join(format!("upm-lock-private-{}", process::id()));
+        let path = dir.join("upm.lock");
+        drop(ProcessLock::acquire_at(&path, WaitPolicy::NoWait).unwrap());
+        assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        fs::remove_file(&path).unwrap();
+        symlink(dir.join("target"), &path).unwrap();
+        assert!(ProcessLock::acquire_at(&path, WaitPolicy::NoWait).is_err());
+        assert!(!dir.join("target").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+//! Manifests of installed packages, like a Brewfile for every package manager at once. A manifest
+//! can be checked into a dotfiles repository to record how a machine was set up. It's stored as
+//! TOML with a table for each package manager mapping package names to their versions:
+//!
+//! ```toml
+//! [managers.cargo]
+//! ripgrep = "0.7.1"
+//! fd-find = "7.0.0"
+//!
+//! [managers.pacman]
+//! neovim = "0.2.2-3"
+//! ```
+//!
+//! A version of `"*"` means any version.
+//!
+//! Applying a manifest installs the packages it lists that are missing, and optionally removes
+//! packages that it doesn't list. Versions are recorded but not enforced, since package managers
+//! can't generally install a particular version.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::ExitStatus;
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use super::{ManagerSet, MissingCommand};
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// The version given to packages whose version doesn't matter
+pub const ANY_VERSION: &str = "*";
+
+/// The packages installed through each package manager
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Package names and versions by package manager name
+    pub managers: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Manifest {
+    /// Create an empty manifest
+    pub fn new() -> Manifest {
+        Manifest::default()
+    }
+
+    /// Capture everything listed by the list_installed command of each package manager. Package
+    /// managers without the command or whose listing fails are left out.
+    pub fn export(managers: &ManagerSet) -> Manifest {
+        let mut manifest = Manifest::new();
+        let listings = run_all(managers.as_slice(), &ConcurrencyPolicy::default(), |manager| {
+            if manager.has_command("list_installed") {
+                Some(manager.list_installed())
+            } else {
+                None
+            }
+        });
+        for (manager, listing) in listings {
+            match listing {
+                Some(Ok(packages)) => {
+                    manifest.managers.entry(manager.name.clone()).or_default();
+                    for package in packages {
+                        let version = package.version.get_representation();
+                        manifest.add(&manager.name, &package.name, version);
+                    }
+                },
+                Some(Err(e)) => warn!("{}: couldn't list installed packages: {}", manager.name, e),
+                None => {},
+            }
+        }
+        manifest
+    }
+
+    /// Add a package to the manifest. An empty version is recorded as any version.
+    pub fn add(&mut self, manager: &str, package: &str, version: &str) {
+        let version = if version.is_empty() { ANY_VERSION } else { version };
+        self.managers.entry(manager.to_owned())
+            .or_default()
+            .insert(package.to_owned(), version.to_owned());
+    }
+
+    /// Get the packages listed for a package manager
+    pub fn packages(&self, manager: &str) -> Option<&BTreeMap<String, String>> {
+        self.managers.get(manager)
+    }
+
+    /// Read a manifest from a TOML value
+    pub fn from_toml(resource: &Value) -> Result<Manifest, Error> {
+        let mut manifest = Manifest::new();
+        let managers = match resource.get("managers") {
+            Some(managers) => match managers.as_table() {
+                Some(managers) => managers,
+                None => bail!("managers must be a table"),
+            },
+            None => return Ok(manifest),
+        };
+        for (manager, packages) in managers {
+            let packages = match packages.as_table() {
+                Some(packages) => packages,
+                None => bail!("The packages for {} must be a table", manager),
+            };
+            manifest.managers.entry(manager.clone()).or_default();
+            for (package, version) in packages {
+                match version.as_str() {
+                    Some(version) => manifest.add(manager, package, version),
+                    None => bail!("The version of {} for {} must be a string", package, manager),
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Read a manifest from a TOML file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Manifest, Error> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        Manifest::from_toml(&content.parse::<Value>()?)
+    }
+
+    /// Convert the manifest to a TOML value
+    pub fn to_toml(&self) -> Value {
+        let mut managers = Table::new();
+        for (manager, packages) in &self.managers {
+            let packages: Table = packages.iter()
+                .map(|(package, version)| (package.clone(), Value::String(version.clone())))
+                .collect();
+            managers.insert(manager.clone(), Value::Table(packages));
+        }
+        let mut resource = Table::new();
+        resource.insert(String::from("managers"), Value::Table(managers));
+        Value::Table(resource)
+    }
+}
+
+/// How to apply a manifest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncOptions {
+    /// Remove packages that aren't in the manifest from the package managers it lists
+    pub remove_extraneous: bool,
+    /// Work out the changes without making them
+    pub dry_run: bool,
+}
+
+/// Whether a package is installed or removed when applying a manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Install,
+    Remove,
+}
+
+/// A single package to install or remove
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub manager: String,
+    pub package: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ChangeKind::Install => write!(f, "+ {} ({})", self.package, self.manager),
+            ChangeKind::Remove => write!(f, "- {} ({})", self.package, self.manager),
+        }
+    }
+}
+
+/// What applying a manifest did
+#[derive(Debug)]
+pub struct SyncReport {
+    /// Every change needed to match the manifest, installs first
+    pub changes: Vec<Change>,
+    /// The outcome of each change in the same order. This is empty for a dry run.
+    pub results: Vec<Result<ExitStatus, Error>>,
+}
+
+impl SyncReport {
+    /// Did every change succeed?
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|result| match *result {
+            Ok(status) => status.success(),
+            Err(_) => false,
+        })
+    }
+}
+
+impl Manifest {
+    /// Work out the changes that make the installed packages match this manifest
+    pub fn diff(&self, installed: &Manifest, options: &SyncOptions) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let nothing = BTreeMap::new();
+        for (manager, packages) in &self.managers {
+            let present = installed.packages(manager).unwrap_or(&nothing);
+            for package in packages.keys() {
+                if !present.contains_key(package) {
+                    changes.push(Change {
+                        kind: ChangeKind::Install,
+                        manager: manager.clone(),
+                        package: package.clone(),
+                    });
+                }
+            }
+            if !options.remove_extraneous {
+                continue;
+            }
+            for package in present.keys() {
+                if !packages.contains_key(package) {
+                    changes.push(Change {
+                        kind: ChangeKind::Remove,
+                        manager: manager.clone(),
+                        package: package.clone(),
+                    });
+                }
+            }
+        }
+        changes.sort();
+        changes
+    }
+
+    /// Install the missing packages and, if asked, remove the extraneous ones. The changes are
+    /// made one at a time since package managers often prompt the user.
+    pub fn apply(&self, managers: &ManagerSet, options: &SyncOptions) -> SyncReport {
+        let installed = Manifest::export(managers);
+        let changes = self.diff(&installed, options);
+        let mut results = Vec::new();
+        if !options.dry_run {
+            for change in &changes {
+                let command = match change.kind {
+                    ChangeKind::Install => "install",
+                    ChangeKind::Remove => "remove",
+                };
+                results.push(match managers.get(&change.manager) {
+                    Some(manager) if manager.has_command(command) => manager.execute(command, &change.package),
+                    Some(_) => Err(MissingCommand { manager: change.manager.clone(), command: command.to_owned() }.into()),
+                    None => Err(format_err!("No package manager named {}", change.manager)),
+                });
+            }
+        }
+        SyncReport { changes, results }
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_toml())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, PackageManager, Scope};
+
+    #[test]
+    fn export() {
+        let set = ManagerSet::new(vec![
+            PackageManager {
+                name: String::from("listed"),
+                config_dir: PathBuf::from("./test-files/"),
+                ..Default::default()
+            }.with_command(CommandKind::Version, Scope::System, "true")
+                .with_command(CommandKind::ListInstalled, Scope::System, "./manifest/list.sh"),
+            PackageManager {
+                name: String::from("unlisted"),
+                ..Default::default()
+            }.with_command(CommandKind::Version, Scope::System, "true"),
+        ]);
+        let manifest = Manifest::export(&set);
+        let packages = manifest.packages("listed").unwrap();
+        assert_eq!(packages.get("ripgrep").map(|v| v.as_str()), Some("0.7.1"));
+        assert_eq!(packages.get("unversioned").map(|v| v.as_str()), Some(ANY_VERSION));
+        assert!(manifest.packages("unlisted").is_none());
+    }
+
+    #[test]
+    fn diff() {
+        let mut wanted = Manifest::new();
+        wanted.add("cargo", "ripgrep", "0.7.1");
+        wanted.add("cargo", "fd-find", "");
+        wanted.add("pip", "black", "");
+        let mut installed = Manifest::new();
+        installed.add("cargo", "ripgrep", "0.6.0");
+        installed.add("cargo", "exa", "0.8.0");
+        installed.add("pacman", "neovim", "0.2.2");
+        let changes: Vec<String> = wanted.diff(&installed, &SyncOptions::default())
+            .iter().map(|change| change.to_string()).collect();
+        assert_eq!(changes, vec!["+ fd-find (cargo)", "+ black (pip)"]);
+        let options = SyncOptions { remove_extraneous: true, dry_run: true };
+        let changes: Vec<String> = wanted.diff(&installed, &options)
+            .iter().map(|change| change.to_string()).collect();
+        assert_eq!(changes, vec!["+ fd-find (cargo)", "+ black (pip)", "- exa (cargo)"]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut manifest = Manifest::new();
+        manifest.add("cargo", "ripgrep", "0.7.1");
+        manifest.add("pacman", "neovim", "");
+        let reparsed = Manifest::from_toml(&manifest.to_string().parse::<Value>().unwrap()).unwrap();
+        assert_eq!(reparsed, manifest);
+        assert!(Manifest::from_toml(&"[managers]\ncargo = 1".parse::<Value>().unwrap()).is_err());
+    }
+}
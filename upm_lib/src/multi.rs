@@ -0,0 +1,187 @@
+//! The results of running an operation with several package managers, such as upgrading with
+//! every package manager at once. Some package managers can succeed while others fail, so a
+//! [`MultiResult`](struct.MultiResult.html) keeps what each one gave and says which ones failed
+//! and what [exit code](../exit/enum.ExitCode.html) the whole operation should have. Aggregated
+//! operations such as [`upgrade_managers`](../fn.upgrade_managers.html),
+//! [`search_all`](../fn.search_all.html), and
+//! [`ManagerSet::refresh_all`](../struct.ManagerSet.html#method.refresh_all) give one.
+
+use std::ops::Deref;
+use std::process::ExitStatus;
+use std::slice;
+use std::vec;
+use failure::Error;
+use super::{CleanReport, PackageManager};
+use exit::ExitCode;
+use refresh::RefreshReport;
+
+/// What an operation gave for one package manager, which can fail without being an error, such as
+/// an exit status
+pub trait Completion {
+    /// The exit code for how the command went for the package manager
+    fn exit_code(&self, manager: &PackageManager, command: &str) -> ExitCode;
+
+    /// Did the operation succeed for the package manager?
+    fn is_ok(&self, manager: &PackageManager, command: &str) -> bool {
+        self.exit_code(manager, command).is_success()
+    }
+}
+
+impl Completion for ExitStatus {
+    fn exit_code(&self, manager: &PackageManager, command: &str) -> ExitCode {
+        if self.success() {
+            ExitCode::Success
+        } else {
+            ExitCode::from_kind(manager.failure_kind(command, *self))
+        }
+    }
+}
+
+impl<T> Completion for Vec<T> {
+    fn exit_code(&self, _manager: &PackageManager, _command: &str) -> ExitCode {
+        ExitCode::Success
+    }
+}
+
+impl<T: Completion> Completion for Result<T, Error> {
+    fn exit_code(&self, manager: &PackageManager, command: &str) -> ExitCode {
+        match *self {
+            Ok(ref value) => value.exit_code(manager, command),
+            Err(ref e) => ExitCode::from_error(e),
+        }
+    }
+}
+
+impl Completion for RefreshReport {
+    fn exit_code(&self, manager: &PackageManager, command: &str) -> ExitCode {
+        self.result.exit_code(manager, command)
+    }
+}
+
+impl Completion for CleanReport {
+    fn exit_code(&self, manager: &PackageManager, _command: &str) -> ExitCode {
+        let clean_cache = self.clean_cache.as_ref().map(|result| result.exit_code(manager, "clean_cache"));
+        let autoremove = self.autoremove.as_ref().map(|result| result.exit_code(manager, "autoremove"));
+        ExitCode::combine(clean_cache.into_iter().chain(autoremove))
+    }
+}
+
+/// What a command gave for each package manager it was run with, in the same order as the
+/// managers. It derefs to a slice of the package managers alongside what they gave.
+#[derive(Debug)]
+pub struct MultiResult<'a, T> {
+    command: &'static str,
+    results: Vec<(&'a PackageManager, T)>,
+}
+
+impl<'a, T: Completion> MultiResult<'a, T> {
+    /// Collect what a command gave for each package manager
+    pub fn new(command: &'static str, results: Vec<(&'a PackageManager, T)>) -> MultiResult<'a, T> {
+        MultiResult { command, results }
+    }
+
+    /// The command that was run
+    pub fn command(&self) -> &'static str {
+        self.command
+    }
+
+    /// Did the command succeed for every package manager?
+    pub fn all_ok(&self) -> bool {
+        self.results.iter().all(|&(manager, ref result)| result.is_ok(manager, self.command))
+    }
+
+    /// Get the package managers the command succeeded for, with what they gave
+    pub fn succeeded<'b>(&'b self) -> Box<dyn Iterator<Item = (&'a PackageManager, &'b T)> + 'b> {
+        Box::new(self.results.iter()
+                 .filter(move |&&(manager, ref result)| result.is_ok(manager, self.command))
+                 .map(|&(manager, ref result)| (manager, result)))
+    }
+
+    /// Get the package managers the command failed for, with what they gave
+    pub fn failed<'b>(&'b self) -> Box<dyn Iterator<Item = (&'a PackageManager, &'b T)> + 'b> {
+        Box::new(self.results.iter()
+                 .filter(move |&&(manager, ref result)| !result.is_ok(manager, self.command))
+                 .map(|&(manager, ref result)| (manager, result)))
+    }
+
+    /// The exit code for each package manager, in order
+    pub fn exit_codes(&self) -> Vec<(&'a PackageManager, ExitCode)> {
+        self.results.iter().map(|&(manager, ref result)| (manager, result.exit_code(manager, self.command))).collect()
+    }
+
+    /// The exit code for the whole command: a partial failure if it failed for only some of the
+    /// package managers. See [`ExitCode::combine`](../exit/enum.ExitCode.html#method.combine).
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::combine(self.exit_codes().into_iter().map(|(_, code)| code))
+    }
+
+    /// Take what each package manager gave
+    pub fn into_vec(self) -> Vec<(&'a PackageManager, T)> {
+        self.results
+    }
+}
+
+impl<'a, T> Deref for MultiResult<'a, T> {
+    type Target = [(&'a PackageManager, T)];
+
+    fn deref(&self) -> &[(&'a PackageManager, T)] {
+        &self.results
+    }
+}
+
+impl<'a, T> IntoIterator for MultiResult<'a, T> {
+    type Item = (&'a PackageManager, T);
+    type IntoIter = vec::IntoIter<(&'a PackageManager, T)>;
+
+    fn into_iter(self) -> vec::IntoIter<(&'a PackageManager, T)> {
+        self.results.into_iter()
+    }
+}
+
+impl<'a, 'b, T> IntoIterator for &'b MultiResult<'a, T> {
+    type Item = &'b (&'a PackageManager, T);
+    type IntoIter = slice::Iter<'b, (&'a PackageManager, T)>;
+
+    fn into_iter(self) -> slice::Iter<'b, (&'a PackageManager, T)> {
+        self.results.iter()
+    }
+}
+
+impl<'a, 'b, T: Completion> From<&'b MultiResult<'a, T>> for ExitCode {
+    fn from(results: &'b MultiResult<'a, T>) -> ExitCode {
+        results.exit_code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, Scope, upgrade_managers};
+    use concurrency::ConcurrencyPolicy;
+    use pin::PinList;
+
+    #[test]
+    fn partial_failures() {
+        let manager = |name: &str, upgrade: &str| PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::UpgradeAll, Scope::System, upgrade);
+        let managers = vec![manager("apt", "true"), manager("cargo", "false"), manager("pip", "true")];
+        let upgraded = upgrade_managers(&managers, &ConcurrencyPolicy::default(), &PinList::new());
+        assert_eq!(upgraded.len(), 3);
+        assert!(!upgraded.all_ok());
+        let failed: Vec<&str> = upgraded.failed().map(|(manager, _)| manager.name.as_str()).collect();
+        assert_eq!(failed, vec!["cargo"]);
+        assert_eq!(upgraded.succeeded().count(), 2);
+        assert_eq!(upgraded.exit_code(), ExitCode::Partial);
+
+        let upgraded = upgrade_managers(&managers[..1], &ConcurrencyPolicy::default(), &PinList::new());
+        assert!(upgraded.all_ok());
+        assert_eq!(ExitCode::from(&upgraded), ExitCode::Success);
+        let searched: MultiResult<Result<Vec<()>, Error>> = MultiResult::new("search", vec![(&managers[0], Err(format_err!("offline")))]);
+        assert_eq!(searched.exit_code(), ExitCode::Failure);
+    }
+}
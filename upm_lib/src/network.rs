@@ -0,0 +1,166 @@
+//! Network settings shared by every package manager, so a proxy is configured once instead of
+//! separately for apt, pip, npm, and the rest. The settings are read from the `network` table of
+//! upm's [configuration file](../fn.config_path.html):
+//!
+//! ```toml
+//! [network]
+//! http_proxy = "http://proxy.example.com:3128"
+//! https_proxy = "http://proxy.example.com:3128"
+//! no_proxy = "localhost,.example.com"
+//!
+//! [network.env]
+//! PIP_INDEX_URL = "https://mirror.example.com/pypi/simple"
+//! ```
+//!
+//! Every command and hook a package manager runs gets the proxies in both their lowercase and
+//! uppercase variables, since package managers disagree on which one they read, along with any
+//! extra variables in `env`. Their `Debug` output [hides secrets](../redact/index.html) such as
+//! proxy passwords and tokens.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+use failure::Error;
+use toml::Value;
+use super::config_path;
+use redact::{redact_url, redact_var};
+
+/// Proxies and environment variables given to every package manager command
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    /// Hosts that are reached without the proxies, separated by commas
+    pub no_proxy: Option<String>,
+    /// Extra environment variables
+    pub env: BTreeMap<String, String>,
+}
+
+impl NetworkConfig {
+    /// Settings that change nothing
+    pub fn new() -> NetworkConfig {
+        NetworkConfig::default()
+    }
+
+    /// Read the settings from the `network` table of a TOML value
+    pub fn from_toml(table: &Value) -> Result<NetworkConfig, Error> {
+        let string = |key: &str| -> Result<Option<String>, Error> {
+            match table.get(key) {
+                Some(value) => match value.as_str() {
+                    Some(value) => Ok(Some(value.to_owned())),
+                    None => bail!("network {} must be a string", key),
+                },
+                None => Ok(None),
+            }
+        };
+        let mut env = BTreeMap::new();
+        if let Some(vars) = table.get("env") {
+            let vars = match vars.as_table() {
+                Some(vars) => vars,
+                None => bail!("network env must be a table"),
+            };
+            for (var, value) in vars {
+                match value.as_str() {
+                    Some(value) => env.insert(var.to_owned(), value.to_owned()),
+                    None => bail!("The value of {} in network env must be a string", var),
+                };
+            }
+        }
+        Ok(NetworkConfig {
+            http_proxy: string("http_proxy")?,
+            https_proxy: string("https_proxy")?,
+            no_proxy: string("no_proxy")?,
+            env,
+        })
+    }
+
+    /// Read the settings from the `network` table of a TOML file. A file that doesn't exist or
+    /// doesn't have the table gives settings that change nothing.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<NetworkConfig, Error> {
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(NetworkConfig::new()),
+            Err(e) => return Err(e.into()),
+        }
+        match content.parse::<Value>()?.get("network") {
+            Some(table) => NetworkConfig::from_toml(table),
+            None => Ok(NetworkConfig::new()),
+        }
+    }
+
+    /// Read the settings from upm's configuration file
+    pub fn load_default() -> Result<NetworkConfig, Error> {
+        match config_path() {
+            Some(path) => NetworkConfig::from_file(path),
+            None => Ok(NetworkConfig::new()),
+        }
+    }
+
+    /// Do the settings change nothing?
+    pub fn is_empty(&self) -> bool {
+        self.vars().is_empty()
+    }
+
+    /// The environment variables the settings give commands
+    pub fn vars(&self) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+        let proxies = [("http_proxy", &self.http_proxy), ("https_proxy", &self.https_proxy), ("no_proxy", &self.no_proxy)];
+        for &(var, value) in &proxies {
+            if let Some(ref value) = *value {
+                vars.push((var.to_owned(), value.clone()));
+                vars.push((var.to_uppercase(), value.clone()));
+            }
+        }
+        vars.extend(self.env.iter().map(|(var, value)| (var.clone(), value.clone())));
+        vars
+    }
+
+    /// Give a command the environment variables
+    pub fn apply(&self, command: &mut Command) {
+        for (var, value) in self.vars() {
+            command.env(var, value);
+        }
+    }
+}
+
+impl fmt::Debug for NetworkConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let env: BTreeMap<&str, String> = self.env.iter()
+            .map(|(var, value)| (var.as_str(), redact_var(var, value)))
+            .collect();
+        f.debug_struct("NetworkConfig")
+            .field("http_proxy", &self.http_proxy.as_ref().map(|proxy| redact_url(proxy)))
+            .field("https_proxy", &self.https_proxy.as_ref().map(|proxy| redact_url(proxy)))
+            .field("no_proxy", &self.no_proxy)
+            .field("env", &env)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_vars() {
+        let config = "[network]\nhttps_proxy = 'http://proxy:3128'\nno_proxy = 'localhost'\n[network.env]\nPIP_INDEX_URL = 'https://mirror'\n";
+        let path = ::std::env::temp_dir().join(format!("upm-network-{}.toml", ::std::process::id()));
+        File::create(&path).unwrap().write_all(config.as_bytes()).unwrap();
+        let network = NetworkConfig::from_file(&path).unwrap();
+        assert_eq!(network.vars(), vec![
+            (String::from("https_proxy"), String::from("http://proxy:3128")),
+            (String::from("HTTPS_PROXY"), String::from("http://proxy:3128")),
+            (String::from("no_proxy"), String::from("localhost")),
+            (String::from("NO_PROXY"), String::from("localhost")),
+            (String::from("PIP_INDEX_URL"), String::from("https://mirror")),
+        ]);
+        ::std::fs::remove_file(&path).unwrap();
+        assert!(NetworkConfig::from_file(&path).unwrap().is_empty());
+        assert!(NetworkConfig::from_toml(&"http_proxy = 3128\n".parse::<Value>().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Merging package manager configurations across configuration directories. When a package
+//! manager is configured in several directories, the configurations are merged field by field with
+//! the directory given first taking precedence. Tables such as `[aliases]` or `[noninteractive]`
+//! are merged key by key as well, so a user configuration can override just `install` and inherit
+//! everything else from the system configuration.
+//!
+//! A configuration can also build on another package manager with `inherit = "base-name"`. The
+//! fully merged configuration of the base, or its [built-in template](../detect/index.html) if it
+//! isn't configured, is used for every field the configuration doesn't set.
+//!
+//! Scripts starting with `./` are run relative to the directory of the file that set them.
+//!
+//! Files that the [trust policy](../trust/index.html) refuses are left out before merging.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{File, read_dir};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use super::{PackageManager, ManagerSpecifier, detect};
+use command;
+use settings;
+use trust::TrustPolicy;
+
+/// The key naming the package manager a configuration builds on
+pub const INHERIT_KEY: &str = "inherit";
+
+//A configuration file that was read along with the directory it was in
+struct Layer {
+    table: Table,
+    config_dir: PathBuf,
+}
+
+/// Read the package manager configurations in the directories, merging configurations with the
+/// same name and resolving `inherit` keys. Directories given first take precedence.
+pub fn read_layered<P: AsRef<Path>>(directories: Vec<P>, names: &ManagerSpecifier) -> Vec<PackageManager> {
+    read_layered_trusted(directories, names, &TrustPolicy::new())
+}
+
+/// Read the package manager configurations in the directories like
+/// [`read_layered`](fn.read_layered.html), leaving out the files the trust policy refuses
+pub fn read_layered_trusted<P: AsRef<Path>>(directories: Vec<P>, names: &ManagerSpecifier, policy: &TrustPolicy) -> Vec<PackageManager> {
+    let mut result: Vec<PackageManager> = Vec::new();
+    for (name, manager) in merge_layered(directories, names, policy) {
+        match manager {
+            Ok(manager) => result.push(manager),
+            Err(e) => warn!("Skipping package manager {}: {}", name, e),
+        }
+    }
+    result.sort_by(|a, b| a.cmp_priority(b));
+    result
+}
+
+/// Merge the package manager configurations in the directories like
+/// [`read_layered_trusted`](fn.read_layered_trusted.html), giving the error for each package
+/// manager that couldn't be merged instead of skipping it. The results are sorted by name.
+pub fn merge_layered<P: AsRef<Path>>(directories: Vec<P>, names: &ManagerSpecifier, policy: &TrustPolicy) -> Vec<(String, Result<PackageManager, Error>)> {
+    //Every configuration is read since one that's left out may still be inherited from
+    let mut layers: HashMap<String, Vec<Layer>> = HashMap::new();
+    for dir in directories {
+        for (name, layer) in read_layers(dir.as_ref(), policy) {
+            layers.entry(name).or_default().push(layer);
+        }
+    }
+    let mut result: Vec<(String, Result<PackageManager, Error>)> = layers.keys()
+        .filter(|name| names.allows(name))
+        .map(|name| {
+            let manager = resolve(name, &layers, &mut Vec::new())
+                .and_then(|layer| PackageManager::from_toml(name, &Value::Table(layer.table), layer.config_dir));
+            (name.clone(), manager)
+        })
+        .collect();
+    result.sort_by(|a, b| a.0.cmp(&b.0));
+    result
+}
+
+/// Merge the overlay into the base. Values in the overlay replace those in the base, except for
+/// tables which are merged recursively.
+pub fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(&mut Value::Table(ref mut base)), Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            },
+        }
+    }
+}
+
+//Read every configuration file in a directory without parsing it into a package manager
+fn read_layers(dir: &Path, policy: &TrustPolicy) -> Vec<(String, Layer)> {
+    let mut result = Vec::new();
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return result,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !settings::is_definition(&path) {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        if let Err(e) = policy.check(&name, &path) {
+            warn!("Refusing package manager config {:?}: {}", path, e);
+            continue;
+        }
+        match read_table(&path) {
+            Ok(table) => result.push((name, Layer { table, config_dir: dir.to_path_buf() })),
+            Err(e) => warn!("Skipping package manager config {:?}: {}", path, e),
+        }
+    }
+    result
+}
+
+fn read_table(path: &Path) -> Result<Table, Error> {
+    debug!("Reading package manager config {:?}", path);
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    match content.parse::<Value>()? {
+        Value::Table(table) => Ok(table),
+        _ => bail!("The configuration isn't a table"),
+    }
+}
+
+//Merge every layer of a package manager along with what it inherits. The chain holds the names
+//being resolved so that inheritance cycles are caught.
+fn resolve(name: &str, layers: &HashMap<String, Vec<Layer>>, chain: &mut Vec<String>) -> Result<Layer, Error> {
+    if chain.iter().any(|seen| seen == name) {
+        bail!("Package manager {} inherits from itself through {}", name, chain.join(" -> "));
+    }
+    chain.push(name.to_owned());
+    let own = match layers.get(name) {
+        Some(own) => own,
+        None => {
+            chain.pop();
+            return builtin(name);
+        },
+    };
+    let config_dir = own[0].config_dir.clone();
+    //Lower precedence layers are merged first so the higher ones override them
+    let mut table = Table::new();
+    for layer in own.iter().rev() {
+        merge(&mut table, relocate(&layer.table, &layer.config_dir, &config_dir));
+    }
+    if let Some(base) = table.remove(INHERIT_KEY) {
+        let base = match base.as_str() {
+            Some(base) => base.to_owned(),
+            None => bail!("{} must be the name of a package manager", INHERIT_KEY),
+        };
+        let mut inherited = resolve(&base, layers, chain)?;
+        inherited.table = relocate(&inherited.table, &inherited.config_dir, &config_dir);
+        merge(&mut inherited.table, table);
+        table = inherited.table;
+    }
+    chain.pop();
+    Ok(Layer { table, config_dir })
+}
+
+//Use the built-in template of a package manager that isn't configured
+fn builtin(name: &str) -> Result<Layer, Error> {
+    match detect::template(name) {
+        Some(template) => match template.parse::<Value>()? {
+            Value::Table(table) => Ok(Layer { table, config_dir: PathBuf::new() }),
+            _ => bail!("The built-in template for {} isn't a table", name),
+        },
+        None => bail!("Package manager {} isn't configured and has no built-in template", name),
+    }
+}
+
+//Rewrite scripts starting with ./ so that they still refer to the directory they were configured
+//in once they are run from another one
+fn relocate(table: &Table, from: &Path, to: &Path) -> Table {
+    if from == to {
+        return table.clone();
+    }
+    //A relative path would be joined to the directory the commands are run from again
+    let from: PathBuf = match env::current_dir() {
+        Ok(current) => current.join(from).components().collect(),
+        Err(_) => from.to_path_buf(),
+    };
+    let from = from.as_path();
+    table.iter().map(|(key, value)| {
+        let value = match *value {
+            Value::String(ref command) if command::parse_key(key).is_some() => Value::String(relocate_command(command, from)),
+            Value::Table(ref commands) if key == "hooks" || key == "extras" => Value::Table(commands.iter()
+                .map(|(hook, value)| match *value {
+                    Value::String(ref command) => (hook.clone(), Value::String(relocate_command(command, from))),
+                    ref other => (hook.clone(), other.clone()),
+                })
+                .collect()),
+            ref other => other.clone(),
+        };
+        (key.clone(), value)
+    }).collect()
+}
+
+fn relocate_command(command: &str, from: &Path) -> String {
+    match command.strip_prefix("./") {
+        Some(relative) => from.join(relative).to_string_lossy().into_owned(),
+        None => command.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use super::super::{CommandKind, Scope};
+
+    fn overlaid() -> Vec<PackageManager> {
+        read_layered(vec!["./test-files/overlay/user", "./test-files/overlay/system"], &ManagerSpecifier::Empty)
+    }
+
+    #[test]
+    fn field_level_merging() {
+        let managers = overlaid();
+        let pacman = managers.iter().find(|manager| manager.name == "pacman").unwrap();
+        assert_eq!(pacman.template(CommandKind::Install, Scope::System).map(|t| t.as_str()), Some("sudo pacman -S"));
+        assert_eq!(pacman.template(CommandKind::Remove, Scope::System).map(|t| t.as_str()), Some("pacman -Rs"));
+        assert_eq!(pacman.noninteractive.get("install"), Some(&String::from("--noconfirm --needed")));
+        assert_eq!(pacman.noninteractive.get("remove"), Some(&String::from("--noconfirm")));
+        assert_eq!(pacman.config_dir, PathBuf::from("./test-files/overlay/user"));
+    }
+
+    #[test]
+    fn relative_directories() {
+        let managers = overlaid();
+        let pacman = managers.iter().find(|manager| manager.name == "pacman").unwrap();
+        let script = env::current_dir().unwrap().join("test-files/overlay/system/version.sh");
+        assert_eq!(pacman.template(CommandKind::Version, Scope::System).unwrap().as_str(), script.to_str().unwrap());
+        assert_eq!(pacman.extras["mirrors"].as_str(), script.to_str().unwrap());
+        assert!(pacman.exists());
+    }
+
+    #[test]
+    fn inheritance() {
+        let managers = overlaid();
+        let yay = managers.iter().find(|manager| manager.name == "yay").unwrap();
+        assert_eq!(yay.template(CommandKind::Install, Scope::System).map(|t| t.as_str()), Some("yay -S"));
+        assert_eq!(yay.template(CommandKind::Remove, Scope::System).map(|t| t.as_str()), Some("pacman -Rs"));
+        assert_eq!(yay.priority, 5);
+        //Cycles are skipped rather than looping forever
+        assert!(managers.iter().all(|manager| manager.name != "loop"));
+        //Excluded package managers can still be inherited from
+        let mut excluded = HashSet::new();
+        excluded.insert(String::from("pacman"));
+        let managers = read_layered(vec!["./test-files/overlay/user", "./test-files/overlay/system"],
+                                    &ManagerSpecifier::Excludes(excluded));
+        assert!(managers.iter().any(|manager| manager.name == "yay"));
+        assert!(managers.iter().all(|manager| manager.name != "pacman"));
+    }
+
+    #[test]
+    fn merging_tables() {
+        let mut base: Table = "a = 1\n[t]\nx = 1\ny = 2".parse::<Value>().unwrap().as_table().unwrap().clone();
+        let overlay: Table = "b = 2\n[t]\ny = 3".parse::<Value>().unwrap().as_table().unwrap().clone();
+        merge(&mut base, overlay);
+        assert_eq!(base["a"].as_integer(), Some(1));
+        assert_eq!(base["b"].as_integer(), Some(2));
+        assert_eq!(base["t"]["x"].as_integer(), Some(1));
+        assert_eq!(base["t"]["y"].as_integer(), Some(3));
+    }
+}
@@ -0,0 +1,291 @@
+//! Holding packages back from upgrades. A [`PinList`](struct.PinList.html) records packages that
+//! [`upgrade_managers`](../fn.upgrade_managers.html) must leave alone, and is kept in
+//! `pins.toml` in the [data directory](../fn.data_dir.html):
+//!
+//! ```toml
+//! [[pin]]
+//! package = "ripgrep"
+//! manager = "cargo"
+//! version = "0.7.1"
+//! ```
+//!
+//! The version is the one the package was held at and is optional.
+//!
+//! A pin can also hold a package to a [range of versions](../version/struct.VersionReq.html)
+//! instead of freezing it, letting it be upgraded as long as the newest version its package
+//! manager offers is in the range:
+//!
+//! ```toml
+//! [[pin]]
+//! package = "node"
+//! manager = "brew"
+//! req = "^20"
+//! ```
+//!
+//! Versions outside the range are treated as unavailable, so the package is left alone until
+//! the range is changed.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use super::{Package, Version, VersionReq, data_dir};
+
+/// A package held back from upgrades
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub package: String,
+    pub manager: String,
+    pub version: Option<String>,
+    /// The range the package may be upgraded within. Packages without a range are frozen.
+    pub req: Option<VersionReq>,
+}
+
+impl Pin {
+    /// Does this pin hold the package?
+    pub fn holds(&self, package: &Package) -> bool {
+        self.package == package.name && self.manager == package.owner.name
+    }
+
+    /// May the package be upgraded to the version?
+    pub fn allows(&self, version: &Version) -> bool {
+        self.req.as_ref().is_some_and(|req| req.matches(version))
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.package)?;
+        if let Some(ref version) = self.version {
+            write!(f, " {}", version)?;
+        }
+        if let Some(ref req) = self.req {
+            write!(f, " {}", req)?;
+        }
+        write!(f, " ({})", self.manager)
+    }
+}
+
+/// The pinned packages, optionally backed by a file
+#[derive(Debug, Clone, Default)]
+pub struct PinList {
+    pins: Vec<Pin>,
+    path: Option<PathBuf>,
+}
+
+/// Get the path of the default pin list, `pins.toml` in the data directory
+pub fn pins_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("pins.toml"))
+}
+
+impl PinList {
+    /// Create an empty pin list that isn't saved anywhere
+    pub fn new() -> PinList {
+        PinList::default()
+    }
+
+    /// Load the pin list from a file. A file that doesn't exist gives an empty list that will be
+    /// saved to that file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PinList, Error> {
+        let path = path.as_ref();
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => return Err(e.into()),
+        }
+        let mut list = PinList::from_toml(&content.parse::<Value>()?)?;
+        list.path = Some(path.to_path_buf());
+        Ok(list)
+    }
+
+    /// Load the default pin list
+    pub fn load_default() -> Result<PinList, Error> {
+        match pins_path() {
+            Some(path) => PinList::load(path),
+            None => bail!("Couldn't find a data directory for the pin list"),
+        }
+    }
+
+    /// Read the pins from a TOML value
+    pub fn from_toml(resource: &Value) -> Result<PinList, Error> {
+        let mut list = PinList::new();
+        let pins = match resource.get("pin") {
+            Some(pins) => match pins.as_array() {
+                Some(pins) => pins,
+                None => bail!("pin must be an array of tables"),
+            },
+            None => return Ok(list),
+        };
+        for pin in pins {
+            let field = |name: &str| pin.get(name).and_then(|value| value.as_str()).map(String::from);
+            let (package, manager) = match (field("package"), field("manager")) {
+                (Some(package), Some(manager)) => (package, manager),
+                _ => bail!("Every pin needs a package and a manager"),
+            };
+            let req = match field("req") {
+                Some(req) => Some(req.parse::<VersionReq>()?),
+                None => None,
+            };
+            list.pins.push(Pin { package, manager, version: field("version"), req });
+        }
+        Ok(list)
+    }
+
+    /// Convert the pins to a TOML value
+    pub fn to_toml(&self) -> Value {
+        let pins = self.pins.iter().map(|pin| {
+            let mut table = Table::new();
+            table.insert(String::from("package"), Value::String(pin.package.clone()));
+            table.insert(String::from("manager"), Value::String(pin.manager.clone()));
+            if let Some(ref version) = pin.version {
+                table.insert(String::from("version"), Value::String(version.clone()));
+            }
+            if let Some(ref req) = pin.req {
+                table.insert(String::from("req"), Value::String(req.to_string()));
+            }
+            Value::Table(table)
+        }).collect();
+        let mut resource = Table::new();
+        resource.insert(String::from("pin"), Value::Array(pins));
+        Value::Table(resource)
+    }
+
+    /// Write the pin list back to the file it was loaded from
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => bail!("The pin list wasn't loaded from a file"),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(path)?.write_all(self.to_toml().to_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// Hold a package back from upgrades, replacing any pin it already had
+    pub fn pin(&mut self, pin: Pin) {
+        self.unpin(&pin.package, Some(&pin.manager));
+        self.pins.push(pin);
+    }
+
+    /// Let a package be upgraded again. Without a manager the package is unpinned for every
+    /// package manager. Gives whether anything was unpinned.
+    pub fn unpin(&mut self, package: &str, manager: Option<&str>) -> bool {
+        let before = self.pins.len();
+        self.pins.retain(|pin| match manager {
+            Some(manager) => pin.package != package || pin.manager != manager,
+            None => pin.package != package,
+        });
+        self.pins.len() != before
+    }
+
+    /// Get every pin
+    pub fn pins(&self) -> &[Pin] {
+        &self.pins
+    }
+
+    /// Get the pins for a package manager
+    pub fn pins_for<'a>(&'a self, manager: &'a str) -> Box<dyn Iterator<Item = &'a Pin> + 'a> {
+        Box::new(self.pins.iter().filter(move |pin| pin.manager == manager))
+    }
+
+    /// Is the package held back from upgrades, either frozen or to a range?
+    pub fn is_pinned(&self, package: &Package) -> bool {
+        self.pins.iter().any(|pin| pin.holds(package))
+    }
+
+    /// Get the pin that holds the package, if it's pinned
+    pub fn pin_for(&self, package: &Package) -> Option<&Pin> {
+        self.pins.iter().find(|pin| pin.holds(package))
+    }
+
+    /// May the package be upgraded to the version? Packages that aren't pinned may always be
+    /// upgraded, and frozen packages never.
+    pub fn allows(&self, package: &Package, version: &Version) -> bool {
+        self.pin_for(package).is_none_or(|pin| pin.allows(version))
+    }
+}
+
+/// Hold a package back in the default pin list
+pub fn pin(pin: Pin) -> Result<(), Error> {
+    let mut list = PinList::load_default()?;
+    list.pin(pin);
+    list.save()
+}
+
+/// Let a package in the default pin list be upgraded again, giving whether it was pinned
+pub fn unpin(package: &str, manager: Option<&str>) -> Result<bool, Error> {
+    let mut list = PinList::load_default()?;
+    let unpinned = list.unpin(package, manager);
+    list.save()?;
+    Ok(unpinned)
+}
+
+/// Get the pins in the default pin list
+pub fn pins() -> Result<Vec<Pin>, Error> {
+    Ok(PinList::load_default()?.pins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn pin(package: &str, manager: &str) -> Pin {
+        Pin {
+            package: String::from(package),
+            manager: String::from(manager),
+            version: None,
+            req: None,
+        }
+    }
+
+    #[test]
+    fn pinning() {
+        let mut list = PinList::new();
+        list.pin(pin("ripgrep", "cargo"));
+        list.pin(pin("ripgrep", "pacman"));
+        list.pin(pin("neovim", "pacman"));
+        assert_eq!(list.pins_for("pacman").count(), 2);
+        assert!(list.unpin("ripgrep", Some("cargo")));
+        assert!(!list.unpin("ripgrep", Some("cargo")));
+        assert!(list.unpin("neovim", None));
+        assert_eq!(list.pins(), &[pin("ripgrep", "pacman")][..]);
+    }
+
+    #[test]
+    fn ranges() {
+        let mut list = PinList::new();
+        list.pin(Pin { req: Some("^20".parse().unwrap()), ..pin("node", "brew") });
+        list.pin(pin("python", "brew"));
+        let package = |name: &str| Package {
+            name: String::from(name),
+            owner: ::PackageManager { name: String::from("brew"), ..Default::default() },
+            ..Default::default()
+        };
+        assert!(list.allows(&package("node"), &Version::from("20.11.1")));
+        assert!(!list.allows(&package("node"), &Version::from("22.1.0")));
+        assert!(!list.allows(&package("python"), &Version::from("3.12.1")));
+        assert!(list.allows(&package("ruby"), &Version::from("3.3.0")));
+        assert_eq!(list.pins()[0].to_string(), "node ^20 (brew)");
+    }
+
+    #[test]
+    fn saving() {
+        let path = env::temp_dir().join("upm-pin-test").join("pins.toml");
+        let _ = fs::remove_file(&path);
+        let mut list = PinList::load(&path).unwrap();
+        assert!(list.pins().is_empty());
+        list.pin(Pin { version: Some(String::from("0.7.1")), ..pin("ripgrep", "cargo") });
+        list.pin(Pin { req: Some("~1.2".parse().unwrap()), ..pin("neovim", "pacman") });
+        list.save().unwrap();
+        let reloaded = PinList::load(&path).unwrap();
+        assert_eq!(reloaded.pins(), list.pins());
+    }
+}
@@ -0,0 +1,338 @@
+//! Rules an organization sets for everyone using upm on a machine, kept in
+//! `/etc/upm/policy.toml` where only administrators can change them:
+//!
+//! ```toml
+//! [[block]]
+//! package = "telnet*"
+//! reason = "Use ssh instead"
+//!
+//! [[block]]
+//! package = "left-pad"
+//! manager = "npm"
+//!
+//! [[mandatory]]
+//! package = "osquery"
+//! manager = "apt"
+//! req = "^5"
+//!
+//! [managers]
+//! users = ["pip", "cargo", "npm"]
+//! admin_groups = ["wheel", "sudo"]
+//! ```
+//!
+//! Blocked packages, given as globs and optionally for one package manager, can't be installed,
+//! and the reason is shown to whoever tries. Mandatory packages can't be removed, and a `req`
+//! holds them to a [range of versions](../version/struct.VersionReq.html) like a
+//! [pin](../pin/index.html). When `users` is given, users outside the admin groups may only use
+//! those package managers through upm. Root is always an administrator.
+//!
+//! Package managers given the policy with
+//! [`set_policy`](../struct.PackageManager.html#method.set_policy) refuse to run install commands
+//! with blocked packages and remove commands with mandatory ones, and install mandatory packages
+//! again if autoremove removes them.
+
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use failure::Error;
+use regex::Regex;
+use toml::Value;
+use super::{PackageManager, VersionReq};
+use pin::{Pin, PinList};
+use rules::glob_regex;
+
+/// The policy file
+pub const POLICY_PATH: &str = "/etc/upm/policy.toml";
+
+/// The groups whose members are administrators when the policy doesn't say
+pub const DEFAULT_ADMIN_GROUPS: &[&str] = &["root", "wheel", "sudo", "admin"];
+
+/// A package that can't be installed
+#[derive(Debug, Clone)]
+pub struct BlockedPackage {
+    /// The glob matching the names of the packages, as it was written
+    pub pattern: String,
+    /// The package manager the package is blocked for, or None for every one
+    pub manager: Option<String>,
+    /// Why the package is blocked, shown to the user
+    pub reason: Option<String>,
+    matcher: Regex,
+}
+
+impl BlockedPackage {
+    /// Block packages matching a glob
+    pub fn new(pattern: &str, manager: Option<&str>, reason: Option<&str>) -> Result<BlockedPackage, Error> {
+        Ok(BlockedPackage {
+            pattern: pattern.to_owned(),
+            manager: manager.map(str::to_owned),
+            reason: reason.map(str::to_owned),
+            matcher: glob_regex(pattern)?,
+        })
+    }
+
+    /// Does this block a package from a package manager?
+    pub fn blocks(&self, package: &str, manager: &str) -> bool {
+        self.manager.as_ref().is_none_or(|blocked| blocked == manager) && self.matcher.is_match(package)
+    }
+}
+
+/// A package that must stay installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MandatoryPackage {
+    pub package: String,
+    pub manager: String,
+    /// The range of versions the package is held to, if it is
+    pub req: Option<VersionReq>,
+}
+
+/// The error returned when the policy blocks a package from being installed
+#[derive(Debug, Fail)]
+pub struct Blocked {
+    pub package: String,
+    pub manager: String,
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The policy blocks installing {} with {}", self.package, self.manager)?;
+        if let Some(ref reason) = self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when the policy requires a package that was going to be removed
+#[derive(Debug, Fail)]
+#[fail(display = "The policy requires {} to stay installed with {}", package, manager)]
+pub struct Mandatory {
+    pub package: String,
+    pub manager: String,
+}
+
+/// The rules of the policy file
+#[derive(Debug, Clone, Default)]
+pub struct OrgPolicy {
+    pub blocked: Vec<BlockedPackage>,
+    pub mandatory: Vec<MandatoryPackage>,
+    /// The package managers users outside the admin groups may use, or None if they may use any
+    pub user_managers: Option<Vec<String>>,
+    /// The groups whose members are administrators
+    pub admin_groups: Vec<String>,
+}
+
+impl OrgPolicy {
+    /// A policy without any rules
+    pub fn new() -> OrgPolicy {
+        OrgPolicy {
+            admin_groups: DEFAULT_ADMIN_GROUPS.iter().map(|&group| group.to_owned()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Read the policy from a TOML value
+    pub fn from_toml(resource: &Value) -> Result<OrgPolicy, Error> {
+        let tables = |key: &str| -> Result<Vec<&Value>, Error> {
+            match resource.get(key) {
+                Some(value) => match value.as_array() {
+                    Some(tables) => Ok(tables.iter().collect()),
+                    None => bail!("{} must be an array of tables", key),
+                },
+                None => Ok(Vec::new()),
+            }
+        };
+        let field = |table: &Value, name: &str| table.get(name).and_then(Value::as_str).map(str::to_owned);
+        let mut policy = OrgPolicy::new();
+        for block in tables("block")? {
+            match field(block, "package") {
+                Some(package) => policy.blocked.push(BlockedPackage::new(&package, field(block, "manager").as_deref(),
+                                                                         field(block, "reason").as_deref())?),
+                None => bail!("Every blocked package needs a package"),
+            }
+        }
+        for mandatory in tables("mandatory")? {
+            let (package, manager) = match (field(mandatory, "package"), field(mandatory, "manager")) {
+                (Some(package), Some(manager)) => (package, manager),
+                _ => bail!("Every mandatory package needs a package and a manager"),
+            };
+            let req = match field(mandatory, "req") {
+                Some(req) => Some(req.parse::<VersionReq>()?),
+                None => None,
+            };
+            policy.mandatory.push(MandatoryPackage { package, manager, req });
+        }
+        if let Some(managers) = resource.get("managers") {
+            let strings = |key: &str| -> Result<Option<Vec<String>>, Error> {
+                match managers.get(key) {
+                    Some(value) => match value.as_array() {
+                        Some(values) => values.iter()
+                            .map(|value| match value.as_str() {
+                                Some(value) => Ok(value.to_owned()),
+                                None => bail!("managers.{} must be an array of strings", key),
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                            .map(Some),
+                        None => bail!("managers.{} must be an array of strings", key),
+                    },
+                    None => Ok(None),
+                }
+            };
+            policy.user_managers = strings("users")?;
+            if let Some(groups) = strings("admin_groups")? {
+                policy.admin_groups = groups;
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Read the policy from a file. A file that doesn't exist gives a policy without any rules.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<OrgPolicy, Error> {
+        let path = path.as_ref();
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(OrgPolicy::new()),
+            Err(e) => return Err(e.into()),
+        }
+        OrgPolicy::from_toml(&content.parse::<Value>()?)
+            .map_err(|e| format_err!("{:?}: {}", path, e))
+    }
+
+    /// Read the policy file
+    pub fn load_default() -> Result<OrgPolicy, Error> {
+        OrgPolicy::load(POLICY_PATH)
+    }
+
+    /// Get the block that stops a package from being installed with a package manager, if any
+    pub fn blocked(&self, package: &str, manager: &str) -> Option<&BlockedPackage> {
+        self.blocked.iter().find(|block| block.blocks(package, manager))
+    }
+
+    /// Check that a package may be installed with a package manager, giving a
+    /// [`Blocked`](struct.Blocked.html) error if it may not
+    pub fn check_install(&self, package: &str, manager: &str) -> Result<(), Error> {
+        match self.blocked(package, manager) {
+            Some(block) => Err(Blocked { package: package.to_owned(), manager: manager.to_owned(), reason: block.reason.clone() }.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that a package may be removed from a package manager, giving a
+    /// [`Mandatory`](struct.Mandatory.html) error if it may not
+    pub fn check_remove(&self, package: &str, manager: &str) -> Result<(), Error> {
+        if self.mandatory.iter().any(|mandatory| mandatory.package == package && mandatory.manager == manager) {
+            return Err(Mandatory { package: package.to_owned(), manager: manager.to_owned() }.into());
+        }
+        Ok(())
+    }
+
+    /// Add pins holding the mandatory packages with a range to a pin list
+    pub fn apply_pins(&self, pins: &mut PinList) {
+        for mandatory in self.mandatory.iter().filter(|mandatory| mandatory.req.is_some()) {
+            pins.pin(Pin {
+                package: mandatory.package.clone(),
+                manager: mandatory.manager.clone(),
+                version: None,
+                req: mandatory.req.clone(),
+            });
+        }
+    }
+
+    /// Is a user in these groups an administrator?
+    pub fn is_admin(&self, groups: &[String]) -> bool {
+        groups.iter().any(|group| group == "root" || self.admin_groups.contains(group))
+    }
+
+    /// May a user in these groups use a package manager?
+    pub fn allows_manager(&self, manager: &str, groups: &[String]) -> bool {
+        match self.user_managers {
+            Some(ref managers) => managers.iter().any(|allowed| allowed == manager) || self.is_admin(groups),
+            None => true,
+        }
+    }
+
+    /// Remove the package managers a user in these groups may not use, giving their names
+    pub fn restrict(&self, managers: &mut Vec<PackageManager>, groups: &[String]) -> Vec<String> {
+        let mut removed = Vec::new();
+        managers.retain(|manager| {
+            let allowed = self.allows_manager(&manager.name, groups);
+            if !allowed {
+                removed.push(manager.name.clone());
+            }
+            allowed
+        });
+        removed
+    }
+}
+
+/// Get the groups of the user running upm, from `id -Gn`. Gives no groups if they can't be found,
+/// so the user isn't treated as an administrator.
+pub fn current_groups() -> Vec<String> {
+    match Command::new("id").arg("-Gn").stdin(Stdio::null()).stderr(Stdio::null()).output() {
+        Ok(ref output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).split_whitespace().map(str::to_owned).collect()
+        },
+        Ok(output) => {
+            warn!("Couldn't find the groups of the current user, id exited with {}", output.status);
+            Vec::new()
+        },
+        Err(e) => {
+            warn!("Couldn't find the groups of the current user: {}", e);
+            Vec::new()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Package, Version};
+
+    const POLICY: &str = "\
+[[block]]
+package = 'telnet*'
+reason = 'Use ssh instead'
+
+[[block]]
+package = 'left-pad'
+manager = 'npm'
+
+[[mandatory]]
+package = 'osquery'
+manager = 'apt'
+req = '^5'
+
+[managers]
+users = ['pip']
+admin_groups = ['wheel']
+";
+
+    #[test]
+    fn org_policies() {
+        let policy = OrgPolicy::from_toml(&POLICY.parse::<Value>().unwrap()).unwrap();
+        assert_eq!(policy.check_install("telnetd", "apt").unwrap_err().to_string(),
+                   "The policy blocks installing telnetd with apt: Use ssh instead");
+        assert!(policy.check_install("left-pad", "npm").is_err());
+        assert!(policy.check_install("left-pad", "cargo").is_ok());
+
+        let apt = PackageManager { name: String::from("apt"), ..Default::default() };
+        let osquery = Package { name: String::from("osquery"), owner: apt.clone(), ..Default::default() };
+        assert!(policy.check_remove("osquery", "apt").unwrap_err().downcast_ref::<Mandatory>().is_some());
+        let mut pins = PinList::new();
+        policy.apply_pins(&mut pins);
+        assert!(pins.allows(&osquery, &Version::from("5.2.0")));
+        assert!(!pins.allows(&osquery, &Version::from("6.0.0")));
+
+        let user = vec![String::from("users")];
+        let mut managers = vec![apt, PackageManager { name: String::from("pip"), ..Default::default() }];
+        assert_eq!(policy.restrict(&mut managers.clone(), &[String::from("wheel")]), Vec::<String>::new());
+        assert_eq!(policy.restrict(&mut managers, &user), vec!["apt"]);
+        assert_eq!(managers.len(), 1);
+        assert!(OrgPolicy::new().allows_manager("apt", &user));
+        assert!(OrgPolicy::from_toml(&"[[mandatory]]\npackage = 'osquery'\n".parse::<Value>().unwrap()).is_err());
+    }
+}
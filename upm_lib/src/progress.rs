@@ -0,0 +1,151 @@
+//! Reporting the progress of package manager commands to a frontend. A
+//! [`ProgressReporter`](trait.ProgressReporter.html) attached to a package manager with
+//! [`set_reporter`](../struct.PackageManager.html#method.set_reporter) is told when each command it
+//! executes starts, every line it prints, how far along it is when a line shows a percentage, and
+//! how it finished. This covers every operation that executes commands, such as installs,
+//! upgrades, cleaning, and applying manifests.
+//!
+//! While a reporter is attached, commands that would print to the terminal have their output
+//! given to the reporter instead, which decides what to show. Commands whose output is captured or
+//! discarded are still reported as starting and finishing.
+//!
+//! [`Silent`](struct.Silent.html) and [`LogReporter`](struct.LogReporter.html) are always
+//! available, and the `indicatif` feature adds [`ProgressBars`](struct.ProgressBars.html).
+
+use std::process::ExitStatus;
+use regex::Regex;
+
+/// Receives the progress of package manager commands. Every method does nothing by default.
+pub trait ProgressReporter: Send + Sync {
+    /// A command was started with the given arguments
+    fn started(&self, _manager: &str, _command: &str, _args: &str) {}
+
+    /// A command printed a line to stdout or stderr
+    fn output_line(&self, _manager: &str, _line: &str) {}
+
+    /// A command printed a line showing it's the given percent done
+    fn percent(&self, _manager: &str, _percent: u8) {}
+
+    /// A command finished, with its exit status unless it couldn't be run
+    fn finished(&self, _manager: &str, _command: &str, _status: Option<ExitStatus>) {}
+}
+
+/// Find the percentage a line of output shows, such as `Fetched 50%`. The last percentage on the
+/// line is used, and values over 100 are ignored.
+pub fn percent_in(line: &str) -> Option<u8> {
+    let percent = Regex::new(r"(\d{1,3})(?:\.\d+)?\s?%").unwrap();
+    let mut percents: Vec<u8> = percent.captures_iter(line)
+        .filter_map(|captures| captures[1].parse::<u8>().ok())
+        .filter(|&value| value <= 100)
+        .collect();
+    percents.pop()
+}
+
+/// A reporter that ignores everything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Silent;
+
+impl ProgressReporter for Silent {}
+
+/// A reporter that logs commands at info level and their output at debug level
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogReporter;
+
+impl ProgressReporter for LogReporter {
+    fn started(&self, manager: &str, command: &str, args: &str) {
+        info!("{}: started {} {}", manager, command, args);
+    }
+
+    fn output_line(&self, manager: &str, line: &str) {
+        debug!("{}: {}", manager, line);
+    }
+
+    fn percent(&self, manager: &str, percent: u8) {
+        debug!("{}: {}% done", manager, percent);
+    }
+
+    fn finished(&self, manager: &str, command: &str, status: Option<ExitStatus>) {
+        match status {
+            Some(status) => info!("{}: {} exited with {}", manager, command, status),
+            None => info!("{}: {} couldn't be run", manager, command),
+        }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+pub use self::bars::ProgressBars;
+
+#[cfg(feature = "indicatif")]
+mod bars {
+    use std::collections::HashMap;
+    use std::process::ExitStatus;
+    use std::sync::Mutex;
+    use indicatif::{ProgressBar, ProgressStyle};
+    use super::ProgressReporter;
+
+    /// A reporter that draws a progress bar on stderr for each running command, showing the
+    /// latest line of output as its message
+    #[derive(Default)]
+    pub struct ProgressBars {
+        bars: Mutex<HashMap<String, ProgressBar>>,
+    }
+
+    impl ProgressBars {
+        pub fn new() -> ProgressBars {
+            ProgressBars::default()
+        }
+
+        fn with_bar<F: FnOnce(&ProgressBar)>(&self, manager: &str, f: F) {
+            if let Some(bar) = self.bars.lock().unwrap().get(manager) {
+                f(bar);
+            }
+        }
+    }
+
+    impl ProgressReporter for ProgressBars {
+        fn started(&self, manager: &str, command: &str, args: &str) {
+            let bar = ProgressBar::new(100);
+            bar.set_style(ProgressStyle::default_bar()
+                          .template("{prefix:>10} [{bar:30}] {pos:>3}% {wide_msg}")
+                          .expect("the progress bar template is valid")
+                          .progress_chars("=> "));
+            bar.set_prefix(manager.to_owned());
+            bar.set_message(format!("{} {}", command, args));
+            self.bars.lock().unwrap().insert(manager.to_owned(), bar);
+        }
+
+        fn output_line(&self, manager: &str, line: &str) {
+            self.with_bar(manager, |bar| bar.set_message(line.to_owned()));
+        }
+
+        fn percent(&self, manager: &str, percent: u8) {
+            self.with_bar(manager, |bar| bar.set_position(u64::from(percent)));
+        }
+
+        fn finished(&self, manager: &str, command: &str, status: Option<ExitStatus>) {
+            if let Some(bar) = self.bars.lock().unwrap().remove(manager) {
+                match status {
+                    Some(status) if status.success() => {
+                        bar.set_position(100);
+                        bar.finish_with_message(format!("{} done", command));
+                    },
+                    Some(status) => bar.abandon_with_message(format!("{} exited with {}", command, status)),
+                    None => bar.abandon_with_message(format!("{} couldn't be run", command)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentages() {
+        assert_eq!(percent_in("Fetched 50%"), Some(50));
+        assert_eq!(percent_in("[ 12.5 %] 3 of 8, then 75%"), Some(75));
+        assert_eq!(percent_in("Got 250% more"), None);
+        assert_eq!(percent_in("Unpacking"), None);
+    }
+}
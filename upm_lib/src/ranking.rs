@@ -0,0 +1,120 @@
+//! One ordering of search results for every frontend. Each result from
+//! [`ManagerSet::search_ranked`](../struct.ManagerSet.html#method.search_ranked) gets a
+//! [`Score`](struct.Score.html) combining, from most to least important:
+//!
+//! 1. How well its name matches the query. With the `fuzzy` feature this is the
+//!    [fuzzy score](../fuzzy/index.html), and otherwise exact names beat names starting with the
+//!    query, which beat names containing it.
+//! 2. Whether it's already installed through its package manager
+//! 3. The priority of its package manager
+//! 4. Whether it's the newest version of the package among the results
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashSet;
+use super::Package;
+#[cfg(feature = "fuzzy")]
+use fuzzy::FuzzyMatcher;
+
+/// How good a search result is. Better results compare greater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Score {
+    /// How well the name matches the query, 0 if it doesn't
+    pub name_match: u32,
+    /// Whether the package is installed through the package manager that found it
+    pub installed: bool,
+    /// The priority of the package manager that found it
+    pub priority: i64,
+    /// Whether no other result has a newer version of the package
+    pub newest: bool,
+}
+
+/// A search result with its score
+#[derive(Clone)]
+pub struct Ranked {
+    pub package: Package,
+    pub score: Score,
+}
+
+/// How well a name matches a query, 0 if it doesn't. Case is ignored.
+#[cfg(feature = "fuzzy")]
+pub fn name_match(query: &str, name: &str) -> u32 {
+    FuzzyMatcher::new().score(query, name).unwrap_or(0)
+}
+
+/// How well a name matches a query, 0 if it doesn't. Case is ignored.
+#[cfg(not(feature = "fuzzy"))]
+pub fn name_match(query: &str, name: &str) -> u32 {
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+    if query.is_empty() {
+        0
+    } else if name == query {
+        1000
+    } else if name.starts_with(&query) {
+        900
+    } else if name.contains(&query) {
+        700
+    } else {
+        0
+    }
+}
+
+/// Score search results and order them from best to worst. `installed` has the package manager
+/// and name of every installed package. Results with the same score keep their order.
+pub fn rank(query: &str, packages: Vec<Package>, installed: &HashSet<(String, String)>) -> Vec<Ranked> {
+    let scores: Vec<Score> = packages.iter()
+        .map(|package| {
+            let newest = packages.iter()
+                .filter(|other| other.name == package.name)
+                .all(|other| other.version.cmp_with(&package.version, other.owner.version_scheme) != Some(Ordering::Greater));
+            Score {
+                name_match: name_match(query, &package.name),
+                installed: installed.contains(&(package.owner.name.clone(), package.name.clone())),
+                priority: package.owner.priority,
+                newest,
+            }
+        })
+        .collect();
+    let mut ranked: Vec<Ranked> = packages.into_iter()
+        .zip(scores)
+        .map(|(package, score)| Ranked { package, score })
+        .collect();
+    ranked.sort_by_key(|ranked| Reverse(ranked.score));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{PackageManager, Version};
+
+    fn package(name: &str, version: &str, manager: &str, priority: i64) -> Package {
+        Package {
+            name: String::from(name),
+            owner: PackageManager { name: String::from(manager), priority, ..Default::default() },
+            version: Version::from(version),
+            description: String::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ranking() {
+        let packages = vec![
+            package("ripgrep-all", "0.9.6", "cargo", 0),
+            package("ripgrep", "13.0.0", "apt", 10),
+            package("ripgrep", "14.1.0", "cargo", 0),
+            package("ripgrep", "14.1.0", "brew", 0),
+        ];
+        let mut installed = HashSet::new();
+        installed.insert((String::from("brew"), String::from("ripgrep")));
+        let ranked = rank("ripgrep", packages, &installed);
+        let order: Vec<(&str, &str)> = ranked.iter()
+            .map(|ranked| (ranked.package.name.as_str(), ranked.package.owner.name.as_str()))
+            .collect();
+        assert_eq!(order, vec![("ripgrep", "brew"), ("ripgrep", "apt"), ("ripgrep", "cargo"), ("ripgrep-all", "cargo")]);
+        assert!(!ranked[1].score.newest);
+        assert!(ranked[2].score.newest);
+        assert!(ranked[0].score > ranked[3].score);
+    }
+}
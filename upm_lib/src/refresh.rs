@@ -0,0 +1,114 @@
+//! Refreshing the package metadata of package managers that keep it separate from upgrades, such
+//! as `apt update` or `pacman -Sy`. A package manager's configuration gives the command that
+//! refreshes its metadata, and optionally how old the metadata may get before upm refreshes it
+//! by itself ahead of searches and upgrades:
+//!
+//! ```toml
+//! refresh = "apt update"
+//! refresh_after = "1d"
+//! metadata_path = "/var/lib/apt/lists"
+//! ```
+//!
+//! The metadata was last refreshed at the latest of the refresh recorded in the
+//! [state database](../state/index.html) and the time `metadata_path` was last modified, so
+//! refreshes made outside of upm count too. Metadata whose age isn't known is always considered
+//! stale.
+
+use std::process::ExitStatus;
+use std::time::{Duration, Instant, SystemTime};
+use failure::Error;
+
+/// What refreshing a package manager's metadata did
+#[derive(Debug)]
+pub struct RefreshReport {
+    pub result: Result<ExitStatus, Error>,
+    /// How long the refresh took
+    pub elapsed: Duration,
+}
+
+impl RefreshReport {
+    /// Time an attempt at refreshing
+    pub fn timed<F: FnOnce() -> Result<ExitStatus, Error>>(refresh: F) -> RefreshReport {
+        let start = Instant::now();
+        let result = refresh();
+        RefreshReport { result, elapsed: start.elapsed() }
+    }
+
+    /// Did the refresh succeed?
+    pub fn is_success(&self) -> bool {
+        match self.result {
+            Ok(status) => status.success(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Is metadata last refreshed at the given time older than the maximum age?
+pub fn is_stale(refreshed: Option<SystemTime>, max_age: Duration) -> bool {
+    match refreshed.map(|time| time.elapsed()) {
+        Some(Ok(age)) => age > max_age,
+        //A time in the future means the clock changed, so the age is unknown
+        _ => true,
+    }
+}
+
+/// Parse an age such as `30m`, `12h`, `7d`, or a number of seconds
+pub fn parse_age(age: &str) -> Result<Duration, Error> {
+    let age = age.trim();
+    let (number, unit) = match age.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => age.split_at(index),
+        None => (age, "s"),
+    };
+    let seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => bail!("Unknown unit in age {:?}, expected s, m, h, d, or w", age),
+    };
+    match number.parse::<u64>() {
+        Ok(number) => Ok(Duration::from_secs(number * seconds)),
+        Err(_) => bail!("{:?} isn't an age such as 12h or 7d", age),
+    }
+}
+
+/// Describe a duration roughly in its largest unit, such as `12 days`
+pub fn describe_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    let (amount, unit) = if seconds >= 86_400 {
+        (seconds / 86_400, "day")
+    } else if seconds >= 3_600 {
+        (seconds / 3_600, "hour")
+    } else if seconds >= 60 {
+        (seconds / 60, "minute")
+    } else {
+        (seconds, "second")
+    };
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages() {
+        assert_eq!(parse_age("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_age("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(parse_age("7d").unwrap(), Duration::from_secs(604_800));
+        assert!(parse_age("7 days").is_err());
+        assert!(parse_age("d").is_err());
+        assert_eq!(describe_age(Duration::from_secs(12 * 86_400 + 5)), "12 days");
+        assert_eq!(describe_age(Duration::from_secs(3_600)), "1 hour");
+    }
+
+    #[test]
+    fn staleness() {
+        let day = Duration::from_secs(86_400);
+        assert!(!is_stale(Some(SystemTime::now()), day));
+        assert!(is_stale(Some(SystemTime::now() - 2 * day), day));
+        assert!(is_stale(None, day));
+        assert!(is_stale(Some(SystemTime::now() + day), day));
+    }
+}
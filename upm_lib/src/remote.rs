@@ -0,0 +1,187 @@
+//! Running package managers on another machine over SSH, with the `remote` feature. A package
+//! manager or a whole [`ManagerSet`](../struct.ManagerSet.html) bound to an
+//! [`SshTarget`](struct.SshTarget.html) runs every command as `ssh -- host command`, so one
+//! machine can search and install packages across many.
+//!
+//! ```no_run
+//! # extern crate upm_lib;
+//! # use upm_lib::ManagerSet;
+//! # use upm_lib::remote::SshTarget;
+//! # fn main() {
+//! let mut managers = ManagerSet::default();
+//! managers.set_remote("admin@web1:2222".parse::<SshTarget>().unwrap());
+//! # }
+//! ```
+//!
+//! Scripts given relative to the configuration directory must be at the same path on the remote
+//! machine. Hooks still run locally, and SSH runs without prompting for a password, so the
+//! machine should accept a key from the user's agent.
+
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+
+/// The SSH options every remote command is run with
+pub const DEFAULT_OPTIONS: &[&str] = &["BatchMode=yes"];
+
+/// A machine reached over SSH
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SshTarget {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Options given to ssh with `-o`, such as `ConnectTimeout=10`
+    pub options: Vec<String>,
+}
+
+impl SshTarget {
+    /// Reach a host as the current user on the default port
+    pub fn new(host: &str) -> SshTarget {
+        SshTarget {
+            host: host.to_owned(),
+            user: None,
+            port: None,
+            options: DEFAULT_OPTIONS.iter().map(|&option| option.to_owned()).collect(),
+        }
+    }
+
+    /// Log in as a user
+    pub fn with_user(mut self, user: &str) -> SshTarget {
+        self.user = Some(user.to_owned());
+        self
+    }
+
+    /// Connect to a port other than 22
+    pub fn with_port(mut self, port: u16) -> SshTarget {
+        self.port = Some(port);
+        self
+    }
+
+    /// Give ssh an option, such as `ConnectTimeout=10`
+    pub fn with_option(mut self, option: &str) -> SshTarget {
+        self.options.push(option.to_owned());
+        self
+    }
+
+    /// The destination given to ssh, the host with the user if there is one
+    pub fn destination(&self) -> String {
+        match self.user {
+            Some(ref user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// The words a command is prefixed with to run it on the host
+    pub fn wrapper(&self) -> Vec<String> {
+        let mut words = vec![String::from("ssh")];
+        if let Some(port) = self.port {
+            words.push(String::from("-p"));
+            words.push(port.to_string());
+        }
+        for option in &self.options {
+            words.push(String::from("-o"));
+            words.push(option.clone());
+        }
+        words.push(String::from("--"));
+        words.push(self.destination());
+        words
+    }
+}
+
+/// Quote a word for the remote shell, which ssh joins the words of a command into. Words of only
+/// letters, digits, and `_@%+=:,./-` are left as they are.
+pub fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+//Whether a user or host could be taken for an option or split into two words by ssh
+fn is_unsafe(word: &str) -> bool {
+    word.is_empty() || word.starts_with('-') || word.contains(char::is_whitespace)
+}
+
+/// Parse a target such as `web1`, `admin@web1`, `admin@web1:2222`, or `ssh://admin@web1:2222`
+impl FromStr for SshTarget {
+    type Err = Error;
+
+    fn from_str(target: &str) -> Result<SshTarget, Error> {
+        let rest = target.trim_start_matches("ssh://");
+        let (user, rest) = match rest.rfind('@') {
+            Some(at) => (Some(&rest[..at]), &rest[at + 1..]),
+            None => (None, rest),
+        };
+        let (host, port) = match rest.rfind(':') {
+            Some(colon) => match rest[colon + 1..].parse::<u16>() {
+                Ok(port) => (&rest[..colon], Some(port)),
+                Err(_) => bail!("The port of SSH target {} must be a number", target),
+            },
+            None => (rest, None),
+        };
+        if is_unsafe(host) || user.is_some_and(is_unsafe) {
+            bail!("{} isn't an SSH target such as user@host:port", target);
+        }
+        let mut result = SshTarget::new(host);
+        result.user = user.map(str::to_owned);
+        result.port = port;
+        Ok(result)
+    }
+}
+
+impl fmt::Display for SshTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.destination())?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, ManagerSet, PackageManager, Scope};
+
+    #[test]
+    fn targets() {
+        let target: SshTarget = "ssh://admin@web1:2222".parse().unwrap();
+        assert_eq!(target, SshTarget::new("web1").with_user("admin").with_port(2222));
+        assert_eq!(target.to_string(), "admin@web1:2222");
+        assert_eq!("web1".parse::<SshTarget>().unwrap().destination(), "web1");
+        assert!("admin@".parse::<SshTarget>().is_err());
+        assert!("web1:ssh".parse::<SshTarget>().is_err());
+        assert!("-oProxyCommand=x".parse::<SshTarget>().is_err());
+        assert!("-oProxyCommand=x@web1".parse::<SshTarget>().is_err());
+        assert!("ad min@web1".parse::<SshTarget>().is_err());
+        assert!("web 1".parse::<SshTarget>().is_err());
+        assert_eq!(target.with_option("ConnectTimeout=10").wrapper().join(" "),
+                   "ssh -p 2222 -o BatchMode=yes -o ConnectTimeout=10 -- admin@web1");
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("ripgrep=1.0"), "ripgrep=1.0");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("x;rm -rf ~"), "'x;rm -rf ~'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn remote_commands() {
+        let manager = PackageManager { name: String::from("apt"), ..Default::default() }
+            .with_command(CommandKind::Version, Scope::System, "apt --version")
+            .with_command(CommandKind::Install, Scope::System, "apt install");
+        let mut managers = ManagerSet::new(vec![manager]);
+        managers.set_remote(SshTarget::new("web1"));
+        let apt = managers.get("apt").unwrap();
+        assert_eq!(apt.remote(), Some(&SshTarget::new("web1")));
+        assert_eq!(apt.command_line("install", "ripgrep").unwrap(),
+                   "ssh -o BatchMode=yes -- web1 apt install ripgrep");
+        assert_eq!(apt.command_line("install", "x;rm -rf ~").unwrap(),
+                   "ssh -o BatchMode=yes -- web1 apt install 'x;rm' -rf '~'");
+    }
+}
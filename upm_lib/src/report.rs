@@ -0,0 +1,336 @@
+//! Summaries of installs and upgrades for frontends to show once they're done, such as
+//! `3 managers, 14 packages upgraded, 2m10s`. An [`OperationReport`](struct.OperationReport.html)
+//! has a [`ManagerReport`](struct.ManagerReport.html) for each package manager that was run, with
+//! how long it took and what it changed.
+//!
+//! The packages changed are found by comparing the installed packages before and after, so they
+//! need the list_installed command. Without it, an install is taken to have changed the packages
+//! it was given. The bytes downloaded and the warnings are read from the output, so they're only
+//! known when the output was captured. Lines such as `Fetched 12.3 MB` or
+//! `Total download size: 12 M` give the bytes downloaded, and lines starting with `warning:`,
+//! `W:`, or `npm WARN` are warnings.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::process::{ExitStatus, Output};
+use std::time::{Duration, Instant};
+use failure::Error;
+use regex::Regex;
+use super::{ManagerSet, PackageManager, StdioPolicy, upgrade_manager};
+use concurrency::{ConcurrencyPolicy, run_all};
+use exit::{ExitCode, FailureKind};
+use history::Operation;
+use pin::PinList;
+use usage::human_size;
+
+/// What running a command did for one package manager
+#[derive(Debug)]
+pub struct ManagerReport {
+    pub manager: String,
+    /// The command that was run, such as `install` or `upgrade_all`
+    pub command: String,
+    pub result: Result<ExitStatus, Error>,
+    /// Why the command failed, if it ran and failed
+    pub failure: Option<FailureKind>,
+    /// How long the command took
+    pub elapsed: Duration,
+    /// The bytes downloaded, if the output said
+    pub downloaded: Option<u64>,
+    /// The packages installed, upgraded, or removed, if they're known
+    pub packages: Option<Vec<String>>,
+    /// The warnings the command printed
+    pub warnings: Vec<String>,
+}
+
+impl ManagerReport {
+    /// Run a command that changes packages, timing it and comparing the installed packages before
+    /// and after. The packages the command was given are used when the installed packages can't
+    /// be listed.
+    pub fn measure<F>(manager: &PackageManager, command: &str, requested: &[&str], operation: F) -> ManagerReport
+        where F: FnOnce() -> Result<Output, Error>
+    {
+        let before = installed_versions(manager);
+        let start = Instant::now();
+        let result = operation();
+        let elapsed = start.elapsed();
+        manager.invalidate_cache();
+        let succeeded = result.as_ref().is_ok_and(|output| output.status.success());
+        let packages = match (before, installed_versions(manager)) {
+            (Some(before), Some(after)) => Some(changed_packages(&before, &after)),
+            _ if succeeded && !requested.is_empty() => Some(requested.iter().map(|&package| package.to_owned()).collect()),
+            _ => None,
+        };
+        let (downloaded, warnings) = match result {
+            Ok(ref output) => {
+                let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+                (parse_downloaded(&text), parse_warnings(&text))
+            },
+            Err(_) => (None, Vec::new()),
+        };
+        let result = result.map(|output| output.status);
+        let failure = match result {
+            Ok(status) if !status.success() => Some(manager.failure_kind(command, status)),
+            _ => None,
+        };
+        ManagerReport {
+            manager: manager.name.clone(),
+            command: command.to_owned(),
+            result,
+            failure,
+            elapsed,
+            downloaded,
+            packages,
+            warnings,
+        }
+    }
+
+    /// A report for a package manager that couldn't be run at all
+    pub fn failed(manager: &str, command: &str, error: Error) -> ManagerReport {
+        ManagerReport {
+            manager: manager.to_owned(),
+            command: command.to_owned(),
+            result: Err(error),
+            failure: None,
+            elapsed: Duration::default(),
+            downloaded: None,
+            packages: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Did the command succeed?
+    pub fn is_success(&self) -> bool {
+        self.exit_code().is_success()
+    }
+
+    /// The exit code for how the command went
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from_result(&self.result, self.failure.unwrap_or(FailureKind::Other))
+    }
+}
+
+/// What an install or upgrade did across package managers
+#[derive(Debug)]
+pub struct OperationReport {
+    pub operation: Operation,
+    /// A report for each package manager that was run, in the order of the managers
+    pub managers: Vec<ManagerReport>,
+    /// How long the whole operation took, which is less than the sum of the package managers'
+    /// times when they ran at once
+    pub elapsed: Duration,
+}
+
+impl OperationReport {
+    /// How many packages were changed, counting only package managers that know
+    pub fn packages_changed(&self) -> usize {
+        self.managers.iter().filter_map(|report| report.packages.as_ref()).map(Vec::len).sum()
+    }
+
+    /// The bytes downloaded by the package managers whose output said, if any did
+    pub fn downloaded(&self) -> Option<u64> {
+        self.managers.iter().filter_map(|report| report.downloaded).fold(None, |total, bytes| Some(total.unwrap_or(0) + bytes))
+    }
+
+    /// Get the warnings of every package manager, alongside its name
+    pub fn warnings(&self) -> Vec<(&str, &str)> {
+        self.managers.iter()
+            .flat_map(|report| report.warnings.iter().map(move |warning| (report.manager.as_str(), warning.as_str())))
+            .collect()
+    }
+
+    /// Did the operation succeed for every package manager?
+    pub fn is_success(&self) -> bool {
+        self.managers.iter().all(ManagerReport::is_success)
+    }
+
+    /// The exit code for the whole operation. See
+    /// [`ExitCode::combine`](../exit/enum.ExitCode.html#method.combine).
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::combine(self.managers.iter().map(ManagerReport::exit_code))
+    }
+}
+
+impl fmt::Display for OperationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.managers.len();
+        write!(f, "{} manager{}", count, if count == 1 { "" } else { "s" })?;
+        if self.managers.iter().any(|report| report.packages.is_some()) {
+            let changed = self.packages_changed();
+            let verb = match self.operation {
+                Operation::Install => "installed",
+                Operation::Remove => "removed",
+                _ => "upgraded",
+            };
+            write!(f, ", {} package{} {}", changed, if changed == 1 { "" } else { "s" }, verb)?;
+        }
+        if let Some(bytes) = self.downloaded() {
+            write!(f, ", {} downloaded", human_size(bytes))?;
+        }
+        write!(f, ", {}", format_duration(self.elapsed))
+    }
+}
+
+/// Install packages, given as the name of the package manager to install each with and the name
+/// of the package, running each package manager once for all of its packages and as many at once
+/// as the policy allows. Package managers that aren't in the set are reported as failed.
+pub fn install(managers: &ManagerSet, installs: &[(String, String)], policy: &ConcurrencyPolicy, stdio: StdioPolicy) -> OperationReport {
+    let start = Instant::now();
+    let mut packages: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (manager, package) in installs {
+        packages.entry(manager.as_str()).or_default().push(package.as_str());
+    }
+    let involved: Vec<PackageManager> = managers.iter()
+        .filter(|manager| packages.contains_key(manager.name.as_str()))
+        .cloned()
+        .collect();
+    let mut reports: Vec<ManagerReport> = run_all(&involved, policy, |manager| {
+        let requested = &packages[manager.name.as_str()];
+        ManagerReport::measure(manager, "install", requested, || manager.execute_with("install", &requested.join(" "), stdio))
+    }).into_iter().map(|(_, report)| report).collect();
+    for &manager in packages.keys() {
+        if managers.get(manager).is_none() {
+            reports.push(ManagerReport::failed(manager, "install", format_err!("{} isn't configured", manager)));
+        }
+    }
+    OperationReport { operation: Operation::Install, managers: reports, elapsed: start.elapsed() }
+}
+
+/// Upgrade every package of every package manager the way
+/// [`upgrade_managers`](../fn.upgrade_managers.html) does, honoring the pins
+pub fn upgrade(managers: &[PackageManager], policy: &ConcurrencyPolicy, pins: &PinList, stdio: StdioPolicy) -> OperationReport {
+    let start = Instant::now();
+    let reports = run_all(managers, policy, |manager| {
+        //Package managers that can't upgrade everything aren't part of the operation
+        if !can_upgrade(manager, pins) {
+            return None;
+        }
+        Some(ManagerReport::measure(manager, "upgrade_all", &[], || {
+            upgrade_manager(manager, pins, stdio)
+                .unwrap_or_else(|| Err(format_err!("{} can't upgrade every package", manager.name)))
+        }))
+    });
+    OperationReport {
+        operation: Operation::UpgradeAll,
+        managers: reports.into_iter().filter_map(|(_, report)| report).collect(),
+        elapsed: start.elapsed(),
+    }
+}
+
+//Can the package manager upgrade every package with upgrade_manager?
+fn can_upgrade(manager: &PackageManager, pins: &PinList) -> bool {
+    pins.pins_for(&manager.name).next().is_some() || manager.has_command("upgrade_all")
+}
+
+//Gets the installed version of every package, if the package manager can list them
+fn installed_versions(manager: &PackageManager) -> Option<HashMap<String, String>> {
+    if !manager.has_command("list_installed") {
+        return None;
+    }
+    match manager.list_installed() {
+        Ok(packages) => Some(packages.into_iter().map(|package| (package.name, package.version.to_string())).collect()),
+        Err(e) => {
+            warn!("{}: couldn't list the installed packages to report on: {}", manager.name, e);
+            None
+        },
+    }
+}
+
+/// Get the packages that were added, removed, or changed version between two listings of the
+/// installed packages, by name
+pub fn changed_packages(before: &HashMap<String, String>, after: &HashMap<String, String>) -> Vec<String> {
+    let mut changed: Vec<String> = after.iter()
+        .filter(|&(name, version)| before.get(name) != Some(version))
+        .map(|(name, _)| name.clone())
+        .chain(before.keys().filter(|name| !after.contains_key(*name)).cloned())
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Find how many bytes some output says were downloaded. The last size given is used, since
+/// package managers such as apt say what they need to get before what they fetched. Units with an
+/// `i`, and the single letters dnf uses, are powers of 1024, while `kB`, `MB`, and `GB` are powers
+/// of 1000.
+pub fn parse_downloaded(output: &str) -> Option<u64> {
+    let size = Regex::new(r"(?im)(?:fetched|downloaded|total download size:?|need to get)\s+([\d.,]+)\s*([kmgt]?)(i?)(b?)\b").unwrap();
+    let captures = size.captures_iter(output).last()?;
+    let number: f64 = captures[1].replace(',', "").parse().ok()?;
+    let power = match captures[2].to_ascii_lowercase().as_str() {
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => 0,
+    };
+    let base: f64 = if captures[3].is_empty() && !captures[4].is_empty() { 1000.0 } else { 1024.0 };
+    Some((number * base.powi(power)) as u64)
+}
+
+/// Get the warnings from some output, without their prefixes
+pub fn parse_warnings(output: &str) -> Vec<String> {
+    let warning = Regex::new(r"(?i)^\s*(?:npm warn|warning:?|warn:|w:)\s+(.*\S)").unwrap();
+    output.lines()
+        .filter_map(|line| warning.captures(line).map(|captures| captures[1].to_owned()))
+        .collect()
+}
+
+/// Format a duration for people, such as `2m10s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3_600 {
+        format!("{}h{}m", seconds / 3_600, seconds % 3_600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m{}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, Scope};
+
+    #[test]
+    fn parsing_output() {
+        assert_eq!(parse_downloaded("Need to get 2,048 kB of archives.\nFetched 1.5 MB in 2s (750 kB/s)"), Some(1_500_000));
+        assert_eq!(parse_downloaded("Total download size: 12 M"), Some(12 * 1024 * 1024));
+        assert_eq!(parse_downloaded("Total Download Size:   2.00 MiB"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_downloaded("Nothing to do."), None);
+        assert_eq!(parse_warnings("W: Some index files failed to download\nok\nnpm WARN deprecated left-pad@1.3.0\n"),
+                   vec!["Some index files failed to download", "deprecated left-pad@1.3.0"]);
+        assert_eq!(format_duration(Duration::from_secs(130)), "2m10s");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+    }
+
+    #[test]
+    fn reports() {
+        let manager = |name: &str, install: &str| PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::Install, Scope::System, install)
+            .with_command(CommandKind::UpgradeAll, Scope::System, install);
+        let managers = ManagerSet::new(vec![manager("apt", "echo Fetched 2 kB in 0s"), manager("pip", "false")]);
+        let installs = vec![(String::from("apt"), String::from("ripgrep")), (String::from("apt"), String::from("fd")),
+                            (String::from("pip"), String::from("black")), (String::from("gem"), String::from("rails"))];
+        let report = install(&managers, &installs, &ConcurrencyPolicy::default(), StdioPolicy::Capture);
+        assert_eq!(report.managers.len(), 3);
+        assert_eq!(report.managers[0].packages, Some(vec![String::from("ripgrep"), String::from("fd")]));
+        assert_eq!(report.packages_changed(), 2);
+        assert_eq!(report.downloaded(), Some(2000));
+        assert_eq!(report.exit_code(), ExitCode::Partial);
+        assert!(report.to_string().starts_with("3 managers, 2 packages installed, 2.0 KiB downloaded, "));
+
+        let report = upgrade(&managers.as_slice()[..1], &ConcurrencyPolicy::default(), &PinList::new(), StdioPolicy::Null);
+        assert!(report.is_success());
+        assert_eq!(report.downloaded(), None);
+        assert!(report.to_string().starts_with("1 manager, "));
+
+        let before: HashMap<String, String> = vec![(String::from("fd"), String::from("8.0")), (String::from("jq"), String::from("1.6"))].into_iter().collect();
+        let after: HashMap<String, String> = vec![(String::from("fd"), String::from("9.0")), (String::from("rg"), String::from("14"))].into_iter().collect();
+        assert_eq!(changed_packages(&before, &after), vec!["fd", "jq", "rg"]);
+    }
+}
@@ -0,0 +1,83 @@
+//! The repositories package managers install from, such as PPAs, brew taps, or extra registries.
+//! A package manager's configuration can give `add_repo` and `remove_repo` commands that take a
+//! repository as the package manager names it, and a `list_repos` command that prints the enabled
+//! repositories following the [script contract](../contract/index.html).
+
+use std::fmt;
+use failure::Error;
+use super::PackageManager;
+use concurrency::{ConcurrencyPolicy, run_all};
+use multi::MultiResult;
+
+/// A repository enabled in a package manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    pub name: String,
+    /// Where the repository is, if the package manager shows it
+    pub url: Option<String>,
+    /// The name of the package manager the repository is enabled in
+    pub manager: String,
+}
+
+impl fmt::Display for Repository {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.url {
+            Some(ref url) => write!(f, "{} ({}): {}", self.name, self.manager, url),
+            None => write!(f, "{} ({})", self.name, self.manager),
+        }
+    }
+}
+
+/// Parse the output of a list_repos command, one repository per line as the name and then the URL
+pub fn parse_repos(listing: &str, manager: &str) -> Vec<Repository> {
+    listing.lines().filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next().map(|name| Repository {
+            name: name.to_owned(),
+            url: fields.next().map(String::from),
+            manager: manager.to_owned(),
+        })
+    }).collect()
+}
+
+/// List the repositories of every package manager with a list_repos command
+pub fn list_all(managers: &[PackageManager]) -> MultiResult<'_, Result<Vec<Repository>, Error>> {
+    MultiResult::new("list_repos", run_all(managers, &ConcurrencyPolicy::default(), |manager| {
+        if manager.has_command("list_repos") {
+            Some(manager.list_repos())
+        } else {
+            None
+        }
+    }).into_iter().filter_map(|(manager, result)| result.map(|result| (manager, result))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, Scope};
+
+    #[test]
+    fn parsing_repos() {
+        let repos = parse_repos("homebrew/core\nppa:git-core/ppa http://ppa.launchpad.net/git-core/ppa/ubuntu\n\n", "apt");
+        assert_eq!(repos.len(), 2);
+        assert_eq!(repos[0].url, None);
+        assert_eq!(repos[1].name, "ppa:git-core/ppa");
+        assert_eq!(repos[1].url, Some(String::from("http://ppa.launchpad.net/git-core/ppa/ubuntu")));
+        assert_eq!(repos[0].to_string(), "homebrew/core (apt)");
+    }
+
+    #[test]
+    fn managing_repos() {
+        let manager = PackageManager {
+            name: String::from("tapped"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::AddRepo, Scope::System, "true")
+            .with_command(CommandKind::RemoveRepo, Scope::System, "false")
+            .with_command(CommandKind::ListRepos, Scope::System, "echo homebrew/core");
+        assert!(manager.add_repo("homebrew/cask").unwrap().success());
+        assert!(!manager.remove_repo("homebrew/cask").unwrap().success());
+        let listed = list_all(::std::slice::from_ref(&manager));
+        assert_eq!(listed[0].1.as_ref().unwrap()[0].name, "homebrew/core");
+    }
+}
@@ -0,0 +1,193 @@
+//! Picking a single package when several package managers provide one with the same name. A
+//! [`Resolver`](struct.Resolver.html) searches a [`ManagerSet`](../struct.ManagerSet.html) for
+//! packages named exactly as asked and settles ties according to a
+//! [`ResolutionPolicy`](enum.ResolutionPolicy.html). Anything the policy can't settle is given
+//! back as [`Resolution::Ambiguous`](enum.Resolution.html) for the frontend to ask the user about.
+//!
+//! The name is translated for each package manager by the resolver's
+//! [aliases](../alias/index.html), so packages are matched by the name their manager uses.
+//! [Rules](../rules/index.html) sending categories of packages to a package manager are consulted
+//! before the policy.
+
+use std::cmp::Ordering;
+use super::{ManagerSet, Package};
+use alias::AliasResolver;
+use rules::RuleSet;
+use concurrency::ConcurrencyPolicy;
+
+/// How to choose between packages with the same name from different package managers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionPolicy {
+    /// Choose the package from the package manager with the highest priority
+    PreferPriority,
+    /// Choose the package with the newest version. Versions that can't be compared are left for
+    /// the user to choose between.
+    PreferNewestVersion,
+    /// Leave every choice to the user
+    AlwaysAsk,
+}
+
+/// The outcome of resolving a package name
+pub enum Resolution {
+    /// A single package was chosen
+    Found(Box<Package>),
+    /// The policy couldn't choose between these packages. They're ordered by the priority of their
+    /// package managers, highest first.
+    Ambiguous(Vec<Package>),
+    /// No package manager has a package with the name
+    NotFound,
+}
+
+/// Finds the package to use for a name across several package managers
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    pub policy: ResolutionPolicy,
+    pub concurrency: ConcurrencyPolicy,
+    pub aliases: AliasResolver,
+    pub rules: RuleSet,
+}
+
+impl Resolver {
+    /// Create a resolver that searches with the default concurrency policy
+    pub fn new(policy: ResolutionPolicy) -> Resolver {
+        Resolver {
+            policy,
+            concurrency: ConcurrencyPolicy::default(),
+            aliases: AliasResolver::new(),
+            rules: RuleSet::new(),
+        }
+    }
+
+    /// Consult the given rules before the policy
+    pub fn with_rules(mut self, rules: RuleSet) -> Resolver {
+        self.rules = rules;
+        self
+    }
+
+    /// Translate names for each package manager with the given aliases
+    pub fn with_aliases(mut self, aliases: AliasResolver) -> Resolver {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Search every package manager in the set for packages with exactly the given name, as
+    /// translated for that manager, and choose between them. Package managers whose search fails
+    /// are skipped.
+    pub fn resolve(&self, name: &str, managers: &ManagerSet) -> Resolution {
+        let mut candidates = Vec::new();
+        for (manager, result) in self.aliases.search_all(managers.as_slice(), name, &self.concurrency) {
+            let wanted = self.aliases.translate(name, manager);
+            match result {
+                Ok(packages) => candidates.extend(packages.into_iter().filter(|p| p.name == wanted)),
+                Err(e) => warn!("{}: search for {} failed: {}", manager.name, name, e),
+            }
+        }
+        self.choose(candidates)
+    }
+
+    /// Choose between packages that were already found, by the rules and then the policy. The
+    /// candidates should be ordered by the priority of their package managers.
+    pub fn choose(&self, mut candidates: Vec<Package>) -> Resolution {
+        if candidates.is_empty() {
+            return Resolution::NotFound;
+        }
+        if candidates.len() == 1 {
+            return Resolution::Found(Box::new(candidates.remove(0)));
+        }
+        if let Some(index) = self.rules.choose(&candidates) {
+            return Resolution::Found(Box::new(candidates.swap_remove(index)));
+        }
+        let chosen = match self.policy {
+            ResolutionPolicy::PreferPriority => unique_best(&candidates, |a, b| {
+                Some(a.owner.priority.cmp(&b.owner.priority))
+            }),
+            ResolutionPolicy::PreferNewestVersion => unique_best(&candidates, |a, b| {
+                //Versions from package managers with the same scheme are compared with it
+                if a.owner.version_scheme == b.owner.version_scheme {
+                    a.version.cmp_with(&b.version, a.owner.version_scheme)
+                } else {
+                    a.version.partial_cmp(&b.version)
+                }
+            }),
+            ResolutionPolicy::AlwaysAsk => None,
+        };
+        match chosen {
+            Some(index) => Resolution::Found(Box::new(candidates.swap_remove(index))),
+            None => Resolution::Ambiguous(candidates),
+        }
+    }
+}
+
+//Find the candidate that is strictly greater than every other one, if there is one
+fn unique_best<F>(candidates: &[Package], compare: F) -> Option<usize>
+    where F: Fn(&Package, &Package) -> Option<Ordering>
+{
+    (0..candidates.len()).find(|&index| {
+        candidates.iter().enumerate().all(|(other, candidate)| {
+            other == index || compare(&candidates[index], candidate) == Some(Ordering::Greater)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{PackageManager, Version};
+    use rules::Rule;
+
+    fn candidate(manager: &str, priority: i64, version: &str) -> Package {
+        Package {
+            name: String::from("ripgrep"),
+            owner: PackageManager {
+                name: String::from(manager),
+                priority,
+                ..Default::default()
+            },
+            version: Version::from(version),
+            description: String::new(),
+            ..Default::default()
+        }
+    }
+
+    fn chosen_manager(resolution: Resolution) -> Option<String> {
+        match resolution {
+            Resolution::Found(package) => Some(package.owner.name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn policies() {
+        let candidates = || vec![candidate("pacman", 10, "0.7.1"), candidate("cargo", 0, "0.8.0")];
+        let priority = Resolver::new(ResolutionPolicy::PreferPriority);
+        assert_eq!(chosen_manager(priority.choose(candidates())), Some(String::from("pacman")));
+        let newest = Resolver::new(ResolutionPolicy::PreferNewestVersion);
+        assert_eq!(chosen_manager(newest.choose(candidates())), Some(String::from("cargo")));
+        match Resolver::new(ResolutionPolicy::AlwaysAsk).choose(candidates()) {
+            Resolution::Ambiguous(packages) => assert_eq!(packages.len(), 2),
+            _ => panic!("AlwaysAsk chose a package"),
+        }
+    }
+
+    #[test]
+    fn rules_come_first() {
+        let mut rules = RuleSet::new();
+        rules.push(Rule::glob("rip*", "cargo").unwrap());
+        let resolver = Resolver::new(ResolutionPolicy::AlwaysAsk).with_rules(rules);
+        let candidates = vec![candidate("pacman", 10, "0.7.1"), candidate("cargo", 0, "0.8.0")];
+        assert_eq!(chosen_manager(resolver.choose(candidates)), Some(String::from("cargo")));
+    }
+
+    #[test]
+    fn ties_are_ambiguous() {
+        let resolver = Resolver::new(ResolutionPolicy::PreferPriority);
+        let tied = vec![candidate("apt", 10, "0.7.1"), candidate("brew", 10, "0.7.1")];
+        assert_eq!(chosen_manager(resolver.choose(tied)), None);
+        let single = vec![candidate("cargo", 0, "0.8.0")];
+        assert_eq!(chosen_manager(resolver.choose(single)), Some(String::from("cargo")));
+        match resolver.choose(Vec::new()) {
+            Resolution::NotFound => {},
+            _ => panic!("Resolved a package from no candidates"),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Finding out what needs restarting after an upgrade. Upgraded libraries and daemons only take
+//! effect once the services using them restart, and a new kernel once the machine reboots. The
+//! optional `needs_restart` command prints one line per service that needs restarting, or a line
+//! starting with `reboot` if the whole system does, each optionally followed by a reason:
+//!
+//! ```text
+//! nginx
+//! sshd uses a deleted libssl.so.3
+//! reboot kernel 6.8.0 is installed but 6.5.0 is running
+//! ```
+//!
+//! That's the output of `dnf needs-restarting --services`, and a script can wrap
+//! `needs-restarting -r` or `checkrestart`. The command exits with 0 even if something needs
+//! restarting. A [`RestartReport`](struct.RestartReport.html) gathers what every package manager
+//! reported.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use super::PackageManager;
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// The first field of a line saying the whole system needs rebooting
+pub const REBOOT: &str = "reboot";
+
+/// What a package manager says needs restarting
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestartNeeds {
+    /// Does the whole system need rebooting?
+    pub reboot: bool,
+    /// Why the system needs rebooting, for the lines that gave a reason
+    pub reasons: Vec<String>,
+    /// The services that need restarting
+    pub services: BTreeSet<String>,
+}
+
+impl RestartNeeds {
+    /// Does anything need restarting?
+    pub fn is_needed(&self) -> bool {
+        self.reboot || !self.services.is_empty()
+    }
+}
+
+/// Parse the output of a needs_restart command
+pub fn parse_needs(output: &str) -> RestartNeeds {
+    let mut needs = RestartNeeds::default();
+    for line in output.lines() {
+        let line = line.trim();
+        let (first, reason) = match line.find(char::is_whitespace) {
+            Some(space) => (&line[..space], line[space..].trim()),
+            None => (line, ""),
+        };
+        if first.is_empty() {
+            continue;
+        } else if first == REBOOT {
+            needs.reboot = true;
+            if !reason.is_empty() {
+                needs.reasons.push(reason.to_owned());
+            }
+        } else {
+            needs.services.insert(first.to_owned());
+        }
+    }
+    needs
+}
+
+/// What needs restarting according to several package managers
+#[derive(Debug, Clone, Default)]
+pub struct RestartReport {
+    /// What each package manager that was checked reported, by name
+    pub managers: Vec<(String, RestartNeeds)>,
+    /// The package managers whose check failed, by name
+    pub unknown: Vec<String>,
+}
+
+impl RestartReport {
+    /// Does the whole system need rebooting?
+    pub fn reboot(&self) -> bool {
+        self.managers.iter().any(|(_, needs)| needs.reboot)
+    }
+
+    /// Get why the system needs rebooting, with the package manager that said so
+    pub fn reasons(&self) -> Vec<(&str, &str)> {
+        self.managers.iter()
+            .flat_map(|(manager, needs)| needs.reasons.iter().map(move |reason| (manager.as_str(), reason.as_str())))
+            .collect()
+    }
+
+    /// Get every service that needs restarting
+    pub fn services(&self) -> BTreeSet<&str> {
+        self.managers.iter().flat_map(|(_, needs)| needs.services.iter().map(String::as_str)).collect()
+    }
+
+    /// Does anything need restarting?
+    pub fn is_needed(&self) -> bool {
+        self.managers.iter().any(|(_, needs)| needs.is_needed())
+    }
+}
+
+impl fmt::Display for RestartReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.reboot() {
+            writeln!(f, "The system needs rebooting")?;
+            for (manager, reason) in self.reasons() {
+                writeln!(f, "  {} ({})", reason, manager)?;
+            }
+        }
+        let services = self.services();
+        if !services.is_empty() {
+            let count = services.len();
+            writeln!(f, "{} service{} need{} restarting: {}", count, if count == 1 { "" } else { "s" },
+                     if count == 1 { "s" } else { "" }, services.into_iter().collect::<Vec<_>>().join(", "))?;
+        }
+        if !self.is_needed() && self.unknown.is_empty() {
+            writeln!(f, "Nothing needs restarting")?;
+        }
+        if !self.unknown.is_empty() {
+            writeln!(f, "Couldn't check {}", self.unknown.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Ask every package manager with a needs_restart command what needs restarting. Checks that fail
+/// are logged and listed as unknown.
+pub fn check(managers: &[PackageManager], policy: &ConcurrencyPolicy) -> RestartReport {
+    let managers: Vec<PackageManager> = managers.iter().filter(|manager| manager.has_command("needs_restart")).cloned().collect();
+    let checked = run_all(&managers, policy, |manager| match manager.needs_restart() {
+        Ok(needs) => Some(needs),
+        Err(e) => {
+            warn!("{}: couldn't check what needs restarting: {}", manager.name, e);
+            None
+        },
+    });
+    let mut report = RestartReport::default();
+    for (manager, needs) in checked {
+        match needs {
+            Some(needs) => report.managers.push((manager.name.clone(), needs)),
+            None => report.unknown.push(manager.name.clone()),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, Scope};
+
+    #[test]
+    fn restart_needs() {
+        let needs = parse_needs("nginx\nsshd uses a deleted libssl.so.3\n\nreboot kernel 6.8.0 is installed\nnginx\n");
+        assert!(needs.reboot);
+        assert_eq!(needs.reasons, vec!["kernel 6.8.0 is installed"]);
+        assert_eq!(needs.services.iter().map(String::as_str).collect::<Vec<_>>(), vec!["nginx", "sshd"]);
+        assert!(!parse_needs("\n").is_needed());
+
+        let managers = vec![
+            PackageManager { name: String::from("dnf"), ..Default::default() }
+                .with_command(CommandKind::NeedsRestart, Scope::System, "echo reboot"),
+            PackageManager { name: String::from("apt"), ..Default::default() }
+                .with_command(CommandKind::NeedsRestart, Scope::System, "false"),
+            PackageManager { name: String::from("pip"), ..Default::default() },
+        ];
+        let report = check(&managers, &ConcurrencyPolicy::default());
+        assert!(report.reboot());
+        assert!(report.services().is_empty());
+        assert_eq!(report.unknown, vec!["apt"]);
+        assert_eq!(report.to_string(), "The system needs rebooting\nCouldn't check apt\n");
+    }
+}
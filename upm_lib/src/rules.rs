@@ -0,0 +1,212 @@
+//! Rules choosing the package manager to use for categories of packages, such as cargo for Rust
+//! crates and pipx for Python tools. The [`Resolver`](../resolve/struct.Resolver.html) consults the
+//! rules before its policy. They're kept in `rules.toml` in the
+//! [data directory](../fn.data_dir.html) as a list that is checked in order:
+//!
+//! ```toml
+//! [[rules]]
+//! pattern = "cargo-*"
+//! manager = "cargo"
+//!
+//! [[rules]]
+//! regex = "^python3?-"
+//! manager = "pipx"
+//!
+//! [[rules]]
+//! pattern = "*"
+//! manager = "apt"
+//! ```
+//!
+//! A `pattern` is a glob where `*` matches any run of characters and `?` matches a single one.
+//! A `regex` is matched anywhere in the name unless anchored.
+
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use failure::Error;
+use regex::{self, Regex};
+use toml::Value;
+use super::{Package, data_dir};
+
+/// A rule sending packages with matching names to a package manager
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// The pattern as it was written, for display
+    pub pattern: String,
+    /// The name of the package manager to use
+    pub manager: String,
+    matcher: Regex,
+}
+
+impl Rule {
+    /// Create a rule from a glob pattern
+    pub fn glob(pattern: &str, manager: &str) -> Result<Rule, Error> {
+        Ok(Rule {
+            pattern: pattern.to_owned(),
+            manager: manager.to_owned(),
+            matcher: glob_regex(pattern)?,
+        })
+    }
+
+    /// Create a rule from a regular expression
+    pub fn regex(expression: &str, manager: &str) -> Result<Rule, Error> {
+        Ok(Rule {
+            pattern: format!("/{}/", expression),
+            manager: manager.to_owned(),
+            matcher: Regex::new(expression)?,
+        })
+    }
+
+    /// Does the rule apply to a package name?
+    pub fn matches(&self, name: &str) -> bool {
+        self.matcher.is_match(name)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.pattern, self.manager)
+    }
+}
+
+/// Compile a glob where `*` matches any run of characters and `?` a single one into a regular
+/// expression matching whole names
+pub fn glob_regex(pattern: &str) -> Result<Regex, Error> {
+    let mut expression = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => expression.push_str(".*"),
+            '?' => expression.push('.'),
+            c => expression.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expression.push('$');
+    Ok(Regex::new(&expression)?)
+}
+
+/// The rules in the order they're checked
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+/// Get the path of the default rules, `rules.toml` in the data directory
+pub fn rules_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("rules.toml"))
+}
+
+impl RuleSet {
+    /// Create an empty set of rules
+    pub fn new() -> RuleSet {
+        RuleSet::default()
+    }
+
+    /// Read the rules from the `rules` array of a TOML value
+    pub fn from_toml(resource: &Value) -> Result<RuleSet, Error> {
+        let mut set = RuleSet::new();
+        let rules = match resource.get("rules") {
+            Some(rules) => match rules.as_array() {
+                Some(rules) => rules,
+                None => bail!("rules must be an array of tables"),
+            },
+            None => return Ok(set),
+        };
+        for rule in rules {
+            let field = |name: &str| rule.get(name).and_then(|value| value.as_str());
+            let manager = match field("manager") {
+                Some(manager) => manager,
+                None => bail!("Every rule needs a manager"),
+            };
+            set.push(match (field("pattern"), field("regex")) {
+                (Some(pattern), None) => Rule::glob(pattern, manager)?,
+                (None, Some(expression)) => Rule::regex(expression, manager)?,
+                _ => bail!("The rule for {} needs either a pattern or a regex", manager),
+            });
+        }
+        Ok(set)
+    }
+
+    /// Read the rules from a file. A file that doesn't exist gives no rules.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RuleSet, Error> {
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(RuleSet::new()),
+            Err(e) => return Err(e.into()),
+        }
+        RuleSet::from_toml(&content.parse::<Value>()?)
+    }
+
+    /// Read the default rules
+    pub fn load_default() -> Result<RuleSet, Error> {
+        match rules_path() {
+            Some(path) => RuleSet::load(path),
+            None => Ok(RuleSet::new()),
+        }
+    }
+
+    /// Add a rule, checked after the existing ones
+    pub fn push(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    /// Get the rules in the order they're checked
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Choose a candidate by the rules. Each candidate's name is checked against the rules in
+    /// order, and the first rule naming the package manager of a candidate it matches chooses
+    /// that candidate.
+    pub fn choose(&self, candidates: &[Package]) -> Option<usize> {
+        self.rules.iter().filter_map(|rule| {
+            candidates.iter().position(|candidate| {
+                candidate.owner.name == rule.manager && rule.matches(&candidate.name)
+            })
+        }).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::PackageManager;
+
+    fn candidate(name: &str, manager: &str) -> Package {
+        Package {
+            name: String::from(name),
+            owner: PackageManager {
+                name: String::from(manager),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matching() {
+        let rule = Rule::glob("cargo-*", "cargo").unwrap();
+        assert!(rule.matches("cargo-edit"));
+        assert!(!rule.matches("rust-cargo-edit"));
+        assert!(Rule::glob("python?-*", "pipx").unwrap().matches("python3-black"));
+        assert!(Rule::glob("c++", "apt").unwrap().matches("c++"));
+        assert!(Rule::regex("^py", "pipx").unwrap().matches("pytest"));
+        assert!(Rule::regex("(", "pipx").is_err());
+    }
+
+    #[test]
+    fn choosing() {
+        let resource = "[[rules]]\npattern = 'cargo-*'\nmanager = 'cargo'\n\
+                        [[rules]]\npattern = '*'\nmanager = 'apt'\n".parse::<Value>().unwrap();
+        let rules = RuleSet::from_toml(&resource).unwrap();
+        let crate_candidates = vec![candidate("cargo-edit", "apt"), candidate("cargo-edit", "cargo")];
+        assert_eq!(rules.choose(&crate_candidates), Some(1));
+        let other = vec![candidate("jq", "brew"), candidate("jq", "apt")];
+        assert_eq!(rules.choose(&other), Some(1));
+        assert_eq!(rules.choose(&[candidate("jq", "brew")]), None);
+        let missing = "[[rules]]\nmanager = 'apt'\n".parse::<Value>().unwrap();
+        assert!(RuleSet::from_toml(&missing).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Running a package manager's scripts in a sandbox. The scripts in a configuration directory run
+//! with the user's privileges, but the ones that only look things up, such as the `search` and
+//! `version` scripts, have no reason to change the system. A package manager can opt in to
+//! sandboxing them:
+//!
+//! ```toml
+//! sandbox = "bubblewrap"
+//! ```
+//!
+//! * `restricted` clears the environment except for a few harmless variables
+//! * `bubblewrap` also runs the script with `bwrap`, seeing the whole filesystem read-only
+//! * `firejail` also runs the script with `firejail`, seeing the whole filesystem read-only
+//!
+//! Only scripts given relative to the configuration directory (`./search.sh`) for the
+//! [`SANDBOXED_COMMANDS`](constant.SANDBOXED_COMMANDS.html) are sandboxed. The package
+//! manager's own binaries are trusted, and commands that install or remove packages need write
+//! access. Bubblewrap and firejail only exist on Linux; elsewhere they fall back to `restricted`.
+
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+use failure::Error;
+
+/// The commands whose scripts are sandboxed
+pub const SANDBOXED_COMMANDS: &[&str] = &["version", "search", "info", "list_installed", "owns_file",
+                                          "reverse_deps", "provides", "predict_size", "verify", "audit",
+                                          "needs_restart", "changelog", "cache_size", "disk_usage", "list_keys",
+                                          "list_repos"];
+
+/// The environment variables kept for sandboxed scripts
+pub const KEPT_VARS: &[&str] = &["PATH", "HOME", "USER", "LANG", "LC_ALL", "TERM", "TMPDIR"];
+
+/// How a package manager's scripts are sandboxed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sandbox {
+    /// Scripts run like any other command
+    #[default]
+    Disabled,
+    /// Scripts run with a cleared environment
+    Restricted,
+    /// Restricted, and run by bubblewrap with a read-only filesystem
+    Bubblewrap,
+    /// Restricted, and run by firejail with a read-only filesystem
+    Firejail,
+}
+
+impl Sandbox {
+    /// Should a command be sandboxed given the command it's made from?
+    pub fn applies_to(self, name: &str, template: &str) -> bool {
+        self != Sandbox::Disabled && SANDBOXED_COMMANDS.contains(&name) && template.starts_with("./")
+    }
+
+    /// The words a sandboxed command is prefixed with
+    pub fn wrapper(self) -> Vec<String> {
+        let words: &[&str] = match self.available() {
+            Sandbox::Bubblewrap => &["bwrap", "--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc",
+                                     "--tmpfs", "/tmp", "--unshare-all", "--share-net", "--die-with-parent"],
+            Sandbox::Firejail => &["firejail", "--quiet", "--noprofile", "--read-only=/", "--private-tmp"],
+            _ => &[],
+        };
+        words.iter().map(|&word| word.to_owned()).collect()
+    }
+
+    /// Clear the environment of a sandboxed command except for the
+    /// [`KEPT_VARS`](constant.KEPT_VARS.html)
+    pub fn restrict(self, command: &mut Command) {
+        if self == Sandbox::Disabled {
+            return;
+        }
+        command.env_clear();
+        for &var in KEPT_VARS {
+            if let Some(value) = ::std::env::var_os(var) {
+                command.env(var, value);
+            }
+        }
+    }
+
+    //The sandbox that can actually be used on this platform
+    fn available(self) -> Sandbox {
+        match self {
+            Sandbox::Bubblewrap | Sandbox::Firejail if !cfg!(target_os = "linux") => {
+                debug!("The {} sandbox is only supported on Linux, using restricted", self);
+                Sandbox::Restricted
+            },
+            _ => self,
+        }
+    }
+}
+
+impl FromStr for Sandbox {
+    type Err = Error;
+
+    fn from_str(sandbox: &str) -> Result<Sandbox, Error> {
+        match sandbox {
+            "none" => Ok(Sandbox::Disabled),
+            "restricted" => Ok(Sandbox::Restricted),
+            "bubblewrap" => Ok(Sandbox::Bubblewrap),
+            "firejail" => Ok(Sandbox::Firejail),
+            _ => bail!("Unknown sandbox {}, expected none, restricted, bubblewrap, or firejail", sandbox),
+        }
+    }
+}
+
+impl fmt::Display for Sandbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Sandbox::Disabled => "none",
+            Sandbox::Restricted => "restricted",
+            Sandbox::Bubblewrap => "bubblewrap",
+            Sandbox::Firejail => "firejail",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sandboxed_commands() {
+        assert!(Sandbox::Restricted.applies_to("search", "./search.sh"));
+        assert!(!Sandbox::Restricted.applies_to("search", "apt search"));
+        assert!(!Sandbox::Restricted.applies_to("install", "./install.sh"));
+        assert!(!Sandbox::Disabled.applies_to("search", "./search.sh"));
+        assert!(Sandbox::Restricted.wrapper().is_empty());
+        if cfg!(target_os = "linux") {
+            assert_eq!(Sandbox::Bubblewrap.wrapper()[0], "bwrap");
+        } else {
+            assert!(Sandbox::Bubblewrap.wrapper().is_empty());
+        }
+        assert_eq!("firejail".parse::<Sandbox>().unwrap(), Sandbox::Firejail);
+        assert!("chroot".parse::<Sandbox>().is_err());
+    }
+
+    #[test]
+    fn restricted_environment() {
+        let output = |sandbox: Sandbox| {
+            let mut command = Command::new("./test-files/sandbox/env.sh");
+            command.env("UPM_SANDBOX_SECRET", "hunter2");
+            sandbox.restrict(&mut command);
+            String::from_utf8(command.output().unwrap().stdout).unwrap()
+        };
+        assert_eq!(output(Sandbox::Restricted), "secret=\n");
+        assert_eq!(output(Sandbox::Disabled), "secret=hunter2\n");
+    }
+}
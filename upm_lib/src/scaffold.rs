@@ -0,0 +1,114 @@
+//! Starting configurations for new package managers. [`scaffold`](fn.scaffold.html) writes a
+//! commented configuration listing every command upm knows, so adding an obscure package manager
+//! is a matter of filling in the blanks. Package managers with a
+//! [built-in template](../detect/index.html) have the commands of the template filled in already.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use super::{CommandKind, PermissionDenied, Scope, detect};
+
+/// The settings listed at the end of a configuration that doesn't set them, with an example value
+/// and what they're for
+const SETTINGS: &[(&str, &str, &str)] = &[
+    ("priority", "0", "Which package manager is preferred when several have a package, highest first"),
+    ("lock_class", "\"\"", "Package managers with the same lock class never run at the same time"),
+    ("version_scheme", "\"semver\"", "How versions are compared: semver, debian, or rpm"),
+    ("version_format", "\"{name}={version}\"", "How a package is named at a particular version"),
+    ("refresh_after", "\"1d\"", "How old the metadata may get before it's refreshed"),
+];
+
+/// Write the commented configuration of a new package manager. The built-in template of the
+/// package manager is filled in if it has one, and otherwise only the version command is.
+pub fn scaffold(name: &str) -> String {
+    let mut template: Table = detect::template(name)
+        .and_then(|template| template.parse::<Value>().ok())
+        .and_then(|template| template.as_table().cloned())
+        .unwrap_or_default();
+    let mut result = format!("# Package manager definition for {}\n", name);
+    if !template.is_empty() {
+        result.push_str("# Filled in from upm's built-in definition.\n");
+    }
+    result.push_str("#\n\
+                     # Commands are run with the packages appended, or in place of {args} if the command has it.\n\
+                     # Commands starting with ./ run a script next to this file. Give a command a _local suffix,\n\
+                     # such as install_local, to run it for the user's home directory instead of the system.\n\
+                     # The output of commands upm reads, such as search and list_installed, must follow upm's\n\
+                     # script contract.\n");
+    for &kind in CommandKind::ALL {
+        let key = kind.key(Scope::System);
+        result.push_str(&format!("\n# {}\n", kind.description()));
+        match template.remove(&key) {
+            Some(command) => result.push_str(&format!("{} = {}\n", key, command)),
+            None if kind == CommandKind::Version => result.push_str(&format!("version = \"{} --version\"\n", name)),
+            None => result.push_str(&format!("#{} = \"\"\n", key)),
+        }
+    }
+    for &(key, example, description) in SETTINGS {
+        result.push_str(&format!("\n# {}\n", description));
+        match template.remove(key) {
+            Some(value) => result.push_str(&format!("{} = {}\n", key, value)),
+            None => result.push_str(&format!("#{} = {}\n", key, example)),
+        }
+    }
+    if !template.is_empty() {
+        result.push('\n');
+        result.push_str(&Value::Table(template).to_string());
+    }
+    result
+}
+
+/// Write the [scaffolded](fn.scaffold.html) configuration of a new package manager into a
+/// configuration directory, creating the directory if needed. An existing configuration is never
+/// replaced. Lacking permission to write the file gives a
+/// [`PermissionDenied`](../struct.PermissionDenied.html) error.
+pub fn write_scaffold<P: AsRef<Path>>(name: &str, dir: P) -> Result<PathBuf, Error> {
+    let path = dir.as_ref().join(format!("{}.toml", name));
+    let created = fs::create_dir_all(dir.as_ref())
+        .and_then(|_| OpenOptions::new().write(true).create_new(true).open(&path));
+    match created {
+        Ok(mut file) => {
+            file.write_all(scaffold(name).as_bytes())?;
+            Ok(path)
+        },
+        Err(ref e) if e.kind() == ErrorKind::AlreadyExists => bail!("{:?} already exists", path),
+        Err(ref e) if e.kind() == ErrorKind::PermissionDenied => Err(PermissionDenied { path }.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+    use super::super::PackageManager;
+
+    #[test]
+    fn scaffolds_parse() {
+        let blank = scaffold("nix");
+        assert!(blank.contains("version = \"nix --version\"\n"));
+        assert!(blank.contains("#install = \"\"\n"));
+        let nix = PackageManager::from_toml("nix", &blank.parse::<Value>().unwrap(), PathBuf::new()).unwrap();
+        assert_eq!(nix.capabilities().commands, vec![(CommandKind::Version, Scope::System)]);
+
+        let apt = scaffold("apt");
+        assert!(apt.contains("install = \"apt install\"\n"));
+        assert!(apt.contains("lock_class = \"dpkg\"\n"));
+        let parsed = PackageManager::from_toml("apt", &apt.parse::<Value>().unwrap(), PathBuf::new()).unwrap();
+        let builtin = PackageManager::from_toml("apt", &detect::template("apt").unwrap().parse::<Value>().unwrap(),
+                                                PathBuf::new()).unwrap();
+        assert_eq!(parsed.to_toml(), builtin.to_toml());
+    }
+
+    #[test]
+    fn writing_scaffolds() {
+        let dir = env::temp_dir().join(format!("upm-scaffold-{}", process::id()));
+        let path = write_scaffold("nix", &dir).unwrap();
+        assert_eq!(path, dir.join("nix.toml"));
+        assert!(write_scaffold("nix", &dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,592 @@
+//! The stable schema of upm's machine-readable output, shared by `--output json`, `--porcelain`,
+//! and the [daemon](../daemon/index.html).
+//!
+//! # JSON
+//! Every command prints a single [`Document`] on one line. Its `data` holds one record per result,
+//! and `errors` the package managers that failed along the way:
+//!
+//! ```text
+//! {"schema":1,"command":"query","data":[{"name":"ripgrep","version":"0.8.1","description":"","manager":"cargo","kind":null}],"errors":[]}
+//! ```
+//!
+//! # Porcelain
+//! Every record is printed as a line of tab separated fields in the order they're declared here,
+//! with missing values left empty. Tabs and newlines inside values are replaced by spaces, and
+//! errors go to stderr as the package manager and the message.
+//!
+//! # Stability
+//! Fields are only added within a [`SCHEMA_VERSION`], and always at the end of a record. Removing
+//! or changing a field increases the version.
+//!
+//! [`Document`]: struct.Document.html
+//! [`SCHEMA_VERSION`]: constant.SCHEMA_VERSION.html
+
+use std::fmt::Display;
+use std::process::ExitStatus;
+use super::{Package, PackageManager};
+use conflicts::Conflict;
+use doctor::{ManagerReport, Severity};
+use history::{HistoryEntry, epoch_seconds};
+use keys::RepoKey;
+use manifest::{Change, ChangeKind};
+use pin::Pin;
+use repos::Repository;
+use security::{IntegrityProblem, Vulnerability};
+use shadow::Shadowing;
+use state::Staleness;
+use status::PackageStatus;
+use which::Availability;
+
+/// The version of the schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The output of a command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document<T> {
+    /// The [`SCHEMA_VERSION`](constant.SCHEMA_VERSION.html) the document follows
+    pub schema: u32,
+    /// The subcommand that was run
+    pub command: String,
+    pub data: Vec<T>,
+    pub errors: Vec<ManagerError>,
+}
+
+impl<T> Document<T> {
+    /// Create a document for the records of a command
+    pub fn new(command: &str, data: Vec<T>, errors: Vec<ManagerError>) -> Document<T> {
+        Document { schema: SCHEMA_VERSION, command: command.to_owned(), data, errors }
+    }
+}
+
+/// A record that can be printed as a porcelain line
+pub trait Porcelain {
+    /// The fields of the record in order. `None` is printed as an empty field.
+    fn fields(&self) -> Vec<Option<String>>;
+
+    /// The record as a line of tab separated fields, without a newline
+    fn porcelain(&self) -> String {
+        self.fields().into_iter()
+            .map(|field| field.unwrap_or_default().replace(['\t', '\n'], " "))
+            .collect::<Vec<String>>()
+            .join("\t")
+    }
+}
+
+/// A package found by a search, listed as installed, or owning a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub manager: String,
+    /// The [kind](../kind/enum.PackageKind.html) of package, if it's known
+    pub kind: Option<String>,
+}
+
+impl<'a> From<&'a Package> for PackageInfo {
+    fn from(package: &'a Package) -> PackageInfo {
+        PackageInfo {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            description: package.description.clone(),
+            manager: package.owner.name.clone(),
+            kind: package.kind().map(|kind| kind.to_string()),
+        }
+    }
+}
+
+impl Porcelain for PackageInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.name.clone()), Some(self.version.clone()), Some(self.description.clone()),
+             Some(self.manager.clone()), self.kind.clone()]
+    }
+}
+
+/// A package manager whose part of a command failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagerError {
+    pub manager: String,
+    pub message: String,
+}
+
+impl Porcelain for ManagerError {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.message.clone())]
+    }
+}
+
+/// A configured package manager
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagerInfo {
+    pub name: String,
+    pub priority: i64,
+}
+
+impl Porcelain for ManagerInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.name.clone()), Some(self.priority.to_string())]
+    }
+}
+
+/// A command a package manager has
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityInfo {
+    pub manager: String,
+    /// The configuration key of the command, such as `install_local`, or the name of the extra
+    /// command
+    pub command: String,
+    /// Whether it's an extra command rather than one upm knows
+    pub extra: bool,
+}
+
+impl CapabilityInfo {
+    /// List the commands and then the extra commands of a package manager
+    pub fn list(manager: &PackageManager) -> Vec<CapabilityInfo> {
+        let capabilities = manager.capabilities();
+        let commands = capabilities.commands.iter().map(|&(kind, scope)| (kind.key(scope), false));
+        let extras = capabilities.extras.into_iter().map(|extra| (extra, true));
+        commands.chain(extras).map(|(command, extra)| CapabilityInfo {
+            manager: manager.name.clone(),
+            command,
+            extra,
+        }).collect()
+    }
+}
+
+impl Porcelain for CapabilityInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.command.clone()), Some(self.extra.to_string())]
+    }
+}
+
+/// The outcome of running a package manager command that changes packages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub manager: String,
+    /// The command from the package manager's configuration, such as `install` or `clean_cache`
+    pub command: String,
+    /// The package the command was run for, if any
+    pub package: Option<String>,
+    pub success: bool,
+    /// The exit code, missing if the command couldn't be run or was killed by a signal
+    pub code: Option<i32>,
+    /// Why the command couldn't be run
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    /// Describe the result of running a command
+    pub fn new<E: Display>(manager: &str, command: &str, package: Option<&str>, result: &Result<ExitStatus, E>) -> Outcome {
+        let (success, code, error) = match *result {
+            Ok(status) => (status.success(), status.code(), None),
+            Err(ref e) => (false, None, Some(e.to_string())),
+        };
+        Outcome {
+            manager: manager.to_owned(),
+            command: command.to_owned(),
+            package: package.map(String::from),
+            success,
+            code,
+            error,
+        }
+    }
+}
+
+impl Porcelain for Outcome {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.command.clone()), self.package.clone(),
+             Some(self.success.to_string()), self.code.map(|code| code.to_string()), self.error.clone()]
+    }
+}
+
+/// Whether a package manager offers a package and whether it's installed through it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AvailabilityInfo {
+    pub manager: String,
+    pub name: String,
+    pub available: Option<String>,
+    pub installed: Option<String>,
+}
+
+impl<'a> From<&'a Availability> for AvailabilityInfo {
+    fn from(availability: &'a Availability) -> AvailabilityInfo {
+        AvailabilityInfo {
+            manager: availability.manager.clone(),
+            name: availability.name.clone(),
+            available: availability.available.as_ref().map(|version| version.to_string()),
+            installed: availability.installed.as_ref().map(|version| version.to_string()),
+        }
+    }
+}
+
+impl Porcelain for AvailabilityInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.name.clone()), self.available.clone(), self.installed.clone()]
+    }
+}
+
+/// How the installed version of a package compares with the version a package manager would
+/// install
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusInfo {
+    pub manager: String,
+    pub name: String,
+    pub installed: Option<String>,
+    pub candidate: Option<String>,
+    /// One of not_installed, up_to_date, upgradable, ahead, or unknown
+    pub state: String,
+}
+
+impl<'a> From<&'a PackageStatus> for StatusInfo {
+    fn from(status: &'a PackageStatus) -> StatusInfo {
+        StatusInfo {
+            manager: status.manager.clone(),
+            name: status.name.clone(),
+            installed: status.installed.as_ref().map(|version| version.to_string()),
+            candidate: status.candidate.as_ref().map(|version| version.to_string()),
+            state: status.state.name().to_owned(),
+        }
+    }
+}
+
+impl Porcelain for StatusInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.name.clone()), self.installed.clone(), self.candidate.clone(),
+             Some(self.state.clone())]
+    }
+}
+
+/// The disk space a package manager uses, in bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageInfo {
+    pub manager: String,
+    pub cache: Option<u64>,
+    pub installed: Option<u64>,
+}
+
+impl Porcelain for UsageInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), self.cache.map(|size| size.to_string()),
+             self.installed.map(|size| size.to_string())]
+    }
+}
+
+/// A package held back from upgrades
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinInfo {
+    pub package: String,
+    pub manager: String,
+    pub version: Option<String>,
+    /// The range of versions the package may be upgraded within
+    pub req: Option<String>,
+}
+
+impl<'a> From<&'a Pin> for PinInfo {
+    fn from(pin: &'a Pin) -> PinInfo {
+        PinInfo {
+            package: pin.package.clone(),
+            manager: pin.manager.clone(),
+            version: pin.version.clone(),
+            req: pin.req.as_ref().map(|req| req.to_string()),
+        }
+    }
+}
+
+impl Porcelain for PinInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.package.clone()), Some(self.manager.clone()), self.version.clone(), self.req.clone()]
+    }
+}
+
+/// Whether a change installs or removes a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Install,
+    Remove,
+}
+
+impl Action {
+    /// The name of the action as it appears in the output
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Install => "install",
+            Action::Remove => "remove",
+        }
+    }
+}
+
+/// A change needed to match a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeInfo {
+    pub action: Action,
+    pub manager: String,
+    pub package: String,
+}
+
+impl<'a> From<&'a Change> for ChangeInfo {
+    fn from(change: &'a Change) -> ChangeInfo {
+        ChangeInfo {
+            action: match change.kind {
+                ChangeKind::Install => Action::Install,
+                ChangeKind::Remove => Action::Remove,
+            },
+            manager: change.manager.clone(),
+            package: change.package.clone(),
+        }
+    }
+}
+
+impl Porcelain for ChangeInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.action.name().to_owned()), Some(self.manager.clone()), Some(self.package.clone())]
+    }
+}
+
+/// A problem found with a package manager's configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindingInfo {
+    pub manager: String,
+    pub path: String,
+    /// Either `warning` or `error`
+    pub severity: String,
+    pub message: String,
+}
+
+impl FindingInfo {
+    /// List the findings of a report
+    pub fn from_report(report: &ManagerReport) -> Vec<FindingInfo> {
+        report.findings.iter().map(|finding| FindingInfo {
+            manager: report.name.clone(),
+            path: report.path.to_string_lossy().into_owned(),
+            severity: String::from(match finding.severity {
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            }),
+            message: finding.message.clone(),
+        }).collect()
+    }
+
+    /// Describe a stale binary that shadows a newer one as a warning for the package manager that
+    /// installed it, if it does
+    pub fn from_shadowing(shadowing: &Shadowing) -> Option<FindingInfo> {
+        let warning = shadowing.warning()?;
+        let winner = shadowing.winner()?;
+        Some(FindingInfo {
+            manager: winner.owner.as_ref().map_or(String::new(), |(manager, _)| manager.clone()),
+            path: winner.path.to_string_lossy().into_owned(),
+            severity: String::from("warning"),
+            message: warning,
+        })
+    }
+
+    /// Describe a package installed through several package managers as a warning for each of them
+    pub fn from_conflict(conflict: &Conflict) -> Vec<FindingInfo> {
+        conflict.installs.iter().map(|(manager, _)| FindingInfo {
+            manager: manager.clone(),
+            path: conflict.binaries.first().map_or(String::new(), |binary| binary.to_string_lossy().into_owned()),
+            severity: String::from("warning"),
+            message: conflict.to_string(),
+        }).collect()
+    }
+}
+
+impl Porcelain for FindingInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.path.clone()), Some(self.severity.clone()),
+             Some(self.message.clone())]
+    }
+}
+
+/// An integrity problem or vulnerability found in an installed package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityInfo {
+    pub manager: String,
+    pub package: String,
+    /// Either `integrity` or `vulnerability`
+    pub kind: String,
+    /// The advisory ID of a vulnerability
+    pub id: Option<String>,
+    /// The severity of a vulnerability as the package manager reports it
+    pub severity: Option<String>,
+    pub message: String,
+    /// The first version that fixes a vulnerability
+    pub fixed_in: Option<String>,
+}
+
+impl<'a> From<&'a IntegrityProblem> for SecurityInfo {
+    fn from(problem: &'a IntegrityProblem) -> SecurityInfo {
+        SecurityInfo {
+            manager: problem.manager.clone(),
+            package: problem.package.clone(),
+            kind: String::from("integrity"),
+            id: None,
+            severity: None,
+            message: problem.problem.clone(),
+            fixed_in: None,
+        }
+    }
+}
+
+impl<'a> From<&'a Vulnerability> for SecurityInfo {
+    fn from(vulnerability: &'a Vulnerability) -> SecurityInfo {
+        SecurityInfo {
+            manager: vulnerability.manager.clone(),
+            package: vulnerability.package.clone(),
+            kind: String::from("vulnerability"),
+            id: Some(vulnerability.id.clone()),
+            severity: Some(vulnerability.severity.clone()),
+            message: vulnerability.description.clone(),
+            fixed_in: vulnerability.fixed_in.clone(),
+        }
+    }
+}
+
+impl Porcelain for SecurityInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), Some(self.package.clone()), Some(self.kind.clone()),
+             self.id.clone(), self.severity.clone(), Some(self.message.clone()), self.fixed_in.clone()]
+    }
+}
+
+/// A recorded command that changed packages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryInfo {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub operation: String,
+    pub manager: String,
+    pub packages: Vec<String>,
+    /// The exit code, missing if the command was killed by a signal
+    pub code: Option<i32>,
+    /// Whether the command reverted an earlier one
+    pub undo: bool,
+    /// The snapshot taken before the command, if there was one
+    pub snapshot: Option<String>,
+}
+
+impl<'a> From<&'a HistoryEntry> for HistoryInfo {
+    fn from(entry: &'a HistoryEntry) -> HistoryInfo {
+        HistoryInfo {
+            time: epoch_seconds(entry.time),
+            operation: entry.operation.name().to_owned(),
+            manager: entry.manager.clone(),
+            packages: entry.packages.clone(),
+            code: entry.code,
+            undo: entry.undo,
+            snapshot: entry.snapshot.clone(),
+        }
+    }
+}
+
+impl Porcelain for HistoryInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.time.to_string()), Some(self.operation.clone()), Some(self.manager.clone()),
+             Some(self.packages.join(" ")), self.code.map(|code| code.to_string()), Some(self.undo.to_string()),
+             self.snapshot.clone()]
+    }
+}
+
+/// A repository signing key trusted by a package manager
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyInfo {
+    pub id: String,
+    pub description: String,
+    pub manager: String,
+}
+
+impl<'a> From<&'a RepoKey> for KeyInfo {
+    fn from(key: &'a RepoKey) -> KeyInfo {
+        KeyInfo { id: key.id.clone(), description: key.description.clone(), manager: key.manager.clone() }
+    }
+}
+
+impl Porcelain for KeyInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.id.clone()), Some(self.description.clone()), Some(self.manager.clone())]
+    }
+}
+
+/// A repository enabled in a package manager
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoInfo {
+    pub name: String,
+    pub url: Option<String>,
+    pub manager: String,
+}
+
+impl<'a> From<&'a Repository> for RepoInfo {
+    fn from(repo: &'a Repository) -> RepoInfo {
+        RepoInfo { name: repo.name.clone(), url: repo.url.clone(), manager: repo.manager.clone() }
+    }
+}
+
+impl Porcelain for RepoInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.name.clone()), self.url.clone(), Some(self.manager.clone())]
+    }
+}
+
+/// How old a package manager's metadata is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StalenessInfo {
+    pub manager: String,
+    /// When the metadata was last refreshed in seconds since the Unix epoch, if that's known
+    pub refreshed: Option<u64>,
+    /// The age of the metadata in seconds, if it's known
+    pub age: Option<u64>,
+    /// Whether the metadata will be refreshed before it's used
+    pub stale: bool,
+}
+
+impl<'a> From<&'a Staleness> for StalenessInfo {
+    fn from(staleness: &'a Staleness) -> StalenessInfo {
+        StalenessInfo {
+            manager: staleness.manager.clone(),
+            refreshed: staleness.refreshed.map(epoch_seconds),
+            age: staleness.age().map(|age| age.as_secs()),
+            stale: staleness.stale,
+        }
+    }
+}
+
+impl Porcelain for StalenessInfo {
+    fn fields(&self) -> Vec<Option<String>> {
+        vec![Some(self.manager.clone()), self.refreshed.map(|time| time.to_string()),
+             self.age.map(|age| age.to_string()), Some(self.stale.to_string())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn porcelain_lines() {
+        let pin = PinInfo { package: String::from("ripgrep"), manager: String::from("cargo"), version: None, req: None };
+        assert_eq!(pin.porcelain(), "ripgrep\tcargo\t\t");
+        let package = PackageInfo {
+            name: String::from("ripgrep"),
+            version: String::from("0.8.1"),
+            description: String::from("Fast\tgrep\nclone"),
+            manager: String::from("cargo"),
+            kind: Some(String::from("tool")),
+        };
+        assert_eq!(package.porcelain(), "ripgrep\t0.8.1\tFast grep clone\tcargo\ttool");
+    }
+
+    #[test]
+    fn json_documents() {
+        let document = Document::new("pins", vec![PinInfo {
+            package: String::from("ripgrep"),
+            manager: String::from("cargo"),
+            version: Some(String::from("0.8.1")),
+            req: None,
+        }], Vec::new());
+        let json = serde_json::to_string(&document).unwrap();
+        assert_eq!(json, r#"{"schema":1,"command":"pins","data":[{"package":"ripgrep","manager":"cargo","version":"0.8.1","req":null}],"errors":[]}"#);
+        let parsed: Document<PinInfo> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, document);
+    }
+}
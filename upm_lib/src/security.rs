@@ -0,0 +1,347 @@
+//! Checking installed packages for damage and known vulnerabilities. A package manager's
+//! configuration can give a `verify` command that checks the installed files against the package
+//! database, such as `rpm -V` or `pacman -Qk`, and an `audit` command that looks the installed
+//! packages up in a vulnerability database, such as `npm audit`, `cargo audit`, or `pip-audit`.
+//!
+//! Both commands print one finding per line following the
+//! [script contract](../contract/index.html) and exit successfully whether or not they found
+//! anything, so their native output usually needs a script. A
+//! [`SecurityReport`](struct.SecurityReport.html) collects the findings of every package manager.
+//!
+//! Package managers disagree on how they name severities and advisories: npm says `moderate`
+//! where Red Hat says `Important` and pip-audit only gives a CVSS score. Each vulnerability can be
+//! normalized into an [`Advisory`](struct.Advisory.html) with a common
+//! [`Severity`](enum.Severity.html), so every vulnerability on the machine can be sorted in one
+//! list.
+
+use std::cmp::Ordering;
+use std::fmt;
+use super::{ManagerSet, Version};
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// An installed package whose files don't match what the package manager installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityProblem {
+    pub package: String,
+    /// What's wrong, such as a file whose checksum changed
+    pub problem: String,
+    /// The name of the package manager that found the problem
+    pub manager: String,
+}
+
+impl fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.package, self.manager, self.problem)
+    }
+}
+
+/// A known vulnerability in an installed package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vulnerability {
+    pub package: String,
+    /// The installed version
+    pub version: String,
+    /// The advisory's ID, such as `CVE-2023-0286` or `GHSA-x4qr-2fvf-3mr5`
+    pub id: String,
+    /// The severity as the package manager reports it
+    pub severity: String,
+    /// The first version that fixes the vulnerability, if there is one
+    pub fixed_in: Option<String>,
+    pub description: String,
+    /// The name of the package manager that found the vulnerability
+    pub manager: String,
+}
+
+impl fmt::Display for Vulnerability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ({}): {} {}", self.package, self.version, self.manager, self.severity, self.id)?;
+        if !self.description.is_empty() {
+            write!(f, ", {}", self.description)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the output of a verify command, one problem per line as the package and then a
+/// description of the problem
+pub fn parse_problems(listing: &str, manager: &str) -> Vec<IntegrityProblem> {
+    listing.lines().filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next().map(|package| IntegrityProblem {
+            package: package.to_owned(),
+            problem: fields.collect::<Vec<&str>>().join(" "),
+            manager: manager.to_owned(),
+        })
+    }).collect()
+}
+
+/// Parse the output of an audit command, one vulnerability per line as the package, the
+/// installed version, the advisory ID, the severity, and then optionally the version that fixes
+/// it, or `-` if none does, and a description. Lines with fewer fields are skipped.
+pub fn parse_vulnerabilities(listing: &str, manager: &str) -> Vec<Vulnerability> {
+    listing.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            return None;
+        }
+        if fields.len() < 4 {
+            warn!("{}: skipping audit line {:?} without a package, version, ID, and severity", manager, line);
+            return None;
+        }
+        Some(Vulnerability {
+            package: fields[0].to_owned(),
+            version: fields[1].to_owned(),
+            id: fields[2].to_owned(),
+            severity: fields[3].to_owned(),
+            fixed_in: fields.get(4).filter(|&&fixed| fixed != "-").map(|&fixed| fixed.to_owned()),
+            description: fields.get(5..).unwrap_or_default().join(" "),
+            manager: manager.to_owned(),
+        })
+    }).collect()
+}
+
+/// How serious a vulnerability is, from least to most serious
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Severity {
+    /// The package manager gave a severity that isn't understood, or none at all
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Understand the severity a package manager reports, ignoring case. The names used by npm,
+    /// cargo audit, GitHub, and the distributions are understood, as are CVSS scores.
+    pub fn normalize(reported: &str) -> Severity {
+        if let Ok(score) = reported.parse::<f32>() {
+            return Severity::from_cvss(score);
+        }
+        match reported.to_lowercase().as_str() {
+            "critical" | "urgent" => Severity::Critical,
+            "high" | "important" => Severity::High,
+            "medium" | "moderate" => Severity::Medium,
+            "low" | "minor" | "negligible" | "info" | "informational" => Severity::Low,
+            _ => Severity::Unknown,
+        }
+    }
+
+    /// Get the severity of a CVSS score using the ranges of CVSS v3. Scores outside 0 to 10 are
+    /// unknown, and a score of 0 is low.
+    pub fn from_cvss(score: f32) -> Severity {
+        match score {
+            s if !(0.0..=10.0).contains(&s) => Severity::Unknown,
+            s if s >= 9.0 => Severity::Critical,
+            s if s >= 7.0 => Severity::High,
+            s if s >= 4.0 => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Unknown => write!(f, "unknown"),
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+/// Write an advisory ID the same way whichever package manager reported it. The prefixes of CVE,
+/// GitHub, RustSec, and PyPI advisories are upper case, and CVE IDs are upper case throughout.
+pub fn normalize_id(id: &str) -> String {
+    let prefix_len = match id.find('-') {
+        Some(len) => len,
+        None => return id.to_owned(),
+    };
+    match id[..prefix_len].to_uppercase().as_str() {
+        "CVE" => id.to_uppercase(),
+        prefix @ "GHSA" | prefix @ "RUSTSEC" | prefix @ "PYSEC" => format!("{}{}", prefix, &id[prefix_len..]),
+        _ => id.to_owned(),
+    }
+}
+
+/// A vulnerability in the same terms whichever package manager found it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advisory {
+    /// The [normalized](fn.normalize_id.html) advisory ID
+    pub id: String,
+    pub package: String,
+    /// The installed version
+    pub version: Version,
+    pub severity: Severity,
+    /// The first version that fixes the vulnerability, if there is one
+    pub fixed_in: Option<Version>,
+    /// The name of the package manager that found the vulnerability
+    pub manager: String,
+}
+
+impl Advisory {
+    /// Order advisories from the most serious to the least, and then by ID and package
+    pub fn cmp_severity(&self, other: &Advisory) -> Ordering {
+        other.severity.cmp(&self.severity)
+            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| self.package.cmp(&other.package))
+            .then_with(|| self.manager.cmp(&other.manager))
+    }
+}
+
+impl<'a> From<&'a Vulnerability> for Advisory {
+    fn from(vulnerability: &'a Vulnerability) -> Advisory {
+        Advisory {
+            id: normalize_id(&vulnerability.id),
+            package: vulnerability.package.clone(),
+            version: Version::from(vulnerability.version.as_str()),
+            severity: Severity::normalize(&vulnerability.severity),
+            fixed_in: vulnerability.fixed_in.as_ref().map(|fixed| Version::from(fixed.as_str())),
+            manager: vulnerability.manager.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Advisory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} ({}): {}", self.severity, self.id, self.package, self.manager, self.version)?;
+        match self.fixed_in {
+            Some(ref fixed) => write!(f, ", fixed in {}", fixed),
+            None => write!(f, ", no fix"),
+        }
+    }
+}
+
+/// The integrity problems and vulnerabilities found across package managers
+#[derive(Debug, Clone, Default)]
+pub struct SecurityReport {
+    pub problems: Vec<IntegrityProblem>,
+    pub vulnerabilities: Vec<Vulnerability>,
+    /// The package managers whose verify or audit command failed, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+impl SecurityReport {
+    /// Run the verify and audit commands of every package manager that has them
+    pub fn check(managers: &ManagerSet) -> SecurityReport {
+        let mut report = SecurityReport::default();
+        let verified = run_all(managers.as_slice(), &ConcurrencyPolicy::default(), |manager| {
+            if manager.has_command("verify") {
+                Some(manager.verify(""))
+            } else {
+                None
+            }
+        });
+        for (manager, result) in verified {
+            match result {
+                Some(Ok(problems)) => report.problems.extend(problems),
+                Some(Err(e)) => report.failed.push((manager.name.clone(), format!("couldn't verify: {}", e))),
+                None => {},
+            }
+        }
+        let audited = run_all(managers.as_slice(), &ConcurrencyPolicy::default(), |manager| {
+            if manager.has_command("audit") {
+                Some(manager.audit())
+            } else {
+                None
+            }
+        });
+        for (manager, result) in audited {
+            match result {
+                Some(Ok(vulnerabilities)) => report.vulnerabilities.extend(vulnerabilities),
+                Some(Err(e)) => report.failed.push((manager.name.clone(), format!("couldn't audit: {}", e))),
+                None => {},
+            }
+        }
+        report
+    }
+
+    /// Normalize every vulnerability found into an advisory, from the most serious to the least
+    pub fn advisories(&self) -> Vec<Advisory> {
+        let mut advisories: Vec<Advisory> = self.vulnerabilities.iter().map(Advisory::from).collect();
+        advisories.sort_by(|a, b| a.cmp_severity(b));
+        advisories
+    }
+
+    /// Was nothing found? Package managers that couldn't be checked don't count.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty() && self.vulnerabilities.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, PackageManager, Scope};
+
+    #[test]
+    fn parsing_findings() {
+        let problems = parse_problems("openssl /usr/lib/libssl.so.3 checksum mismatch\n\n", "rpm");
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].to_string(), "openssl (rpm): /usr/lib/libssl.so.3 checksum mismatch");
+        let vulnerabilities = parse_vulnerabilities("lodash 4.17.20 GHSA-35jh-r3h4-6jhm high 4.17.21 Command injection\n\
+                                                     broken line\nopenssl 3.0.7 CVE-2023-0286 7.4\n", "npm");
+        assert_eq!(vulnerabilities.len(), 2);
+        assert_eq!(vulnerabilities[0].to_string(), "lodash 4.17.20 (npm): high GHSA-35jh-r3h4-6jhm, Command injection");
+        assert_eq!(vulnerabilities[0].fixed_in, Some(String::from("4.17.21")));
+        assert_eq!(vulnerabilities[1].fixed_in, None);
+    }
+
+    #[test]
+    fn normalizing_advisories() {
+        assert_eq!(Severity::normalize("Important"), Severity::High);
+        assert_eq!(Severity::normalize("moderate"), Severity::Medium);
+        assert_eq!(Severity::normalize("9.8"), Severity::Critical);
+        assert_eq!(Severity::normalize("11"), Severity::Unknown);
+        assert_eq!(Severity::normalize("whatever"), Severity::Unknown);
+        assert_eq!(normalize_id("cve-2023-0286"), "CVE-2023-0286");
+        assert_eq!(normalize_id("ghsa-35jh-r3h4-6jhm"), "GHSA-35jh-r3h4-6jhm");
+        assert_eq!(normalize_id("DLA-3325-1"), "DLA-3325-1");
+        let vulnerability = |id: &str, severity: &str, fixed_in: Option<&str>| Vulnerability {
+            package: String::from("openssl"),
+            version: String::from("3.0.7"),
+            id: id.to_owned(),
+            severity: severity.to_owned(),
+            fixed_in: fixed_in.map(String::from),
+            description: String::new(),
+            manager: String::from("apt"),
+        };
+        let report = SecurityReport {
+            vulnerabilities: vec![vulnerability("cve-2023-0464", "low", None),
+                                  vulnerability("CVE-2023-0286", "important", Some("3.0.8")),
+                                  vulnerability("CVE-2023-0215", "7.5", Some("3.0.8"))],
+            ..Default::default()
+        };
+        let advisories = report.advisories();
+        assert_eq!(advisories.iter().map(|a| a.id.as_str()).collect::<Vec<&str>>(),
+                   vec!["CVE-2023-0215", "CVE-2023-0286", "CVE-2023-0464"]);
+        assert_eq!(advisories[0].fixed_in, Some(Version::from("3.0.8")));
+        assert_eq!(advisories[0].to_string(), "high CVE-2023-0215 openssl (apt): 3.0.7, fixed in 3.0.8");
+    }
+
+    #[test]
+    fn checking_managers() {
+        let manager = |name: &str| PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/security"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true");
+        let set = ManagerSet::new(vec![
+            manager("npm").with_command(CommandKind::Audit, Scope::System, "./audit.sh"),
+            manager("rpm").with_command(CommandKind::Verify, Scope::System, "./verify.sh")
+                .with_command(CommandKind::Audit, Scope::System, "false"),
+            manager("apk"),
+        ]);
+        let report = SecurityReport::check(&set);
+        assert!(!report.is_clean());
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.vulnerabilities.iter().map(|v| v.id.as_str()).collect::<Vec<&str>>(),
+                   vec!["GHSA-35jh-r3h4-6jhm", "GHSA-p6mc-m468-83gw"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "rpm");
+    }
+}
@@ -0,0 +1,347 @@
+//! Settings for how frontends behave, shared so every frontend reads the same file instead of
+//! inventing its own. They are read from `upm.toml` in `/etc/upm` and then in the user's
+//! [configuration directory](../fn.user_config_dir.html), where the user's settings win:
+//!
+//! ```toml
+//! output = "json"
+//! assume_yes = true
+//! exclude = ["snap"]
+//! cache_ttl = "10m"
+//! refresh_after = "1d"
+//! sudo = "doas"
+//! audit_log = "/var/log/upm/audit.log"
+//!
+//! [priorities]
+//! flatpak = 20
+//! apt = 10
+//!
+//! [confirm]
+//! install = 5
+//!
+//! [snapshot]
+//! tool = "snapper"
+//!
+//! [license]
+//! deny = ["AGPL-*"]
+//!
+//! [concurrency]
+//! max_parallel = 2
+//! ```
+//!
+//! `config_dirs`, the [directories package managers are configured in](../dirs/index.html), is
+//! only read from the global file since the user's directory is always read first. `confirm` has
+//! the [thresholds](../confirm/index.html) under which operations go ahead without asking, and
+//! `snapshot` the [snapshots](../snapshot/index.html) taken around upgrades, and `license` the
+//! [licenses](../license/index.html) packages may be installed with. `audit_log` turns on the
+//! [audit log](../audit/index.html), relative to the settings file's directory. `concurrency` limits
+//! how many package managers are [run at once](../concurrency/index.html).
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use failure::Error;
+use toml::Value;
+use super::{ManagerSpecifier, PackageManager, refresh, user_config_dir};
+use concurrency::ConcurrencyPolicy;
+use confirm::ConfirmationPolicy;
+use license::LicensePolicy;
+use snapshot::SnapshotPolicy;
+
+/// The name of the settings file
+pub const FILE_NAME: &str = "upm.toml";
+
+/// The global settings file
+pub const GLOBAL_PATH: &str = "/etc/upm/upm.toml";
+
+/// The keys a settings file can have
+pub const KEYS: &[&str] = &["config_dirs", "output", "assume_yes", "priorities", "exclude", "cache_ttl",
+                            "refresh_after", "sudo", "confirm", "snapshot", "license", "audit_log", "concurrency"];
+
+/// How results are printed by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+    Porcelain,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<OutputFormat, Error> {
+        match name {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            "porcelain" => Ok(OutputFormat::Porcelain),
+            _ => bail!("Unknown output format {}, expected human, json, or porcelain", name),
+        }
+    }
+}
+
+/// How frontends behave unless told otherwise
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// The system directories package managers are configured in
+    pub config_dirs: Vec<PathBuf>,
+    /// How results are printed when no format is asked for
+    pub output: Option<OutputFormat>,
+    /// Whether to answer yes to every question
+    pub assume_yes: bool,
+    /// Priorities that replace those of the package managers' configurations
+    pub priorities: BTreeMap<String, i64>,
+    /// Package managers that are never used unless they're asked for by name
+    pub exclude: Vec<String>,
+    /// How long the results of queries are cached, if they are
+    pub cache_ttl: Option<Duration>,
+    /// How old package metadata may get before it's refreshed, for package managers whose
+    /// configurations don't say
+    pub refresh_after: Option<Duration>,
+    /// The program used to run commands as root, `sudo` if not set
+    pub sudo: Option<String>,
+    /// Which operations go ahead without asking
+    pub confirm: ConfirmationPolicy,
+    /// The snapshots taken around operations, if any are
+    pub snapshot: Option<SnapshotPolicy>,
+    /// The licenses packages may be installed with, if they're restricted
+    pub license: Option<LicensePolicy>,
+    /// The audit log the commands that change packages are recorded in, if they are
+    pub audit_log: Option<PathBuf>,
+    /// How many package managers are run at once, if not the default
+    pub concurrency: Option<ConcurrencyPolicy>,
+}
+
+impl Settings {
+    /// The settings used when there's no settings file
+    pub fn new() -> Settings {
+        Settings::default()
+    }
+
+    /// Read the settings from a TOML value. Relative `config_dirs` are relative to `base`.
+    pub fn from_toml(resource: &Value, base: &Path) -> Result<Settings, Error> {
+        let string = |key: &str| -> Result<Option<&str>, Error> {
+            match resource.get(key) {
+                Some(value) => match value.as_str() {
+                    Some(value) => Ok(Some(value)),
+                    None => bail!("{} must be a string", key),
+                },
+                None => Ok(None),
+            }
+        };
+        let strings = |key: &str| -> Result<Vec<String>, Error> {
+            match resource.get(key) {
+                Some(value) => match value.as_array() {
+                    Some(values) => values.iter()
+                        .map(|value| match value.as_str() {
+                            Some(value) => Ok(value.to_owned()),
+                            None => bail!("{} must be an array of strings", key),
+                        })
+                        .collect(),
+                    None => bail!("{} must be an array of strings", key),
+                },
+                None => Ok(Vec::new()),
+            }
+        };
+        let age = |key: &str| -> Result<Option<Duration>, Error> {
+            match string(key)? {
+                Some(age) => Ok(Some(refresh::parse_age(age)?)),
+                None => Ok(None),
+            }
+        };
+        if let Some(table) = resource.as_table() {
+            for key in table.keys().filter(|key| !KEYS.contains(&key.as_str())) {
+                warn!("Ignoring the unknown setting {}", key);
+            }
+        }
+        let assume_yes = match resource.get("assume_yes") {
+            Some(value) => match value.as_bool() {
+                Some(value) => value,
+                None => bail!("assume_yes must be true or false"),
+            },
+            None => false,
+        };
+        let mut priorities = BTreeMap::new();
+        if let Some(table) = resource.get("priorities") {
+            let table = match table.as_table() {
+                Some(table) => table,
+                None => bail!("priorities must be a table of package managers"),
+            };
+            for (name, priority) in table {
+                match priority.as_integer() {
+                    Some(priority) => priorities.insert(name.to_owned(), priority),
+                    None => bail!("The priority of {} must be an integer", name),
+                };
+            }
+        }
+        Ok(Settings {
+            config_dirs: strings("config_dirs")?.into_iter().map(|dir| base.join(dir)).collect(),
+            output: match string("output")? {
+                Some(format) => Some(format.parse()?),
+                None => None,
+            },
+            assume_yes,
+            priorities,
+            exclude: strings("exclude")?,
+            cache_ttl: age("cache_ttl")?,
+            refresh_after: age("refresh_after")?,
+            sudo: string("sudo")?.map(String::from),
+            confirm: match resource.get("confirm") {
+                Some(table) => ConfirmationPolicy::from_toml(table)?,
+                None => ConfirmationPolicy::new(),
+            },
+            snapshot: match resource.get("snapshot") {
+                Some(table) => Some(SnapshotPolicy::from_toml(table)?),
+                None => None,
+            },
+            license: match resource.get("license") {
+                Some(table) => Some(LicensePolicy::from_toml(table)?),
+                None => None,
+            },
+            audit_log: string("audit_log")?.map(|path| base.join(path)),
+            concurrency: match resource.get("concurrency") {
+                Some(table) => Some(ConcurrencyPolicy::from_toml(table)?),
+                None => None,
+            },
+        })
+    }
+
+    /// Read the settings from a file. A file that doesn't exist gives the default settings.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Settings, Error> {
+        let path = path.as_ref();
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Settings::new()),
+            Err(e) => return Err(e.into()),
+        }
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        Settings::from_toml(&content.parse::<Value>()?, base)
+            .map_err(|e| format_err!("{:?}: {}", path, e))
+    }
+
+    /// Read the global settings and then the user's
+    pub fn load_default() -> Result<Settings, Error> {
+        let mut settings = Settings::from_file(GLOBAL_PATH)?;
+        if let Some(dir) = user_config_dir() {
+            let mut user = Settings::from_file(dir.join(FILE_NAME))?;
+            user.config_dirs.clear();
+            settings.merge(user);
+        }
+        Ok(settings)
+    }
+
+    /// Replace these settings with those set in other settings. Priorities and confirmation
+    /// thresholds are combined and exclusions are added.
+    pub fn merge(&mut self, other: Settings) {
+        if !other.config_dirs.is_empty() {
+            self.config_dirs = other.config_dirs;
+        }
+        self.output = other.output.or(self.output);
+        self.assume_yes |= other.assume_yes;
+        self.priorities.extend(other.priorities);
+        for name in other.exclude {
+            if !self.exclude.contains(&name) {
+                self.exclude.push(name);
+            }
+        }
+        self.cache_ttl = other.cache_ttl.or(self.cache_ttl);
+        self.refresh_after = other.refresh_after.or(self.refresh_after);
+        self.sudo = other.sudo.or_else(|| self.sudo.take());
+        self.confirm.merge(other.confirm);
+        self.snapshot = other.snapshot.or_else(|| self.snapshot.take());
+        self.license = other.license.or_else(|| self.license.take());
+        self.audit_log = other.audit_log.or_else(|| self.audit_log.take());
+        self.concurrency = other.concurrency.or_else(|| self.concurrency.take());
+    }
+
+    /// The program used to run commands as root
+    pub fn sudo(&self) -> &str {
+        self.sudo.as_ref().map_or("sudo", |sudo| sudo.as_str())
+    }
+
+    /// How many package managers are run at once
+    pub fn concurrency(&self) -> ConcurrencyPolicy {
+        self.concurrency.clone().unwrap_or_default()
+    }
+
+    /// Add the excluded package managers to a specifier. Package managers that were asked for by
+    /// name are used even if they're excluded.
+    pub fn specifier(&self, specifier: ManagerSpecifier) -> ManagerSpecifier {
+        match specifier {
+            ManagerSpecifier::Includes(names) => ManagerSpecifier::Includes(names),
+            ManagerSpecifier::Excludes(mut names) => {
+                names.extend(self.exclude.iter().cloned());
+                ManagerSpecifier::Excludes(names)
+            },
+            ManagerSpecifier::Empty if self.exclude.is_empty() => ManagerSpecifier::Empty,
+            ManagerSpecifier::Empty => ManagerSpecifier::Excludes(self.exclude.iter().cloned().collect()),
+        }
+    }
+
+    /// Give package managers the priorities and metadata age of the settings
+    pub fn apply(&self, managers: &mut [PackageManager]) {
+        for manager in managers {
+            if let Some(&priority) = self.priorities.get(&manager.name) {
+                manager.priority = priority;
+            }
+            if manager.refresh_after.is_none() {
+                manager.refresh_after = self.refresh_after;
+            }
+        }
+    }
+}
+
+/// Is the file a package manager configuration, rather than the settings file?
+pub fn is_definition(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "toml")
+        && path.file_name().is_none_or(|name| name != FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use confirm::Action;
+
+    #[test]
+    fn reading_settings() {
+        let global = "config_dirs = ['managers']\noutput = 'json'\nexclude = ['snap']\nrefresh_after = '1d'\naudit_log = 'audit.log'\n\
+                      [priorities]\napt = 10\n";
+        let mut settings = Settings::from_toml(&global.parse::<Value>().unwrap(), Path::new("/etc/upm")).unwrap();
+        assert_eq!(settings.config_dirs, vec![PathBuf::from("/etc/upm/managers")]);
+        assert_eq!(settings.output, Some(OutputFormat::Json));
+        assert_eq!(settings.sudo(), "sudo");
+        assert_eq!(settings.audit_log, Some(PathBuf::from("/etc/upm/audit.log")));
+
+        let user = "output = 'human'\nassume_yes = true\nexclude = ['flatpak']\nsudo = 'doas'\n[priorities]\ncargo = 5\n\
+                    [confirm]\nremove = 2\n[snapshot]\ntool = 'timeshift'\n[license]\ndeny = ['AGPL-*']\n\
+                    [concurrency]\nmax_parallel = 2\n[concurrency.lock_classes]\ndpkg = ['apt', 'aptitude']\n";
+        settings.merge(Settings::from_toml(&user.parse::<Value>().unwrap(), Path::new("")).unwrap());
+        assert_eq!(settings.output, Some(OutputFormat::Human));
+        assert!(settings.assume_yes);
+        assert_eq!(settings.exclude, vec!["snap", "flatpak"]);
+        assert_eq!(settings.priorities.len(), 2);
+        assert_eq!(settings.refresh_after, Some(Duration::from_secs(86_400)));
+        assert_eq!(settings.sudo(), "doas");
+        assert_eq!(settings.confirm.thresholds.get(&Action::Remove), Some(&2));
+        assert_eq!(settings.snapshot, Some(SnapshotPolicy::tool("timeshift").unwrap()));
+        assert_eq!(settings.license.as_ref().unwrap().deny, vec!["AGPL-*"]);
+        assert_eq!(settings.concurrency().max_parallel, 2);
+        assert_eq!(settings.concurrency().lock_classes.get("aptitude").map(String::as_str), Some("dpkg"));
+        assert_eq!(Settings::new().concurrency(), ConcurrencyPolicy::default());
+        assert!(!settings.specifier(ManagerSpecifier::Empty).allows("snap"));
+
+        let mut managers = vec![PackageManager { name: String::from("apt"), ..Default::default() }];
+        settings.apply(&mut managers);
+        assert_eq!(managers[0].priority, 10);
+        assert_eq!(managers[0].refresh_after, Some(Duration::from_secs(86_400)));
+
+        assert!(Settings::from_toml(&"output = 'xml'".parse::<Value>().unwrap(), Path::new("")).is_err());
+        assert!(Settings::from_toml(&"assume_yes = 'yes'".parse::<Value>().unwrap(), Path::new("")).is_err());
+        assert!(Settings::from_toml(&"[concurrency]\nmax_parallel = 0".parse::<Value>().unwrap(), Path::new("")).is_err());
+        assert!(is_definition(Path::new("/etc/upm/managers/apt.toml")));
+        assert!(!is_definition(Path::new("/home/me/.config/upm/upm.toml")));
+    }
+}
@@ -0,0 +1,143 @@
+//! Finding which install of a binary actually runs. A package installed both in the user's home
+//! directory, such as with `pip install --user`, and for the whole system leaves two binaries in
+//! `PATH`, and only the one in the directory searched first runs. [`analyze`](fn.analyze.html)
+//! finds every copy of a package's binaries, asks the package managers which package owns each
+//! one, and warns when the copy that wins is older than one it shadows.
+//!
+//! Binaries under the home directory are in the [user scope](../enum.Scope.html) and the rest are
+//! in the system scope.
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use super::{ManagerSet, Scope, Version, find_all_in_path};
+
+/// A copy of a binary found in `PATH`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryInstall {
+    pub path: PathBuf,
+    pub scope: Scope,
+    /// The package manager whose package owns the binary and the version of the package, if a
+    /// package manager with an owns_file command claims it
+    pub owner: Option<(String, Version)>,
+}
+
+impl BinaryInstall {
+    /// The version of the package that owns the binary, if it's known
+    pub fn version(&self) -> Option<&Version> {
+        self.owner.as_ref().map(|(_, version)| version)
+    }
+}
+
+impl fmt::Display for BinaryInstall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.owner {
+            Some((ref manager, ref version)) => write!(f, "{} ({} {}, {})", self.path.display(), manager, version, self.scope.name()),
+            None => write!(f, "{} ({})", self.path.display(), self.scope.name()),
+        }
+    }
+}
+
+/// Every copy of a binary in `PATH`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadowing {
+    pub binary: String,
+    /// The copies in the order `PATH` is searched, so the first one is the one that runs
+    pub installs: Vec<BinaryInstall>,
+}
+
+impl Shadowing {
+    /// The copy that runs, if there is one
+    pub fn winner(&self) -> Option<&BinaryInstall> {
+        self.installs.first()
+    }
+
+    /// Does the copy that runs shadow others?
+    pub fn is_shadowed(&self) -> bool {
+        self.installs.len() > 1
+    }
+
+    /// The newest copy shadowed by an older one that runs. Copies whose versions can't be
+    /// compared with the one that runs are ignored.
+    pub fn newer_shadowed(&self) -> Option<&BinaryInstall> {
+        let winner = self.winner()?.version()?;
+        let newer = self.installs[1..].iter().filter(|install| install.version().is_some_and(|v| v.is_newer_than(winner)));
+        newer.fold(None, |newest: Option<&BinaryInstall>, install| match newest {
+            Some(newest) if !install.version().unwrap().is_newer_than(newest.version().unwrap()) => Some(newest),
+            _ => Some(install),
+        })
+    }
+
+    /// Describe a stale copy that shadows a newer one, if there is one
+    pub fn warning(&self) -> Option<String> {
+        let newer = self.newer_shadowed()?;
+        Some(format!("{} shadows the newer {}", self.winner().unwrap(), newer))
+    }
+}
+
+/// Find every copy of the binaries in `PATH` and the packages that own them
+pub fn analyze(managers: &ManagerSet, binaries: &[&str]) -> Vec<Shadowing> {
+    let path = ::std::env::var_os("PATH").unwrap_or_default();
+    let home = ::std::env::var_os("HOME").map(PathBuf::from);
+    analyze_with(managers, binaries, &path, home.as_deref())
+}
+
+//Finds the copies of the binaries in the given PATH value, with binaries under home in the
+//user scope
+fn analyze_with(managers: &ManagerSet, binaries: &[&str], path: &OsStr, home: Option<&Path>) -> Vec<Shadowing> {
+    binaries.iter().map(|&binary| Shadowing {
+        binary: binary.to_owned(),
+        installs: find_all_in_path(binary, path).into_iter().map(|path| BinaryInstall {
+            scope: scope_of(&path, home),
+            owner: managers.who_owns(&path).into_iter().next()
+                .map(|package| (package.owner.name, package.version)),
+            path,
+        }).collect(),
+    }).collect()
+}
+
+fn scope_of(path: &Path, home: Option<&Path>) -> Scope {
+    match home {
+        Some(home) if path.starts_with(home) => Scope::User,
+        _ => Scope::System,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use super::super::{CommandKind, PackageManager};
+
+    #[test]
+    fn stale_user_install() {
+        let manager = |name: &str| PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/shadow"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::OwnsFile, Scope::System, format!("./{}.sh", name));
+        let set = ManagerSet::new(vec![manager("apt"), manager("pip")]);
+        let root = env::temp_dir().join(format!("upm-shadow-{}", ::std::process::id()));
+        let (home, system) = (root.join("home"), root.join("usr"));
+        let local = home.join(".local").join("bin");
+        fs::create_dir_all(&local).unwrap();
+        fs::create_dir_all(&system).unwrap();
+        fs::write(local.join("ansible"), "").unwrap();
+        fs::write(system.join("ansible"), "").unwrap();
+        fs::write(system.join("curl"), "").unwrap();
+        let path = env::join_paths(vec![&local, &system]).unwrap();
+        let found = analyze_with(&set, &["ansible", "curl", "missing"], &path, Some(&home));
+        let ansible = &found[0];
+        assert!(ansible.is_shadowed());
+        assert_eq!(ansible.winner().unwrap().scope, Scope::User);
+        assert_eq!(ansible.installs[1].scope, Scope::System);
+        assert_eq!(ansible.warning().unwrap(), format!("{} (pip 2.9.0, user) shadows the newer {} (apt 2.10.0, system)",
+                                                       local.join("ansible").display(), system.join("ansible").display()));
+        assert!(!found[1].is_shadowed());
+        assert_eq!(found[1].warning(), None);
+        assert!(found[2].winner().is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+//! Taking filesystem snapshots around operations such as upgrading everything, so a bad upgrade
+//! can be rolled back by hand. The [settings](../settings/index.html) can name a snapshot tool:
+//!
+//! ```toml
+//! [snapshot]
+//! tool = "snapper"
+//! ```
+//!
+//! or give the commands themselves:
+//!
+//! ```toml
+//! [snapshot]
+//! pre = "sudo btrfs subvolume snapshot -r / /.snapshots/{label}"
+//! id_regex = "in '(?P<id>[^']+)'"
+//! operations = ["upgrade_all", "install"]
+//! required = false
+//! ```
+//!
+//! `{label}` is replaced by a label naming the operation and its transaction id, such as
+//! `upm-upgrade_all-1717171717-4242`, and `{pre}` in the post command by the id of the snapshot
+//! taken before. A snapshot's id is the `id` captured by `id_regex` from the command's output,
+//! otherwise the last line of the output, otherwise the label. The id of the snapshot taken before
+//! is recorded with every command of the operation in the [history](../history/index.html).
+//!
+//! Snapshots are taken around `upgrade_all` unless `operations` says otherwise. If the snapshot
+//! before can't be taken the operation doesn't run, unless `required` is false.
+
+use std::process::{self, Command, Stdio};
+use std::time::SystemTime;
+use failure::Error;
+use regex::Regex;
+use toml::Value;
+use super::ManagerSet;
+use history::{Operation, epoch_seconds};
+
+/// The placeholder replaced by the label of the snapshot
+pub const LABEL_PLACEHOLDER: &str = "{label}";
+
+/// The placeholder in the post command replaced by the id of the snapshot taken before
+pub const PRE_PLACEHOLDER: &str = "{pre}";
+
+/// The snapshot tools with built-in commands
+pub const TOOLS: &[&str] = &["snapper", "timeshift", "btrfs"];
+
+/// How snapshots are taken around operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotPolicy {
+    /// The command taking a snapshot before the operation
+    pub pre: String,
+    /// The command taking a snapshot after the operation, if there is one
+    pub post: Option<String>,
+    /// A regex with a capture named `id` pulling the snapshot's id out of the output
+    pub id_regex: Option<String>,
+    /// The operations snapshots are taken around
+    pub operations: Vec<Operation>,
+    /// Whether the operation is refused when the snapshot before can't be taken
+    pub required: bool,
+}
+
+/// The snapshots taken around an operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The id of the operation, which is part of the label
+    pub transaction: String,
+    pub label: String,
+    /// The id of the snapshot taken before, None if it couldn't be taken
+    pub pre: Option<String>,
+    /// The id of the snapshot taken after, if there is a post command and it worked
+    pub post: Option<String>,
+}
+
+impl SnapshotPolicy {
+    /// The built-in policy for a snapshot tool, one of the [`TOOLS`](constant.TOOLS.html)
+    pub fn tool(name: &str) -> Result<SnapshotPolicy, Error> {
+        let (pre, post, id_regex) = match name {
+            "snapper" => ("snapper create --type pre --cleanup-algorithm number --print-number --description {label}",
+                          Some("snapper create --type post --pre-number {pre} --cleanup-algorithm number --print-number --description {label}"),
+                          None),
+            "timeshift" => ("timeshift --create --scripted --comments {label}", None, Some(r"Tagged snapshot '(?P<id>[^']+)'")),
+            "btrfs" => ("btrfs subvolume snapshot -r / /.snapshots/{label}", None, Some(r"in '(?P<id>[^']+)'")),
+            _ => bail!("Unknown snapshot tool {}, expected one of {}", name, TOOLS.join(", ")),
+        };
+        Ok(SnapshotPolicy {
+            pre: pre.to_owned(),
+            post: post.map(String::from),
+            id_regex: id_regex.map(String::from),
+            operations: vec![Operation::UpgradeAll],
+            required: true,
+        })
+    }
+
+    /// Read a policy from the `snapshot` table of the settings
+    pub fn from_toml(table: &Value) -> Result<SnapshotPolicy, Error> {
+        let string = |key: &str| -> Result<Option<String>, Error> {
+            match table.get(key) {
+                Some(value) => match value.as_str() {
+                    Some(value) => Ok(Some(value.to_owned())),
+                    None => bail!("snapshot {} must be a string", key),
+                },
+                None => Ok(None),
+            }
+        };
+        let mut policy = match string("tool")? {
+            Some(tool) => SnapshotPolicy::tool(&tool)?,
+            None => SnapshotPolicy {
+                pre: String::new(),
+                post: None,
+                id_regex: None,
+                operations: vec![Operation::UpgradeAll],
+                required: true,
+            },
+        };
+        if let Some(pre) = string("pre")? {
+            policy.pre = pre;
+        }
+        if let Some(post) = string("post")? {
+            policy.post = Some(post);
+        }
+        if let Some(pattern) = string("id_regex")? {
+            id_regex(&pattern)?;
+            policy.id_regex = Some(pattern);
+        }
+        if let Some(operations) = table.get("operations") {
+            policy.operations = match operations.as_array() {
+                Some(operations) => operations.iter()
+                    .map(|operation| match operation.as_str() {
+                        Some(operation) => operation.parse(),
+                        None => bail!("snapshot operations must be strings"),
+                    })
+                    .collect::<Result<Vec<Operation>, Error>>()?,
+                None => bail!("snapshot operations must be an array of operations"),
+            };
+        }
+        if let Some(required) = table.get("required") {
+            policy.required = match required.as_bool() {
+                Some(required) => required,
+                None => bail!("snapshot required must be true or false"),
+            };
+        }
+        if policy.pre.trim().is_empty() {
+            bail!("snapshot needs a tool or a pre command");
+        }
+        Ok(policy)
+    }
+
+    /// Are snapshots taken around the operation?
+    pub fn applies_to(&self, operation: Operation) -> bool {
+        self.operations.contains(&operation)
+    }
+
+    /// Run an operation with the package managers, taking snapshots around it if the policy
+    /// applies to it. The package managers the operation is given record the snapshot taken
+    /// before in the history. Gives an error without running the operation if the snapshot is
+    /// required and couldn't be taken.
+    pub fn around<T, F>(&self, operation: Operation, managers: &ManagerSet, run: F) -> Result<(T, Option<Snapshot>), Error>
+        where F: FnOnce(&ManagerSet) -> T
+    {
+        if !self.applies_to(operation) {
+            return Ok((run(managers), None));
+        }
+        let transaction = transaction_id();
+        let label = format!("upm-{}-{}", operation, transaction);
+        let pre = match self.take(&self.pre, &label, None) {
+            Ok(id) => Some(id),
+            Err(e) if self.required => bail!("Couldn't take a snapshot before {}: {}", operation, e),
+            Err(e) => {
+                warn!("Couldn't take a snapshot before {}, continuing without one: {}", operation, e);
+                None
+            },
+        };
+        let mut snapshotted = managers.clone();
+        snapshotted.set_snapshot(pre.clone());
+        let result = run(&snapshotted);
+        let post = match (self.post.as_ref(), pre.as_ref()) {
+            (Some(post), Some(pre)) => match self.take(post, &label, Some(pre)) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    warn!("Couldn't take a snapshot after {}: {}", operation, e);
+                    None
+                },
+            },
+            _ => None,
+        };
+        Ok((result, Some(Snapshot { transaction, label, pre, post })))
+    }
+
+    //Runs a snapshot command, giving the id of the snapshot
+    fn take(&self, template: &str, label: &str, pre: Option<&str>) -> Result<String, Error> {
+        let mut words = template.split_whitespace().map(|word| {
+            word.replace(LABEL_PLACEHOLDER, label).replace(PRE_PLACEHOLDER, pre.unwrap_or(""))
+        });
+        let mut command = Command::new(words.next().unwrap_or_default());
+        command.args(words).stdin(Stdio::null()).stderr(Stdio::inherit());
+        debug!("Taking a snapshot with {:?}", command);
+        let output = command.output()?;
+        if !output.status.success() {
+            bail!("{} exited with {}", template.split_whitespace().next().unwrap_or_default(), output.status);
+        }
+        parse_id(&String::from_utf8_lossy(&output.stdout), self.id_regex.as_deref(), label)
+    }
+}
+
+impl Snapshot {
+    /// Was the snapshot before the operation taken?
+    pub fn is_taken(&self) -> bool {
+        self.pre.is_some()
+    }
+}
+
+/// Get the id of a snapshot from the output of the command that took it
+pub fn parse_id(output: &str, pattern: Option<&str>, label: &str) -> Result<String, Error> {
+    if let Some(pattern) = pattern {
+        return match id_regex(pattern)?.captures(output) {
+            Some(captures) => Ok(captures["id"].to_owned()),
+            None => bail!("The snapshot id wasn't found in the output"),
+        };
+    }
+    Ok(output.lines().rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or(label)
+        .to_owned())
+}
+
+/// Create an id for an operation, unique on this machine
+pub fn transaction_id() -> String {
+    format!("{}-{}", epoch_seconds(SystemTime::now()), process::id())
+}
+
+//Compiles an id regex, checking that it has the id capture
+fn id_regex(pattern: &str) -> Result<Regex, Error> {
+    let regex = Regex::new(pattern)?;
+    if !regex.capture_names().any(|name| name == Some("id")) {
+        bail!("The snapshot id_regex needs a capture named id");
+    }
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use super::super::{CommandKind, PackageManager, Scope};
+    use history::HistoryLog;
+
+    fn policy(config: &str) -> Result<SnapshotPolicy, Error> {
+        SnapshotPolicy::from_toml(&config.parse::<Value>().unwrap())
+    }
+
+    #[test]
+    fn policies() {
+        let snapper = policy("tool = 'snapper'\n").unwrap();
+        assert!(snapper.post.as_ref().unwrap().contains(PRE_PLACEHOLDER));
+        assert!(snapper.applies_to(Operation::UpgradeAll) && !snapper.applies_to(Operation::Install));
+        let custom = policy("pre = 'echo {label}'\noperations = ['install']\nrequired = false\n").unwrap();
+        assert_eq!(custom.post, None);
+        assert!(custom.applies_to(Operation::Install) && !custom.required);
+        assert!(policy("tool = 'zfs'\n").is_err());
+        assert!(policy("required = true\n").is_err());
+        assert!(policy("pre = 'true'\nid_regex = '[0-9]+'\n").is_err());
+        assert!(policy("pre = 'true'\noperations = ['reboot']\n").is_err());
+
+        assert_eq!(parse_id("Creating snapshot\n42\n\n", None, "label").unwrap(), "42");
+        assert_eq!(parse_id("", None, "label").unwrap(), "label");
+        assert_eq!(parse_id("Tagged snapshot '2024-03-01_12-00-00': ondemand", SnapshotPolicy::tool("timeshift").unwrap().id_regex.as_deref(),
+                            "label").unwrap(), "2024-03-01_12-00-00");
+    }
+
+    #[test]
+    fn snapshots_around_operations() {
+        let log = Arc::new(HistoryLog::at(temp_dir().join(format!("upm-snapshot-{}.log", process::id()))));
+        let _ = remove_file(log.path());
+        let mut manager = PackageManager {
+            name: String::from("dnf"),
+            config_dir: PathBuf::from("./test-files/"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::UpgradeAll, Scope::System, "true");
+        manager.set_history(log.clone());
+        let managers = ManagerSet::new(vec![manager]);
+
+        let policy = policy("pre = 'echo 7'\npost = 'echo {pre}-after'\n").unwrap();
+        let (status, snapshot) = policy.around(Operation::UpgradeAll, &managers, |managers| {
+            managers.get("dnf").unwrap().execute("upgrade_all", "").unwrap()
+        }).unwrap();
+        assert!(status.success());
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.pre.as_deref(), Some("7"));
+        assert_eq!(snapshot.post.as_deref(), Some("7-after"));
+        assert!(snapshot.label.starts_with("upm-upgrade_all-"));
+        assert_eq!(log.entries().unwrap()[0].snapshot.as_deref(), Some("7"));
+
+        let (_, skipped) = policy.around(Operation::Install, &managers, |_| ()).unwrap();
+        assert_eq!(skipped, None);
+        let failing = SnapshotPolicy { pre: String::from("false"), ..policy.clone() };
+        assert!(failing.around(Operation::UpgradeAll, &managers, |_| panic!("ran without a snapshot")).is_err());
+        let optional = SnapshotPolicy { required: false, ..failing };
+        let (_, snapshot) = optional.around(Operation::UpgradeAll, &managers, |_| ()).unwrap();
+        assert!(!snapshot.unwrap().is_taken());
+        remove_file(log.path()).unwrap();
+    }
+}
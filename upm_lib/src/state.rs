@@ -0,0 +1,179 @@
+//! State upm keeps about package managers between runs. The
+//! [`StateDb`](struct.StateDb.html) records when each package manager's metadata was last
+//! refreshed successfully, so frontends can show how stale it is and the
+//! [auto-refresh](../refresh/index.html) knows when it's needed even for package managers without
+//! a `metadata_path`.
+//!
+//! The database is `state.toml` in the [data directory](../fn.data_dir.html), with a table of
+//! times in seconds since the Unix epoch:
+//!
+//! ```toml
+//! [refreshed]
+//! apt = 1528311542
+//! ```
+
+use std::fmt;
+use std::fs::{OpenOptions, create_dir_all};
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use failure::Error;
+use toml::Value;
+use toml::value::Table;
+use super::data_dir;
+use history::epoch_seconds;
+use refresh::describe_age;
+
+/// Get the path of the default state database, `state.toml` in the data directory
+pub fn state_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("state.toml"))
+}
+
+/// A file of state kept between runs. Every access locks the file, so several processes can
+/// update it at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDb {
+    path: PathBuf,
+}
+
+impl StateDb {
+    /// Use the database at the given path, which is created when something is recorded
+    pub fn at<P: AsRef<Path>>(path: P) -> StateDb {
+        StateDb { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Use the default database, if there's a data directory
+    pub fn default_db() -> Option<StateDb> {
+        state_path().map(StateDb::at)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get when a package manager's metadata was last refreshed through upm
+    pub fn last_refresh(&self, manager: &str) -> Result<Option<SystemTime>, Error> {
+        let state = self.read()?;
+        Ok(state.get("refreshed")
+            .and_then(|refreshed| refreshed.get(manager))
+            .and_then(|seconds| seconds.as_integer())
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64)))
+    }
+
+    /// Record that a package manager's metadata was refreshed at the given time
+    pub fn record_refresh(&self, manager: &str, time: SystemTime) -> Result<(), Error> {
+        self.update(|state| {
+            let refreshed = state.entry(String::from("refreshed")).or_insert_with(|| Value::Table(Table::new()));
+            match *refreshed {
+                Value::Table(ref mut refreshed) => {
+                    refreshed.insert(manager.to_owned(), Value::Integer(epoch_seconds(time) as i64));
+                    Ok(())
+                },
+                _ => bail!("refreshed must be a table"),
+            }
+        })
+    }
+
+    //Reads the whole database, giving an empty one if it doesn't exist
+    fn read(&self) -> Result<Table, Error> {
+        let mut file = match OpenOptions::new().read(true).open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ::std::io::ErrorKind::NotFound => return Ok(Table::new()),
+            Err(e) => return Err(e.into()),
+        };
+        file.lock_shared()?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        parse(&content)
+    }
+
+    //Changes the database while holding an exclusive lock on it
+    fn update<F: FnOnce(&mut Table) -> Result<(), Error>>(&self, change: F) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&self.path)?;
+        file.lock()?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let mut state = parse(&content)?;
+        change(&mut state)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.set_len(0)?;
+        file.write_all(Value::Table(state).to_string().as_bytes())?;
+        Ok(())
+    }
+}
+
+fn parse(content: &str) -> Result<Table, Error> {
+    match content.parse::<Value>()? {
+        Value::Table(table) => Ok(table),
+        _ => bail!("The state database isn't a table"),
+    }
+}
+
+/// How old a package manager's metadata is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Staleness {
+    pub manager: String,
+    /// When the metadata was last refreshed, if that's known
+    pub refreshed: Option<SystemTime>,
+    /// Whether the metadata is older than the package manager's refresh_after. Metadata of package
+    /// managers without refresh_after is never stale.
+    pub stale: bool,
+}
+
+impl Staleness {
+    /// The age of the metadata, if it's known
+    pub fn age(&self) -> Option<Duration> {
+        self.refreshed.and_then(|time| time.elapsed().ok())
+    }
+}
+
+impl fmt::Display for Staleness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.age() {
+            Some(age) => write!(f, "{} metadata is {} old", self.manager, describe_age(age))?,
+            None if self.refreshed.is_some() => write!(f, "{} metadata was refreshed in the future", self.manager)?,
+            None => write!(f, "{} metadata has never been refreshed by upm", self.manager)?,
+        }
+        if self.stale {
+            write!(f, " and will be refreshed")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+
+    #[test]
+    fn recording_refreshes() {
+        let db = StateDb::at(temp_dir().join(format!("upm-state-{}.toml", ::std::process::id())));
+        let _ = remove_file(db.path());
+        assert_eq!(db.last_refresh("apt").unwrap(), None);
+        let time = UNIX_EPOCH + Duration::from_secs(1_528_311_542);
+        db.record_refresh("apt", time).unwrap();
+        db.record_refresh("pacman", SystemTime::now()).unwrap();
+        assert_eq!(db.last_refresh("apt").unwrap(), Some(time));
+        assert!(db.last_refresh("pacman").unwrap().is_some());
+        assert_eq!(db.last_refresh("brew").unwrap(), None);
+        remove_file(db.path()).unwrap();
+    }
+
+    #[test]
+    fn describing_staleness() {
+        let staleness = Staleness {
+            manager: String::from("apt"),
+            refreshed: Some(SystemTime::now() - Duration::from_secs(12 * 86_400 + 60)),
+            stale: true,
+        };
+        assert_eq!(staleness.to_string(), "apt metadata is 12 days old and will be refreshed");
+        let never = Staleness { manager: String::from("brew"), refreshed: None, stale: false };
+        assert_eq!(never.to_string(), "brew metadata has never been refreshed by upm");
+    }
+}
@@ -0,0 +1,160 @@
+//! Comparing the installed version of a package with the version that would be installed.
+//! [`status`](fn.status.html) asks every package manager about a package the way
+//! [`which`](../which/index.html) does and says, for each one, whether the package is up to date,
+//! can be upgraded, or isn't installed, comparing versions with the package manager's version
+//! scheme.
+
+use std::cmp::Ordering;
+use std::fmt;
+use super::{ManagerSet, Version, VersionScheme};
+use alias::AliasResolver;
+use which::{self, Availability};
+
+/// How the installed version of a package compares with the version that would be installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeState {
+    /// The package isn't installed through the package manager
+    NotInstalled,
+    /// The installed version is the one that would be installed
+    UpToDate,
+    /// A newer version would be installed
+    Upgradable,
+    /// The installed version is newer than the one that would be installed, such as one
+    /// installed from elsewhere
+    Ahead,
+    /// The package is installed, but the package manager doesn't offer it or the versions can't
+    /// be compared
+    Unknown,
+}
+
+impl UpgradeState {
+    /// Compare an installed version with the version that would be installed
+    pub fn compare(installed: Option<&Version>, candidate: Option<&Version>, scheme: VersionScheme) -> UpgradeState {
+        let installed = match installed {
+            Some(installed) => installed,
+            None => return UpgradeState::NotInstalled,
+        };
+        match candidate.and_then(|candidate| candidate.cmp_with(installed, scheme)) {
+            Some(Ordering::Equal) => UpgradeState::UpToDate,
+            Some(Ordering::Greater) => UpgradeState::Upgradable,
+            Some(Ordering::Less) => UpgradeState::Ahead,
+            None => UpgradeState::Unknown,
+        }
+    }
+
+    /// The name of the state in machine-readable output
+    pub fn name(self) -> &'static str {
+        match self {
+            UpgradeState::NotInstalled => "not_installed",
+            UpgradeState::UpToDate => "up_to_date",
+            UpgradeState::Upgradable => "upgradable",
+            UpgradeState::Ahead => "ahead",
+            UpgradeState::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for UpgradeState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            UpgradeState::NotInstalled => "not installed",
+            UpgradeState::UpToDate => "up to date",
+            UpgradeState::Upgradable => "upgrade available",
+            UpgradeState::Ahead => "newer than available",
+            UpgradeState::Unknown => "unknown",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// A package as seen by one package manager, with its installed and candidate versions compared
+#[derive(Debug, Clone)]
+pub struct PackageStatus {
+    /// The name of the package manager
+    pub manager: String,
+    /// The name of the package for the package manager, after aliases
+    pub name: String,
+    /// The version installed through the package manager, if it's installed
+    pub installed: Option<Version>,
+    /// The version the package manager would install, if it offers the package
+    pub candidate: Option<Version>,
+    pub state: UpgradeState,
+}
+
+impl PackageStatus {
+    /// Compare the versions a package manager found with its version scheme
+    pub fn from_availability(availability: Availability, scheme: VersionScheme) -> PackageStatus {
+        let state = UpgradeState::compare(availability.installed.as_ref(), availability.available.as_ref(), scheme);
+        PackageStatus {
+            manager: availability.manager,
+            name: availability.name,
+            installed: availability.installed,
+            candidate: availability.available,
+            state,
+        }
+    }
+
+    /// Can the package be upgraded through the package manager?
+    pub fn has_upgrade(&self) -> bool {
+        self.state == UpgradeState::Upgradable
+    }
+}
+
+impl fmt::Display for PackageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.manager, self.name)?;
+        if let Some(ref version) = self.installed {
+            write!(f, " installed {}", version)?;
+        }
+        if let Some(ref version) = self.candidate {
+            let separator = if self.installed.is_some() { "," } else { "" };
+            write!(f, "{} candidate {}", separator, version)?;
+        }
+        write!(f, " ({})", self.state)
+    }
+}
+
+/// Compare the installed and candidate versions of a package for every package manager that
+/// offers it or has it installed, found as [`which::locate`](../which/fn.locate.html) finds them
+pub fn status(managers: &ManagerSet, name: &str, aliases: &AliasResolver) -> Vec<PackageStatus> {
+    which::locate(managers, name, aliases).into_iter()
+        .map(|availability| {
+            let scheme = managers.as_slice().iter()
+                .find(|manager| manager.name == availability.manager)
+                .map_or_else(VersionScheme::default, |manager| manager.version_scheme);
+            PackageStatus::from_availability(availability, scheme)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, PackageManager, Scope};
+
+    #[test]
+    fn comparing() {
+        let version = |version: &str| Version::from(version);
+        let compare = |installed: Option<&str>, candidate: Option<&str>| {
+            UpgradeState::compare(installed.map(version).as_ref(), candidate.map(version).as_ref(), VersionScheme::Semver)
+        };
+        assert_eq!(compare(None, Some("1.0.0")), UpgradeState::NotInstalled);
+        assert_eq!(compare(Some("1.0.0"), Some("1.0.0")), UpgradeState::UpToDate);
+        assert_eq!(compare(Some("1.0.0"), Some("1.1.0")), UpgradeState::Upgradable);
+        assert_eq!(compare(Some("1.1.0"), Some("1.0.0")), UpgradeState::Ahead);
+        assert_eq!(compare(Some("1.0.0"), None), UpgradeState::Unknown);
+
+        let cargo = PackageManager {
+            name: String::from("cargo"),
+            config_dir: PathBuf::from("./test-files/"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::Search, Scope::System, "./which/search.sh")
+            .with_command(CommandKind::ListInstalled, Scope::System, "./manifest/list.sh");
+        let found = status(&ManagerSet::new(vec![cargo]), "ripgrep", &AliasResolver::new());
+        assert_eq!(found.len(), 1);
+        assert!(found[0].has_upgrade());
+        assert_eq!(found[0].to_string(), "cargo: ripgrep installed 0.7.1, candidate 0.8.0 (upgrade available)");
+    }
+}
@@ -0,0 +1,180 @@
+//! Searching without waiting for the slowest package manager. A
+//! [`SearchStream`](struct.SearchStream.html) runs the searches in the background and gives the
+//! results of each package manager as soon as its output is parsed, so a frontend can show the
+//! first results while the rest are still coming. Searching for something as common as `python`
+//! can find thousands of packages, so the results of each package manager can be capped.
+//!
+//! ```no_run
+//! # use upm_lib::{ManagerSet, ManagerSpecifier};
+//! # use upm_lib::stream::{SearchOptions, SearchUpdate};
+//! let managers = ManagerSet::from_config_dirs(vec!["/etc/upm/managers"], &ManagerSpecifier::Empty);
+//! for update in managers.search_stream("python", SearchOptions::new().with_limit(50)) {
+//!     if let SearchUpdate::Found { manager, packages, .. } = update {
+//!         println!("{}: {} results", manager, packages.len());
+//!     }
+//! }
+//! ```
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use failure::Error;
+use super::{Package, PackageManager};
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// How a streaming search is run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// The most results given for each package manager, or None for all of them
+    pub limit: Option<usize>,
+    /// How many package managers are searched at once
+    pub policy: ConcurrencyPolicy,
+}
+
+impl SearchOptions {
+    /// Give every result, searching as many package managers at once as the default policy allows
+    pub fn new() -> SearchOptions {
+        SearchOptions::default()
+    }
+
+    /// Give at most this many results for each package manager
+    pub fn with_limit(mut self, limit: usize) -> SearchOptions {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Search package managers as the policy allows
+    pub fn with_policy(mut self, policy: ConcurrencyPolicy) -> SearchOptions {
+        self.policy = policy;
+        self
+    }
+}
+
+/// The results of one package manager's search
+pub enum SearchUpdate {
+    /// The package manager's search finished. `truncated` is whether results past the limit were
+    /// left out.
+    Found { manager: String, packages: Vec<Package>, truncated: bool },
+    /// The package manager's search failed
+    Failed { manager: String, error: Error },
+}
+
+impl SearchUpdate {
+    /// The name of the package manager the update is for
+    pub fn manager(&self) -> &str {
+        match *self {
+            SearchUpdate::Found { ref manager, .. } | SearchUpdate::Failed { ref manager, .. } => manager,
+        }
+    }
+}
+
+/// The results of a search, given by package manager in the order they finish. Iterating blocks
+/// until the next package manager finishes and ends once they all have.
+pub struct SearchStream {
+    updates: Receiver<SearchUpdate>,
+    //An update taken to check whether the search is done
+    pending: Option<SearchUpdate>,
+}
+
+impl SearchStream {
+    /// Search every package manager with a search command in the background
+    pub fn start(managers: Vec<PackageManager>, query: &str, options: SearchOptions) -> SearchStream {
+        let (sender, updates) = mpsc::channel();
+        let query = query.to_owned();
+        thread::spawn(move || {
+            let managers: Vec<PackageManager> = managers.into_iter()
+                .filter(|manager| manager.has_command("search"))
+                .collect();
+            run_all(&managers, &options.policy, |manager| {
+                let update = match manager.search_packages(&query) {
+                    Ok(mut packages) => {
+                        let truncated = options.limit.is_some_and(|limit| packages.len() > limit);
+                        if let Some(limit) = options.limit {
+                            packages.truncate(limit);
+                        }
+                        SearchUpdate::Found { manager: manager.name.clone(), packages, truncated }
+                    },
+                    Err(error) => SearchUpdate::Failed { manager: manager.name.clone(), error },
+                };
+                //The receiver is gone if the frontend stopped listening, and the rest of the
+                //results are dropped
+                let _ = sender.send(update);
+            });
+        });
+        SearchStream { updates, pending: None }
+    }
+
+    /// Get the next update if a package manager has finished, without waiting. Gives None both
+    /// when no package manager has finished since the last update and when the search is done,
+    /// which [`is_done`](#method.is_done) tells apart.
+    pub fn try_next(&mut self) -> Option<SearchUpdate> {
+        self.pending.take().or_else(|| self.updates.try_recv().ok())
+    }
+
+    /// Wait up to a timeout for the next update
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<SearchUpdate> {
+        self.pending.take().or_else(|| self.updates.recv_timeout(timeout).ok())
+    }
+
+    /// Has every package manager finished and every update been taken?
+    pub fn is_done(&mut self) -> bool {
+        if self.pending.is_some() {
+            return false;
+        }
+        match self.updates.try_recv() {
+            Ok(update) => {
+                self.pending = Some(update);
+                false
+            },
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => true,
+        }
+    }
+}
+
+impl Iterator for SearchStream {
+    type Item = SearchUpdate;
+
+    fn next(&mut self) -> Option<SearchUpdate> {
+        self.pending.take().or_else(|| self.updates.recv().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use super::super::{CommandKind, Scope};
+
+    fn manager(name: &str, search: &str) -> PackageManager {
+        PackageManager {
+            name: String::from(name),
+            config_dir: PathBuf::from("./test-files/stream"),
+            ..Default::default()
+        }.with_command(CommandKind::Search, Scope::System, search)
+    }
+
+    #[test]
+    fn streaming() {
+        let managers = vec![
+            manager("slow", "./slow.sh"),
+            manager("fast", "./fast.sh"),
+            manager("broken", "false"),
+            PackageManager { name: String::from("none"), ..Default::default() },
+        ];
+        let mut stream = SearchStream::start(managers, "ripgrep", SearchOptions::new().with_limit(2));
+        assert!(!stream.is_done());
+        let updates: Vec<SearchUpdate> = stream.by_ref().collect();
+        assert!(stream.is_done());
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates.last().unwrap().manager(), "slow");
+        match updates.iter().find(|update| update.manager() == "fast") {
+            Some(&SearchUpdate::Found { ref packages, truncated, .. }) => {
+                assert_eq!(packages.len(), 2);
+                assert!(truncated);
+            },
+            _ => panic!("fast should have found packages"),
+        }
+        assert!(updates.iter().any(|update| matches!(*update, SearchUpdate::Failed { .. })));
+    }
+}
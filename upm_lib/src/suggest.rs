@@ -0,0 +1,245 @@
+//! Suggestions for package names that a query didn't find. The same software is often packaged
+//! under different names (`node` versus `nodejs`) or is best installed through a tool that upm
+//! doesn't manage (`nvm`). The [`SuggestionEngine`](struct.SuggestionEngine.html) combines
+//! equivalent name data, builtin knowledge of common cases, and fuzzy matching against indexes
+//! of known package names to point the user somewhere useful.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use super::PackageManager;
+
+/// Names that commonly refer to the same software across package managers
+const BUILTIN_EQUIVALENTS: &[&[&str]] = &[
+    &["node", "nodejs"],
+    &["ripgrep", "rg"],
+    &["fd", "fd-find"],
+    &["bat", "batcat"],
+    &["python", "python3"],
+    &["pip", "python-pip", "python3-pip"],
+    &["vim", "vim-enhanced"],
+    &["httpd", "apache2"],
+];
+
+/// Installers that upm doesn't manage but are a common way of getting a package
+const BUILTIN_UNMANAGED: &[(&str, &str)] = &[
+    ("node", "nvm"),
+    ("nodejs", "nvm"),
+    ("rust", "rustup"),
+    ("rustc", "rustup"),
+    ("cargo", "rustup"),
+    ("python", "pyenv"),
+    ("python3", "pyenv"),
+    ("ruby", "rbenv"),
+    ("java", "sdkman"),
+];
+
+/// A suggestion for a package name that returned no results
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Suggestion {
+    /// The package may be called something else. If the name was found in the index of a package
+    /// manager then that manager is given.
+    Rename { name: String, manager: Option<String> },
+    /// The package is available through an installer that upm doesn't manage
+    Unmanaged { source: String },
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Suggestion::Rename { ref name, manager: Some(ref manager) } => {
+                write!(f, "did you mean '{}' from {}?", name, manager)
+            },
+            Suggestion::Rename { ref name, manager: None } => write!(f, "did you mean '{}'?", name),
+            Suggestion::Unmanaged { ref source } => {
+                write!(f, "also available via {} (not managed by upm)", source)
+            },
+        }
+    }
+}
+
+/// Produces suggestions for package names that weren't found. Equivalent names and unmanaged
+/// sources can be added on top of the builtin knowledge, and indexes of known package names per
+/// package manager are used for fuzzy matching.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestionEngine {
+    equivalents: HashMap<String, HashSet<String>>,
+    unmanaged: HashMap<String, Vec<String>>,
+    indexes: HashMap<String, Vec<String>>,
+}
+
+impl SuggestionEngine {
+    /// Create an engine with no knowledge of equivalent names
+    pub fn new() -> SuggestionEngine {
+        SuggestionEngine::default()
+    }
+
+    /// Create an engine that knows about common equivalent names and unmanaged installers
+    pub fn with_builtins() -> SuggestionEngine {
+        let mut engine = SuggestionEngine::new();
+        for group in BUILTIN_EQUIVALENTS {
+            engine.add_equivalents(group);
+        }
+        for &(name, source) in BUILTIN_UNMANAGED {
+            engine.add_unmanaged(name, source);
+        }
+        engine
+    }
+
+    /// Record that all of the given names refer to the same software
+    pub fn add_equivalents(&mut self, names: &[&str]) {
+        for name in names {
+            let entry = self.equivalents.entry(name.to_string()).or_default();
+            for other in names.iter().filter(|other| *other != name) {
+                entry.insert(other.to_string());
+            }
+        }
+    }
+
+    /// Record that a package can be installed through a source that upm doesn't manage
+    pub fn add_unmanaged(&mut self, name: &str, source: &str) {
+        let sources = self.unmanaged.entry(name.to_owned()).or_default();
+        if !sources.iter().any(|s| s == source) {
+            sources.push(source.to_owned());
+        }
+    }
+
+    /// Provide the package names known to be available from a package manager. These are used to
+    /// confirm equivalent names and to find close matches for misspelled names.
+    pub fn add_index(&mut self, manager: &str, names: Vec<String>) {
+        self.indexes.insert(manager.to_owned(), names);
+    }
+
+    /// Suggest alternatives for a package name that wasn't found in any of the given managers
+    pub fn suggest(&self, name: &str, managers: &[PackageManager]) -> Vec<Suggestion> {
+        let mut result = Vec::new();
+        let mut equivalents: Vec<&String> = match self.equivalents.get(name) {
+            Some(names) => names.iter().collect(),
+            None => Vec::new(),
+        };
+        equivalents.sort();
+        for equivalent in equivalents {
+            let mut found = false;
+            for manager in managers {
+                if self.index_contains(&manager.name, equivalent) {
+                    found = true;
+                    result.push(Suggestion::Rename {
+                        name: equivalent.to_owned(),
+                        manager: Some(manager.name.to_owned()),
+                    });
+                }
+            }
+            if !found {
+                result.push(Suggestion::Rename { name: equivalent.to_owned(), manager: None });
+            }
+        }
+        for manager in managers {
+            if let Some(index) = self.indexes.get(&manager.name) {
+                for candidate in index {
+                    if candidate != name && is_close(name, candidate) {
+                        let suggestion = Suggestion::Rename {
+                            name: candidate.to_owned(),
+                            manager: Some(manager.name.to_owned()),
+                        };
+                        if !result.contains(&suggestion) {
+                            result.push(suggestion);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(sources) = self.unmanaged.get(name) {
+            for source in sources {
+                //Only mention sources that upm isn't already managing
+                if !managers.iter().any(|manager| &manager.name == source) {
+                    result.push(Suggestion::Unmanaged { source: source.to_owned() });
+                }
+            }
+        }
+        result
+    }
+
+    fn index_contains(&self, manager: &str, name: &str) -> bool {
+        match self.indexes.get(manager) {
+            Some(index) => index.iter().any(|candidate| candidate == name),
+            None => false,
+        }
+    }
+}
+
+//Names are close when they're within the typos allowed for the name
+fn is_close(name: &str, candidate: &str) -> bool {
+    let allowed = typo_allowance(name);
+    allowed > 0 && edit_distance(name, candidate) <= allowed
+}
+
+/// How many typos a name can have and still be recognized, which grows with its length. Names of
+/// three letters or fewer can't have any.
+pub fn typo_allowance(name: &str) -> usize {
+    match name.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// The Levenshtein distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(name: &str) -> PackageManager {
+        PackageManager {
+            name: String::from(name),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("ripgrep", "ripgrep"), 0);
+        assert_eq!(edit_distance("riggrep", "ripgrep"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn builtin_suggestions() {
+        let engine = SuggestionEngine::with_builtins();
+        let managers = vec![manager("pacman")];
+        let suggestions = engine.suggest("node", &managers);
+        assert!(suggestions.contains(&Suggestion::Rename { name: String::from("nodejs"), manager: None }));
+        assert!(suggestions.contains(&Suggestion::Unmanaged { source: String::from("nvm") }));
+        let managers = vec![manager("pacman"), manager("nvm")];
+        let suggestions = engine.suggest("node", &managers);
+        assert!(!suggestions.contains(&Suggestion::Unmanaged { source: String::from("nvm") }));
+    }
+
+    #[test]
+    fn indexed_suggestions() {
+        let mut engine = SuggestionEngine::new();
+        engine.add_index("cargo", vec![String::from("ripgrep"), String::from("fd-find")]);
+        let managers = vec![manager("cargo")];
+        let suggestions = engine.suggest("riggrep", &managers);
+        assert_eq!(suggestions, vec![Suggestion::Rename {
+            name: String::from("ripgrep"),
+            manager: Some(String::from("cargo")),
+        }]);
+        assert!(engine.suggest("zzz", &managers).is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Reverting installs and removals recorded in the [history log](../history/index.html). A
+//! [`Transaction`](struct.Transaction.html) is a recorded command that can be undone by running
+//! the inverse command, removing what was installed or installing what was removed.
+//!
+//! The command run to undo another is recorded as an undo, so undoing repeatedly walks further
+//! back through the history instead of redoing what was just undone.
+
+use std::process::ExitStatus;
+use failure::Error;
+use super::{ManagerSet, PackageManager};
+use command::{CommandKind, Scope};
+use history::{HistoryEntry, HistoryLog, Operation};
+
+/// A recorded install or removal that can be reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub entry: HistoryEntry,
+}
+
+/// What undoing a transaction runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoPlan {
+    pub manager: String,
+    pub operation: Operation,
+    /// The scope the transaction ran in, which it's undone in
+    pub scope: Scope,
+    pub packages: Vec<String>,
+    /// The command line that is executed
+    pub command_line: String,
+}
+
+impl Transaction {
+    /// Find the most recent successful install or removal that hasn't been undone
+    pub fn last(log: &HistoryLog) -> Result<Option<Transaction>, Error> {
+        let mut undone = 0;
+        for entry in log.entries()?.into_iter().rev() {
+            //A failed undo didn't revert anything, so it's skipped like any failed command
+            if entry.undo && entry.is_success() {
+                undone += 1;
+            } else if entry.is_success() && entry.operation.inverse().is_some() {
+                if undone == 0 {
+                    return Ok(Some(Transaction { entry }));
+                }
+                undone -= 1;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Work out what undoing the transaction would run, without running it
+    pub fn plan(&self, managers: &ManagerSet) -> Result<UndoPlan, Error> {
+        let (manager, operation, command) = self.inverse(managers)?;
+        let packages = self.entry.packages.join(" ");
+        let command_line = match manager.command_line(&command, &packages) {
+            Some(command_line) => command_line,
+            None => bail!("{} has no {} command to undo with", manager.name, command),
+        };
+        Ok(UndoPlan {
+            manager: manager.name.clone(),
+            operation,
+            scope: self.entry.scope,
+            packages: self.entry.packages.clone(),
+            command_line,
+        })
+    }
+
+    /// Revert the transaction, recording the command that reverted it as an undo
+    pub fn undo(&self, log: &HistoryLog, managers: &ManagerSet) -> Result<ExitStatus, Error> {
+        let (manager, operation, command) = self.inverse(managers)?;
+        //The command is recorded here rather than by the package manager so it's marked as an undo
+        let mut manager = manager.clone();
+        manager.history = None;
+        let packages = self.entry.packages.join(" ");
+        let status = manager.execute(&command, &packages)?;
+        let mut entry = HistoryEntry::new(operation, &manager.name, &packages, status);
+        entry.undo = true;
+        entry.scope = self.entry.scope;
+        log.record(&entry)?;
+        Ok(status)
+    }
+
+    /// Revert the most recent install or removal that hasn't been undone, giving it along with
+    /// the exit status of the command that reverted it. Gives None if there's nothing to undo.
+    pub fn undo_last(log: &HistoryLog, managers: &ManagerSet) -> Result<Option<(Transaction, ExitStatus)>, Error> {
+        match Transaction::last(log)? {
+            Some(transaction) => {
+                let status = transaction.undo(log, managers)?;
+                Ok(Some((transaction, status)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    //Find the package manager, the operation that reverts the transaction, and the command that
+    //performs it in the scope the transaction ran in
+    fn inverse<'a>(&self, managers: &'a ManagerSet) -> Result<(&'a PackageManager, Operation, String), Error> {
+        let operation = match self.entry.operation.inverse() {
+            Some(operation) => operation,
+            None => bail!("{} can't be undone", self.entry.operation),
+        };
+        let command = operation.name().parse::<CommandKind>()?.key(self.entry.scope);
+        match managers.get(&self.entry.manager) {
+            Some(manager) => Ok((manager, operation, command)),
+            None => bail!("{} isn't configured anymore", self.entry.manager),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, Scope};
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+
+    #[test]
+    fn undoing() {
+        let log = HistoryLog::at(temp_dir().join(format!("upm-undo-{}.log", ::std::process::id())));
+        let _ = remove_file(log.path());
+        for line in &["1528243200\tinstall\tcargo\tripgrep fd-find\t0",
+                      "1528243300\tremove\tcargo\texa\t0",
+                      "1528243400\tinstall\tcargo\tbroken\t101",
+                      "1528243500\tupgrade_all\tcargo\t\t0"] {
+            log.record(&HistoryEntry::parse(line).unwrap()).unwrap();
+        }
+        let managers = ManagerSet::new(vec![PackageManager {
+            name: String::from("cargo"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::Install, Scope::System, "true --locked")
+            .with_command(CommandKind::Remove, Scope::System, "true uninstall")]);
+
+        //Failed commands and ones without an inverse are skipped
+        let last = Transaction::last(&log).unwrap().unwrap();
+        assert_eq!(last.entry.packages, vec![String::from("exa")]);
+        let plan = last.plan(&managers).unwrap();
+        assert_eq!(plan.operation, Operation::Install);
+        assert_eq!(plan.command_line, "true --locked exa");
+
+        let (undone, status) = Transaction::undo_last(&log, &managers).unwrap().unwrap();
+        assert_eq!(undone, last);
+        assert!(status.success());
+        //The undo itself isn't undone next, the install before it is
+        let next = Transaction::last(&log).unwrap().unwrap();
+        assert_eq!(next.plan(&managers).unwrap().command_line, "true uninstall ripgrep fd-find");
+        Transaction::undo_last(&log, &managers).unwrap();
+        assert!(Transaction::last(&log).unwrap().is_none());
+        //An undo that failed leaves the transaction to be undone again
+        log.record(&HistoryEntry::parse("1528243600\tremove\tcargo\tbat\t0").unwrap()).unwrap();
+        log.record(&HistoryEntry::parse("1528243700\tinstall\tcargo\tbat\t1\tundo").unwrap()).unwrap();
+        assert_eq!(Transaction::last(&log).unwrap().unwrap().entry.packages, vec![String::from("bat")]);
+
+        let missing = ManagerSet::new(Vec::new());
+        assert!(next.plan(&missing).is_err());
+        remove_file(log.path()).unwrap();
+    }
+
+    #[test]
+    fn undoing_in_scope() {
+        let log = HistoryLog::at(temp_dir().join(format!("upm-undo-scope-{}.log", ::std::process::id())));
+        let _ = remove_file(log.path());
+        log.record(&HistoryEntry::parse("1528243200\tinstall\tnpm\ttypescript\t0\tuser").unwrap()).unwrap();
+        let managers = ManagerSet::new(vec![PackageManager {
+            name: String::from("npm"),
+            ..Default::default()
+        }.with_command(CommandKind::Version, Scope::System, "true")
+            .with_command(CommandKind::Remove, Scope::System, "true uninstall -g")
+            .with_command(CommandKind::Remove, Scope::User, "true uninstall")]);
+
+        let plan = Transaction::last(&log).unwrap().unwrap().plan(&managers).unwrap();
+        assert_eq!(plan.scope, Scope::User);
+        assert_eq!(plan.command_line, "true uninstall typescript");
+        Transaction::undo_last(&log, &managers).unwrap();
+        let entries = log.entries().unwrap();
+        assert!(entries[1].undo);
+        assert_eq!(entries[1].scope, Scope::User);
+        remove_file(log.path()).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+//! Verifying package manager configurations before they're loaded. A configuration defines
+//! arbitrary commands to run, so a modified one in a system-wide directory can run anything as
+//! whoever uses upm. Each configuration can be accompanied by
+//!
+//! * a detached GPG signature, `apt.toml.sig`, checked with `gpg --verify`, or
+//! * a SHA-256 checksum, `apt.toml.sha256`, in the format written by `sha256sum`
+//!
+//! The [`TrustPolicy`](struct.TrustPolicy.html) chooses the directories whose configurations must
+//! be verified. It's kept in `trust.toml` in the [data directory](../fn.data_dir.html):
+//!
+//! ```toml
+//! verify = ["/etc/upm"]
+//! allow = ["yay"]
+//! keyring = "/etc/upm-keys/trusted.gpg"
+//! ```
+//!
+//! Configurations in those directories that are unsigned are refused, unless the package manager
+//! is allowed by name. A configuration that doesn't match its signature or checksum is refused
+//! wherever it is, even if it's allowed.
+
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use failure::Error;
+use sha2::{Digest, Sha256};
+use toml::Value;
+use super::data_dir;
+
+/// The extension of a detached signature, added to the configuration's file name
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// The extension of a checksum, added to the configuration's file name
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// Get the path of the trust policy, `trust.toml` in the data directory
+pub fn trust_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("trust.toml"))
+}
+
+/// How a configuration file was verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Its detached signature is good
+    Signed,
+    /// It matches its checksum
+    Checksummed,
+    /// It has neither a signature nor a checksum
+    Unverified,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Verification::Signed => "signed",
+            Verification::Checksummed => "checksummed",
+            Verification::Unverified => "unverified",
+        })
+    }
+}
+
+/// Which configuration directories must be verified
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustPolicy {
+    /// The directories whose configurations must be signed or checksummed
+    pub verify: Vec<PathBuf>,
+    /// Package managers loaded from those directories even when unverified
+    pub allow: Vec<String>,
+    /// The keyring signatures are checked against instead of the user's default one
+    pub keyring: Option<PathBuf>,
+}
+
+impl TrustPolicy {
+    /// A policy that verifies nothing
+    pub fn new() -> TrustPolicy {
+        TrustPolicy::default()
+    }
+
+    /// Read the policy from a TOML value
+    pub fn from_toml(resource: &Value) -> Result<TrustPolicy, Error> {
+        let strings = |key: &str| -> Result<Vec<String>, Error> {
+            match resource.get(key) {
+                Some(values) => match values.as_array() {
+                    Some(values) => values.iter().map(|value| match value.as_str() {
+                        Some(value) => Ok(value.to_owned()),
+                        None => bail!("Every entry of {} must be a string", key),
+                    }).collect(),
+                    None => bail!("{} must be an array of strings", key),
+                },
+                None => Ok(Vec::new()),
+            }
+        };
+        let keyring = match resource.get("keyring") {
+            Some(keyring) => match keyring.as_str() {
+                Some(keyring) => Some(PathBuf::from(keyring)),
+                None => bail!("keyring must be a path"),
+            },
+            None => None,
+        };
+        Ok(TrustPolicy {
+            verify: strings("verify")?.into_iter().map(PathBuf::from).collect(),
+            allow: strings("allow")?,
+            keyring,
+        })
+    }
+
+    /// Read the policy from a file. A file that doesn't exist gives a policy that verifies nothing.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TrustPolicy, Error> {
+        let mut content = String::new();
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut content)?; },
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(TrustPolicy::new()),
+            Err(e) => return Err(e.into()),
+        }
+        TrustPolicy::from_toml(&content.parse::<Value>()?)
+    }
+
+    /// Read the default policy
+    pub fn load_default() -> Result<TrustPolicy, Error> {
+        match trust_path() {
+            Some(path) => TrustPolicy::load(path),
+            None => Ok(TrustPolicy::new()),
+        }
+    }
+
+    /// Must configurations in the directory be verified?
+    pub fn requires_verification(&self, dir: &Path) -> bool {
+        self.verify.iter().any(|verified| same_dir(verified, dir))
+    }
+
+    /// Check that a package manager's configuration may be loaded. Configurations outside the
+    /// verified directories are accepted without a signature or checksum, but never with one that
+    /// doesn't match.
+    pub fn check(&self, name: &str, path: &Path) -> Result<Verification, Error> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let verification = verify(path, self.keyring.as_deref())?;
+        if verification == Verification::Unverified && self.requires_verification(dir)
+            && !self.allow.iter().any(|allowed| allowed == name) {
+            bail!("{:?} has no signature or checksum and {} isn't allowed unverified", path, name);
+        }
+        debug!("Package manager config {:?} is {}", path, verification);
+        Ok(verification)
+    }
+}
+
+/// Verify a configuration file against its signature, or its checksum if it isn't signed. An
+/// error is given if the signature or checksum doesn't match.
+pub fn verify(path: &Path, keyring: Option<&Path>) -> Result<Verification, Error> {
+    let signature = with_extension(path, SIGNATURE_EXTENSION);
+    if signature.exists() {
+        let mut gpg = Command::new("gpg");
+        gpg.arg("--batch");
+        if let Some(keyring) = keyring {
+            gpg.arg("--no-default-keyring").arg("--keyring").arg(keyring);
+        }
+        let status = gpg.arg("--verify").arg(&signature).arg(path)
+            .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null())
+            .status();
+        return match status {
+            Ok(status) if status.success() => Ok(Verification::Signed),
+            Ok(_) => bail!("{:?} doesn't match its signature {:?}", path, signature),
+            Err(e) => bail!("Couldn't run gpg to check {:?}: {}", signature, e),
+        };
+    }
+    let checksum = with_extension(path, CHECKSUM_EXTENSION);
+    let mut expected = String::new();
+    match File::open(&checksum) {
+        Ok(mut file) => { file.read_to_string(&mut expected)?; },
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Verification::Unverified),
+        Err(e) => return Err(e.into()),
+    }
+    //sha256sum writes the checksum followed by the file name
+    let expected = match expected.split_whitespace().next() {
+        Some(expected) => expected.to_lowercase(),
+        None => bail!("The checksum {:?} is empty", checksum),
+    };
+    if sha256(path)? == expected {
+        Ok(Verification::Checksummed)
+    } else {
+        bail!("{:?} doesn't match its checksum {:?}", path, checksum)
+    }
+}
+
+/// The SHA-256 checksum of a file as lowercase hex
+pub fn sha256(path: &Path) -> Result<String, Error> {
+    let mut content = Vec::new();
+    File::open(path)?.read_to_end(&mut content)?;
+    Ok(format!("{:x}", Sha256::digest(&content)))
+}
+
+//Adds an extension after the existing one, turning apt.toml into apt.toml.sig
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+//Compares directories after resolving symlinks and relative components where possible
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str]) -> TrustPolicy {
+        TrustPolicy {
+            verify: vec![PathBuf::from("./test-files/trust")],
+            allow: allow.iter().map(|name| String::from(*name)).collect(),
+            keyring: None,
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        let dir = Path::new("./test-files/trust");
+        assert_eq!(verify(&dir.join("checksummed.toml"), None).unwrap(), Verification::Checksummed);
+        assert!(verify(&dir.join("modified.toml"), None).is_err());
+        assert_eq!(verify(&dir.join("unsigned.toml"), None).unwrap(), Verification::Unverified);
+    }
+
+    #[test]
+    fn policies() {
+        let dir = Path::new("./test-files/trust");
+        let strict = policy(&[]);
+        assert!(strict.check("checksummed", &dir.join("checksummed.toml")).is_ok());
+        assert!(strict.check("unsigned", &dir.join("unsigned.toml")).is_err());
+        assert!(strict.check("modified", &dir.join("modified.toml")).is_err());
+        let allowing = policy(&["unsigned", "modified"]);
+        assert!(allowing.check("unsigned", &dir.join("unsigned.toml")).is_ok());
+        assert!(allowing.check("modified", &dir.join("modified.toml")).is_err());
+        //Directories that aren't verified accept anything that isn't modified
+        assert!(TrustPolicy::new().check("unsigned", &dir.join("unsigned.toml")).is_ok());
+        assert_eq!(TrustPolicy::new().check("checksummed", &dir.join("checksummed.toml")).unwrap(), Verification::Checksummed);
+        assert!(TrustPolicy::new().check("modified", &dir.join("modified.toml")).is_err());
+
+        let parsed = TrustPolicy::from_toml(&"verify = ['/etc/upm']\nallow = ['yay']\n".parse::<Value>().unwrap()).unwrap();
+        assert!(parsed.requires_verification(Path::new("/etc/upm")));
+        assert_eq!(parsed.allow, vec![String::from("yay")]);
+        assert!(TrustPolicy::from_toml(&"verify = '/etc/upm'\n".parse::<Value>().unwrap()).is_err());
+    }
+}
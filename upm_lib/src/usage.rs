@@ -0,0 +1,283 @@
+//! How much disk space each package manager uses, for people juggling many language package
+//! managers. The optional `cache_size` and `disk_usage` commands print the size in bytes of a
+//! package manager's download cache and of its installed packages, as the first field of their
+//! first line. That's the output of `du -sb`, so `cache_size = "du -sb /var/cache/apt"` works.
+//!
+//! Before installing, the optional `predict_size` command is given the packages and prints how
+//! many bytes installing them would download and then how many they would use once installed,
+//! such as a script summing the sizes from `apt-get install --print-uris`. The
+//! [estimates](fn.estimate_installs.html) of every package manager are added together so a
+//! frontend can say how much an install will take across all of them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use failure::Error;
+use super::{ManagerSet, PackageManager};
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// The disk space used by a package manager. Sizes the package manager can't report are None.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// The size of the download cache in bytes
+    pub cache: Option<u64>,
+    /// The size of the installed packages in bytes
+    pub installed: Option<u64>,
+}
+
+impl DiskUsage {
+    /// Get the total size that's known
+    pub fn total(&self) -> u64 {
+        self.cache.unwrap_or(0) + self.installed.unwrap_or(0)
+    }
+
+    /// Add another usage to this one
+    pub fn add(&mut self, other: &DiskUsage) {
+        let sum = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+        };
+        self.cache = sum(self.cache, other.cache);
+        self.installed = sum(self.installed, other.installed);
+    }
+}
+
+impl fmt::Display for DiskUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size = |bytes: Option<u64>| bytes.map_or_else(|| String::from("?"), human_size);
+        write!(f, "cache {}, installed {}", size(self.cache), size(self.installed))
+    }
+}
+
+/// The disk usage of several package managers
+#[derive(Debug, Clone, Default)]
+pub struct UsageReport {
+    /// The usage of each package manager that reported any, by name
+    pub managers: Vec<(String, DiskUsage)>,
+}
+
+impl UsageReport {
+    /// Get the usage of every package manager added together
+    pub fn total(&self) -> DiskUsage {
+        let mut total = DiskUsage::default();
+        for (_, usage) in &self.managers {
+            total.add(usage);
+        }
+        total
+    }
+}
+
+/// Measure the disk usage of every package manager with a cache_size or disk_usage command.
+/// Measurements that fail are logged and left unknown.
+pub fn measure(managers: &[PackageManager], policy: &ConcurrencyPolicy) -> UsageReport {
+    let measured = run_all(managers, policy, |manager| {
+        let size = |command: &str, result: Result<u64, Error>| match result {
+            Ok(size) => Some(size),
+            Err(e) => {
+                warn!("{}: couldn't measure {}: {}", manager.name, command, e);
+                None
+            },
+        };
+        DiskUsage {
+            cache: if manager.has_command("cache_size") { size("cache_size", manager.cache_size()) } else { None },
+            installed: if manager.has_command("disk_usage") { size("disk_usage", manager.disk_usage()) } else { None },
+        }
+    });
+    UsageReport {
+        managers: measured.into_iter()
+            .filter(|&(_, usage)| usage.cache.is_some() || usage.installed.is_some())
+            .map(|(manager, usage)| (manager.name.clone(), usage))
+            .collect(),
+    }
+}
+
+/// How much installing packages would take
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeEstimate {
+    /// The bytes that would be downloaded
+    pub download: u64,
+    /// The bytes the packages would use once installed
+    pub installed: u64,
+}
+
+impl SizeEstimate {
+    /// Add another estimate to this one
+    pub fn add(&mut self, other: &SizeEstimate) {
+        self.download += other.download;
+        self.installed += other.installed;
+    }
+}
+
+impl fmt::Display for SizeEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "download {} and use {}", human_size(self.download), human_size(self.installed))
+    }
+}
+
+/// The estimated size of installing packages across package managers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallEstimate {
+    /// The estimates of every package manager that gave one added together
+    pub total: SizeEstimate,
+    /// The package managers that couldn't estimate their packages, by name
+    pub unknown: Vec<String>,
+}
+
+impl InstallEstimate {
+    /// Is anything known about the size?
+    pub fn is_known(&self) -> bool {
+        self.total != SizeEstimate::default()
+    }
+}
+
+/// Estimate the size of installing packages, given as the name of the package manager to install
+/// each with and the name of the package. Each package manager is asked once for all of its
+/// packages. Package managers without a predict_size command, or whose estimate fails, are listed
+/// as unknown.
+pub fn estimate_installs(managers: &ManagerSet, installs: &[(String, String)]) -> InstallEstimate {
+    let mut packages: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (manager, package) in installs {
+        packages.entry(manager.as_str()).or_default().push(package.as_str());
+    }
+    let involved: Vec<PackageManager> = managers.iter()
+        .filter(|manager| packages.contains_key(manager.name.as_str()))
+        .cloned()
+        .collect();
+    let estimates = run_all(&involved, &ConcurrencyPolicy::default(), |manager| {
+        if !manager.has_command("predict_size") {
+            return None;
+        }
+        match manager.predict_size(&packages[manager.name.as_str()].join(" ")) {
+            Ok(estimate) => Some(estimate),
+            Err(e) => {
+                warn!("{}: couldn't estimate the size of the install: {}", manager.name, e);
+                None
+            },
+        }
+    });
+    let mut result = InstallEstimate::default();
+    for (manager, estimate) in estimates {
+        match estimate {
+            Some(estimate) => result.total.add(&estimate),
+            None => result.unknown.push(manager.name.clone()),
+        }
+    }
+    //Package managers that aren't in the set can't estimate anything either
+    for &manager in packages.keys() {
+        if managers.get(manager).is_none() {
+            result.unknown.push(manager.to_owned());
+        }
+    }
+    result
+}
+
+/// Read the download and installed sizes in bytes from the first two fields of the first line of
+/// some output
+pub fn parse_estimate(output: &str) -> Result<SizeEstimate, Error> {
+    let mut fields = output.lines().next().unwrap_or("").split_whitespace();
+    let mut size = |what: &str| match fields.next() {
+        Some(field) => match field.parse::<u64>() {
+            Ok(size) => Ok(size),
+            Err(_) => bail!("{:?} isn't a size in bytes", field),
+        },
+        None => bail!("No {} size was given", what),
+    };
+    Ok(SizeEstimate { download: size("download")?, installed: size("installed")? })
+}
+
+/// Read a size in bytes from the first field of the first line of some output
+pub fn parse_size(output: &str) -> Result<u64, Error> {
+    match output.split_whitespace().next() {
+        Some(field) => match field.parse::<u64>() {
+            Ok(size) => Ok(size),
+            Err(_) => bail!("{:?} isn't a size in bytes", field),
+        },
+        None => bail!("No size was given"),
+    }
+}
+
+/// Format a size in bytes for people, such as `1.5 GiB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, Scope};
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("12345\t/var/cache/apt\n").unwrap(), 12345);
+        assert!(parse_size("").is_err());
+        assert!(parse_size("12K /var/cache/apt").is_err());
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536 * 1024 * 1024), "1.5 GiB");
+    }
+
+    #[test]
+    fn measuring() {
+        let manager = |name: &str, cache: Option<&str>, installed: Option<&str>| {
+            let mut manager = PackageManager {
+                name: String::from(name),
+                ..Default::default()
+            }.with_command(CommandKind::Version, Scope::System, "true");
+            if let Some(cache) = cache {
+                manager.set_command(CommandKind::CacheSize, Scope::System, cache);
+            }
+            if let Some(installed) = installed {
+                manager.set_command(CommandKind::DiskUsage, Scope::System, installed);
+            }
+            manager
+        };
+        let managers = vec![
+            manager("apt", Some("echo 2048"), Some("echo 4096")),
+            manager("cargo", None, Some("echo 1024")),
+            manager("npm", Some("false"), None),
+            manager("pip", None, None),
+        ];
+        let report = measure(&managers, &ConcurrencyPolicy::default());
+        let names: Vec<&str> = report.managers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["apt", "cargo"]);
+        assert_eq!(report.total(), DiskUsage { cache: Some(2048), installed: Some(5120) });
+        assert_eq!(report.total().total(), 7168);
+    }
+
+    #[test]
+    fn estimating() {
+        assert_eq!(parse_estimate("88080384 220200960\n").unwrap(), SizeEstimate { download: 88080384, installed: 220200960 });
+        assert!(parse_estimate("1024").is_err());
+        assert!(parse_estimate("84M 210M").is_err());
+
+        let manager = |name: &str, predict: Option<&str>| {
+            let manager = PackageManager { name: String::from(name), ..Default::default() };
+            match predict {
+                Some(predict) => manager.with_command(CommandKind::PredictSize, Scope::System, predict),
+                None => manager,
+            }
+        };
+        let managers = ManagerSet::new(vec![
+            manager("apt", Some("echo 1024 4096")),
+            manager("cargo", Some("echo 512 2048")),
+            manager("pip", None),
+        ]);
+        let installs: Vec<(String, String)> = vec![("apt", "ripgrep"), ("apt", "fd-find"), ("cargo", "bat"), ("pip", "httpie"), ("gem", "rails")]
+            .into_iter()
+            .map(|(manager, package)| (String::from(manager), String::from(package)))
+            .collect();
+        let estimate = estimate_installs(&managers, &installs);
+        assert_eq!(estimate.total, SizeEstimate { download: 1536, installed: 6144 });
+        assert_eq!(estimate.unknown, vec!["pip", "gem"]);
+        assert_eq!(estimate.total.to_string(), "download 1.5 KiB and use 6.0 KiB");
+    }
+}
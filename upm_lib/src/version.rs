@@ -0,0 +1,960 @@
+//! Versions of packages and package managers. [`Version`](struct.Version.html) is used in place of
+//! [semver](https://crates.io/crates/semver) due to the need to support non-semantic versions,
+//! though common non-semantic schemes such as dates and four numbers can still be broken into
+//! their [parts](enum.VersionParts.html).
+//!
+//! Versions of distribution packages, such as `1:2.3.4-5ubuntu1` from Debian or `2.3.4-r3` from
+//! Alpine, have an epoch before a colon and a package revision after the last hyphen. These are
+//! only recognized when parsing in [extended mode](enum.ParseMode.html), since the revision can't
+//! otherwise be told apart from a semantic pre-release. Extended versions are ordered the way dpkg
+//! orders them.
+//!
+//! Package managers that know how their versions are ordered can pick a
+//! [`VersionScheme`](enum.VersionScheme.html) and compare with
+//! [`Version::cmp_with`](struct.Version.html#method.cmp_with), which orders every version the way
+//! dpkg or rpm would.
+//!
+//! A [`VersionReq`](struct.VersionReq.html) is a range of versions, such as `^20`, used to hold
+//! packages within a range.
+//!
+//! The version of a package manager is pulled out of whatever its version command prints with
+//! [`extract_version`](fn.extract_version.html), using the package manager's `version_regex` if
+//! it has one:
+//!
+//! ```toml
+//! version = "brew --version"
+//! version_regex = '^Homebrew (?P<version>[\d.]+)'
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use failure::Error;
+use regex::Regex;
+
+//The patterns versions are parsed with, compiled once rather than for every version
+lazy_static! {
+    static ref SEMANTIC: Regex = Regex::new(r"^(\d+)\.(\d+)\.(\d+)(?:-([\dA-Za-z-]+(?:\.[\dA-Za-z-]+)*))?(?:\+([\dA-Za-z-]+(?:\.[\dA-Za-z-]+)*))?$").unwrap();
+    static ref DISTRO: Regex = Regex::new(r"^(?:(\d+):)?([0-9][A-Za-z0-9.+~:-]*?)(?:-([A-Za-z0-9.+~]+))?$").unwrap();
+    static ref DATE: Regex = Regex::new(r"^(\d{4})-?(\d{2})-?(\d{2})$").unwrap();
+}
+
+/// A simple representation of a version string. For semantic versioning Steve Klabnik's semver
+/// crate is preferable. But non-semantic versioning is also permitted in this struct.
+#[derive(Debug,Default,Clone)]
+pub struct Version {
+    representation: String,
+    semantic: bool,
+    mode: ParseMode,
+    parts: VersionParts,
+}
+
+/// How a version string is broken into parts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Recognize semantic, date, and four component versions
+    #[default]
+    Standard,
+    /// Also recognize the epochs and package revisions of distribution packages
+    Extended,
+}
+
+/// The algorithm used to order the versions of a package manager
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VersionScheme {
+    /// Order semantic versions by their release numbers, leaving other versions unordered
+    #[default]
+    Semver,
+    /// Order versions like `dpkg --compare-versions`
+    Debian,
+    /// Order versions like rpmvercmp
+    Rpm,
+}
+
+impl FromStr for VersionScheme {
+    type Err = Error;
+
+    fn from_str(scheme: &str) -> Result<VersionScheme, Error> {
+        match scheme {
+            "semver" => Ok(VersionScheme::Semver),
+            "debian" => Ok(VersionScheme::Debian),
+            "rpm" => Ok(VersionScheme::Rpm),
+            _ => bail!("Unknown version scheme {}, expected semver, debian, or rpm", scheme),
+        }
+    }
+}
+
+impl fmt::Display for VersionScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VersionScheme::Semver => write!(f, "semver"),
+            VersionScheme::Debian => write!(f, "debian"),
+            VersionScheme::Rpm => write!(f, "rpm"),
+        }
+    }
+}
+
+/// The reason a string couldn't be parsed as a version
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum VersionError {
+    #[fail(display = "The version is empty")]
+    Empty,
+    #[fail(display = "The version {:?} contains whitespace", _0)]
+    Whitespace(String),
+}
+
+/// The structure of a version, as far as it can be recognized
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum VersionParts {
+    /// A semantic version, such as `1.2.3-beta+build.5`
+    Semantic {
+        major: u64,
+        minor: u64,
+        patch: u64,
+        pre_release: Option<String>,
+        build_metadata: Option<String>,
+    },
+    /// A date, such as `2018-01-02` or `20180102`
+    Date { year: u32, month: u32, day: u32 },
+    /// Four numbers separated by dots, such as `1.2.3.4`
+    FourComponent([u64; 4]),
+    /// A distribution package version with an epoch, an upstream version, and a package
+    /// revision, such as `1:2.3.4-5ubuntu1`. Only recognized in extended mode.
+    Distro {
+        epoch: u64,
+        upstream: String,
+        revision: Option<String>,
+    },
+    /// A version without a recognized structure
+    #[default]
+    Unstructured,
+}
+
+impl Version {
+    /// Create a blank version
+    pub fn new() -> Version {
+        Version::default()
+    }
+
+    /// Get the string representation of the version
+    pub fn get_representation(&self) -> &str {
+        &self.representation
+    }
+
+    /// Change the version along with checking if this new version appears to be semantic
+    pub fn set_representation(&mut self, val: String) {
+        self.representation = val;
+        self.semantic = Version::is_semantic(&self.representation);
+        self.parts = Version::parse_parts(&self.representation, self.semantic, self.mode);
+    }
+
+    /// Check if a representation appears to be semantic versioning
+    pub fn is_semantic(representation: &str) -> bool {
+        SEMANTIC.is_match(representation)
+    }
+
+    /// Explicitly set whether the version is semantic. If the version string doesn't pass
+    /// is_semantic, then it won't set semantic to true and will return false.
+    pub fn set_semantic(&mut self, val: bool) -> Result<(),Error> {
+        if val && !Version::is_semantic(&self.representation) {
+            bail!("Version does not match semantic structure");
+        }
+        self.semantic = val;
+        self.parts = Version::parse_parts(&self.representation, self.semantic, self.mode);
+        Ok(())
+    }
+
+    /// Is this a semantic version?
+    pub fn get_semantic(&self) -> bool {
+        self.semantic
+    }
+
+    /// Create a version from a string using the given parse mode
+    pub fn parse_with(representation: &str, mode: ParseMode) -> Version {
+        let mut version = Version::from(representation);
+        if mode == ParseMode::Extended {
+            version.mode = mode;
+            version.parts = Version::parse_parts(representation, version.semantic, mode);
+        }
+        version
+    }
+
+    /// How this version was parsed
+    pub fn mode(&self) -> ParseMode {
+        self.mode
+    }
+
+    /// Get the parts of the version. Semantic versions are always given as semantic, even when
+    /// they look like dates.
+    pub fn parts(&self) -> &VersionParts {
+        &self.parts
+    }
+
+    /// The major version number of a semantic version
+    pub fn major(&self) -> Option<u64> {
+        match self.parts {
+            VersionParts::Semantic { major, .. } => Some(major),
+            _ => None,
+        }
+    }
+
+    /// The minor version number of a semantic version
+    pub fn minor(&self) -> Option<u64> {
+        match self.parts {
+            VersionParts::Semantic { minor, .. } => Some(minor),
+            _ => None,
+        }
+    }
+
+    /// The patch version number of a semantic version
+    pub fn patch(&self) -> Option<u64> {
+        match self.parts {
+            VersionParts::Semantic { patch, .. } => Some(patch),
+            _ => None,
+        }
+    }
+
+    /// The pre-release of a semantic version, such as `beta.2` in `1.0.0-beta.2`
+    pub fn pre_release(&self) -> Option<&str> {
+        match self.parts {
+            VersionParts::Semantic { ref pre_release, .. } => pre_release.as_ref().map(|p| p.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The build metadata of a semantic version, such as `20180102` in `1.0.0+20180102`
+    pub fn build_metadata(&self) -> Option<&str> {
+        match self.parts {
+            VersionParts::Semantic { ref build_metadata, .. } => build_metadata.as_ref().map(|b| b.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Get the next major version of a semantic version, such as 2.0.0 for 1.2.3-beta. Gives
+    /// None for versions that aren't semantic.
+    pub fn bump_major(&self) -> Option<Version> {
+        self.release().map(|(major, _, _)| Version::from(format!("{}.0.0", major + 1).as_str()))
+    }
+
+    /// Get the next minor version of a semantic version, such as 1.3.0 for 1.2.3-beta. Gives
+    /// None for versions that aren't semantic.
+    pub fn bump_minor(&self) -> Option<Version> {
+        self.release().map(|(major, minor, _)| Version::from(format!("{}.{}.0", major, minor + 1).as_str()))
+    }
+
+    /// Get the next patch version of a semantic version, such as 1.2.4 for 1.2.3-beta. Gives
+    /// None for versions that aren't semantic.
+    pub fn bump_patch(&self) -> Option<Version> {
+        self.release().map(|(major, minor, patch)| Version::from(format!("{}.{}.{}", major, minor, patch + 1).as_str()))
+    }
+
+    /// Is this version known to be newer than the other? Versions that can't be compared are
+    /// never newer.
+    pub fn is_newer_than(&self, other: &Version) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Greater)
+    }
+
+    /// Compare with another version using the ordering of a version scheme. The Debian and RPM
+    /// schemes can order any two versions, while the semver scheme is the same as `partial_cmp`.
+    pub fn cmp_with(&self, other: &Version, scheme: VersionScheme) -> Option<Ordering> {
+        match scheme {
+            VersionScheme::Semver => self.partial_cmp(other),
+            VersionScheme::Debian => {
+                let (self_epoch, self_upstream, self_revision) = split_distro(&self.representation);
+                let (other_epoch, other_upstream, other_revision) = split_distro(&other.representation);
+                Some(self_epoch.cmp(&other_epoch)
+                     .then_with(|| compare_segments(self_upstream, other_upstream))
+                     .then_with(|| compare_segments(self_revision, other_revision)))
+            },
+            VersionScheme::Rpm => {
+                let (self_epoch, self_version, self_release) = split_distro(&self.representation);
+                let (other_epoch, other_version, other_release) = split_distro(&other.representation);
+                //rpm only compares releases when both versions have one
+                let releases = if self_release.is_empty() || other_release.is_empty() {
+                    Ordering::Equal
+                } else {
+                    compare_rpm_segments(self_release, other_release)
+                };
+                Some(self_epoch.cmp(&other_epoch)
+                     .then_with(|| compare_rpm_segments(self_version, other_version))
+                     .then(releases))
+            },
+        }
+    }
+
+    /// Get the newest of several versions. Versions that can't be compared with the newest one
+    /// found before them are skipped, so the first version wins when none can be compared.
+    pub fn newest<'a, I: IntoIterator<Item = &'a Version>>(versions: I) -> Option<&'a Version> {
+        versions.into_iter().fold(None, |newest, version| match newest {
+            Some(newest) if !version.is_newer_than(newest) => Some(newest),
+            _ => Some(version),
+        })
+    }
+
+    //The major, minor, and patch numbers of a semantic version
+    fn release(&self) -> Option<(u64, u64, u64)> {
+        match self.parts {
+            VersionParts::Semantic { major, minor, patch, .. } => Some((major, minor, patch)),
+            _ => None,
+        }
+    }
+
+    //The epoch, upstream version, and revision used to compare extended versions. Versions
+    //without them have an epoch of 0 and no revision.
+    fn distro_parts(&self) -> (u64, &str, &str) {
+        match self.parts {
+            VersionParts::Distro { epoch, ref upstream, ref revision } => {
+                (epoch, upstream, revision.as_ref().map_or("", |r| r.as_str()))
+            },
+            _ => (0, &self.representation, ""),
+        }
+    }
+
+    //Break a representation into its parts, which is only done when the representation changes
+    fn parse_parts(representation: &str, semantic: bool, mode: ParseMode) -> VersionParts {
+        if mode == ParseMode::Extended {
+            if let Some(captures) = DISTRO.captures(representation) {
+                let epoch = captures.get(1).map(|epoch| epoch.as_str().parse::<u64>().ok());
+                let revision = captures.get(3).map(|revision| revision.as_str().to_owned());
+                //An epoch too large to store isn't a distro version
+                if epoch != Some(None) && (epoch.is_some() || revision.is_some()) {
+                    return VersionParts::Distro {
+                        epoch: epoch.and_then(|epoch| epoch).unwrap_or(0),
+                        upstream: captures.get(2).unwrap().as_str().to_owned(),
+                        revision,
+                    };
+                }
+            }
+        }
+        if semantic {
+            let captures = SEMANTIC.captures(representation).unwrap();
+            let number = |group: usize| captures.get(group).unwrap().as_str().parse::<u64>();
+            let text = |group: usize| captures.get(group).map(|m| m.as_str().to_owned());
+            //A number too large for a u64 can't be represented, so leave the version unstructured
+            //and only compare it by its representation
+            return match (number(1), number(2), number(3)) {
+                (Ok(major), Ok(minor), Ok(patch)) => VersionParts::Semantic {
+                    major,
+                    minor,
+                    patch,
+                    pre_release: text(4),
+                    build_metadata: text(5),
+                },
+                _ => VersionParts::Unstructured,
+            };
+        }
+        if let Some(captures) = DATE.captures(representation) {
+            let number = |group: usize| captures.get(group).unwrap().as_str().parse::<u32>().unwrap();
+            let (year, month, day) = (number(1), number(2), number(3));
+            if (1..=12).contains(&month) && (1..=31).contains(&day) {
+                return VersionParts::Date { year, month, day };
+            }
+        }
+        let numbers: Vec<Result<u64, _>> = representation.split('.').map(|n| n.parse::<u64>()).collect();
+        if let [Ok(a), Ok(b), Ok(c), Ok(d)] = numbers[..] {
+            return VersionParts::FourComponent([a, b, c, d]);
+        }
+        VersionParts::Unstructured
+    }
+}
+
+impl<'a> From<&'a str> for Version {
+    /// Create a version from any string. Checks if the version fits with semantic versioning 2.0.0
+    /// and sets semantic to true if it does.
+    fn from(representation: &'a str) -> Version {
+        let semantic = Version::is_semantic(representation);
+        Version {
+            representation: String::from(representation),
+            semantic,
+            mode: ParseMode::Standard,
+            parts: Version::parse_parts(representation, semantic, ParseMode::Standard),
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = VersionError;
+
+    /// Parse a version, ignoring surrounding whitespace. Unlike `From<&str>` this rejects empty
+    /// versions and versions with whitespace inside them.
+    fn from_str(representation: &str) -> Result<Version, VersionError> {
+        let representation = representation.trim();
+        if representation.is_empty() {
+            return Err(VersionError::Empty);
+        }
+        if representation.contains(char::is_whitespace) {
+            return Err(VersionError::Whitespace(representation.to_owned()));
+        }
+        Ok(Version::from(representation))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.representation)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        if self.mode == ParseMode::Extended || other.mode == ParseMode::Extended {
+            self.partial_cmp(other) == Some(Ordering::Equal)
+        }
+        else if self.semantic != other.semantic {
+            false
+        }
+        else if let (Some(a), Some(b)) = (self.release(), other.release()) {
+            a == b
+        } else {
+            self.representation == other.representation
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    /// Semantic versions are ordered by their major, minor, and patch numbers. If either version
+    /// was parsed in extended mode they're ordered like dpkg orders versions. Other versions are
+    /// only comparable when they're equal.
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        if self.mode == ParseMode::Extended || other.mode == ParseMode::Extended {
+            let (self_epoch, self_upstream, self_revision) = self.distro_parts();
+            let (other_epoch, other_upstream, other_revision) = other.distro_parts();
+            Some(self_epoch.cmp(&other_epoch)
+                 .then_with(|| compare_segments(self_upstream, other_upstream))
+                 .then_with(|| compare_segments(self_revision, other_revision)))
+        } else if let (Some(a), Some(b)) = (self.release(), other.release()) {
+            Some(a.cmp(&b))
+        } else if self == other {
+            Some(Ordering::Equal)
+        } else {
+            None
+        }
+    }
+}
+
+/// A range of versions, such as `^20`, `~1.2`, or `>=1.0, <2.0`. The operators follow Cargo:
+/// `^` allows changes that don't modify the leftmost nonzero number, `~` allows changes to the
+/// last number given after the major one, `=` requires the given numbers, and `*` allows any
+/// version. A range without an operator is a `^` range. Several ranges separated by commas must
+/// all hold.
+///
+/// Only the leading release numbers of a version are compared, so `20.11.1_1` and `1:20.11-3` are
+/// both in `^20`. Versions without release numbers are never in a range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    representation: String,
+    comparators: Vec<Comparator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    numbers: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+    Wildcard,
+}
+
+impl VersionReq {
+    /// Is the version in the range?
+    pub fn matches(&self, version: &Version) -> bool {
+        let numbers = release_numbers(&version.representation);
+        !numbers.is_empty() && self.comparators.iter().all(|comparator| comparator.matches(&numbers))
+    }
+}
+
+impl Comparator {
+    fn matches(&self, numbers: &[u64]) -> bool {
+        let cmp = compare_numbers(numbers, &self.numbers);
+        match self.op {
+            Op::Exact => numbers.iter().chain(::std::iter::repeat(&0)).zip(&self.numbers).all(|(a, b)| a == b),
+            Op::Greater => cmp == Ordering::Greater,
+            Op::GreaterEq => cmp != Ordering::Less,
+            Op::Less => cmp == Ordering::Less,
+            Op::LessEq => cmp != Ordering::Greater,
+            Op::Tilde => cmp != Ordering::Less && compare_numbers(numbers, &self.upper(self.numbers.len().min(2) - 1)) == Ordering::Less,
+            Op::Caret => {
+                let kept = self.numbers.iter().position(|&n| n != 0).unwrap_or(self.numbers.len() - 1);
+                cmp != Ordering::Less && compare_numbers(numbers, &self.upper(kept)) == Ordering::Less
+            },
+            Op::Wildcard => true,
+        }
+    }
+
+    //The smallest numbers above the range that only allows changes after the given index
+    fn upper(&self, index: usize) -> Vec<u64> {
+        let mut upper = self.numbers[..index + 1].to_vec();
+        upper[index] += 1;
+        upper
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = Error;
+
+    fn from_str(representation: &str) -> Result<VersionReq, Error> {
+        let mut comparators = Vec::new();
+        for part in representation.split(',').map(|part| part.trim()) {
+            if part == "*" {
+                comparators.push(Comparator { op: Op::Wildcard, numbers: Vec::new() });
+                continue;
+            }
+            let (op, rest) = [(">=", Op::GreaterEq), ("<=", Op::LessEq), (">", Op::Greater), ("<", Op::Less),
+                              ("=", Op::Exact), ("~", Op::Tilde), ("^", Op::Caret)].iter()
+                .find(|&&(prefix, _)| part.starts_with(prefix))
+                .map_or((Op::Caret, part), |&(prefix, op)| (op, part[prefix.len()..].trim()));
+            let numbers: Result<Vec<u64>, _> = rest.split('.').map(|n| n.parse::<u64>()).collect();
+            match numbers {
+                Ok(ref numbers) if numbers.len() <= 3 => comparators.push(Comparator { op, numbers: numbers.clone() }),
+                _ => bail!("{:?} isn't a version range such as ^20, ~1.2, or >=1.0, <2.0", representation),
+            }
+        }
+        Ok(VersionReq { representation: representation.trim().to_owned(), comparators })
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.representation)
+    }
+}
+
+//The leading release numbers of a version, skipping any epoch and stopping at the first part
+//that isn't a number
+fn release_numbers(representation: &str) -> Vec<u64> {
+    let (_, upstream, _) = split_distro(representation);
+    let mut numbers = Vec::new();
+    for part in upstream.split('.') {
+        let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        match digits.parse::<u64>() {
+            Ok(number) => numbers.push(number),
+            Err(_) => break,
+        }
+        if digits.len() != part.len() {
+            break;
+        }
+    }
+    numbers
+}
+
+//Compare release numbers, treating missing numbers as zero
+fn compare_numbers(a: &[u64], b: &[u64]) -> Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+//Split a distribution version into its epoch, upstream version, and revision. A missing epoch is
+//0 and a missing revision is empty.
+fn split_distro(representation: &str) -> (u64, &str, &str) {
+    let (epoch, rest) = match representation.find(':') {
+        Some(colon) => match representation[..colon].parse::<u64>() {
+            Ok(epoch) => (epoch, &representation[colon + 1..]),
+            Err(_) => (0, representation),
+        },
+        None => (0, representation),
+    };
+    match rest.rfind('-') {
+        Some(hyphen) => (epoch, &rest[..hyphen], &rest[hyphen + 1..]),
+        None => (epoch, rest, ""),
+    }
+}
+
+/// Compare two version strings the way rpmvercmp does. Alphanumeric segments are compared with
+/// everything else acting as a separator. Numeric segments are compared numerically and are newer
+/// than alphabetic ones. `~` sorts before everything, like dpkg, while `^` sorts after the end of
+/// the string but before anything else.
+pub fn compare_rpm_segments(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let is_separator = |c: u8| !c.is_ascii_alphanumeric() && c != b'~' && c != b'^';
+    let (mut i, mut j) = (0, 0);
+    loop {
+        while i < a.len() && is_separator(a[i]) {
+            i += 1;
+        }
+        while j < b.len() && is_separator(b[j]) {
+            j += 1;
+        }
+        let (ca, cb) = (a.get(i).cloned(), b.get(j).cloned());
+        if ca == Some(b'~') || cb == Some(b'~') {
+            if ca != Some(b'~') {
+                return Ordering::Greater;
+            }
+            if cb != Some(b'~') {
+                return Ordering::Less;
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if ca == Some(b'^') || cb == Some(b'^') {
+            if ca.is_none() {
+                return Ordering::Less;
+            }
+            if cb.is_none() {
+                return Ordering::Greater;
+            }
+            if ca != Some(b'^') {
+                return Ordering::Greater;
+            }
+            if cb != Some(b'^') {
+                return Ordering::Less;
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if ca.is_none() || cb.is_none() {
+            break;
+        }
+        let numeric = a[i].is_ascii_digit();
+        let in_segment = |c: &u8| if numeric { c.is_ascii_digit() } else { c.is_ascii_alphabetic() };
+        let segment_a = &a[i..i + a[i..].iter().take_while(|c| in_segment(c)).count()];
+        let segment_b = &b[j..j + b[j..].iter().take_while(|c| in_segment(c)).count()];
+        i += segment_a.len();
+        j += segment_b.len();
+        if segment_b.is_empty() {
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+        let order = if numeric {
+            let trim = |segment: &[u8]| -> usize {
+                segment.iter().take_while(|&&c| c == b'0').count()
+            };
+            let (segment_a, segment_b) = (&segment_a[trim(segment_a)..], &segment_b[trim(segment_b)..]);
+            segment_a.len().cmp(&segment_b.len()).then_with(|| segment_a.cmp(segment_b))
+        } else {
+            segment_a.cmp(segment_b)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    (a.len() - i).min(1).cmp(&(b.len() - j).min(1))
+}
+
+/// Compare two version strings the way dpkg does. The strings are split into alternating runs of
+/// non-digits and digits. Non-digit runs are compared character by character with letters sorting
+/// before other characters and `~` sorting before everything, even the end of the string, so
+/// `1.0~rc1` is older than `1.0`. Digit runs are compared numerically.
+pub fn compare_segments(a: &str, b: &str) -> Ordering {
+    //The sort weight of a character in a non-digit run, where None is the end of the run
+    fn weight(c: Option<u8>) -> i32 {
+        match c {
+            Some(b'~') => -1,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+            Some(c) => i32::from(c) + 256,
+            None => 0,
+        }
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let (wa, wb) = (weight(a.get(i).cloned()), weight(b.get(j).cloned()));
+            if wa != wb {
+                return wa.cmp(&wb);
+            }
+            i += 1;
+            j += 1;
+        }
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_difference = Ordering::Equal;
+        while i < a.len() && j < b.len() && a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            if first_difference == Ordering::Equal {
+                first_difference = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_difference != Ordering::Equal {
+            return first_difference;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Compare two versions of a package manager itself. They're compared segment by segment like dpkg
+/// compares versions, whatever scheme the package manager's packages use, so versions like `23.0`
+/// that aren't semantic versions can still be ordered.
+pub fn compare_manager_versions(a: &Version, b: &Version) -> Ordering {
+    a.cmp_with(b, VersionScheme::Debian).unwrap_or(Ordering::Equal)
+}
+
+/// The name of the capture in a version_regex holding the version
+pub const VERSION_CAPTURE: &str = "version";
+
+/// Compile a version_regex, which must have a capture named `version`
+pub fn version_regex(pattern: &str) -> Result<Regex, Error> {
+    let regex = Regex::new(pattern)?;
+    if !regex.capture_names().any(|name| name == Some(VERSION_CAPTURE)) {
+        bail!("The version_regex {:?} must have a capture named {}", pattern, VERSION_CAPTURE);
+    }
+    Ok(regex)
+}
+
+/// Pull a version out of the output of a version command. With a regex it's the trimmed
+/// `version` capture of the first match. Without one it's the first word of the first line that
+/// starts with a digit, without any trailing punctuation, or the whole first line if none does.
+/// Gives None if the regex doesn't match or there's no output.
+pub fn extract_version(output: &str, regex: Option<&Regex>) -> Option<String> {
+    if let Some(regex) = regex {
+        return regex.captures(output)
+            .and_then(|captures| captures.name(VERSION_CAPTURE))
+            .map(|version| version.as_str().trim().to_owned())
+            .filter(|version| !version.is_empty());
+    }
+    let line = output.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let version = line.split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map_or(line, |word| word.trim_end_matches(|c: char| !c.is_alphanumeric()));
+    Some(version.to_owned())
+}
+
+/// Does the version start with a number, as the versions of package managers should?
+pub fn is_numeric(version: &Version) -> bool {
+    version.get_representation().starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Find the version of a package manager in the output of its version command without a
+/// version_regex, giving None unless it starts with a number
+pub fn parse_manager_version(output: &str) -> Option<Version> {
+    extract_version(output, None).map(|version| Version::from(version.as_str())).filter(is_numeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn semantic_matching() {
+        let semantics: Vec<&str> = vec![
+            "0.1.1",
+            "0.1.1-prerelease",
+            "0.1.1-prerelease.x.3",
+            "0.1.1-pre-pre-release",
+            "0.1.1+builddata",
+            "0.1.1+build-data",
+            "0.1.1+builddata.3",
+            "0.1.1-prerelease+builddata",
+        ];
+        let jejune: Vec<&str> = vec!["a.b.c", "1-1-1", "0.1.1-b@d", "0.1.1+b@d"];
+        for string in &semantics {
+            assert!(Version::is_semantic(string), "{} was detected as not semantic", string);
+        }
+        for string in &jejune {
+            assert!(!Version::is_semantic(string), "{} was detected as semantic", string);
+        }
+    }
+
+    #[test]
+    fn version_ordering() {
+        assert!(Version::from("0.10.0") > Version::from("0.9.12"));
+        assert!(Version::from("1.2.3") < Version::from("1.2.4-beta"));
+        assert_eq!(Version::from("2.0.0-rc1").partial_cmp(&Version::from("2.0.0")),
+                   Some(Ordering::Equal));
+        assert_eq!(Version::from("20180102").partial_cmp(&Version::from("20180101")), None);
+        assert_eq!(Version::from("r24").partial_cmp(&Version::from("r24")),
+                   Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn creation_test() {
+        let blank_version = Version::new();
+        assert_eq!(blank_version.representation, String::new());
+        assert!(!blank_version.semantic);
+        let semantic_string = "0.1.2";
+        let non_semantic_string = "1.4rc2";
+        let semantic_version = Version::from(semantic_string);
+        assert!(semantic_version.get_semantic());
+        let non_semantic_version = Version::from(non_semantic_string);
+        assert!(!non_semantic_version.get_semantic());
+    }
+
+    #[test]
+    fn ranges() {
+        let req = |req: &str| req.parse::<VersionReq>().unwrap();
+        let v = |v: &str| Version::from(v);
+        assert!(req("^20").matches(&v("20.11.1_1")));
+        assert!(!req("^20").matches(&v("21.0.0")));
+        assert!(!req("^20").matches(&v("19.9.0")));
+        assert!(req("20").matches(&v("1:20.3-2")));
+        assert!(req("^0.2.1").matches(&v("0.2.5")));
+        assert!(!req("^0.2.1").matches(&v("0.3.0")));
+        assert!(req("~1.2").matches(&v("1.2.9")));
+        assert!(!req("~1.2").matches(&v("1.3.0")));
+        assert!(req(">=1.0, <2.0").matches(&v("1.9")));
+        assert!(!req(">=1.0, <2.0").matches(&v("2.0")));
+        assert!(req("=1.2").matches(&v("1.2.7")));
+        assert!(!req("*").matches(&v("latest")));
+        assert_eq!(req(" ^20 ").to_string(), "^20");
+        assert!("^twenty".parse::<VersionReq>().is_err());
+        assert!("".parse::<VersionReq>().is_err());
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!("".parse::<Version>(), Err(VersionError::Empty));
+        assert_eq!("1.2 beta".parse::<Version>(), Err(VersionError::Whitespace(String::from("1.2 beta"))));
+        let version: Version = " 0.7.1\n".parse().unwrap();
+        assert_eq!(version.to_string(), "0.7.1");
+        assert!(version.get_semantic());
+    }
+
+    #[test]
+    fn structured_parts() {
+        assert_eq!(*Version::from("1.2.3-beta+5").parts(), VersionParts::Semantic {
+            major: 1,
+            minor: 2,
+            patch: 3,
+            pre_release: Some(String::from("beta")),
+            build_metadata: Some(String::from("5")),
+        });
+        assert_eq!(*Version::from("2018-01-02").parts(), VersionParts::Date { year: 2018, month: 1, day: 2 });
+        assert_eq!(*Version::from("20181231").parts(), VersionParts::Date { year: 2018, month: 12, day: 31 });
+        assert_eq!(*Version::from("20181331").parts(), VersionParts::Unstructured);
+        assert_eq!(*Version::from("10.0.17134.1").parts(), VersionParts::FourComponent([10, 0, 17134, 1]));
+        assert_eq!(*Version::from("1.4rc2").parts(), VersionParts::Unstructured);
+    }
+
+    #[test]
+    fn accessors() {
+        let version = Version::from("1.2.3-beta.2+20180102");
+        assert_eq!((version.major(), version.minor(), version.patch()), (Some(1), Some(2), Some(3)));
+        assert_eq!(version.pre_release(), Some("beta.2"));
+        assert_eq!(version.build_metadata(), Some("20180102"));
+        let mut version = Version::from("1.4rc2");
+        assert_eq!(version.major(), None);
+        version.set_representation(String::from("1.4.2"));
+        assert_eq!(version.minor(), Some(4));
+        assert_eq!(version.pre_release(), None);
+        version.set_semantic(false).unwrap();
+        assert_eq!(version.patch(), None);
+    }
+
+    #[test]
+    fn bumping() {
+        let version = Version::from("1.2.3-beta+5");
+        assert_eq!(version.bump_major().unwrap().to_string(), "2.0.0");
+        assert_eq!(version.bump_minor().unwrap().to_string(), "1.3.0");
+        assert_eq!(version.bump_patch().unwrap().to_string(), "1.2.4");
+        assert!(Version::from("1.4rc2").bump_patch().is_none());
+    }
+
+    #[test]
+    fn newest() {
+        let versions: Vec<Version> = ["0.9.12", "r24", "0.10.0", "0.10.0-rc1"].iter()
+            .map(|v| Version::from(*v))
+            .collect();
+        assert_eq!(Version::newest(&versions).unwrap().to_string(), "0.10.0");
+        assert!(versions[2].is_newer_than(&versions[0]));
+        assert!(!versions[1].is_newer_than(&versions[0]));
+        assert!(!versions[0].is_newer_than(&versions[1]));
+        assert!(Version::newest(&[]).is_none());
+    }
+
+    #[test]
+    fn distro_versions() {
+        let debian = Version::parse_with("1:2.3.4-5ubuntu1", ParseMode::Extended);
+        assert_eq!(*debian.parts(), VersionParts::Distro {
+            epoch: 1,
+            upstream: String::from("2.3.4"),
+            revision: Some(String::from("5ubuntu1")),
+        });
+        let extended = |v: &str| Version::parse_with(v, ParseMode::Extended);
+        assert!(debian > extended("9.0.0-1"));
+        assert!(extended("2.3.4-5ubuntu1") < extended("2.3.4-5ubuntu2"));
+        assert!(extended("2.3.10-r1") > extended("2.3.9-r1"));
+        assert!(extended("2.3.4-r3") > extended("2.3.4-r2"));
+        assert!(extended("1.0~rc1-1") < extended("1.0-1"));
+        assert_eq!(extended("1.02-1"), extended("1.2-1"));
+        assert_eq!(*extended("2.3.4").parts(), *Version::from("2.3.4").parts());
+        assert!(extended("2.3.4-1") > Version::from("2.3.4"));
+    }
+
+    #[test]
+    fn schemes() {
+        let compare = |a: &str, b: &str, scheme| Version::from(a).cmp_with(&Version::from(b), scheme);
+        assert_eq!(compare("1:1.0-1", "2.0-1", VersionScheme::Debian), Some(Ordering::Greater));
+        assert_eq!(compare("1.0~rc1", "1.0", VersionScheme::Debian), Some(Ordering::Less));
+        assert_eq!(compare("2.3.4-r3", "2.3.4-r2", VersionScheme::Debian), Some(Ordering::Greater));
+        assert_eq!(compare("1.0-1.fc27", "1.0", VersionScheme::Rpm), Some(Ordering::Equal));
+        assert_eq!(compare("1.0-1.fc27", "1.0-2.fc27", VersionScheme::Rpm), Some(Ordering::Less));
+        assert_eq!(compare("20180102", "20180101", VersionScheme::Semver), None);
+        assert_eq!("rpm".parse::<VersionScheme>().unwrap(), VersionScheme::Rpm);
+        assert!("pep440".parse::<VersionScheme>().is_err());
+    }
+
+    #[test]
+    fn rpm_segment_comparison() {
+        assert_eq!(compare_rpm_segments("1.0", "1.0"), Ordering::Equal);
+        assert_eq!(compare_rpm_segments("1.0", "1_0"), Ordering::Equal);
+        assert_eq!(compare_rpm_segments("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(compare_rpm_segments("1.a", "1.1"), Ordering::Less);
+        assert_eq!(compare_rpm_segments("1.010", "1.9"), Ordering::Greater);
+        assert_eq!(compare_rpm_segments("1.0~rc1", "1.0"), Ordering::Less);
+        assert_eq!(compare_rpm_segments("1.0^git1", "1.0"), Ordering::Greater);
+        assert_eq!(compare_rpm_segments("1.0^git1", "1.0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn segment_comparison() {
+        assert_eq!(compare_segments("1.0", "1.0"), Ordering::Equal);
+        assert_eq!(compare_segments("1.0a", "1.0"), Ordering::Greater);
+        assert_eq!(compare_segments("1.0~", "1.0"), Ordering::Less);
+        assert_eq!(compare_segments("1.0+dfsg", "1.0a"), Ordering::Greater);
+        assert_eq!(compare_segments("1.10", "1.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn equality_test() {
+        let version1 = Version::from("0.1.2");
+        let version2 = Version::from("1.4rc2");
+        let mut version3 = Version::from("0.1.2");
+        assert_eq!(version1,version3);
+        assert_ne!(version1,version2);
+        let res = version3.set_semantic(false);
+        assert!(res.is_ok());
+        assert_ne!(version1,version3);
+    }
+
+    #[test]
+    fn manager_versions() {
+        assert_eq!(parse_manager_version("pip 23.0.1 from /usr/lib/python3 (python 3.11)").unwrap().get_representation(), "23.0.1");
+        assert_eq!(parse_manager_version("Homebrew 4.2.0,\n").unwrap().get_representation(), "4.2.0");
+        assert!(parse_manager_version("apt (unknown)").is_none());
+        assert_eq!(extract_version("  \n apt (unknown)\nmore", None).unwrap(), "apt (unknown)");
+        let brew = version_regex(r"^Homebrew (?P<version>[\d.]+)").unwrap();
+        let output = "Homebrew 4.1.2\nHomebrew/homebrew-core (git revision 2c5e; last commit 2023-07-01)\n";
+        assert_eq!(extract_version(output, Some(&brew)).unwrap(), "4.1.2");
+        assert!(extract_version("brew 4.1.2", Some(&brew)).is_none());
+        assert!(version_regex(r"Homebrew ([\d.]+)").is_err());
+        assert!(version_regex(r"(?P<version>").is_err());
+        assert_eq!(compare_manager_versions(&Version::from("9.0.1"), &Version::from("23.0")), Ordering::Less);
+        assert_eq!(compare_manager_versions(&Version::from("2.4.0"), &Version::from("2.4.0")), Ordering::Equal);
+    }
+}
@@ -0,0 +1,193 @@
+//! Keeping a [`ManagerSet`](../struct.ManagerSet.html) up to date with its configuration
+//! directories for long running frontends. This needs the `watch` feature.
+//!
+//! A [`ConfigWatcher`](struct.ConfigWatcher.html) watches the directories and, whenever a
+//! configuration file is added, changed, or removed, reloads every package manager so that
+//! [merged](../overlay/index.html) and inheriting configurations are updated too. The frontend is
+//! told which package managers changed through [`ConfigEvent`s](enum.ConfigEvent.html).
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::Duration;
+use failure::Error;
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher, watcher};
+use super::{Interaction, ManagerSet, ManagerSpecifier};
+
+/// How long to wait for a burst of changes to a file to settle before reloading
+pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A change to the configuration of a package manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigEvent {
+    /// A package manager was configured
+    Added(String),
+    /// The configuration of a package manager changed
+    Changed(String),
+    /// A package manager's configuration was removed
+    Removed(String),
+}
+
+impl ConfigEvent {
+    /// The name of the package manager that changed
+    pub fn name(&self) -> &str {
+        match *self {
+            ConfigEvent::Added(ref name) | ConfigEvent::Changed(ref name) | ConfigEvent::Removed(ref name) => name,
+        }
+    }
+}
+
+impl fmt::Display for ConfigEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigEvent::Added(ref name) => write!(f, "{} was added", name),
+            ConfigEvent::Changed(ref name) => write!(f, "{} was changed", name),
+            ConfigEvent::Removed(ref name) => write!(f, "{} was removed", name),
+        }
+    }
+}
+
+/// Watches configuration directories and reloads package managers when they change
+pub struct ConfigWatcher {
+    //Kept so the directories stay watched
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+    directories: Vec<PathBuf>,
+    names: ManagerSpecifier,
+    interaction: Interaction,
+}
+
+impl ConfigWatcher {
+    /// Start watching the configuration directories, listed from highest precedence to lowest as
+    /// for [`read_config_dirs`](../fn.read_config_dirs.html). Directories that don't exist are
+    /// skipped.
+    pub fn new<P: AsRef<Path>>(directories: Vec<P>, names: ManagerSpecifier) -> Result<ConfigWatcher, Error> {
+        let (sender, events) = channel();
+        let mut notifier = watcher(sender, DEBOUNCE)?;
+        let directories: Vec<PathBuf> = directories.iter().map(|dir| dir.as_ref().to_path_buf()).collect();
+        for dir in directories.iter().filter(|dir| dir.is_dir()) {
+            notifier.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        Ok(ConfigWatcher {
+            _watcher: notifier,
+            events,
+            directories,
+            names,
+            interaction: Interaction::default(),
+        })
+    }
+
+    /// Set the interaction of the package managers that are reloaded
+    pub fn with_interaction(mut self, interaction: Interaction) -> ConfigWatcher {
+        self.interaction = interaction;
+        self
+    }
+
+    /// Load the package managers from the watched directories
+    pub fn load(&self) -> ManagerSet {
+        let mut set = ManagerSet::from_config_dirs(self.directories.clone(), &self.names);
+        set.set_interaction(self.interaction);
+        set
+    }
+
+    /// Apply any changes that have happened since the last call without blocking, giving what
+    /// changed
+    pub fn poll(&self, set: &mut ManagerSet) -> Result<Vec<ConfigEvent>, Error> {
+        let mut changed = Vec::new();
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => changed.extend(changed_file(event)?),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => bail!("The configuration watcher stopped"),
+            }
+        }
+        Ok(self.reload(set, changed))
+    }
+
+    /// Block until a configuration changes, then apply it and give what changed
+    pub fn wait(&self, set: &mut ManagerSet) -> Result<Vec<ConfigEvent>, Error> {
+        loop {
+            let changed = changed_file(self.events.recv()?)?;
+            let events = self.reload(set, changed);
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+
+    //Reload every package manager if any configuration file changed. The events are only for the
+    //package managers named by the files, though those inheriting from them are updated as well.
+    fn reload(&self, set: &mut ManagerSet, changed: Vec<PathBuf>) -> Vec<ConfigEvent> {
+        let mut names: Vec<String> = changed.iter()
+            .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(String::from))
+            .filter(|name| self.names.allows(name))
+            .collect();
+        if names.is_empty() {
+            return Vec::new();
+        }
+        names.sort();
+        names.dedup();
+        let reloaded = self.load();
+        let events = names.into_iter()
+            .filter_map(|name| match (set.get(&name).is_some(), reloaded.get(&name).is_some()) {
+                (false, true) => Some(ConfigEvent::Added(name)),
+                (true, true) => Some(ConfigEvent::Changed(name)),
+                (true, false) => Some(ConfigEvent::Removed(name)),
+                (false, false) => None,
+            })
+            .collect();
+        *set = reloaded;
+        events
+    }
+}
+
+//Get the files a notification is about
+fn changed_file(event: DebouncedEvent) -> Result<Vec<PathBuf>, Error> {
+    Ok(match event {
+        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) | DebouncedEvent::Remove(path) => vec![path],
+        DebouncedEvent::Rename(from, to) => vec![from, to],
+        DebouncedEvent::Error(e, _) => return Err(e.into()),
+        //Notices come before the debounced event and permissions don't change the configuration
+        _ => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, remove_file, write};
+    use super::super::{CommandKind, Scope};
+
+    #[test]
+    fn reloading() {
+        let dir = temp_dir().join(format!("upm-watch-{}", ::std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let watcher = ConfigWatcher::new(vec![&dir], ManagerSpecifier::Empty).unwrap();
+        let mut set = watcher.load();
+        assert!(set.get("pip").is_none());
+
+        let config = dir.join("pip.toml");
+        write(&config, "version = 'pip --version'\n").unwrap();
+        let events = watcher.reload(&mut set, changed_file(DebouncedEvent::Create(config.clone())).unwrap());
+        assert_eq!(events, vec![ConfigEvent::Added(String::from("pip"))]);
+        assert!(!set.get("pip").unwrap().has_command("install"));
+
+        write(&config, "version = 'pip --version'\ninstall = 'pip install'\n").unwrap();
+        let events = watcher.reload(&mut set, changed_file(DebouncedEvent::Write(config.clone())).unwrap());
+        assert_eq!(events, vec![ConfigEvent::Changed(String::from("pip"))]);
+        assert_eq!(set.get("pip").unwrap().template(CommandKind::Install, Scope::System).map(|t| t.as_str()), Some("pip install"));
+
+        remove_file(&config).unwrap();
+        let events = watcher.reload(&mut set, changed_file(DebouncedEvent::Remove(config.clone())).unwrap());
+        assert_eq!(events, vec![ConfigEvent::Removed(String::from("pip"))]);
+        assert!(set.get("pip").is_none());
+
+        //Files other than configurations are ignored
+        let events = watcher.reload(&mut set, vec![dir.join("search.sh")]);
+        assert!(events.is_empty());
+        remove_dir_all(&dir).unwrap();
+    }
+}
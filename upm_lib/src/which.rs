@@ -0,0 +1,141 @@
+//! Finding where a package would come from. [`locate`](fn.locate.html) asks every package manager
+//! whether it offers a package and whether it's installed through it, answering which managers a
+//! package is available from, at what versions, and which one installed it.
+
+use std::fmt;
+use failure::Error;
+use super::{ManagerSet, Package, PackageManager, Version};
+use alias::AliasResolver;
+use concurrency::{ConcurrencyPolicy, run_all};
+
+/// A package as seen by one package manager
+#[derive(Debug, Clone)]
+pub struct Availability {
+    /// The name of the package manager
+    pub manager: String,
+    /// The name of the package for the package manager, after aliases
+    pub name: String,
+    /// The version the package manager offers, if it offers the package
+    pub available: Option<Version>,
+    /// The version installed through the package manager, if it's installed
+    pub installed: Option<Version>,
+}
+
+impl Availability {
+    /// Is the package installed through this package manager?
+    pub fn is_installed(&self) -> bool {
+        self.installed.is_some()
+    }
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.manager, self.name)?;
+        if let Some(ref version) = self.available {
+            write!(f, " {}", version)?;
+        }
+        if let Some(ref version) = self.installed {
+            write!(f, " (installed {})", version)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find every package manager that offers a package or has it installed. Names are translated
+/// for each package manager by the aliases. Package managers with an [info](../info/index.html)
+/// command are asked with it, and the rest with their search and list_installed commands. Package managers that neither offer nor have the
+/// package are left out, and failed queries are treated as not finding it.
+pub fn locate(managers: &ManagerSet, name: &str, aliases: &AliasResolver) -> Vec<Availability> {
+    run_all(managers.as_slice(), &ConcurrencyPolicy::default(), |manager| {
+        let name = aliases.translate(name, manager);
+        if manager.has_command("info") {
+            return info_availability(manager, name);
+        }
+        let available = if manager.has_command("search") {
+            find_version(manager, name, manager.search_packages(name), "search")
+        } else {
+            None
+        };
+        let installed = if manager.has_command("list_installed") {
+            find_version(manager, name, manager.list_installed(), "list installed packages")
+        } else {
+            None
+        };
+        Availability {
+            manager: manager.name.clone(),
+            name: name.to_owned(),
+            available,
+            installed,
+        }
+    }).into_iter()
+        .map(|(_, availability)| availability)
+        .filter(|availability| availability.available.is_some() || availability.is_installed())
+        .collect()
+}
+
+//Gets the versions of a package from the info command of a package manager
+fn info_availability(manager: &PackageManager, name: &str) -> Availability {
+    let (available, installed) = match manager.info(name) {
+        Ok(package) => {
+            let available = Some(package.version.clone()).filter(|version| !version.get_representation().is_empty());
+            (available, package.installed.map(|installed| installed.version))
+        },
+        Err(e) => {
+            warn!("{}: couldn't get the info of {}: {}", manager.name, name, e);
+            (None, None)
+        },
+    };
+    Availability {
+        manager: manager.name.clone(),
+        name: name.to_owned(),
+        available,
+        installed,
+    }
+}
+
+//Get the version of the package with exactly the given name from a query
+fn find_version(manager: &PackageManager, name: &str, packages: Result<Vec<Package>, Error>, query: &str) -> Option<Version> {
+    match packages {
+        Ok(packages) => packages.into_iter().find(|p| p.is_called(name)).map(|p| p.version),
+        Err(e) => {
+            warn!("{}: couldn't {} for {}: {}", manager.name, query, name, e);
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{CommandKind, Scope};
+    use std::path::PathBuf;
+
+    #[test]
+    fn locating() {
+        let manager = |name: &str, search: Option<&str>, list: Option<&str>| {
+            let mut manager = PackageManager {
+                name: String::from(name),
+                config_dir: PathBuf::from("./test-files/"),
+                ..Default::default()
+            }.with_command(CommandKind::Version, Scope::System, "true");
+            if let Some(search) = search {
+                manager.set_command(CommandKind::Search, Scope::System, search);
+            }
+            if let Some(list) = list {
+                manager.set_command(CommandKind::ListInstalled, Scope::System, list);
+            }
+            manager
+        };
+        let set = ManagerSet::new(vec![
+            manager("cargo", Some("./which/search.sh"), Some("./manifest/list.sh")),
+            manager("brew", Some("./which/search.sh"), None),
+            manager("npm", Some("false"), Some("false")),
+        ]);
+        let found = locate(&set, "ripgrep", &AliasResolver::new());
+        let managers: Vec<&str> = found.iter().map(|a| a.manager.as_str()).collect();
+        assert_eq!(managers, vec!["brew", "cargo"]);
+        assert!(!found[0].is_installed());
+        assert_eq!(found[1].to_string(), "cargo: ripgrep 0.8.0 (installed 0.7.1)");
+        assert!(locate(&set, "ripgrep-al", &AliasResolver::new()).is_empty());
+    }
+}
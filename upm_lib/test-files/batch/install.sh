@@ -0,0 +1,10 @@
+#! /usr/bin/env sh
+status=0
+for package in "$@"; do
+    case "$package" in
+        missing) echo "$package failed" >> "$UPM_STATUS_FILE"; status=1 ;;
+        quiet) ;;
+        *) echo "$package ok" >> "$UPM_STATUS_FILE" ;;
+    esac
+done
+exit $status
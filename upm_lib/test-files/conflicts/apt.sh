@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+printf "ansible 2.9.6\ncurl 7.68.0\n"
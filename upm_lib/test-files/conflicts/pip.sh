@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+printf "ansible 2.10.0\nrequests 2.25.1\n"
@@ -0,0 +1,6 @@
+#! /usr/bin/env sh
+if [ "$UPM_PROBE" = "1" ]; then
+    echo "ripgrep 0.7.1 Recursively searches directories for a regex pattern"
+    exit 0
+fi
+exit 1
@@ -0,0 +1,3 @@
+#! /usr/bin/env sh
+touch "$1.deb"
+echo "$UPM_DOWNLOAD_DIR"
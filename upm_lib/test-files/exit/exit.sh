@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+exit "$1"
@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+echo "$UPM_MANAGER $UPM_COMMAND $UPM_PACKAGE $UPM_STATUS" >> "$1"
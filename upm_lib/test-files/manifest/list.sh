@@ -0,0 +1,3 @@
+#! /usr/bin/env sh
+echo "ripgrep 0.7.1"
+echo "unversioned"
@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+echo 1.0.0
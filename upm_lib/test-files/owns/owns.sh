@@ -0,0 +1,8 @@
+#! /usr/bin/env sh
+if [ "$UPM_PROBE" = "1" ]; then
+    exit 0
+fi
+case "$1" in
+    /usr/bin/rg) echo "ripgrep 0.7.1" ;;
+    *) exit 2 ;;
+esac
@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+printf "node 20.1.0\npython 3.11.0\nruby 3.0.0\ndeno 1.0.0\n"
@@ -0,0 +1,5 @@
+#! /usr/bin/env sh
+case "$1" in
+    node) echo "node 20.5.0 JavaScript runtime" ;;
+    deno) echo "deno 2.0.0 JavaScript runtime" ;;
+esac
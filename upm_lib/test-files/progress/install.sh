@@ -0,0 +1,5 @@
+#! /usr/bin/env sh
+echo "Downloading $1"
+echo "Fetched 50%"
+echo "Unpacking" >&2
+echo "Installed 100%"
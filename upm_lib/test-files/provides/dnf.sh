@@ -0,0 +1,6 @@
+#! /usr/bin/env sh
+if [ "$1" = "libssl.so.3" ]; then
+    echo "openssl-libs 3.0.7 General purpose cryptography library"
+    exit 0
+fi
+exit 2
@@ -0,0 +1,5 @@
+#! /usr/bin/env sh
+if [ "$1" = "openssl" ]; then
+    echo "curl 7.58.0"
+    echo "openssh 7.6p1"
+fi
@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+echo "secret=$UPM_SANDBOX_SECRET"
@@ -0,0 +1,3 @@
+#! /usr/bin/env sh
+echo "lodash 4.17.20 GHSA-35jh-r3h4-6jhm high 4.17.21 Command injection in lodash"
+echo "lodash 4.17.20 GHSA-p6mc-m468-83gw moderate - Prototype pollution in lodash"
@@ -0,0 +1,2 @@
+#! /usr/bin/env sh
+echo "openssl /usr/lib/libssl.so.3 checksum mismatch"
@@ -0,0 +1,4 @@
+#!/bin/sh
+echo "rg 1.0"
+echo "ripgrep 2.0 Recursively search directories"
+echo "ripgrep-all 1.0 ripgrep for PDFs and archives"
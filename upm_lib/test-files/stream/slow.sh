@@ -0,0 +1,3 @@
+#!/bin/sh
+sleep 0.3
+echo "ripgrep 1.0 Recursively search directories"
@@ -0,0 +1,3 @@
+#! /usr/bin/env sh
+echo "ripgrep 0.8.0 Recursively searches directories for a regex pattern"
+echo "ripgrep-all 0.9.0 ripgrep for documents and archives"